@@ -0,0 +1,221 @@
+//! Module for extracting archives of WIT packages.
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+/// The environment variable specifying the directory to extract archives to.
+pub const ARCHIVE_CACHE_DIR_ENV_VAR: &str = "CARGO_COMPONENT_ARCHIVE_CACHE_DIR";
+
+/// The supported formats of archives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    /// A gzip-compressed tar archive (`.tar.gz` or `.tgz`).
+    TarGz,
+    /// A zip archive (`.zip`).
+    Zip,
+}
+
+impl ArchiveFormat {
+    fn from_path(path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else if name.ends_with(".zip") {
+            Ok(Self::Zip)
+        } else {
+            bail!(
+                "archive `{path}` has an unsupported extension; expected `.tar.gz`, `.tgz`, or `.zip`",
+                path = path.display()
+            )
+        }
+    }
+}
+
+/// Gets the directory archives are extracted to.
+///
+/// This is the directory specified by the `CARGO_COMPONENT_ARCHIVE_CACHE_DIR`
+/// environment variable, if set, or an `archives` directory in the
+/// `cargo-component` user cache directory.
+///
+/// Extracted archives are reused based on the digest of the archive, so they
+/// are never extracted to a shared directory such as the system's temporary
+/// directory where another user could place content under that digest.
+pub fn archive_cache_dir() -> Result<PathBuf> {
+    match std::env::var_os(ARCHIVE_CACHE_DIR_ENV_VAR) {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(crate::cache_dir()?.join("archives")),
+    }
+}
+
+/// Extracts the given archive into the cache directory.
+///
+/// Archives are extracted to a directory named after the digest of their
+/// contents, so an archive is only extracted again if it changes.
+///
+/// Returns the path of the given subdirectory of the extracted archive, or of
+/// the extracted archive itself if no subdirectory is given.
+pub fn extract_archive(path: &Path, subdir: Option<&Path>) -> Result<PathBuf> {
+    extract_archive_to(path, subdir, &archive_cache_dir()?)
+}
+
+/// Extracts the given archive into the given cache directory.
+///
+/// See [`extract_archive`].
+pub fn extract_archive_to(path: &Path, subdir: Option<&Path>, cache_dir: &Path) -> Result<PathBuf> {
+    let format = ArchiveFormat::from_path(path)?;
+    let contents = fs::read(path)
+        .with_context(|| format!("failed to read archive `{path}`", path = path.display()))?;
+
+    let digest: String = Sha256::digest(&contents)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let dir = cache_dir.join(digest);
+
+    if !dir.is_dir() {
+        log::debug!(
+            "extracting archive `{path}` to `{dir}`",
+            path = path.display(),
+            dir = dir.display()
+        );
+
+        fs::create_dir_all(cache_dir).with_context(|| {
+            format!(
+                "failed to create archive cache directory `{path}`",
+                path = cache_dir.display()
+            )
+        })?;
+
+        // Extract to a temporary directory first so a partially extracted
+        // archive is never used
+        let temp = tempfile::tempdir_in(cache_dir).with_context(|| {
+            format!(
+                "failed to create temporary directory in `{path}`",
+                path = cache_dir.display()
+            )
+        })?;
+
+        let file = File::open(path)
+            .with_context(|| format!("failed to open archive `{path}`", path = path.display()))?;
+
+        match format {
+            ArchiveFormat::TarGz => {
+                tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(temp.path())
+            }
+            ArchiveFormat::Zip => zip::ZipArchive::new(file)
+                .and_then(|mut archive| archive.extract(temp.path()))
+                .map_err(Into::into),
+        }
+        .with_context(|| format!("failed to extract archive `{path}`", path = path.display()))?;
+
+        if let Err(e) = fs::rename(temp.into_path(), &dir) {
+            // Another process may have extracted the same archive
+            if !dir.is_dir() {
+                return Err(e).with_context(|| {
+                    format!(
+                        "failed to move extracted archive to `{dir}`",
+                        dir = dir.display()
+                    )
+                });
+            }
+        }
+    }
+
+    let dir = match subdir {
+        Some(subdir) => dir.join(subdir),
+        None => dir,
+    };
+
+    if !dir.exists() {
+        bail!(
+            "archive `{path}` does not contain `{subdir}`",
+            path = path.display(),
+            subdir = subdir.unwrap_or(Path::new("")).display()
+        );
+    }
+
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    const WIT: &str = "package foo:bar;\n\ninterface baz {}\n";
+
+    #[test]
+    fn it_extracts_tar_gz_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("wit.tar.gz");
+
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(WIT.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "bundle/wit/bar.wit", WIT.as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let cache = dir.path().join("cache");
+        let extracted =
+            extract_archive_to(&archive, Some(Path::new("bundle/wit")), &cache).unwrap();
+        assert!(extracted.starts_with(&cache));
+        assert_eq!(fs::read_to_string(extracted.join("bar.wit")).unwrap(), WIT);
+
+        // Extracting again reuses the extracted archive
+        assert_eq!(
+            extract_archive_to(&archive, Some(Path::new("bundle/wit")), &cache).unwrap(),
+            extracted
+        );
+
+        assert!(
+            extract_archive_to(&archive, Some(Path::new("missing")), &cache)
+                .unwrap_err()
+                .to_string()
+                .contains("does not contain `missing`")
+        );
+    }
+
+    #[test]
+    fn it_extracts_zip_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("wit.zip");
+
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        writer
+            .start_file("bar.wit", zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(WIT.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let extracted = extract_archive_to(&archive, None, &dir.path().join("cache")).unwrap();
+        assert_eq!(fs::read_to_string(extracted.join("bar.wit")).unwrap(), WIT);
+    }
+
+    #[test]
+    fn it_rejects_unknown_archive_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("wit.rar");
+        fs::write(&archive, "").unwrap();
+
+        assert!(extract_archive_to(&archive, None, dir.path())
+            .unwrap_err()
+            .to_string()
+            .contains("unsupported extension"));
+    }
+}
@@ -0,0 +1,501 @@
+//! Module for WIT feature gates.
+//!
+//! The WIT parser in use does not understand the `@since`, `@unstable`, and
+//! `@deprecated` annotations, so they are applied to WIT sources before the
+//! sources are parsed: items gated on a disabled feature are removed and the
+//! annotations themselves are blanked out.
+//!
+//! Removed text is replaced with spaces (keeping line breaks), so positions
+//! in parse errors still refer to the original sources.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use wit_parser::{SourceMap, UnresolvedPackage};
+
+/// The WIT features enabled for a package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureSet {
+    /// Whether all features are enabled.
+    pub all: bool,
+    /// The names of the enabled features.
+    pub features: BTreeSet<String>,
+}
+
+impl FeatureSet {
+    /// Creates a new feature set with the given features enabled.
+    pub fn new(features: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            all: false,
+            features: features.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Determines if the given feature is enabled.
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        self.all || self.features.contains(feature)
+    }
+}
+
+/// A gate of a WIT item.
+enum Gate {
+    /// The item is stable, optionally since the version a feature was
+    /// stabilized in.
+    Stable(Option<String>),
+    /// The item is only included when the feature is enabled.
+    Unstable(String),
+}
+
+impl Gate {
+    fn feature(&self) -> Option<&str> {
+        match self {
+            Self::Stable(feature) => feature.as_deref(),
+            Self::Unstable(feature) => Some(feature),
+        }
+    }
+
+    fn included(&self, features: &FeatureSet) -> bool {
+        match self {
+            Self::Stable(_) => true,
+            Self::Unstable(feature) => features.is_enabled(feature),
+        }
+    }
+}
+
+/// Applies the feature gates of the given WIT source.
+///
+/// Returns the source with the items gated on disabled features removed
+/// and with all gate annotations blanked out.
+pub fn apply_gates(source: &str, features: &FeatureSet) -> Result<String> {
+    Ok(process(source, features)?.0)
+}
+
+/// Gets the names of the features declared by the gates of the given WIT
+/// source.
+pub fn declared_features(source: &str) -> Result<BTreeSet<String>> {
+    let all = FeatureSet {
+        all: true,
+        features: Default::default(),
+    };
+    Ok(process(source, &all)?.1)
+}
+
+/// Parses a WIT package from the given file or directory with the given
+/// features enabled.
+///
+/// A directory is parsed like [`UnresolvedPackage::parse_dir`] and a file
+/// like [`UnresolvedPackage::parse_file`].
+pub fn parse_path(path: &Path, features: &FeatureSet) -> Result<UnresolvedPackage> {
+    let mut map = SourceMap::default();
+    for path in source_files(path)? {
+        let contents =
+            fs::read_to_string(&path).with_context(|| format!("failed to read file {path:?}"))?;
+        let contents = apply_gates(&contents, features).with_context(|| {
+            format!(
+                "failed to apply the feature gates of `{path}`",
+                path = path.display()
+            )
+        })?;
+        map.push(&path, contents);
+    }
+
+    map.parse()
+}
+
+/// Gets the names of the features declared by the WIT package in the given
+/// file or directory.
+///
+/// A file that is not a WIT source (e.g. an encoded package) declares no
+/// features.
+pub fn declared_path_features(path: &Path) -> Result<BTreeSet<String>> {
+    let mut declared = BTreeSet::new();
+    for path in source_files(path)? {
+        let bytes = fs::read(&path).with_context(|| format!("failed to read file {path:?}"))?;
+        let Ok(contents) = std::str::from_utf8(&bytes) else {
+            continue;
+        };
+
+        if bytes.starts_with(b"\0asm") {
+            continue;
+        }
+
+        declared.extend(declared_features(contents).with_context(|| {
+            format!(
+                "failed to read the feature gates of `{path}`",
+                path = path.display()
+            )
+        })?);
+    }
+
+    Ok(declared)
+}
+
+/// Gets the WIT source files of the given file or directory.
+fn source_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let cx = || format!("failed to read directory {path:?}");
+    let mut files = Vec::new();
+    for entry in path.read_dir().with_context(cx)? {
+        let entry = entry.with_context(cx)?;
+        let path = entry.path();
+        if path.is_dir() {
+            continue;
+        }
+
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+
+        if name.ends_with(".wit") || name.ends_with(".wit.md") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Applies the gates of the given source, returning the resulting source and
+/// the features declared by the gates that were visited.
+fn process(source: &str, features: &FeatureSet) -> Result<(String, BTreeSet<String>)> {
+    let bytes = source.as_bytes();
+    let mut output = bytes.to_vec();
+    let mut declared = BTreeSet::new();
+    let mut docs = None;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        if let Some((end, doc)) = comment(bytes, pos)? {
+            if doc && docs.is_none() {
+                docs = Some(pos);
+            }
+
+            pos = end;
+            continue;
+        }
+
+        if bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        if !is_annotation(bytes, pos) {
+            docs = None;
+            pos += 1;
+            continue;
+        }
+
+        // Collect the annotations of the item
+        let start = pos;
+        let mut gates = Vec::new();
+        let mut annotations = Vec::new();
+        let mut item = pos;
+        while is_annotation(bytes, item) {
+            let (gate, end) = annotation(source, item)?;
+            annotations.push(item..end);
+            if let Some(feature) = gate.feature() {
+                declared.insert(feature.to_string());
+            }
+
+            gates.push(gate);
+            item = skip_trivia(bytes, end)?;
+        }
+
+        if gates.iter().all(|g| g.included(features)) {
+            for range in annotations {
+                blank(&mut output, range);
+            }
+
+            // Continue within the item, which may contain gated items
+            pos = item;
+        } else {
+            let end = item_end(bytes, item)?;
+            blank(&mut output, docs.unwrap_or(start)..end);
+            pos = end;
+        }
+
+        docs = None;
+    }
+
+    Ok((
+        String::from_utf8(output).expect("blanking should preserve UTF-8"),
+        declared,
+    ))
+}
+
+/// Determines if an annotation (e.g. `@since(...)`) starts at the given
+/// position.
+///
+/// The `@` of a package version is followed by a digit instead.
+fn is_annotation(bytes: &[u8], pos: usize) -> bool {
+    bytes.get(pos) == Some(&b'@') && bytes.get(pos + 1).is_some_and(u8::is_ascii_alphabetic)
+}
+
+/// Parses the annotation at the given position, returning its gate and the
+/// position following it.
+fn annotation(source: &str, pos: usize) -> Result<(Gate, usize)> {
+    let rest = &source[pos + 1..];
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .unwrap_or(rest.len());
+    let name = &rest[..name_len];
+    let after = rest[name_len..].trim_start();
+    let Some(args) = after.strip_prefix('(') else {
+        bail!("expected `(` after annotation `@{name}`");
+    };
+    let Some(close) = args.find(')') else {
+        bail!("unterminated annotation `@{name}`");
+    };
+    let end = source.len() - args.len() + close + 1;
+
+    let mut version = None;
+    let mut feature = None;
+    for arg in args[..close].split(',').map(str::trim) {
+        let Some((key, value)) = arg.split_once('=') else {
+            bail!("invalid argument `{arg}` of annotation `@{name}`");
+        };
+
+        match key.trim() {
+            "version" => version = Some(value.trim().to_string()),
+            "feature" => feature = Some(value.trim().to_string()),
+            key => bail!("unknown argument `{key}` of annotation `@{name}`"),
+        }
+    }
+
+    let gate = match (name, feature) {
+        ("unstable", Some(feature)) => Gate::Unstable(feature),
+        ("unstable", None) => bail!("annotation `@unstable` requires a `feature` argument"),
+        ("since" | "deprecated", _) if version.is_none() => {
+            bail!("annotation `@{name}` requires a `version` argument")
+        }
+        ("since" | "deprecated", feature) => Gate::Stable(feature),
+        _ => bail!("unsupported annotation `@{name}`"),
+    };
+
+    Ok((gate, end))
+}
+
+/// Gets the end of the comment at the given position and whether it is a
+/// documentation comment, if a comment starts there.
+fn comment(bytes: &[u8], pos: usize) -> Result<Option<(usize, bool)>> {
+    let rest = &bytes[pos..];
+    if rest.starts_with(b"//") {
+        let doc = rest.starts_with(b"///") && !rest.starts_with(b"////");
+        let end = rest
+            .iter()
+            .position(|b| *b == b'\n')
+            .map(|i| pos + i + 1)
+            .unwrap_or(bytes.len());
+        return Ok(Some((end, doc)));
+    }
+
+    if rest.starts_with(b"/*") {
+        let doc = rest.starts_with(b"/**") && !rest.starts_with(b"/**/");
+        let mut depth = 0;
+        let mut i = pos;
+        while i < bytes.len() {
+            if bytes[i..].starts_with(b"/*") {
+                depth += 1;
+                i += 2;
+            } else if bytes[i..].starts_with(b"*/") {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Ok(Some((i, doc)));
+                }
+            } else {
+                i += 1;
+            }
+        }
+
+        bail!("unterminated block comment");
+    }
+
+    Ok(None)
+}
+
+/// Skips whitespace and comments starting at the given position.
+fn skip_trivia(bytes: &[u8], mut pos: usize) -> Result<usize> {
+    while pos < bytes.len() {
+        if let Some((end, _)) = comment(bytes, pos)? {
+            pos = end;
+        } else if bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        } else {
+            break;
+        }
+    }
+
+    Ok(pos)
+}
+
+/// Gets the end of the item starting at the given position.
+///
+/// An item ends with a `;` or with the `}` closing its body, followed by an
+/// optional `;` (as in `use foo.{bar};`).
+fn item_end(bytes: &[u8], mut pos: usize) -> Result<usize> {
+    let mut depth = 0usize;
+    while pos < bytes.len() {
+        if let Some((end, _)) = comment(bytes, pos)? {
+            pos = end;
+            continue;
+        }
+
+        match bytes[pos] {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => bail!("unexpected `}}` in a gated item"),
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let next = skip_trivia(bytes, pos + 1)?;
+                    return Ok(if bytes.get(next) == Some(&b';') {
+                        next + 1
+                    } else {
+                        pos + 1
+                    });
+                }
+            }
+            b';' if depth == 0 => return Ok(pos + 1),
+            _ => {}
+        }
+
+        pos += 1;
+    }
+
+    bail!("unterminated gated item")
+}
+
+/// Replaces the given range of the source with spaces, keeping line breaks.
+fn blank(output: &mut [u8], range: std::ops::Range<usize>) {
+    for b in &mut output[range] {
+        if *b != b'\n' && *b != b'\r' {
+            *b = b' ';
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = r#"package foo:bar@1.0.0;
+
+@since(version = 1.0.0)
+interface stable {
+  /// A function added later.
+  @unstable(feature = extra)
+  extra: func();
+
+  run: func();
+}
+
+/// A fancy interface.
+@unstable(feature = fancy)
+interface fancy {
+  @since(version = 1.0.0, feature = fancy)
+  go: func();
+}
+
+world example {
+  @since(version = 1.0.0)
+  import stable;
+  @unstable(feature = fancy)
+  import fancy;
+  @unstable(feature = fancy)
+  use fancy.{};
+}
+"#;
+
+    fn parse(features: &FeatureSet) -> UnresolvedPackage {
+        UnresolvedPackage::parse(
+            Path::new("test.wit"),
+            &apply_gates(SOURCE, features).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_applies_feature_gates() {
+        let package = parse(&FeatureSet::default());
+        assert_eq!(package.interfaces.len(), 1);
+        let (_, stable) = package.interfaces.iter().next().unwrap();
+        assert_eq!(stable.name.as_deref(), Some("stable"));
+        assert!(stable.functions.contains_key("run"));
+        assert!(!stable.functions.contains_key("extra"));
+        let (_, world) = package.worlds.iter().next().unwrap();
+        assert_eq!(world.imports.len(), 1);
+
+        let package = parse(&FeatureSet::new(["fancy"]));
+        let names: Vec<_> = package
+            .interfaces
+            .iter()
+            .filter_map(|(_, i)| i.name.clone())
+            .collect();
+        assert_eq!(names, ["stable", "fancy"]);
+        let (_, world) = package.worlds.iter().next().unwrap();
+        assert_eq!(world.imports.len(), 2);
+
+        let package = parse(&FeatureSet {
+            all: true,
+            features: Default::default(),
+        });
+        let (_, stable) = package.interfaces.iter().next().unwrap();
+        assert!(stable.functions.contains_key("extra"));
+    }
+
+    #[test]
+    fn it_preserves_positions() {
+        let gated = apply_gates(SOURCE, &FeatureSet::default()).unwrap();
+        assert_eq!(gated.len(), SOURCE.len());
+        assert_eq!(gated.lines().count(), SOURCE.lines().count());
+        assert!(!gated.contains("fancy"));
+        assert!(!gated.contains("A fancy interface"));
+    }
+
+    #[test]
+    fn it_collects_declared_features() {
+        assert_eq!(
+            declared_features(SOURCE).unwrap(),
+            BTreeSet::from(["extra".to_string(), "fancy".to_string()])
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_annotations() {
+        for (source, message) in [
+            (
+                "@unstable interface foo {}",
+                "expected `(` after annotation `@unstable`",
+            ),
+            (
+                "@unstable() interface foo {}",
+                "invalid argument `` of annotation `@unstable`",
+            ),
+            (
+                "@since(feature = foo) interface foo {}",
+                "annotation `@since` requires a `version` argument",
+            ),
+            (
+                "@gated(feature = foo) interface foo {}",
+                "unsupported annotation `@gated`",
+            ),
+            (
+                "@unstable(feature = foo) interface foo {",
+                "unterminated gated item",
+            ),
+        ] {
+            assert_eq!(
+                apply_gates(source, &FeatureSet::default())
+                    .unwrap_err()
+                    .to_string(),
+                message
+            );
+        }
+    }
+}
@@ -4,15 +4,29 @@
 
 use anyhow::Context;
 use semver::VersionReq;
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr};
 use warg_protocol::registry::PackageName;
 
+pub mod archive;
 pub mod command;
+pub mod features;
 pub mod lock;
+pub mod oci;
 pub mod progress;
 pub mod registry;
 pub mod terminal;
 
+/// Gets the directory `cargo-component` caches content in.
+///
+/// This is a `cargo-component` directory in the user's cache directory, which
+/// is where warg registry content is cached by default, rather than in the
+/// system's shared temporary directory.
+pub fn cache_dir() -> anyhow::Result<PathBuf> {
+    dirs::cache_dir()
+        .map(|dir| dir.join("cargo-component"))
+        .context("failed to determine the user's cache directory")
+}
+
 /// Represents a versioned component package name.
 #[derive(Clone)]
 pub struct VersionedPackageName {
@@ -14,7 +14,16 @@ use warg_crypto::hash::AnyHash;
 use warg_protocol::registry::PackageName;
 
 /// The file format version of the lock file.
-const LOCK_FILE_VERSION: i64 = 1;
+///
+/// Version 2 added the `local-package` table; lock files without local
+/// packages are still written as version 1 so that existing lock files are
+/// not rewritten.
+const LOCK_FILE_VERSION: i64 = 2;
+
+/// The oldest file format version of the lock file that can be read.
+///
+/// Lock files of an older supported version are upgraded when read.
+const MIN_LOCK_FILE_VERSION: i64 = 1;
 
 /// Represents a locked package in a lock file.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -63,6 +72,22 @@ impl LockedPackageVersion {
     }
 }
 
+/// Represents a locked local dependency in a lock file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LockedLocalPackage {
+    /// The name of the local dependency.
+    pub name: PackageName,
+    /// The digest of the local dependency's contents.
+    pub digest: AnyHash,
+}
+
+impl LockedLocalPackage {
+    /// Gets the key used in sorting and searching the local package list.
+    pub fn key(&self) -> (&PackageName, String) {
+        (&self.name, self.digest.to_string())
+    }
+}
+
 /// Represents a resolver for a lock file.
 #[derive(Clone, Copy, Debug)]
 pub struct LockFileResolver<'a>(&'a LockFile);
@@ -104,6 +129,30 @@ impl<'a> LockFileResolver<'a> {
         log::info!("dependency package `{name}` from registry `{registry}` with requirement `{requirement}` was not in the lock file");
         Ok(None)
     }
+
+    /// Gets the locked versions of a package for the given version
+    /// requirement from every registry in the lock file.
+    ///
+    /// The versions are returned with the name of their registry, in the
+    /// order of the registries in the lock file.
+    pub fn locked_versions(
+        &self,
+        name: &PackageName,
+        requirement: &VersionReq,
+    ) -> Vec<(&'a str, &'a LockedPackageVersion)> {
+        let requirement = requirement.to_string();
+        self.0
+            .packages
+            .iter()
+            .filter(|pkg| &pkg.name == name)
+            .filter_map(|pkg| {
+                pkg.versions
+                    .binary_search_by_key(&requirement.as_str(), LockedPackageVersion::key)
+                    .ok()
+                    .map(|index| (pkg.key().1, &pkg.versions[index]))
+            })
+            .collect()
+    }
 }
 
 /// Represents a resolved dependency lock file.
@@ -115,13 +164,23 @@ impl<'a> LockFileResolver<'a> {
 pub struct LockFile {
     /// The version of the lock file.
     ///
-    /// Currently this is always `1`.
+    /// Currently this is always `2`; the lock file is written as version `1`
+    /// if it has no local packages.
     pub version: i64,
     /// The locked dependencies in the lock file.
     ///
     /// This list is sorted by the key of the locked package.
     #[serde(rename = "package", default, skip_serializing_if = "Vec::is_empty")]
     pub packages: Vec<LockedPackage>,
+    /// The locked local dependencies in the lock file.
+    ///
+    /// This list is sorted by the key of the locked local package.
+    #[serde(
+        rename = "local-package",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub local_packages: Vec<LockedLocalPackage>,
 }
 
 impl LockFile {
@@ -132,9 +191,33 @@ impl LockFile {
         Self {
             version: LOCK_FILE_VERSION,
             packages: packages.into(),
+            local_packages: Vec::new(),
         }
     }
 
+    /// Gets the local dependencies whose digests differ from those in the
+    /// given lock file.
+    ///
+    /// Local dependencies not present in the given lock file are not included.
+    pub fn changed_local_packages<'a>(&'a self, old: &LockFile) -> Vec<&'a PackageName> {
+        let mut changed: Vec<_> = self
+            .local_packages
+            .iter()
+            .filter(|new| {
+                let mut old = old
+                    .local_packages
+                    .iter()
+                    .filter(|old| old.name == new.name)
+                    .peekable();
+                old.peek().is_some() && old.all(|old| old.digest != new.digest)
+            })
+            .map(|p| &p.name)
+            .collect();
+
+        changed.dedup();
+        changed
+    }
+
     /// Reads the lock file from the given file object.
     pub fn read(mut file: &File) -> Result<Self> {
         let mut contents = String::new();
@@ -142,29 +225,45 @@ impl LockFile {
 
         let document: DocumentMut = contents.parse()?;
 
-        match document.as_table().get("version") {
+        let version = match document.as_table().get("version") {
             Some(Item::Value(Value::Integer(v))) => {
-                if *v.value() != LOCK_FILE_VERSION {
-                    bail!(
-                        "unsupported file format version {version}",
-                        version = v.value()
-                    );
+                let version = *v.value();
+                if !(MIN_LOCK_FILE_VERSION..=LOCK_FILE_VERSION).contains(&version) {
+                    bail!("unsupported file format version {version}");
                 }
 
-                // In the future, we should convert between supported versions here.
+                version
             }
             Some(_) => bail!("file format version is not an integer"),
             None => bail!("missing file format version"),
+        };
+
+        let mut lock_file =
+            Self::deserialize(document.into_deserializer()).context("invalid file format")?;
+
+        // Version 1 lock files have no local packages; they are locked as the
+        // lock file is next written
+        if version == 1 {
+            lock_file.local_packages.clear();
         }
 
-        Self::deserialize(document.into_deserializer()).context("invalid file format")
+        lock_file.version = LOCK_FILE_VERSION;
+        Ok(lock_file)
     }
 
     /// Writes the lock file to the given file object.
     ///
     /// The app name is used to generate a header comment.
     pub fn write(&self, mut file: &File, app: &str) -> Result<()> {
-        let content = toml_edit::ser::to_string_pretty(&self)?;
+        let content = if self.local_packages.is_empty() {
+            toml_edit::ser::to_string_pretty(&Self {
+                version: MIN_LOCK_FILE_VERSION,
+                packages: self.packages.clone(),
+                local_packages: Vec::new(),
+            })?
+        } else {
+            toml_edit::ser::to_string_pretty(&self)?
+        };
 
         file.set_len(0)?;
         write!(file, "# This file is automatically generated by {app}.\n# It is not intended for manual editing.\n")?;
@@ -179,6 +278,7 @@ impl Default for LockFile {
         Self {
             version: LOCK_FILE_VERSION,
             packages: Vec::new(),
+            local_packages: Vec::new(),
         }
     }
 }
@@ -562,3 +662,68 @@ mod sys {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn read(contents: &str) -> Result<LockFile> {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        LockFile::read(&file)
+    }
+
+    #[test]
+    fn it_reads_previous_versions() {
+        let digest = "sha256:7c8aa6e0c3b6a0b5ffb8e6f19d7dd1cdd6e40d5e4ce63c8a7b06c8ee7ac62b7b";
+        let lock_file = read(&format!(
+            r#"version = 1
+
+[[package]]
+name = "foo:bar"
+
+[[package.version]]
+requirement = "^1.0.0"
+version = "1.0.0"
+digest = "{digest}"
+"#
+        ))
+        .unwrap();
+
+        assert_eq!(lock_file.version, LOCK_FILE_VERSION);
+        assert_eq!(lock_file.packages.len(), 1);
+        assert!(lock_file.local_packages.is_empty());
+
+        let lock_file = read(&format!(
+            r#"version = 2
+
+[[local-package]]
+name = "foo:local"
+digest = "{digest}"
+"#
+        ))
+        .unwrap();
+        assert_eq!(lock_file.local_packages.len(), 1);
+
+        // Lock files are only written as version 2 if they have local packages
+        let written = |lock_file: &LockFile| {
+            let mut file = tempfile::tempfile().unwrap();
+            lock_file.write(&file, "test").unwrap();
+            file.seek(SeekFrom::Start(0)).unwrap();
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            contents
+        };
+        assert!(written(&lock_file).contains("\nversion = 2\n"));
+        assert!(written(&LockFile::default()).contains("\nversion = 1\n"));
+
+        for version in [0, 3] {
+            assert!(read(&format!("version = {version}\n"))
+                .unwrap_err()
+                .to_string()
+                .contains("unsupported file format version"));
+        }
+    }
+}
@@ -0,0 +1,838 @@
+//! Module for fetching packages from OCI registries.
+
+use crate::lock::LockedPackageVersion;
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use oci_distribution::{
+    client::{ClientConfig, ClientProtocol},
+    secrets::RegistryAuth,
+    Client, Reference,
+};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use warg_crypto::hash::AnyHash;
+use warg_protocol::registry::PackageName;
+
+/// The environment variable specifying the directory to cache OCI packages in.
+pub const OCI_CACHE_DIR_ENV_VAR: &str = "CARGO_COMPONENT_OCI_CACHE_DIR";
+
+/// The environment variable specifying the directory of the docker
+/// configuration file used for OCI registry credentials.
+pub const DOCKER_CONFIG_ENV_VAR: &str = "DOCKER_CONFIG";
+
+/// The media type of the layer containing the package contents.
+const WASM_LAYER_MEDIA_TYPE: &str = "application/wasm";
+
+/// Gets the directory packages fetched from OCI registries are cached in.
+///
+/// This is the directory specified by the `CARGO_COMPONENT_OCI_CACHE_DIR`
+/// environment variable, if set, or an `oci` directory in the
+/// [`cargo-component` cache directory](crate::cache_dir).
+pub fn oci_cache_dir() -> Result<PathBuf> {
+    match std::env::var_os(OCI_CACHE_DIR_ENV_VAR) {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(crate::cache_dir()?.join("oci")),
+    }
+}
+
+/// Gets the OCI reference of a package in the given registry namespace.
+///
+/// The reference is tagged with the given version, if any.
+pub fn package_reference(
+    namespace: &str,
+    package: &PackageName,
+    version: Option<&Version>,
+) -> String {
+    let reference = format!(
+        "{namespace}/{ns}/{name}",
+        ns = package.namespace(),
+        name = package.name()
+    );
+
+    match version {
+        Some(version) => format!("{reference}:{version}"),
+        None => reference,
+    }
+}
+
+/// Represents a package fetched from an OCI registry.
+#[derive(Debug, Clone)]
+pub struct OciPackage {
+    /// The version of the package that was fetched.
+    pub version: Version,
+    /// The digest of the package contents.
+    pub digest: AnyHash,
+    /// The path to the cached package contents.
+    pub path: PathBuf,
+}
+
+/// Represents the credentials of OCI registries from a docker configuration
+/// file.
+///
+/// Only credentials stored directly in the `auths` table are supported;
+/// credential helpers are not consulted.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DockerCredentials {
+    #[serde(default)]
+    auths: HashMap<String, DockerAuth>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct DockerAuth {
+    auth: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl DockerCredentials {
+    /// Loads the credentials from the docker configuration file.
+    ///
+    /// The file is `config.json` in the directory given by the
+    /// `DOCKER_CONFIG` environment variable, or in `~/.docker` otherwise.
+    /// A missing file has no credentials.
+    pub fn load() -> Result<Self> {
+        let dir = match std::env::var_os(DOCKER_CONFIG_ENV_VAR) {
+            Some(dir) => PathBuf::from(dir),
+            None => match std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+                Some(home) => Path::new(&home).join(".docker"),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let path = dir.join("config.json");
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents).with_context(|| {
+                format!(
+                    "failed to parse docker configuration file `{path}`",
+                    path = path.display()
+                )
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| {
+                format!(
+                    "failed to read docker configuration file `{path}`",
+                    path = path.display()
+                )
+            }),
+        }
+    }
+
+    /// Parses the credentials from the contents of a docker configuration
+    /// file.
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(serde_json::from_str(contents)?)
+    }
+
+    /// Gets the authentication to use for the given registry host.
+    ///
+    /// Entries of the configuration may be a bare host (e.g. `ghcr.io`) or a
+    /// URL (e.g. `https://ghcr.io/v2/`).
+    pub fn auth(&self, registry: &str) -> Result<RegistryAuth> {
+        let Some(entry) = self.auths.iter().find_map(|(key, entry)| {
+            let host = key
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .split('/')
+                .next()
+                .unwrap_or_default();
+            (host == registry).then_some(entry)
+        }) else {
+            return Ok(RegistryAuth::Anonymous);
+        };
+
+        if let (Some(username), Some(password)) = (&entry.username, &entry.password) {
+            return Ok(RegistryAuth::Basic(username.clone(), password.clone()));
+        }
+
+        let Some(auth) = &entry.auth else {
+            return Ok(RegistryAuth::Anonymous);
+        };
+
+        let decoded = STANDARD
+            .decode(auth)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .with_context(|| format!("invalid docker credentials for OCI registry `{registry}`"))?;
+        let (username, password) = decoded
+            .split_once(':')
+            .with_context(|| format!("invalid docker credentials for OCI registry `{registry}`"))?;
+        Ok(RegistryAuth::Basic(
+            username.to_string(),
+            password.to_string(),
+        ))
+    }
+}
+
+/// A client for fetching packages from OCI registries.
+///
+/// Fetched packages are stored in the client's cache directory.
+#[derive(Debug, Clone)]
+pub struct OciClient {
+    cache_dir: PathBuf,
+    credentials: DockerCredentials,
+}
+
+impl OciClient {
+    /// Creates a new client with the given cache directory and credentials.
+    pub fn new(cache_dir: PathBuf, credentials: DockerCredentials) -> Self {
+        Self {
+            cache_dir,
+            credentials,
+        }
+    }
+
+    /// Creates a new client using the default cache directory and the
+    /// credentials of the docker configuration file.
+    pub fn from_env() -> Result<Self> {
+        Ok(Self::new(oci_cache_dir()?, DockerCredentials::load()?))
+    }
+
+    /// Gets the directory packages are cached in.
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Fetches the latest version of a package matching the given requirement
+    /// from the given OCI registry namespace.
+    ///
+    /// If the package is locked and its contents are already cached, the
+    /// registry is not contacted. Likewise, if `prefer_cached` is set and the
+    /// package is not locked, the latest cached version matching the
+    /// requirement is used.
+    ///
+    /// Cached contents are only used if they still match their digest.
+    pub async fn fetch_package(
+        &self,
+        namespace: &str,
+        package: &PackageName,
+        requirement: &VersionReq,
+        locked: Option<&LockedPackageVersion>,
+        network_allowed: bool,
+        prefer_cached: bool,
+    ) -> Result<OciPackage> {
+        if let Some(locked) = locked {
+            if let Some(path) = self.cached_contents(&locked.digest)? {
+                return Ok(OciPackage {
+                    version: locked.version.clone(),
+                    digest: locked.digest.clone(),
+                    path,
+                });
+            }
+        } else if prefer_cached {
+            if let Some(cached) = self.latest_cached_version(namespace, package, requirement) {
+                log::info!(
+                    "using cached version {version} of OCI package `{package}`",
+                    version = cached.version
+                );
+                return Ok(cached);
+            }
+        }
+
+        if !network_allowed {
+            bail!("a component registry update is required but network access is disabled");
+        }
+
+        let reference = parse_reference(&package_reference(namespace, package, None))?;
+        let client = Client::new(ClientConfig {
+            protocol: client_protocol(reference.registry()),
+            ..Default::default()
+        });
+        let auth = self.credentials.auth(reference.registry())?;
+
+        let version = match locked {
+            Some(locked) => locked.version.clone(),
+            None => {
+                let tags = client
+                    .list_tags(&reference, &auth, None, None)
+                    .await
+                    .with_context(|| {
+                        format!("failed to list the tags of OCI package `{reference}`")
+                    })?;
+
+                tags.tags
+                    .iter()
+                    .filter_map(|tag| tag.parse::<Version>().ok())
+                    .filter(|version| requirement.matches(version))
+                    .max()
+                    .with_context(|| {
+                        format!("OCI package `{reference}` has no version matching requirement `{requirement}`")
+                    })?
+            }
+        };
+
+        let reference = parse_reference(&package_reference(namespace, package, Some(&version)))?;
+        log::debug!("pulling OCI package `{reference}`");
+
+        let image = client
+            .pull(&reference, &auth, vec![WASM_LAYER_MEDIA_TYPE])
+            .await
+            .with_context(|| format!("failed to pull OCI package `{reference}`"))?;
+
+        let layer = image
+            .layers
+            .into_iter()
+            .find(|layer| layer.media_type == WASM_LAYER_MEDIA_TYPE)
+            .with_context(|| {
+                format!(
+                    "OCI package `{reference}` does not contain a `{WASM_LAYER_MEDIA_TYPE}` layer"
+                )
+            })?;
+
+        let digest = content_digest(&layer.data)?;
+
+        if let Some(locked) = locked {
+            if locked.digest != digest {
+                bail!(
+                    "OCI package `{reference}` has digest `{digest}` but the lock file expects `{expected}`",
+                    expected = locked.digest
+                );
+            }
+        }
+
+        let path = self.cache_dir.join(content_file_name(&digest));
+        write_atomic(&path, &layer.data).with_context(|| {
+            format!(
+                "failed to write OCI package contents to `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        // Record the version of the cached contents for `prefer_cached`
+        let index_path = self.cached_version_path(namespace, package, &version);
+        write_atomic(&index_path, digest.to_string().as_bytes()).with_context(|| {
+            format!(
+                "failed to write OCI cache index `{path}`",
+                path = index_path.display()
+            )
+        })?;
+
+        Ok(OciPackage {
+            version,
+            digest,
+            path,
+        })
+    }
+
+    /// Gets the path of the cached contents with the given digest.
+    ///
+    /// Returns `None` if the contents are not cached or no longer match the
+    /// digest, in which case they are removed from the cache.
+    fn cached_contents(&self, digest: &AnyHash) -> Result<Option<PathBuf>> {
+        let path = self.cache_dir.join(content_file_name(digest));
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "failed to read cached OCI package contents `{path}`",
+                        path = path.display()
+                    )
+                })
+            }
+        };
+
+        if content_digest(&contents)? == *digest {
+            return Ok(Some(path));
+        }
+
+        log::warn!(
+            "cached OCI package contents `{path}` do not match digest `{digest}`; fetching them again",
+            path = path.display()
+        );
+        fs::remove_file(&path).with_context(|| {
+            format!(
+                "failed to remove cached OCI package contents `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        Ok(None)
+    }
+
+    /// Gets the path of the cache index entry recording the digest of the
+    /// given version of a package.
+    pub(crate) fn cached_version_path(
+        &self,
+        namespace: &str,
+        package: &PackageName,
+        version: &Version,
+    ) -> PathBuf {
+        let reference = package_reference(namespace, package, None).replace(['/', ':'], "-");
+        self.cache_dir
+            .join("index")
+            .join(reference)
+            .join(version.to_string())
+    }
+
+    /// Gets the latest version of a package matching the given requirement
+    /// whose contents are cached.
+    fn latest_cached_version(
+        &self,
+        namespace: &str,
+        package: &PackageName,
+        requirement: &VersionReq,
+    ) -> Option<OciPackage> {
+        let dir = self
+            .cached_version_path(namespace, package, &Version::new(0, 0, 0))
+            .parent()?
+            .to_path_buf();
+
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let version: Version = entry.file_name().to_str()?.parse().ok()?;
+                if !requirement.matches(&version) {
+                    return None;
+                }
+
+                let digest: AnyHash = fs::read_to_string(entry.path()).ok()?.trim().parse().ok()?;
+                let path = self.cached_contents(&digest).ok()??;
+                Some(OciPackage {
+                    version,
+                    digest,
+                    path,
+                })
+            })
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+/// Gets the protocol used to contact the given registry.
+///
+/// As with docker, registries on the local host are contacted over plain
+/// HTTP.
+fn client_protocol(registry: &str) -> ClientProtocol {
+    let host = match registry.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => registry,
+    };
+
+    match host {
+        "localhost" | "127.0.0.1" | "[::1]" => ClientProtocol::Http,
+        _ => ClientProtocol::Https,
+    }
+}
+
+fn parse_reference(reference: &str) -> Result<Reference> {
+    reference
+        .parse()
+        .with_context(|| format!("invalid OCI reference `{reference}`"))
+}
+
+/// Gets the digest of the given package contents.
+fn content_digest(contents: &[u8]) -> Result<AnyHash> {
+    Ok(format!(
+        "sha256:{digest}",
+        digest = Sha256::digest(contents)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>()
+    )
+    .parse()?)
+}
+
+/// Writes the given contents to the given path by renaming a temporary file
+/// in the same directory, so the path never has partially written contents.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().context("path has no parent directory")?;
+    fs::create_dir_all(dir).with_context(|| {
+        format!(
+            "failed to create OCI cache directory `{path}`",
+            path = dir.display()
+        )
+    })?;
+
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    std::io::Write::write_all(&mut file, contents)?;
+    file.persist(path)?;
+    Ok(())
+}
+
+fn content_file_name(digest: &AnyHash) -> String {
+    format!(
+        "{digest}.wasm",
+        digest = digest.to_string().replace(':', "-")
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+    };
+
+    fn sha256(contents: &[u8]) -> String {
+        format!(
+            "sha256:{digest}",
+            digest = Sha256::digest(contents)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        )
+    }
+
+    /// Starts a minimal OCI registry serving `test/wasi/http` over plain HTTP.
+    ///
+    /// Each version's package contents are `contents of <version>`. If
+    /// credentials are given, requests must use them with basic
+    /// authentication. Returns the address of the registry.
+    fn start_registry(versions: &[&str], credentials: Option<(&str, &str)>) -> String {
+        let packages: Vec<_> = versions
+            .iter()
+            .map(|version| TestPackage {
+                repository: "test/wasi/http".to_string(),
+                version: version.to_string(),
+                contents: format!("contents of {version}").into_bytes(),
+            })
+            .collect();
+        start_test_registry(&packages, &HashMap::new(), credentials)
+    }
+
+    /// Represents a package version served by a test OCI registry.
+    pub(crate) struct TestPackage {
+        /// The repository of the package (e.g. `test/wasi/http`).
+        pub repository: String,
+        /// The version (tag) of the package.
+        pub version: String,
+        /// The contents of the package's wasm layer.
+        pub contents: Vec<u8>,
+    }
+
+    /// Starts a minimal OCI registry serving the given packages over plain
+    /// HTTP.
+    ///
+    /// Each connection is handled on its own thread, and listing the tags of
+    /// a repository in `delays` (including repositories that are not served)
+    /// is delayed by the given duration. If credentials are given, requests
+    /// must use them with basic authentication. Returns the address of the
+    /// registry.
+    pub(crate) fn start_test_registry(
+        packages: &[TestPackage],
+        delays: &HashMap<String, std::time::Duration>,
+        credentials: Option<(&str, &str)>,
+    ) -> String {
+        const CONFIG: &[u8] = b"{}";
+
+        let mut routes: HashMap<String, (&str, Vec<u8>, Option<String>)> = HashMap::new();
+        routes.insert(
+            "/v2/".to_string(),
+            ("application/json", b"{}".to_vec(), None),
+        );
+
+        let mut tags: HashMap<&str, Vec<&str>> = HashMap::new();
+        for package in packages {
+            tags.entry(&package.repository)
+                .or_default()
+                .push(&package.version);
+        }
+
+        for (repository, tags) in tags {
+            routes.insert(
+                format!("/v2/{repository}/tags/list"),
+                (
+                    "application/json",
+                    serde_json::json!({ "name": repository, "tags": tags })
+                        .to_string()
+                        .into_bytes(),
+                    None,
+                ),
+            );
+            routes.insert(
+                format!("/v2/{repository}/blobs/{digest}", digest = sha256(CONFIG)),
+                ("application/octet-stream", CONFIG.to_vec(), None),
+            );
+        }
+
+        for TestPackage {
+            repository,
+            version,
+            contents,
+        } in packages
+        {
+            let manifest = serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                "config": {
+                    "mediaType": "application/vnd.oci.image.config.v1+json",
+                    "digest": sha256(CONFIG),
+                    "size": CONFIG.len(),
+                },
+                "layers": [{
+                    "mediaType": WASM_LAYER_MEDIA_TYPE,
+                    "digest": sha256(contents),
+                    "size": contents.len(),
+                }],
+            })
+            .to_string()
+            .into_bytes();
+
+            let digest = sha256(&manifest);
+            routes.insert(
+                format!("/v2/{repository}/manifests/{version}"),
+                (
+                    "application/vnd.oci.image.manifest.v1+json",
+                    manifest,
+                    Some(digest),
+                ),
+            );
+            routes.insert(
+                format!("/v2/{repository}/blobs/{digest}", digest = sha256(contents)),
+                ("application/octet-stream", contents.clone(), None),
+            );
+        }
+
+        let expected_auth = credentials.map(|(username, password)| {
+            format!(
+                "Basic {auth}",
+                auth = STANDARD.encode(format!("{username}:{password}"))
+            )
+        });
+
+        let routes = std::sync::Arc::new(routes);
+        let delays = std::sync::Arc::new(delays.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let routes = routes.clone();
+                let delays = delays.clone();
+                let expected_auth = expected_auth.clone();
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request = String::new();
+                    reader.read_line(&mut request).unwrap();
+                    let path = request.split(' ').nth(1).unwrap_or_default();
+                    let path = path.split('?').next().unwrap().to_string();
+
+                    let mut authorization = None;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("authorization") {
+                                authorization = Some(value.trim().to_string());
+                            }
+                        }
+                    }
+
+                    if let Some(delay) = delays
+                        .iter()
+                        .find(|(repository, _)| path == format!("/v2/{repository}/tags/list"))
+                        .map(|(_, delay)| *delay)
+                    {
+                        std::thread::sleep(delay);
+                    }
+
+                    let authorized = expected_auth.is_none() || authorization == expected_auth;
+                    let (status, headers, body) = match routes.get(&path) {
+                        _ if !authorized => (
+                            "401 Unauthorized",
+                            "WWW-Authenticate: Basic realm=\"test\"\r\n".to_string(),
+                            Vec::new(),
+                        ),
+                        Some((content_type, body, digest)) => (
+                            "200 OK",
+                            format!(
+                                "Content-Type: {content_type}\r\n{digest}",
+                                digest = digest
+                                    .as_ref()
+                                    .map(|d| format!("Docker-Content-Digest: {d}\r\n"))
+                                    .unwrap_or_default()
+                            ),
+                            body.clone(),
+                        ),
+                        None => ("404 Not Found", String::new(), Vec::new()),
+                    };
+
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {status}\r\n{headers}Content-Length: {len}\r\nConnection: close\r\n\r\n",
+                        len = body.len()
+                    );
+                    let _ = stream.write_all(&body);
+                });
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn it_reads_docker_credentials() {
+        let credentials = DockerCredentials::parse(&format!(
+            r#"{{
+    "auths": {{
+        "ghcr.io": {{ "auth": "{auth}" }},
+        "https://registry.example.com/v2/": {{ "username": "user", "password": "pass" }},
+        "invalid.example.com": {{ "auth": "not base64!" }}
+    }},
+    "credsStore": "desktop"
+}}"#,
+            auth = STANDARD.encode("octocat:token")
+        ))
+        .unwrap();
+
+        assert!(matches!(
+            credentials.auth("ghcr.io").unwrap(),
+            RegistryAuth::Basic(username, password) if username == "octocat" && password == "token"
+        ));
+        assert!(matches!(
+            credentials.auth("registry.example.com").unwrap(),
+            RegistryAuth::Basic(username, password) if username == "user" && password == "pass"
+        ));
+        assert!(matches!(
+            credentials.auth("docker.io").unwrap(),
+            RegistryAuth::Anonymous
+        ));
+        assert_eq!(
+            credentials
+                .auth("invalid.example.com")
+                .unwrap_err()
+                .to_string(),
+            "invalid docker credentials for OCI registry `invalid.example.com`"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fetches_packages_from_a_registry() {
+        let addr = start_registry(&["1.0.0", "1.1.0", "2.0.0"], Some(("user", "secret")));
+        let namespace = format!("{addr}/test");
+        let package: PackageName = "wasi:http".parse().unwrap();
+        let requirement: VersionReq = "^1.0.0".parse().unwrap();
+        let credentials = DockerCredentials::parse(&format!(
+            r#"{{ "auths": {{ "{addr}": {{ "auth": "{auth}" }} }} }}"#,
+            auth = STANDARD.encode("user:secret")
+        ))
+        .unwrap();
+
+        // Fetching without credentials is rejected by the registry
+        let cache = tempfile::tempdir().unwrap();
+        let client = OciClient::new(cache.path().to_path_buf(), DockerCredentials::default());
+        let err = client
+            .fetch_package(&namespace, &package, &requirement, None, true, false)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("failed to list the tags"),
+            "{err:?}"
+        );
+
+        // The latest matching version is fetched and cached
+        let client = OciClient::new(cache.path().to_path_buf(), credentials.clone());
+        let fetched = client
+            .fetch_package(&namespace, &package, &requirement, None, true, false)
+            .await
+            .unwrap();
+        assert_eq!(fetched.version, Version::new(1, 1, 0));
+        assert_eq!(fetched.digest.to_string(), sha256(b"contents of 1.1.0"));
+        assert_eq!(fs::read(&fetched.path).unwrap(), b"contents of 1.1.0");
+
+        // The cached version is used without contacting the registry
+        let cached = client
+            .fetch_package(&namespace, &package, &requirement, None, false, true)
+            .await
+            .unwrap();
+        assert_eq!(cached.version, fetched.version);
+        assert_eq!(cached.path, fetched.path);
+
+        // A locked version is pulled and its digest is verified
+        let cache = tempfile::tempdir().unwrap();
+        let client = OciClient::new(cache.path().to_path_buf(), credentials);
+        let locked = LockedPackageVersion {
+            requirement: "^1.0.0".to_string(),
+            version: Version::new(1, 0, 0),
+            digest: sha256(b"contents of 1.0.0").parse().unwrap(),
+        };
+        let fetched = client
+            .fetch_package(
+                &namespace,
+                &package,
+                &requirement,
+                Some(&locked),
+                true,
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(fetched.version, Version::new(1, 0, 0));
+        assert_eq!(fs::read(&fetched.path).unwrap(), b"contents of 1.0.0");
+
+        // Cached contents that no longer match their digest are fetched again
+        fs::write(&fetched.path, b"tampered").unwrap();
+        let fetch = |network_allowed| {
+            client.fetch_package(
+                &namespace,
+                &package,
+                &requirement,
+                Some(&locked),
+                network_allowed,
+                false,
+            )
+        };
+        let err = fetch(false).await.unwrap_err();
+        assert!(
+            err.to_string().contains("network access is disabled"),
+            "{err:?}"
+        );
+        assert!(!fetched.path.exists());
+        let refetched = fetch(true).await.unwrap();
+        assert_eq!(fs::read(&refetched.path).unwrap(), b"contents of 1.0.0");
+
+        let cache = tempfile::tempdir().unwrap();
+        let client = OciClient::new(cache.path().to_path_buf(), client.credentials);
+        let locked = LockedPackageVersion {
+            digest: sha256(b"tampered").parse().unwrap(),
+            ..locked
+        };
+        let err = client
+            .fetch_package(
+                &namespace,
+                &package,
+                &requirement,
+                Some(&locked),
+                true,
+                false,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "OCI package `{namespace}/wasi/http:1.0.0` has digest `{digest}` but the lock file expects `{expected}`",
+                digest = sha256(b"contents of 1.0.0"),
+                expected = sha256(b"tampered")
+            )
+        );
+        assert!(fs::read_dir(cache.path()).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn it_formats_package_references() {
+        let package = "wasi:http".parse().unwrap();
+        assert_eq!(
+            package_reference("ghcr.io/example", &package, None),
+            "ghcr.io/example/wasi/http"
+        );
+        assert_eq!(
+            package_reference("ghcr.io/example", &package, Some(&Version::new(0, 2, 0))),
+            "ghcr.io/example/wasi/http:0.2.0"
+        );
+    }
+}
@@ -1,25 +1,35 @@
 //! Module for resolving dependencies from a component registry.
 
 use crate::{
+    archive::extract_archive,
+    features::{self, FeatureSet},
     lock::{LockFileResolver, LockedPackageVersion},
+    oci::OciClient,
     progress::{ProgressBar, ProgressStyle},
     terminal::{Colors, Terminal},
 };
-use anyhow::{bail, Context, Result};
-use futures::{stream::FuturesUnordered, StreamExt};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::{
+    stream::{self, FuturesUnordered},
+    StreamExt,
+};
 use indexmap::IndexMap;
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
 use semver::{Comparator, Op, Version, VersionReq};
 use serde::{
     de::{self, value::MapAccessDeserializer},
     Deserialize, Serialize,
 };
+use sha2::{Digest, Sha256};
 use std::{
-    collections::{hash_map, HashMap, HashSet},
+    borrow::Cow,
+    collections::{hash_map, BTreeMap, HashMap, HashSet},
     fs,
+    future::Future,
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
+    time::Duration,
 };
 use url::Url;
 use warg_client::{
@@ -28,119 +38,136 @@ use warg_client::{
 };
 use warg_credentials::keyring::get_auth_token;
 use warg_crypto::hash::AnyHash;
-use warg_protocol::registry;
+use warg_protocol::{package::Release, registry};
 use wit_component::DecodedWasm;
 use wit_parser::{PackageId, PackageName, Resolve, UnresolvedPackage, WorldId};
 
 /// The name of the default registry.
 pub const DEFAULT_REGISTRY_NAME: &str = "default";
 
-/// Finds the URL for the given registry name.
-pub fn find_url<'a>(
-    name: Option<&str>,
-    urls: &'a HashMap<String, Url>,
-    default: Option<&'a str>,
-) -> Result<&'a str> {
-    let name = name.unwrap_or(DEFAULT_REGISTRY_NAME);
-    match urls.get(name) {
-        Some(url) => Ok(url.as_str()),
-        None if name != DEFAULT_REGISTRY_NAME => {
-            bail!("component registry `{name}` does not exist in the configuration")
+/// The default maximum number of packages fetched concurrently.
+pub const DEFAULT_FETCH_CONCURRENCY: usize = 8;
+
+/// The environment variable used to specify the default registry URL.
+///
+/// This is used when the default registry is not present in the configuration.
+pub const REGISTRY_ENV_VAR: &str = "CARGO_COMPONENT_REGISTRY";
+
+/// The warg protocol version preferred by the registry client.
+///
+/// This is the version used for a warg registry without an explicit
+/// `protocol` in its registry entry.
+pub const WARG_PROTOCOL_VERSION: u8 = 1;
+
+/// The warg protocol versions supported by the registry client.
+pub const SUPPORTED_WARG_PROTOCOL_VERSIONS: &[u8] = &[WARG_PROTOCOL_VERSION];
+
+/// Represents a reference to a component registry.
+///
+/// A registry is specified either as a bare URL, which refers to a warg
+/// registry, or as a table with a `warg` or `oci` key.
+///
+/// A `warg` table may also specify the `protocol` version of the registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryRef {
+    /// A warg registry at the given URL.
+    Warg {
+        /// The URL of the registry.
+        url: Url,
+        /// The warg protocol version of the registry.
+        ///
+        /// Defaults to [`WARG_PROTOCOL_VERSION`] when `None`.
+        ///
+        /// This is a validation check only: the version must be supported by
+        /// the client and served by the registry, but the registry client
+        /// always uses the protocol version it was built with.
+        protocol: Option<u8>,
+    },
+    /// An OCI registry namespace (e.g. `ghcr.io/example`).
+    ///
+    /// Packages are referenced as `<namespace>/<package namespace>/<package name>:<version>`.
+    Oci(String),
+}
+
+impl RegistryRef {
+    /// Gets the URL of a warg registry.
+    ///
+    /// Returns `None` for an OCI registry.
+    pub fn warg_url(&self) -> Option<&Url> {
+        match self {
+            Self::Warg { url, .. } => Some(url),
+            Self::Oci(_) => None,
         }
-        None => default.context("a default component registry has not been set"),
     }
-}
 
-/// Gets the auth token for the given registry URL.
-pub fn auth_token(config: &Config, registry: Option<String>) -> Result<Option<Secret<String>>> {
-    if config.keyring_auth {
-        return if let Some(reg_url) = registry {
-            Ok(get_auth_token(&RegistryUrl::new(reg_url)?)?)
-        } else if let Some(url) = config.home_url.as_ref() {
-            Ok(get_auth_token(&RegistryUrl::new(url)?)?)
-        } else {
-            Ok(None)
-        };
+    /// Gets the explicit warg protocol version of a warg registry.
+    ///
+    /// Returns `None` for an OCI registry or if no version was specified.
+    pub fn warg_protocol(&self) -> Option<u8> {
+        match self {
+            Self::Warg { protocol, .. } => *protocol,
+            Self::Oci(_) => None,
+        }
     }
-    Ok(None)
 }
-/// Creates a registry client with the given warg configuration.
-pub fn create_client(
-    config: &warg_client::Config,
-    url: &str,
-    terminal: &Terminal,
-) -> Result<FileSystemClient> {
-    match FileSystemClient::try_new_with_config(
-        Some(url),
-        config,
-        auth_token(config, Some(url.to_string()))?,
-    )? {
-        StorageLockResult::Acquired(client) => Ok(client),
-        StorageLockResult::NotAcquired(path) => {
-            terminal.status_with_color(
-                "Blocking",
-                format!("waiting for file lock on `{path}`", path = path.display()),
-                Colors::Cyan,
-            )?;
 
-            Ok(FileSystemClient::new_with_config(
-                Some(url),
-                config,
-                auth_token(config, Some(url.to_string()))?,
-            )?)
+impl From<Url> for RegistryRef {
+    fn from(url: Url) -> Self {
+        Self::Warg {
+            url,
+            protocol: None,
         }
     }
 }
 
-/// Represents a WIT package dependency.
-#[derive(Debug, Clone)]
-pub enum Dependency {
-    /// The dependency is a registry package.
-    Package(RegistryPackage),
-
-    /// The dependency is a path to a local directory or file.
-    Local(PathBuf),
+impl std::fmt::Display for RegistryRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warg { url, .. } => write!(f, "{url}"),
+            Self::Oci(namespace) => write!(f, "oci://{namespace}"),
+        }
+    }
 }
 
-impl Serialize for Dependency {
+impl Serialize for RegistryRef {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
         match self {
-            Self::Package(package) => {
-                if package.name.is_none() && package.registry.is_none() {
-                    let version = package.version.to_string();
-                    version.trim_start_matches('^').serialize(serializer)
-                } else {
-                    #[derive(Serialize)]
-                    struct Entry<'a> {
-                        package: Option<&'a registry::PackageName>,
-                        version: &'a str,
-                        registry: Option<&'a str>,
-                    }
+            Self::Warg {
+                url,
+                protocol: None,
+            } => url.serialize(serializer),
+            Self::Warg {
+                url,
+                protocol: Some(protocol),
+            } => {
+                #[derive(Serialize)]
+                struct Entry<'a> {
+                    warg: &'a Url,
+                    protocol: u8,
+                }
 
-                    Entry {
-                        package: package.name.as_ref(),
-                        version: package.version.to_string().trim_start_matches('^'),
-                        registry: package.registry.as_deref(),
-                    }
-                    .serialize(serializer)
+                Entry {
+                    warg: url,
+                    protocol: *protocol,
                 }
+                .serialize(serializer)
             }
-            Self::Local(path) => {
+            Self::Oci(namespace) => {
                 #[derive(Serialize)]
                 struct Entry<'a> {
-                    path: &'a PathBuf,
+                    oci: &'a str,
                 }
 
-                Entry { path }.serialize(serializer)
+                Entry { oci: namespace }.serialize(serializer)
             }
         }
     }
 }
 
-impl<'de> Deserialize<'de> for Dependency {
+impl<'de> Deserialize<'de> for RegistryRef {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
@@ -148,17 +175,20 @@ impl<'de> Deserialize<'de> for Dependency {
         struct Visitor;
 
         impl<'de> de::Visitor<'de> for Visitor {
-            type Value = Dependency;
+            type Value = RegistryRef;
 
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                write!(formatter, "a string or a table")
+                write!(formatter, "a URL or a table")
             }
 
             fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
             where
                 E: de::Error,
             {
-                Ok(Self::Value::Package(s.parse().map_err(de::Error::custom)?))
+                Ok(RegistryRef::Warg {
+                    url: s.parse().map_err(de::Error::custom)?,
+                    protocol: None,
+                })
             }
 
             fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
@@ -168,34 +198,50 @@ impl<'de> Deserialize<'de> for Dependency {
                 #[derive(Default, Deserialize)]
                 #[serde(default, deny_unknown_fields)]
                 struct Entry {
-                    path: Option<PathBuf>,
-                    package: Option<registry::PackageName>,
-                    version: Option<VersionReq>,
-                    registry: Option<String>,
+                    warg: Option<Url>,
+                    oci: Option<String>,
+                    protocol: Option<u8>,
                 }
 
-                let entry = Entry::deserialize(MapAccessDeserializer::new(map))?;
+                match Entry::deserialize(MapAccessDeserializer::new(map))? {
+                    Entry {
+                        warg: Some(url),
+                        oci: None,
+                        protocol,
+                    } => Ok(RegistryRef::Warg { url, protocol }),
+                    Entry {
+                        warg: None,
+                        oci: Some(_),
+                        protocol: Some(_),
+                    } => Err(de::Error::custom(
+                        "the `protocol` field is only supported for `warg` registries",
+                    )),
+                    Entry {
+                        warg: None,
+                        oci: Some(namespace),
+                        protocol: None,
+                    } => {
+                        let namespace = namespace.trim_end_matches('/');
+                        if namespace.is_empty() {
+                            return Err(de::Error::custom("the `oci` field cannot be empty"));
+                        }
 
-                match (entry.path, entry.package, entry.version, entry.registry) {
-                    (Some(path), None, None, None) => Ok(Self::Value::Local(path)),
-                    (None, name, Some(version), registry) => {
-                        Ok(Self::Value::Package(RegistryPackage {
-                            name,
-                            version,
-                            registry,
-                        }))
+                        Ok(RegistryRef::Oci(namespace.to_string()))
                     }
-                    (Some(_), None, Some(_), _) => Err(de::Error::custom(
-                        "cannot specify both `path` and `version` fields in a dependency entry",
-                    )),
-                    (Some(_), None, None, Some(_)) => Err(de::Error::custom(
-                        "cannot specify both `path` and `registry` fields in a dependency entry",
+                    Entry {
+                        warg: Some(_),
+                        oci: Some(_),
+                        ..
+                    } => Err(de::Error::custom(
+                        "cannot specify both `warg` and `oci` fields in a registry entry",
                     )),
-                    (Some(_), Some(_), _, _) => Err(de::Error::custom(
-                        "cannot specify both `path` and `package` fields in a dependency entry",
+                    Entry {
+                        warg: None,
+                        oci: None,
+                        ..
+                    } => Err(de::Error::custom(
+                        "a registry entry must specify either a `warg` or an `oci` field",
                     )),
-                    (None, None, _, _) => Err(de::Error::missing_field("package")),
-                    (None, Some(_), None, _) => Err(de::Error::missing_field("version")),
                 }
             }
         }
@@ -204,705 +250,3850 @@ impl<'de> Deserialize<'de> for Dependency {
     }
 }
 
-impl FromStr for Dependency {
-    type Err = anyhow::Error;
+/// Finds the URL for the given registry name.
+///
+/// If the default registry is not present in the configuration, the
+/// `CARGO_COMPONENT_REGISTRY` environment variable is used before falling
+/// back to the given default.
+///
+/// Returns an error if the registry is an OCI registry.
+pub fn find_url<'a>(
+    name: Option<&str>,
+    urls: &'a HashMap<String, RegistryRef>,
+    default: Option<&'a str>,
+) -> Result<Cow<'a, str>> {
+    find_url_with_env(name, urls, default, std::env::var(REGISTRY_ENV_VAR).ok())
+}
 
-    fn from_str(s: &str) -> Result<Self> {
-        Ok(Self::Package(s.parse()?))
+/// Finds the URL for the given registry name, given the value of the
+/// `CARGO_COMPONENT_REGISTRY` environment variable.
+fn find_url_with_env<'a>(
+    name: Option<&str>,
+    urls: &'a HashMap<String, RegistryRef>,
+    default: Option<&'a str>,
+    env_url: Option<String>,
+) -> Result<Cow<'a, str>> {
+    let name = name.unwrap_or(DEFAULT_REGISTRY_NAME);
+    match urls.get(name) {
+        Some(RegistryRef::Warg { url, .. }) => Ok(url.as_str().into()),
+        Some(RegistryRef::Oci(namespace)) => {
+            bail!("component registry `{name}` is an OCI registry (`{namespace}`) and does not have a URL")
+        }
+        None if name != DEFAULT_REGISTRY_NAME => {
+            bail!("component registry `{name}` does not exist in the configuration")
+        }
+        None => match env_url {
+            Some(url) => {
+                Url::parse(&url).with_context(|| {
+                    format!("invalid registry URL `{url}` specified by environment variable `{REGISTRY_ENV_VAR}`")
+                })?;
+                Ok(url.into())
+            }
+            None => default
+                .map(Into::into)
+                .context("a default component registry has not been set"),
+        },
     }
 }
 
-/// Represents a reference to a registry package.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-pub struct RegistryPackage {
-    /// The name of the package.
-    ///
-    /// If not specified, the name from the mapping will be used.
-    pub name: Option<registry::PackageName>,
+/// Finds the explicit warg protocol version for the given registry name.
+///
+/// Returns `None` if the registry is not configured, is an OCI registry, or
+/// does not specify a protocol version.
+pub fn find_protocol(name: Option<&str>, refs: &HashMap<String, RegistryRef>) -> Option<u8> {
+    refs.get(name.unwrap_or(DEFAULT_REGISTRY_NAME))
+        .and_then(RegistryRef::warg_protocol)
+}
 
-    /// The version requirement of the package.
-    pub version: VersionReq,
+/// Selects the warg protocol version to use for the registry at the given URL.
+///
+/// The registry's protocol version is the given version, or
+/// [`WARG_PROTOCOL_VERSION`] if `None`; an error is returned if the client
+/// does not support it.
+pub fn select_protocol(url: &str, protocol: Option<u8>) -> Result<u8> {
+    let protocol = protocol.unwrap_or(WARG_PROTOCOL_VERSION);
+    if !SUPPORTED_WARG_PROTOCOL_VERSIONS.contains(&protocol) {
+        bail!(
+            "component registry `{url}` uses warg protocol version {protocol}, which is incompatible with the supported {versions}",
+            versions = SUPPORTED_WARG_PROTOCOL_VERSIONS
+                .iter()
+                .map(|v| format!("version {v}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
 
-    /// The name of the component registry containing the package.
-    ///
-    /// If not specified, the default registry is used.
-    pub registry: Option<String>,
+    Ok(protocol)
 }
 
-impl FromStr for RegistryPackage {
-    type Err = anyhow::Error;
+/// Checks that the registry at the given URL serves the given warg protocol
+/// version.
+///
+/// The checkpoint endpoint of the protocol version is requested from the
+/// registry; a registry that does not serve the version responds with
+/// `404 Not Found`. Other responses (e.g. authentication failures) are left
+/// to the registry client to report.
+///
+/// The given auth token of the registry is sent with the request, so a
+/// protected registry that hides its endpoints from anonymous requests is
+/// not mistaken for one that does not serve the version.
+pub async fn check_protocol(url: &str, protocol: u8, token: Option<&Secret<String>>) -> Result<()> {
+    // Registry URLs without a scheme default to `https`
+    let mut base = if url.contains("://") {
+        Url::parse(url)
+    } else {
+        Url::parse(&format!("https://{url}"))
+    }
+    .with_context(|| format!("invalid registry URL `{url}`"))?;
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{path}/", path = base.path()));
+    }
 
-    fn from_str(s: &str) -> Result<Self> {
-        Ok(Self {
-            name: None,
-            version: s
-                .parse()
-                .with_context(|| format!("'{s}' is an invalid registry package version"))?,
-            registry: None,
-        })
+    let endpoint = base.join(&format!("v{protocol}/fetch/checkpoint"))?;
+
+    let mut client = reqwest::Client::builder().no_proxy();
+    if let Some(proxy) = ProxyEnv::from_env().select(url)? {
+        client = client.proxy(reqwest::Proxy::all(proxy.as_str())?);
     }
-}
 
-/// Represents information about a resolution of a registry package.
-#[derive(Clone, Debug)]
-pub struct RegistryResolution {
-    /// The name of the dependency that was resolved.
-    ///
-    /// This may differ from `package` if the dependency was renamed.
-    pub name: registry::PackageName,
-    /// The name of the package from the registry that was resolved.
-    pub package: registry::PackageName,
-    /// The name of the registry used to resolve the package.
-    ///
-    /// A value of `None` indicates that the default registry was used.
-    pub registry: Option<String>,
-    /// The version requirement that was used to resolve the package.
-    pub requirement: VersionReq,
-    /// The package version that was resolved.
-    pub version: Version,
-    /// The digest of the package contents.
-    pub digest: AnyHash,
-    /// The path to the resolved dependency.
-    pub path: PathBuf,
-}
+    let mut request = client.build()?.get(endpoint.clone());
+    if let Some(token) = token {
+        request = request.bearer_auth(token.expose_secret());
+    }
 
-/// Represents information about a resolution of a local file.
-#[derive(Clone, Debug)]
-pub struct LocalResolution {
-    /// The name of the dependency that was resolved.
-    pub name: registry::PackageName,
-    /// The path to the resolved dependency.
-    pub path: PathBuf,
+    let response = request.send().await.with_context(|| {
+        format!("failed to check the warg protocol version of component registry `{url}`")
+    })?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        bail!(
+            "component registry `{url}` does not serve warg protocol version {protocol} (`{endpoint}` was not found)\n\n\
+             check the `protocol` setting of the registry"
+        );
+    }
+
+    log::debug!(
+        "component registry `{url}` responded to `{endpoint}` with status {status}",
+        status = response.status()
+    );
+    Ok(())
 }
 
-/// Represents a resolution of a dependency.
-#[derive(Debug, Clone)]
-#[allow(clippy::large_enum_variant)]
-pub enum DependencyResolution {
-    /// The dependency is resolved from a registry package.
-    Registry(RegistryResolution),
-    /// The dependency is resolved from a local path.
-    Local(LocalResolution),
+/// The name of the file configuring the replacement of registry sources.
+pub const SOURCE_REPLACEMENT_FILE_NAME: &str = "cargo-component-config.toml";
+
+/// Represents the replacement of registry sources with mirrors.
+///
+/// This is read from the `[source-replacement]` table of a
+/// `cargo-component-config.toml` file. Keys are either registry URLs or
+/// package name prefixes (e.g. `wasi:` or `wasi:http`); values are the URLs
+/// of the mirrors to use instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SourceReplacement {
+    /// The mirror URLs keyed by registry URL or package name prefix.
+    #[serde(default)]
+    pub source_replacement: HashMap<String, Url>,
 }
 
-impl DependencyResolution {
-    /// Gets the name of the dependency that was resolved.
-    pub fn name(&self) -> &registry::PackageName {
-        match self {
-            Self::Registry(res) => &res.name,
-            Self::Local(res) => &res.name,
+impl SourceReplacement {
+    /// Finds and loads the source replacement configuration for the given
+    /// directory.
+    ///
+    /// The directory and its ancestors are searched for a
+    /// `cargo-component-config.toml` file; no sources are replaced if none
+    /// is found.
+    pub fn find(dir: &Path) -> Result<Self> {
+        for dir in dir.ancestors() {
+            let path = dir.join(SOURCE_REPLACEMENT_FILE_NAME);
+            if path.is_file() {
+                return Self::from_file(&path);
+            }
         }
+
+        Ok(Self::default())
     }
 
-    /// Gets the path to the resolved dependency.
-    pub fn path(&self) -> &Path {
-        match self {
-            Self::Registry(res) => &res.path,
-            Self::Local(res) => &res.path,
-        }
+    /// Loads the source replacement configuration from the given file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).with_context(|| {
+            format!(
+                "failed to read configuration file `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        toml_edit::de::from_str(&contents).with_context(|| {
+            format!(
+                "failed to parse configuration file `{path}`",
+                path = path.display()
+            )
+        })
     }
 
-    /// Gets the resolved version.
+    /// Gets the mirror to use in place of the given registry URL for the
+    /// given package.
     ///
-    /// Returns `None` if the dependency is not resolved from a registry package.
-    pub fn version(&self) -> Option<&Version> {
-        match self {
-            Self::Registry(res) => Some(&res.version),
-            Self::Local(_) => None,
-        }
+    /// A replacement of the registry URL takes precedence over a replacement
+    /// of a package name prefix; the longest matching prefix is used.
+    ///
+    /// Returns `None` if the source is not replaced.
+    pub fn replace(&self, package: &registry::PackageName, url: &str) -> Option<&Url> {
+        let url = url.trim_end_matches('/');
+        let package = package.to_string();
+
+        self.source_replacement
+            .iter()
+            .find(|(key, _)| key.contains("://") && key.trim_end_matches('/') == url)
+            .or_else(|| {
+                self.source_replacement
+                    .iter()
+                    .filter(|(key, _)| !key.contains("://") && package.starts_with(key.as_str()))
+                    .max_by_key(|(key, _)| key.len())
+            })
+            .map(|(_, mirror)| mirror)
     }
+}
 
-    /// The key used in sorting and searching the lock file package list.
-    ///
-    /// Returns `None` if the dependency is not resolved from a registry package.
-    pub fn key(&self) -> Option<(&registry::PackageName, Option<&str>)> {
-        match self {
-            DependencyResolution::Registry(pkg) => Some((&pkg.package, pkg.registry.as_deref())),
-            DependencyResolution::Local(_) => None,
-        }
+/// Expands the `{name}` and `{version}` placeholders in a registry URL.
+///
+/// URLs without placeholders are returned unchanged; an error is returned if
+/// the expanded URL does not parse.
+pub fn expand_url<'a>(
+    url: &'a str,
+    name: &registry::PackageName,
+    version: &str,
+) -> Result<Cow<'a, str>> {
+    const NAME: [&str; 2] = ["{name}", "%7Bname%7D"];
+    const VERSION: [&str; 2] = ["{version}", "%7Bversion%7D"];
+
+    if !NAME.iter().chain(VERSION.iter()).any(|p| url.contains(p)) {
+        return Ok(url.into());
     }
 
-    /// Decodes the resolved dependency.
-    pub fn decode(&self) -> Result<DecodedDependency> {
-        // If the dependency path is a directory, assume it contains wit to parse as a package.
-        if self.path().is_dir() {
-            return Ok(DecodedDependency::Wit {
-                resolution: self,
-                package: UnresolvedPackage::parse_dir(self.path()).with_context(|| {
-                    format!(
-                        "failed to parse dependency `{path}`",
-                        path = self.path().display()
-                    )
-                })?,
-            });
-        }
+    let name = name.to_string();
+    let mut expanded = url.to_string();
+    for placeholder in NAME {
+        expanded = expanded.replace(placeholder, &name);
+    }
 
-        let bytes = fs::read(self.path()).with_context(|| {
-            format!(
-                "failed to read content of dependency `{name}` at path `{path}`",
-                name = self.name(),
-                path = self.path().display()
-            )
-        })?;
+    for placeholder in VERSION {
+        expanded = expanded.replace(placeholder, version);
+    }
 
-        if &bytes[0..4] != b"\0asm" {
-            return Ok(DecodedDependency::Wit {
-                resolution: self,
-                package: UnresolvedPackage::parse(
-                    self.path(),
-                    std::str::from_utf8(&bytes).with_context(|| {
-                        format!(
-                            "dependency `{path}` is not UTF-8 encoded",
-                            path = self.path().display()
-                        )
-                    })?,
-                )?,
-            });
+    Url::parse(&expanded)
+        .with_context(|| format!("registry URL `{url}` expanded to invalid URL `{expanded}`"))?;
+
+    Ok(expanded.into())
+}
+
+/// Gets the version used to expand a templated registry URL for the given
+/// version requirement.
+///
+/// A requirement of a single version (e.g. `1.2.3` or `=1.2.3`) expands to
+/// that version; any other requirement expands to the requirement itself.
+fn template_version(requirement: &VersionReq) -> String {
+    match requirement.comparators.as_slice() {
+        [comparator] => {
+            let mut version = comparator.major.to_string();
+            if let Some(minor) = comparator.minor {
+                version += &format!(".{minor}");
+            }
+            if let Some(patch) = comparator.patch {
+                version += &format!(".{patch}");
+            }
+            if !comparator.pre.is_empty() {
+                version += &format!("-{pre}", pre = comparator.pre);
+            }
+            version
         }
+        _ => requirement.to_string(),
+    }
+}
 
-        Ok(DecodedDependency::Wasm {
-            resolution: self,
-            decoded: wit_component::decode(&bytes).with_context(|| {
-                format!(
-                    "failed to decode content of dependency `{name}` at path `{path}`",
-                    name = self.name(),
-                    path = self.path().display()
-                )
-            })?,
-        })
+/// Gets the auth token for the given registry URL.
+pub fn auth_token(config: &Config, registry: Option<String>) -> Result<Option<Secret<String>>> {
+    if config.keyring_auth {
+        return if let Some(reg_url) = registry {
+            Ok(get_auth_token(&RegistryUrl::new(reg_url)?)?)
+        } else if let Some(url) = config.home_url.as_ref() {
+            Ok(get_auth_token(&RegistryUrl::new(url)?)?)
+        } else {
+            Ok(None)
+        };
     }
+    Ok(None)
 }
 
-/// Represents a decoded dependency.
-pub enum DecodedDependency<'a> {
-    /// The dependency decoded from an unresolved WIT package.
-    Wit {
-        /// The resolution related to the decoded dependency.
-        resolution: &'a DependencyResolution,
-        /// The unresolved WIT package.
-        package: UnresolvedPackage,
-    },
-    /// The dependency decoded from a Wasm file.
-    Wasm {
-        /// The resolution related to the decoded dependency.
-        resolution: &'a DependencyResolution,
-        /// The decoded Wasm file.
-        decoded: DecodedWasm,
-    },
+/// The environment variable used to specify the path to the registry
+/// credentials file.
+pub const CREDENTIALS_ENV_VAR: &str = "CARGO_COMPONENT_CREDENTIALS";
+
+/// The environment variable used to specify a registry auth token.
+///
+/// The token is only sent to the registry specified by the
+/// `CARGO_COMPONENT_REGISTRY_TOKEN_URL` environment variable; it takes
+/// precedence over any token in the credentials file for that registry.
+pub const TOKEN_ENV_VAR: &str = "CARGO_COMPONENT_REGISTRY_TOKEN";
+
+/// The environment variable used to specify the URL of the registry the
+/// `CARGO_COMPONENT_REGISTRY_TOKEN` auth token is for.
+pub const TOKEN_URL_ENV_VAR: &str = "CARGO_COMPONENT_REGISTRY_TOKEN_URL";
+
+/// Represents the credentials of a single registry in the credentials file.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistryCredentials {
+    /// The auth token of the registry.
+    pub token: Option<String>,
+    /// Whether the registry requires authentication.
+    pub protected: bool,
 }
 
-impl<'a> DecodedDependency<'a> {
-    /// Fully resolves the dependency.
+impl std::fmt::Debug for RegistryCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegistryCredentials")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("protected", &self.protected)
+            .finish()
+    }
+}
+
+/// Represents the registry credentials file (`credentials.toml`).
+///
+/// The file contains a `registries` table keyed by registry URL.
+#[derive(Debug, Default)]
+pub struct Credentials {
+    path: PathBuf,
+    registries: HashMap<String, RegistryCredentials>,
+}
+
+impl Credentials {
+    /// Loads the credentials file.
     ///
-    /// If the dependency is an unresolved WIT package, it will assume that the
-    /// package has no foreign dependencies.
-    pub fn resolve(self) -> Result<(Resolve, PackageId, Vec<PathBuf>)> {
-        match self {
-            Self::Wit { package, .. } => {
-                let mut resolve = Resolve::new();
-                let source_files = package.source_files().map(Path::to_path_buf).collect();
-                let pkg = resolve.push(package)?;
-                Ok((resolve, pkg, source_files))
-            }
-            Self::Wasm { decoded, .. } => match decoded {
-                DecodedWasm::WitPackage(resolve, pkg) => Ok((resolve, pkg, Vec::new())),
-                DecodedWasm::Component(resolve, world) => {
-                    let pkg = resolve.worlds[world].package.unwrap();
-                    Ok((resolve, pkg, Vec::new()))
+    /// The path is taken from the `CARGO_COMPONENT_CREDENTIALS` environment
+    /// variable, defaulting to `component/credentials.toml` in the Cargo home
+    /// directory. A missing file has no credentials.
+    pub fn load() -> Result<Self> {
+        let path = match std::env::var_os(CREDENTIALS_ENV_VAR) {
+            Some(path) => PathBuf::from(path),
+            None => match std::env::var_os("CARGO_HOME") {
+                Some(home) => PathBuf::from(home),
+                None => {
+                    match std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+                        Some(home) => Path::new(&home).join(".cargo"),
+                        None => return Ok(Self::default()),
+                    }
                 }
-            },
-        }
-    }
+            }
+            .join("component")
+            .join("credentials.toml"),
+        };
 
-    /// Gets the package name of the decoded dependency.
-    pub fn package_name(&self) -> &PackageName {
-        match self {
-            Self::Wit { package, .. } => &package.name,
-            Self::Wasm { decoded, .. } => &decoded.resolve().packages[decoded.package()].name,
+        if !path.is_file() {
+            return Ok(Self {
+                path,
+                registries: Default::default(),
+            });
         }
+
+        let contents = fs::read_to_string(&path).with_context(|| {
+            format!(
+                "failed to read credentials file `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        Self::parse(path, &contents)
     }
 
-    /// Converts the decoded dependency into a component world.
-    ///
-    /// Returns an error if the dependency is not a decoded component.
-    pub fn into_component_world(self) -> Result<(Resolve, WorldId)> {
-        match self {
-            Self::Wasm {
-                decoded: DecodedWasm::Component(resolve, world),
-                ..
-            } => Ok((resolve, world)),
-            _ => bail!("dependency is not a WebAssembly component"),
+    /// Parses the contents of a credentials file at the given path.
+    pub fn parse(path: PathBuf, contents: &str) -> Result<Self> {
+        #[derive(Default, Deserialize)]
+        #[serde(default, deny_unknown_fields)]
+        struct File {
+            registries: HashMap<String, RegistryCredentials>,
         }
-    }
-}
 
-/// Used to resolve dependencies for a WIT package.
-pub struct DependencyResolver<'a> {
-    terminal: &'a Terminal,
-    registry_urls: &'a HashMap<String, Url>,
-    warg_config: &'a Config,
-    lock_file: Option<LockFileResolver<'a>>,
-    registries: IndexMap<&'a str, Registry<'a>>,
-    resolutions: HashMap<registry::PackageName, DependencyResolution>,
-    network_allowed: bool,
-}
+        let file: File = toml_edit::de::from_str(contents).with_context(|| {
+            format!(
+                "failed to parse credentials file `{path}`",
+                path = path.display()
+            )
+        })?;
 
-impl<'a> DependencyResolver<'a> {
-    /// Creates a new dependency resolver.
-    pub fn new(
-        warg_config: &'a Config,
-        registry_urls: &'a HashMap<String, Url>,
-        lock_file: Option<LockFileResolver<'a>>,
-        terminal: &'a Terminal,
-        network_allowed: bool,
-    ) -> Result<Self> {
-        Ok(DependencyResolver {
-            terminal,
-            registry_urls,
-            warg_config,
-            lock_file,
-            registries: Default::default(),
-            resolutions: Default::default(),
-            network_allowed,
+        Ok(Self {
+            path,
+            registries: file
+                .registries
+                .into_iter()
+                .map(|(url, credentials)| (url.trim_end_matches('/').to_string(), credentials))
+                .collect(),
         })
     }
 
-    /// Add a dependency to the resolver.
-    pub async fn add_dependency(
-        &mut self,
-        name: &'a registry::PackageName,
-        dependency: &'a Dependency,
-    ) -> Result<()> {
-        match dependency {
-            Dependency::Package(package) => {
-                // Dependency comes from a registry, add a dependency to the resolver
-                let registry_name = package.registry.as_deref().unwrap_or(DEFAULT_REGISTRY_NAME);
-                let package_name = package.name.clone().unwrap_or_else(|| name.clone());
-
-                // Resolve the version from the lock file if there is one
-                let locked = match self.lock_file.as_ref().and_then(|resolver| {
-                    resolver
-                        .resolve(registry_name, &package_name, &package.version)
-                        .transpose()
-                }) {
-                    Some(Ok(locked)) => Some(locked),
-                    Some(Err(e)) => return Err(e),
-                    _ => None,
-                };
+    /// Gets the auth token for the given registry URL.
+    ///
+    /// The token is taken from the first of the following that has one:
+    ///
+    /// * the `CARGO_COMPONENT_REGISTRY_TOKEN` environment variable, if
+    ///   `CARGO_COMPONENT_REGISTRY_TOKEN_URL` is the given registry URL.
+    /// * the credentials file.
+    /// * the keyring, if enabled in the given warg configuration.
+    ///
+    /// Returns an error if the registry is protected and no token is available.
+    pub fn token(&self, config: &warg_client::Config, url: &str) -> Result<Option<Secret<String>>> {
+        self.token_with(
+            url,
+            std::env::var(TOKEN_ENV_VAR).ok(),
+            std::env::var(TOKEN_URL_ENV_VAR).ok(),
+            || auth_token(config, Some(url.to_string())),
+        )
+    }
 
-                let registry = match self.registries.entry(registry_name) {
-                    indexmap::map::Entry::Occupied(e) => e.into_mut(),
-                    indexmap::map::Entry::Vacant(e) => {
-                        let url = find_url(
-                            Some(registry_name),
-                            self.registry_urls,
-                            self.warg_config.home_url.as_deref(),
-                        )?;
-                        e.insert(Registry {
-                            client: Arc::new(create_client(self.warg_config, url, self.terminal)?),
-                            packages: HashMap::new(),
-                            dependencies: Vec::new(),
-                            upserts: HashSet::new(),
-                        })
-                    }
-                };
+    fn token_with(
+        &self,
+        url: &str,
+        env: Option<String>,
+        env_url: Option<String>,
+        keyring: impl FnOnce() -> Result<Option<Secret<String>>>,
+    ) -> Result<Option<Secret<String>>> {
+        let url = url.trim_end_matches('/');
 
-                registry
-                    .add_dependency(name, package_name, &package.version, registry_name, locked)
-                    .await?;
+        if let Some(token) = env.filter(|t| !t.is_empty()) {
+            match env_url.as_deref().map(|u| u.trim_end_matches('/')) {
+                Some(env_url) if env_url == url => {
+                    log::debug!("using auth token from environment variable `{TOKEN_ENV_VAR}` for component registry `{url}`");
+                    return Ok(Some(Secret::new(token)));
+                }
+                Some(_) => {}
+                None => log::warn!(
+                    "ignoring environment variable `{TOKEN_ENV_VAR}` because `{TOKEN_URL_ENV_VAR}` is not set to the URL of the registry the token is for"
+                ),
             }
-            Dependency::Local(p) => {
-                // A local path dependency, insert a resolution immediately
-                let res = DependencyResolution::Local(LocalResolution {
-                    name: name.clone(),
-                    path: p.clone(),
-                });
+        }
 
-                let prev = self.resolutions.insert(name.clone(), res);
-                assert!(prev.is_none());
-            }
+        let credentials = self.registries.get(url);
+        if let Some(token) = credentials.and_then(|c| c.token.as_ref()) {
+            log::debug!(
+                "using auth token from `{path}` for component registry `{url}`",
+                path = self.path.display()
+            );
+            return Ok(Some(Secret::new(token.clone())));
         }
 
-        Ok(())
+        if let Some(token) = keyring()? {
+            log::debug!("using auth token from the keyring for component registry `{url}`");
+            return Ok(Some(token));
+        }
+
+        if credentials.map(|c| c.protected).unwrap_or(false) {
+            bail!(
+                "authentication required for component registry `{url}`\n\n\
+                 add a `token` for the registry to `{path}`, store a token in the keyring, \
+                 or set the `{TOKEN_ENV_VAR}` and `{TOKEN_URL_ENV_VAR}` environment variables",
+                path = self.path.display()
+            );
+        }
+
+        Ok(None)
     }
+}
 
-    /// Resolve all dependencies.
-    ///
-    /// This will download all dependencies that are not already present in client storage.
-    ///
-    /// Returns the dependency resolution map.
-    pub async fn resolve(self) -> Result<DependencyResolutionMap> {
-        let Self {
-            mut registries,
-            mut resolutions,
-            terminal,
-            network_allowed,
-            ..
-        } = self;
+/// The retry and timeout policy of a registry client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistryClientConfig {
+    /// The timeout, in seconds, of each attempt of a registry operation.
+    pub timeout_secs: u64,
+    /// The maximum number of times a failed registry operation is retried.
+    pub max_retries: u32,
+}
 
-        // Start by updating the packages that need updating
-        // This will determine the contents that need to be downloaded
-        let downloads = Self::update_packages(&mut registries, terminal, network_allowed).await?;
+impl RegistryClientConfig {
+    /// The delay before the first retry; the delay doubles for each retry.
+    const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
 
-        // Finally, download and resolve the dependencies
-        for resolution in
-            Self::download_and_resolve(registries, downloads, terminal, network_allowed).await?
-        {
-            let prev = resolutions.insert(resolution.name().clone(), resolution);
-            assert!(prev.is_none());
-        }
+    /// Gets the client configuration of the given registry from the given
+    /// configurations, falling back to the default configuration.
+    pub fn for_registry(configs: &HashMap<String, Self>, registry: &str) -> Self {
+        configs.get(registry).copied().unwrap_or_default()
+    }
 
-        Ok(resolutions)
+    /// Gets the timeout of each attempt of a registry operation.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
     }
 
-    async fn update_packages(
-        registries: &mut IndexMap<&'a str, Registry<'a>>,
-        terminal: &Terminal,
-        network_allowed: bool,
-    ) -> Result<DownloadMap<'a>> {
-        let task_count = registries
-            .iter()
-            .filter(|(_, r)| !r.upserts.is_empty())
-            .count();
+    /// Gets the delay before the given retry, starting from zero.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        Self::INITIAL_BACKOFF * 2u32.saturating_pow(retry)
+    }
 
-        let mut progress = ProgressBar::with_style("Updating", ProgressStyle::Ratio, terminal);
+    /// Runs a registry operation, retrying it if it fails or times out.
+    pub async fn run<T, E, F, Fut>(&self, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Into<anyhow::Error>,
+    {
+        let mut retry = 0;
+        loop {
+            let error = match tokio::time::timeout(self.timeout(), operation()).await {
+                Ok(Ok(value)) => return Ok(value),
+                Ok(Err(e)) => e.into(),
+                Err(_) => anyhow!(
+                    "registry operation timed out after {secs} seconds",
+                    secs = self.timeout_secs
+                ),
+            };
 
-        if task_count > 0 {
-            if !network_allowed {
-                bail!("a component registry update is required but network access is disabled");
+            if retry >= self.max_retries {
+                return Err(error);
             }
 
-            terminal.status("Updating", "component registry package logs")?;
-            progress.tick_now(0, task_count, "")?;
+            let delay = self.backoff(retry);
+            log::debug!("retrying registry operation in {delay:?}: {error:#}");
+            tokio::time::sleep(delay).await;
+            retry += 1;
         }
+    }
+}
 
-        let mut downloads = DownloadMap::new();
-        let mut futures = FuturesUnordered::new();
-        for (index, (name, registry)) in registries.iter_mut().enumerate() {
-            let upserts = std::mem::take(&mut registry.upserts);
-            if upserts.is_empty() {
-                // No upserts needed, add the necessary downloads now
-                registry.add_downloads(name, &mut downloads).await?;
-                continue;
-            }
+impl Default for RegistryClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 60,
+            max_retries: 2,
+        }
+    }
+}
 
-            log::info!("updating package logs for registry `{name}`");
+/// Represents the proxy settings from the environment.
+#[derive(Debug, Default, Clone)]
+pub struct ProxyEnv {
+    /// The value of the `HTTP_PROXY` environment variable.
+    pub http_proxy: Option<String>,
+    /// The value of the `HTTPS_PROXY` environment variable.
+    pub https_proxy: Option<String>,
+    /// The value of the `NO_PROXY` environment variable.
+    pub no_proxy: Option<String>,
+}
 
-            let client = registry.client.clone();
-            futures.push(tokio::spawn(async move {
-                (index, client.upsert(upserts.iter()).await)
-            }))
+impl ProxyEnv {
+    /// Reads the proxy settings from the environment.
+    ///
+    /// Both upper and lower case variable names are supported.
+    pub fn from_env() -> Self {
+        fn var(name: &str) -> Option<String> {
+            std::env::var(name)
+                .or_else(|_| std::env::var(name.to_lowercase()))
+                .ok()
+                .filter(|v| !v.is_empty())
         }
 
-        assert_eq!(futures.len(), task_count);
+        Self {
+            http_proxy: var("HTTP_PROXY"),
+            https_proxy: var("HTTPS_PROXY"),
+            no_proxy: var("NO_PROXY"),
+        }
+    }
 
-        let mut finished = 0;
-        while let Some(res) = futures.next().await {
-            let (index, res) = res.context("failed to join registry update task")?;
-            let (name, registry) = registries
+    /// Selects the proxy to use for the given registry URL.
+    ///
+    /// The proxy is that of the `HTTP_PROXY` or `HTTPS_PROXY` environment
+    /// variable for the scheme of the URL; no proxy is used for hosts
+    /// matching `NO_PROXY`.
+    pub fn select(&self, url: &str) -> Result<Option<Url>> {
+        // Registry URLs without a scheme default to `https`
+        let url = if url.contains("://") {
+            Url::parse(url)
+        } else {
+            Url::parse(&format!("https://{url}"))
+        }
+        .with_context(|| format!("invalid registry URL `{url}`"))?;
+
+        if let Some(host) = url.host_str() {
+            if self.bypasses(host) {
+                return Ok(None);
+            }
+        }
+
+        let proxy = match url.scheme() {
+            "http" => self.http_proxy.as_deref(),
+            _ => self.https_proxy.as_deref(),
+        };
+
+        proxy
+            .map(|p| Url::parse(p).with_context(|| format!("invalid proxy URL `{p}`")))
+            .transpose()
+    }
+
+    fn bypasses(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        self.no_proxy
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .any(|entry| {
+                let entry = entry.trim_start_matches('.');
+                entry == "*" || host == entry || host.ends_with(&format!(".{entry}"))
+            })
+    }
+}
+
+/// Creates a registry client with the given warg configuration.
+///
+/// Registry clients construct their own HTTP clients, so only the proxies of
+/// the environment (see [`ProxyEnv`]) are used for registry requests.
+///
+/// The given warg protocol version is only validated: it is checked with
+/// [`select_protocol`], and an explicitly given version is also checked with
+/// the registry using [`check_protocol`]. The registry client itself always
+/// uses the protocol version it was built with.
+pub async fn create_client(
+    config: &warg_client::Config,
+    url: &str,
+    protocol: Option<u8>,
+    terminal: &Terminal,
+) -> Result<FileSystemClient> {
+    let explicit = protocol.is_some();
+    let protocol = select_protocol(url, protocol)?;
+    log::debug!("using warg protocol version {protocol} for component registry `{url}`");
+
+    if let Some(proxy) = ProxyEnv::from_env().select(url)? {
+        log::info!("using proxy `{proxy}` for component registry `{url}`");
+    }
+
+    let token = Credentials::load()?.token(config, url)?;
+
+    if explicit {
+        check_protocol(url, protocol, token.as_ref()).await?;
+    }
+
+    match FileSystemClient::try_new_with_config(Some(url), config, token.clone())? {
+        StorageLockResult::Acquired(client) => Ok(client),
+        StorageLockResult::NotAcquired(path) => {
+            terminal.status_with_color(
+                "Blocking",
+                format!("waiting for file lock on `{path}`", path = path.display()),
+                Colors::Cyan,
+            )?;
+
+            Ok(FileSystemClient::new_with_config(Some(url), config, token)?)
+        }
+    }
+}
+
+/// Parses a package name, normalizing it to its canonical lowercase form.
+///
+/// WIT identifiers are not permitted to differ only by case, so `WASI:http`
+/// and `wasi:http` refer to the same package.
+pub fn normalize_package_name(name: &str) -> Result<registry::PackageName> {
+    name.to_lowercase()
+        .parse()
+        .with_context(|| format!("invalid package name `{name}`"))
+}
+
+/// Deserializes a map keyed by package names, normalizing the names.
+///
+/// An error is returned if two keys normalize to the same package name.
+pub fn deserialize_package_map<'de, D, V>(
+    deserializer: D,
+) -> Result<HashMap<registry::PackageName, V>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    let entries = HashMap::<String, V>::deserialize(deserializer)?;
+    let mut keys: HashMap<registry::PackageName, String> = HashMap::with_capacity(entries.len());
+    let mut map = HashMap::with_capacity(entries.len());
+    for (key, value) in entries {
+        let name = normalize_package_name(&key).map_err(|e| de::Error::custom(format!("{e:#}")))?;
+        if let Some(prev) = keys.insert(name.clone(), key.clone()) {
+            let (first, second) = if prev < key { (prev, key) } else { (key, prev) };
+            return Err(de::Error::custom(format!(
+                "package names `{first}` and `{second}` refer to the same package `{name}`"
+            )));
+        }
+
+        map.insert(name, value);
+    }
+
+    Ok(map)
+}
+
+/// Serializes a map with its entries sorted by key.
+///
+/// This keeps the serialized output deterministic regardless of the iteration
+/// order of the map.
+pub fn serialize_sorted_map<S, K, V>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    K: std::fmt::Display,
+    V: Serialize,
+{
+    map.iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+/// Represents a WIT package dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dependency {
+    /// The dependency is a registry package.
+    Package(RegistryPackage),
+
+    /// The dependency is a path to a local directory or file.
+    Local(PathBuf),
+
+    /// The dependency is an archive of WIT files (`.tar.gz`, `.tgz`, or `.zip`).
+    ///
+    /// The archive is extracted into a cache directory and the given
+    /// subdirectory of it is used as a local dependency.
+    Archive {
+        /// The path to the archive.
+        path: PathBuf,
+        /// The subdirectory of the archive containing the WIT package.
+        subdir: Option<PathBuf>,
+    },
+}
+
+impl Serialize for Dependency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::Package(package) => {
+                if package.name.is_none()
+                    && package.registry.is_none()
+                    && package.registries.is_empty()
+                {
+                    let version = package.version.to_string();
+                    version.trim_start_matches('^').serialize(serializer)
+                } else {
+                    #[derive(Serialize)]
+                    struct Entry<'a> {
+                        package: Option<&'a registry::PackageName>,
+                        version: &'a str,
+                        registry: Option<&'a str>,
+                        #[serde(skip_serializing_if = "<[_]>::is_empty")]
+                        registries: &'a [String],
+                    }
+
+                    Entry {
+                        package: package.name.as_ref(),
+                        version: package.version.to_string().trim_start_matches('^'),
+                        registry: package.registry.as_deref(),
+                        registries: &package.registries,
+                    }
+                    .serialize(serializer)
+                }
+            }
+            Self::Local(path) => {
+                #[derive(Serialize)]
+                struct Entry<'a> {
+                    path: &'a PathBuf,
+                }
+
+                Entry { path }.serialize(serializer)
+            }
+            Self::Archive { path, subdir } => {
+                #[derive(Serialize)]
+                struct Entry<'a> {
+                    archive: &'a PathBuf,
+                    #[serde(skip_serializing_if = "Option::is_none")]
+                    subdir: Option<&'a PathBuf>,
+                }
+
+                Entry {
+                    archive: path,
+                    subdir: subdir.as_ref(),
+                }
+                .serialize(serializer)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Dependency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Dependency;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "a string or a table")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Self::Value::Package(s.parse().map_err(de::Error::custom)?))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                #[derive(Default, Deserialize)]
+                #[serde(default, deny_unknown_fields)]
+                struct Entry {
+                    path: Option<PathBuf>,
+                    archive: Option<PathBuf>,
+                    subdir: Option<PathBuf>,
+                    package: Option<registry::PackageName>,
+                    version: Option<VersionReq>,
+                    registry: Option<String>,
+                    registries: Option<Vec<String>>,
+                }
+
+                let entry = Entry::deserialize(MapAccessDeserializer::new(map))?;
+
+                if let Some(path) = entry.archive {
+                    for (present, name) in [
+                        (entry.path.is_some(), "path"),
+                        (entry.package.is_some(), "package"),
+                        (entry.version.is_some(), "version"),
+                        (entry.registry.is_some(), "registry"),
+                        (entry.registries.is_some(), "registries"),
+                    ] {
+                        if present {
+                            return Err(de::Error::custom(format!(
+                                "cannot specify both `archive` and `{name}` fields in a dependency entry"
+                            )));
+                        }
+                    }
+
+                    return Ok(Self::Value::Archive {
+                        path,
+                        subdir: entry.subdir,
+                    });
+                }
+
+                if entry.subdir.is_some() {
+                    return Err(de::Error::custom(
+                        "the `subdir` field requires the `archive` field in a dependency entry",
+                    ));
+                }
+
+                let registries = match entry.registries {
+                    Some(_) if entry.path.is_some() => {
+                        return Err(de::Error::custom(
+                            "cannot specify both `path` and `registries` fields in a dependency entry",
+                        ))
+                    }
+                    Some(_) if entry.registry.is_some() => {
+                        return Err(de::Error::custom(
+                            "cannot specify both `registry` and `registries` fields in a dependency entry",
+                        ))
+                    }
+                    Some(registries) if registries.is_empty() => {
+                        return Err(de::Error::custom(
+                            "the `registries` field of a dependency entry cannot be empty",
+                        ))
+                    }
+                    registries => registries.unwrap_or_default(),
+                };
+
+                match (entry.path, entry.package, entry.version, entry.registry) {
+                    (Some(path), None, None, None) => Ok(Self::Value::Local(path)),
+                    (None, name, Some(version), registry) => {
+                        Ok(Self::Value::Package(RegistryPackage {
+                            name,
+                            version,
+                            registry,
+                            registries,
+                        }))
+                    }
+                    (Some(_), None, Some(_), _) => Err(de::Error::custom(
+                        "cannot specify both `path` and `version` fields in a dependency entry",
+                    )),
+                    (Some(_), None, None, Some(_)) => Err(de::Error::custom(
+                        "cannot specify both `path` and `registry` fields in a dependency entry",
+                    )),
+                    (Some(_), Some(_), _, _) => Err(de::Error::custom(
+                        "cannot specify both `path` and `package` fields in a dependency entry",
+                    )),
+                    (None, None, _, _) => Err(de::Error::missing_field("package")),
+                    (None, Some(_), None, _) => Err(de::Error::missing_field("version")),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+impl FromStr for Dependency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self::Package(s.parse()?))
+    }
+}
+
+/// Represents a reference to a registry package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryPackage {
+    /// The registry-side name of the package.
+    ///
+    /// If not specified, the name from the mapping will be used.
+    ///
+    /// When specified, the package is fetched from the registry by this name
+    /// but is resolved under the name from the mapping; this allows a fork
+    /// published under a different name to satisfy imports of the original.
+    pub name: Option<registry::PackageName>,
+
+    /// The version requirement of the package.
+    pub version: VersionReq,
+
+    /// The name of the component registry containing the package.
+    ///
+    /// If not specified, the default registry is used.
+    pub registry: Option<String>,
+
+    /// The names of component registries to try, in order, when resolving
+    /// the package.
+    ///
+    /// Each registry is tried until one resolves the package; this allows a
+    /// mirror to be used as a fallback for an unavailable registry.
+    ///
+    /// This cannot be specified with `registry`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registries: Vec<String>,
+}
+
+impl RegistryPackage {
+    /// Gets the names of the registries to resolve the package from, in the
+    /// order they should be tried.
+    pub fn candidate_registries(&self) -> Vec<&str> {
+        if !self.registries.is_empty() {
+            return self.registries.iter().map(String::as_str).collect();
+        }
+
+        vec![self.registry.as_deref().unwrap_or(DEFAULT_REGISTRY_NAME)]
+    }
+}
+
+impl FromStr for RegistryPackage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self {
+            name: None,
+            version: s
+                .parse()
+                .with_context(|| format!("'{s}' is an invalid registry package version"))?,
+            registry: None,
+            registries: Vec::new(),
+        })
+    }
+}
+
+/// Represents information about a resolution of a registry package.
+#[derive(Clone, Debug)]
+pub struct RegistryResolution {
+    /// The name of the dependency that was resolved.
+    ///
+    /// This may differ from `package` if the dependency was renamed.
+    pub name: registry::PackageName,
+    /// The name of the package from the registry that was resolved.
+    pub package: registry::PackageName,
+    /// The name of the registry used to resolve the package.
+    ///
+    /// A value of `None` indicates that the default registry was used.
+    pub registry: Option<String>,
+    /// The version requirement that was used to resolve the package.
+    pub requirement: VersionReq,
+    /// The package version that was resolved.
+    pub version: Version,
+    /// The digest of the package contents.
+    pub digest: AnyHash,
+    /// The path to the resolved dependency.
+    pub path: PathBuf,
+}
+
+/// Represents information about a resolution of a local file.
+#[derive(Clone, Debug)]
+pub struct LocalResolution {
+    /// The name of the dependency that was resolved.
+    pub name: registry::PackageName,
+    /// The path to the resolved dependency.
+    pub path: PathBuf,
+}
+
+impl LocalResolution {
+    /// Computes the digest of the local dependency's contents.
+    ///
+    /// For a directory, the digest covers the relative paths and contents of
+    /// all files in the directory, so it is independent of where the
+    /// directory is located.
+    pub fn digest(&self) -> Result<AnyHash> {
+        fn update(hasher: &mut Sha256, bytes: &[u8]) {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        }
+
+        fn visit(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> Result<()> {
+            for entry in fs::read_dir(dir)
+                .with_context(|| format!("failed to read directory `{}`", dir.display()))?
+            {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let name = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{prefix}/{name}")
+                };
+
+                if entry.file_type()?.is_dir() {
+                    visit(&entry.path(), &name, files)?;
+                } else {
+                    files.push((name, entry.path()));
+                }
+            }
+
+            Ok(())
+        }
+
+        let mut files = Vec::new();
+        if self.path.is_dir() {
+            visit(&self.path, "", &mut files)?;
+            files.sort();
+        } else {
+            files.push((String::new(), self.path.clone()));
+        }
+
+        let mut hasher = Sha256::new();
+        for (name, path) in files {
+            let contents = fs::read(&path)
+                .with_context(|| format!("failed to read file `{}`", path.display()))?;
+            update(&mut hasher, name.as_bytes());
+            update(&mut hasher, &contents);
+        }
+
+        let digest: String = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        Ok(format!("sha256:{digest}").parse()?)
+    }
+}
+
+/// Represents a resolution of a dependency.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum DependencyResolution {
+    /// The dependency is resolved from a registry package.
+    Registry(RegistryResolution),
+    /// The dependency is resolved from a local path.
+    Local(LocalResolution),
+}
+
+impl DependencyResolution {
+    /// Gets the name of the dependency that was resolved.
+    pub fn name(&self) -> &registry::PackageName {
+        match self {
+            Self::Registry(res) => &res.name,
+            Self::Local(res) => &res.name,
+        }
+    }
+
+    /// Gets the path to the resolved dependency.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Registry(res) => &res.path,
+            Self::Local(res) => &res.path,
+        }
+    }
+
+    /// Gets the resolved version.
+    ///
+    /// Returns `None` if the dependency is not resolved from a registry package.
+    pub fn version(&self) -> Option<&Version> {
+        match self {
+            Self::Registry(res) => Some(&res.version),
+            Self::Local(_) => None,
+        }
+    }
+
+    /// The key used in sorting and searching the lock file package list.
+    ///
+    /// Returns `None` if the dependency is not resolved from a registry package.
+    pub fn key(&self) -> Option<(&registry::PackageName, Option<&str>)> {
+        match self {
+            DependencyResolution::Registry(pkg) => Some((&pkg.package, pkg.registry.as_deref())),
+            DependencyResolution::Local(_) => None,
+        }
+    }
+
+    /// Gets the name the dependency is imported as if the package was
+    /// fetched from the registry under a different name.
+    ///
+    /// Returns `None` if the dependency was not renamed.
+    pub fn renamed(&self) -> Option<&registry::PackageName> {
+        match self {
+            Self::Registry(res) if res.name != res.package => Some(&res.name),
+            _ => None,
+        }
+    }
+
+    /// Decodes the resolved dependency.
+    ///
+    /// If the dependency was renamed, the decoded package is renamed to the
+    /// name it is imported as.
+    ///
+    /// Returns an error if a local dependency declares a package with a name
+    /// other than the name of the dependency.
+    pub fn decode(&self) -> Result<DecodedDependency<'_>> {
+        self.decode_with_features(&FeatureSet::default())
+    }
+
+    /// Decodes the resolved dependency with the given WIT features enabled.
+    ///
+    /// The features apply only to dependencies in the WIT text format; an
+    /// encoded package has its feature gates applied when it was encoded.
+    pub fn decode_with_features(&self, features: &FeatureSet) -> Result<DecodedDependency<'_>> {
+        let mut decoded = self.decode_content(features)?;
+
+        if let Self::Local(res) = self {
+            // Components have no declared package to check
+            let declared = match &decoded {
+                DecodedDependency::Wasm {
+                    decoded: DecodedWasm::Component(..),
+                    ..
+                } => None,
+                decoded => Some(decoded.package_name()),
+            };
+
+            if let Some(declared) = declared {
+                if declared.namespace != res.name.namespace() || declared.name != res.name.name() {
+                    bail!(
+                        "local dependency `{name}` at path `{path}` declares package `{declared}`; \
+                         the name of the dependency must match the declared package",
+                        name = res.name,
+                        path = res.path.display(),
+                    );
+                }
+            }
+        }
+
+        if let Some(name) = self.renamed() {
+            log::debug!(
+                "renaming package `{package}` to `{name}`",
+                package = decoded.package_name()
+            );
+
+            match &mut decoded {
+                DecodedDependency::Wit { package, .. } => rename_package(&mut package.name, name),
+                DecodedDependency::Wasm { decoded, .. } => {
+                    let id = decoded.package();
+                    let resolve = match decoded {
+                        DecodedWasm::WitPackage(resolve, _)
+                        | DecodedWasm::Component(resolve, _) => resolve,
+                    };
+
+                    let prev = resolve.packages[id].name.clone();
+                    rename_package(&mut resolve.packages[id].name, name);
+                    resolve.package_names.shift_remove(&prev);
+                    resolve
+                        .package_names
+                        .insert(resolve.packages[id].name.clone(), id);
+                }
+            }
+        }
+
+        Ok(decoded)
+    }
+
+    fn decode_content(&self, features: &FeatureSet) -> Result<DecodedDependency<'_>> {
+        // If the dependency path is a directory, assume it contains wit to parse as a package.
+        if self.path().is_dir() {
+            return Ok(DecodedDependency::Wit {
+                resolution: self,
+                package: features::parse_path(self.path(), features).with_context(|| {
+                    format!(
+                        "failed to parse dependency `{path}`",
+                        path = self.path().display()
+                    )
+                })?,
+            });
+        }
+
+        let bytes = fs::read(self.path()).with_context(|| {
+            format!(
+                "failed to read content of dependency `{name}` at path `{path}`",
+                name = self.name(),
+                path = self.path().display()
+            )
+        })?;
+
+        if &bytes[0..4] != b"\0asm" {
+            return Ok(DecodedDependency::Wit {
+                resolution: self,
+                package: UnresolvedPackage::parse(
+                    self.path(),
+                    &features::apply_gates(
+                        std::str::from_utf8(&bytes).with_context(|| {
+                            format!(
+                                "dependency `{path}` is not UTF-8 encoded",
+                                path = self.path().display()
+                            )
+                        })?,
+                        features,
+                    )?,
+                )?,
+            });
+        }
+
+        Ok(DecodedDependency::Wasm {
+            resolution: self,
+            decoded: wit_component::decode(&bytes).with_context(|| {
+                format!(
+                    "failed to decode content of dependency `{name}` at path `{path}`",
+                    name = self.name(),
+                    path = self.path().display()
+                )
+            })?,
+        })
+    }
+}
+
+/// Renames a WIT package to the given registry package name, preserving its version.
+fn rename_package(package: &mut PackageName, name: &registry::PackageName) {
+    package.namespace = name.namespace().to_string();
+    package.name = name.name().to_string();
+}
+
+/// Represents a decoded dependency.
+pub enum DecodedDependency<'a> {
+    /// The dependency decoded from an unresolved WIT package.
+    Wit {
+        /// The resolution related to the decoded dependency.
+        resolution: &'a DependencyResolution,
+        /// The unresolved WIT package.
+        package: UnresolvedPackage,
+    },
+    /// The dependency decoded from a Wasm file.
+    Wasm {
+        /// The resolution related to the decoded dependency.
+        resolution: &'a DependencyResolution,
+        /// The decoded Wasm file.
+        decoded: DecodedWasm,
+    },
+}
+
+impl<'a> DecodedDependency<'a> {
+    /// Fully resolves the dependency.
+    ///
+    /// If the dependency is an unresolved WIT package, it will assume that the
+    /// package has no foreign dependencies.
+    pub fn resolve(self) -> Result<(Resolve, PackageId, Vec<PathBuf>)> {
+        match self {
+            Self::Wit { package, .. } => {
+                let mut resolve = Resolve::new();
+                let source_files = package.source_files().map(Path::to_path_buf).collect();
+                let pkg = resolve.push(package)?;
+                Ok((resolve, pkg, source_files))
+            }
+            Self::Wasm { decoded, .. } => match decoded {
+                DecodedWasm::WitPackage(resolve, pkg) => Ok((resolve, pkg, Vec::new())),
+                DecodedWasm::Component(resolve, world) => {
+                    let pkg = resolve.worlds[world].package.unwrap();
+                    Ok((resolve, pkg, Vec::new()))
+                }
+            },
+        }
+    }
+
+    /// Gets the package name of the decoded dependency.
+    pub fn package_name(&self) -> &PackageName {
+        match self {
+            Self::Wit { package, .. } => &package.name,
+            Self::Wasm { decoded, .. } => &decoded.resolve().packages[decoded.package()].name,
+        }
+    }
+
+    /// Converts the decoded dependency into a component world.
+    ///
+    /// Returns an error if the dependency is not a decoded component.
+    pub fn into_component_world(self) -> Result<(Resolve, WorldId)> {
+        match self {
+            Self::Wasm {
+                decoded: DecodedWasm::Component(resolve, world),
+                ..
+            } => Ok((resolve, world)),
+            _ => bail!("dependency is not a WebAssembly component"),
+        }
+    }
+}
+
+/// Observes the fetching of package contents during dependency resolution.
+///
+/// All methods default to doing nothing, so implementations only need to
+/// override the events they are interested in.
+pub trait FetchProgress: Send + Sync {
+    /// Called when fetching the contents of a package has started.
+    fn started(&self, package: &registry::PackageName, registry: &str) {
+        let _ = (package, registry);
+    }
+
+    /// Called when a package has been fetched; `finished` of `total`
+    /// packages being fetched together have been fetched.
+    fn progress(&self, finished: usize, total: usize) {
+        let _ = (finished, total);
+    }
+
+    /// Called when the contents of the given version of a package have been
+    /// fetched.
+    fn finished(&self, package: &registry::PackageName, registry: &str, version: &Version) {
+        let _ = (package, registry, version);
+    }
+}
+
+/// A [`FetchProgress`] that ignores all events.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopFetchProgress;
+
+impl FetchProgress for NoopFetchProgress {}
+
+/// The contents of a package version given by a [`PackageProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvidedPackage {
+    /// The version of the package.
+    pub version: Version,
+    /// The contents of the package, either a binary-encoded WIT package or
+    /// the WIT source of the package.
+    pub contents: Vec<u8>,
+}
+
+/// Provides the contents of registry packages during dependency resolution.
+///
+/// The resolver consults the provider before the lock file and registries;
+/// this allows tests to resolve packages without a registry.
+pub trait PackageProvider: Send + Sync {
+    /// Provides the contents of the latest version of the given package
+    /// satisfying the given requirement.
+    ///
+    /// Returns `Ok(None)` if the package should be resolved from its registry.
+    fn provide(
+        &self,
+        package: &registry::PackageName,
+        requirement: &VersionReq,
+    ) -> Result<Option<ProvidedPackage>>;
+}
+
+/// A [`PackageProvider`] that resolves every package from its registry.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RegistryPackageProvider;
+
+impl PackageProvider for RegistryPackageProvider {
+    fn provide(
+        &self,
+        _package: &registry::PackageName,
+        _requirement: &VersionReq,
+    ) -> Result<Option<ProvidedPackage>> {
+        Ok(None)
+    }
+}
+
+/// The mode in which dependencies are resolved.
+///
+/// This mirrors the `--offline` and `--frozen` options of cargo.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FrozenMode {
+    /// Packages may be fetched and the lock file may be updated.
+    #[default]
+    Normal,
+    /// Packages may not be fetched, but the lock file may be updated with
+    /// packages already in the cache.
+    Offline,
+    /// Packages may not be fetched and the lock file may not be updated.
+    ///
+    /// Every registry package must be locked and already in the cache.
+    Frozen,
+}
+
+impl FrozenMode {
+    /// Gets the mode for whether network access is allowed.
+    pub fn from_network_allowed(network_allowed: bool) -> Self {
+        if network_allowed {
+            Self::Normal
+        } else {
+            Self::Offline
+        }
+    }
+
+    /// Determines if packages may be fetched over the network.
+    pub fn network_allowed(self) -> bool {
+        self == Self::Normal
+    }
+
+    /// Determines if the lock file may be updated.
+    pub fn lock_update_allowed(self) -> bool {
+        self != Self::Frozen
+    }
+}
+
+/// Used to resolve dependencies for a WIT package.
+pub struct DependencyResolver<'a> {
+    terminal: &'a Terminal,
+    registry_refs: &'a HashMap<String, RegistryRef>,
+    warg_config: &'a Config,
+    lock_file: Option<LockFileResolver<'a>>,
+    /// The registries in use, keyed by registry name and (expanded) URL.
+    registries: IndexMap<(&'a str, String), Registry<'a>>,
+    resolutions: HashMap<registry::PackageName, DependencyResolution>,
+    network_allowed: bool,
+    client_configs: Option<&'a HashMap<String, RegistryClientConfig>>,
+    fetch_progress: &'a dyn FetchProgress,
+    package_provider: &'a dyn PackageProvider,
+    /// The directory the contents of provided packages are written to.
+    ///
+    /// This is created on first use and is unique to the resolver.
+    provided_dir: Option<PathBuf>,
+    source_replacement: Option<&'a SourceReplacement>,
+    frozen_mode: FrozenMode,
+    /// The packages to fetch from OCI registries, in the order they were added.
+    oci_fetches: Vec<OciFetch<'a>>,
+    /// The client for OCI registries; created from the environment if unset.
+    oci_client: Option<OciClient>,
+    concurrency: usize,
+    prefer_cached: bool,
+}
+
+impl<'a> DependencyResolver<'a> {
+    /// Creates a new dependency resolver.
+    pub fn new(
+        warg_config: &'a Config,
+        registry_refs: &'a HashMap<String, RegistryRef>,
+        lock_file: Option<LockFileResolver<'a>>,
+        terminal: &'a Terminal,
+        network_allowed: bool,
+    ) -> Result<Self> {
+        Ok(DependencyResolver {
+            terminal,
+            registry_refs,
+            warg_config,
+            lock_file,
+            registries: Default::default(),
+            resolutions: Default::default(),
+            network_allowed,
+            client_configs: None,
+            fetch_progress: &NoopFetchProgress,
+            package_provider: &RegistryPackageProvider,
+            provided_dir: None,
+            source_replacement: None,
+            frozen_mode: FrozenMode::Normal,
+            oci_fetches: Vec::new(),
+            oci_client: None,
+            concurrency: DEFAULT_FETCH_CONCURRENCY,
+            prefer_cached: false,
+        })
+    }
+
+    /// Sets the retry and timeout policies of registry clients, keyed by
+    /// registry name.
+    ///
+    /// Registries without a policy use the default policy.
+    pub fn with_client_configs(
+        mut self,
+        configs: &'a HashMap<String, RegistryClientConfig>,
+    ) -> Self {
+        self.client_configs = Some(configs);
+        self
+    }
+
+    /// Sets the observer of package fetches.
+    ///
+    /// By default, fetches are not observed.
+    pub fn with_fetch_progress(mut self, progress: &'a dyn FetchProgress) -> Self {
+        self.fetch_progress = progress;
+        self
+    }
+
+    /// Sets the provider of package contents consulted before the lock file
+    /// and registries.
+    ///
+    /// By default, packages are resolved from their registries.
+    pub fn with_package_provider(mut self, provider: &'a dyn PackageProvider) -> Self {
+        self.package_provider = provider;
+        self
+    }
+
+    /// Sets the replacement of registry sources with mirrors.
+    ///
+    /// Sources are not replaced for registries named in the configured
+    /// registries.
+    pub fn with_source_replacement(mut self, replacement: &'a SourceReplacement) -> Self {
+        self.source_replacement = Some(replacement);
+        self
+    }
+
+    /// Sets the mode in which dependencies are resolved.
+    ///
+    /// Network access is disabled for any mode other than
+    /// [`FrozenMode::Normal`]; in [`FrozenMode::Frozen`], every registry
+    /// package must also be locked.
+    pub fn with_frozen_mode(mut self, mode: FrozenMode) -> Self {
+        self.frozen_mode = mode;
+        self.network_allowed &= mode.network_allowed();
+        self
+    }
+
+    /// Sets the client used to fetch packages from OCI registries.
+    ///
+    /// Defaults to a client using the default OCI cache directory and the
+    /// credentials of the docker configuration file.
+    pub fn with_oci_client(mut self, client: OciClient) -> Self {
+        self.oci_client = Some(client);
+        self
+    }
+
+    /// Sets the maximum number of packages fetched concurrently.
+    ///
+    /// Defaults to [`DEFAULT_FETCH_CONCURRENCY`]; a limit of zero is treated
+    /// as a limit of one.
+    pub fn with_concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// Sets whether unlocked packages resolve to the latest cached version
+    /// satisfying their requirement, if any, rather than the latest version
+    /// in the registry.
+    ///
+    /// By default, the latest version in the registry is used.
+    pub fn with_prefer_cached(mut self, prefer_cached: bool) -> Self {
+        self.prefer_cached = prefer_cached;
+        self
+    }
+
+    /// Add a dependency to the resolver.
+    pub async fn add_dependency(
+        &mut self,
+        name: &'a registry::PackageName,
+        dependency: &'a Dependency,
+    ) -> Result<()> {
+        match dependency {
+            Dependency::Package(package) if self.add_provided_package(name, package)? => {}
+            Dependency::Package(package) => match package.candidate_registries().as_slice() {
+                [registry_name] => self.add_package(name, package, registry_name).await?,
+                registry_names => {
+                    self.add_package_with_fallbacks(name, package, registry_names)
+                        .await?
+                }
+            },
+            Dependency::Local(p) => {
+                // A local path dependency, insert a resolution immediately
+                let res = DependencyResolution::Local(LocalResolution {
+                    name: name.clone(),
+                    path: p.clone(),
+                });
+
+                let prev = self.resolutions.insert(name.clone(), res);
+                assert!(prev.is_none());
+            }
+            Dependency::Archive { path, subdir } => {
+                // Extract the archive and treat it as a local path dependency
+                let res = DependencyResolution::Local(LocalResolution {
+                    name: name.clone(),
+                    path: extract_archive(path, subdir.as_deref())?,
+                });
+
+                let prev = self.resolutions.insert(name.clone(), res);
+                assert!(prev.is_none());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds a dependency on a package given by the package provider.
+    ///
+    /// Returns `false` if the provider does not provide the package.
+    fn add_provided_package(
+        &mut self,
+        name: &'a registry::PackageName,
+        package: &'a RegistryPackage,
+    ) -> Result<bool> {
+        let package_name = package.name.clone().unwrap_or_else(|| name.clone());
+        let Some(provided) = self
+            .package_provider
+            .provide(&package_name, &package.version)?
+        else {
+            return Ok(false);
+        };
+
+        if !package.version.matches(&provided.version) {
+            bail!(
+                "provided version {version} of package `{package_name}` does not satisfy requirement `{req}`",
+                version = provided.version,
+                req = package.version
+            );
+        }
+
+        let digest: String = Sha256::digest(&provided.contents)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        let dir = match &self.provided_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = tempfile::Builder::new()
+                    .prefix("cargo-component-provided-")
+                    .tempdir()
+                    .context("failed to create provided package directory")?
+                    .into_path();
+                self.provided_dir = Some(dir.clone());
+                dir
+            }
+        };
+
+        let path = dir.join(format!("sha256-{digest}"));
+        fs::write(&path, &provided.contents).with_context(|| {
+            format!(
+                "failed to write provided package `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        log::info!(
+            "dependency package `{package_name}` with requirement `{req}` was provided as version {version}",
+            req = package.version,
+            version = provided.version
+        );
+
+        let registry = package.candidate_registries()[0];
+        let res = DependencyResolution::Registry(RegistryResolution {
+            name: name.clone(),
+            package: package_name,
+            registry: (registry != DEFAULT_REGISTRY_NAME).then(|| registry.to_string()),
+            requirement: package.version.clone(),
+            version: provided.version,
+            digest: format!("sha256:{digest}").parse()?,
+            path,
+        });
+
+        let prev = self.resolutions.insert(name.clone(), res);
+        assert!(prev.is_none());
+        Ok(true)
+    }
+
+    /// Adds a dependency on a package from the given registry.
+    async fn add_package(
+        &mut self,
+        name: &'a registry::PackageName,
+        package: &'a RegistryPackage,
+        registry_name: &'a str,
+    ) -> Result<()> {
+        let package_name = package.name.clone().unwrap_or_else(|| name.clone());
+
+        // Resolve the version from the lock file if there is one
+        let locked = match self.lock_file.as_ref().and_then(|resolver| {
+            resolver
+                .resolve(registry_name, &package_name, &package.version)
+                .transpose()
+        }) {
+            Some(Ok(locked)) => Some(locked),
+            Some(Err(e)) => return Err(e),
+            _ => None,
+        };
+
+        // A locked version that no longer satisfies the requirement (e.g. an
+        // edited lock file) is resolved again
+        let locked = match locked {
+            Some(locked) if !package.version.matches(&locked.version) => {
+                if !self.frozen_mode.lock_update_allowed() {
+                    bail!(
+                        "locked version {version} of dependency `{name}` does not satisfy \
+                         requirement `{req}` and dependency resolution is frozen",
+                        version = locked.version,
+                        req = package.version
+                    );
+                }
+
+                log::info!(
+                    "locked version {version} of dependency `{name}` does not satisfy requirement `{req}`; resolving it again",
+                    version = locked.version,
+                    req = package.version
+                );
+                None
+            }
+            locked => locked,
+        };
+
+        if locked.is_none() && !self.frozen_mode.lock_update_allowed() {
+            if self.lock_file.is_none() {
+                bail!(
+                    "dependency `{name}` cannot be resolved because the lock file does not exist \
+                     and dependency resolution is frozen"
+                );
+            }
+
+            bail!(
+                "dependency `{name}` is not in the lock file \
+                 and dependency resolution is frozen"
+            );
+        }
+
+        // Packages from OCI registries are fetched when resolving
+        if let Some(RegistryRef::Oci(namespace)) = self.registry_refs.get(registry_name) {
+            self.oci_fetches.push(OciFetch {
+                name,
+                package: package_name,
+                registry: registry_name,
+                namespace,
+                requirement: &package.version,
+                locked: locked.cloned(),
+                prefer_cached: self.prefer_cached,
+            });
+            return Ok(());
+        }
+
+        // Registry URLs may be templated per dependency
+        let url = find_url(
+            Some(registry_name),
+            self.registry_refs,
+            self.warg_config.home_url.as_deref(),
+        )?;
+        let url =
+            expand_url(&url, &package_name, &template_version(&package.version))?.into_owned();
+
+        // Explicitly configured registries are never replaced
+        let url = match self.source_replacement {
+            Some(replacement) if !self.registry_refs.contains_key(registry_name) => {
+                match replacement.replace(&package_name, &url) {
+                    Some(mirror) => {
+                        log::info!("using mirror `{mirror}` for package `{package_name}` in place of registry `{url}`");
+                        mirror.to_string()
+                    }
+                    None => url,
+                }
+            }
+            _ => url,
+        };
+
+        let registry = match self.registries.entry((registry_name, url)) {
+            indexmap::map::Entry::Occupied(e) => e.into_mut(),
+            indexmap::map::Entry::Vacant(e) => {
+                let client = create_client(
+                    self.warg_config,
+                    &e.key().1,
+                    find_protocol(Some(registry_name), self.registry_refs),
+                    self.terminal,
+                )
+                .await?;
+                let policy = self
+                    .client_configs
+                    .map(|configs| RegistryClientConfig::for_registry(configs, registry_name))
+                    .unwrap_or_default();
+                e.insert(Registry {
+                    client: Arc::new(client),
+                    policy,
+                    packages: HashMap::new(),
+                    dependencies: Vec::new(),
+                    upserts: HashSet::new(),
+                    prefer_cached: self.prefer_cached,
+                })
+            }
+        };
+
+        registry
+            .add_dependency(name, package_name, &package.version, registry_name, locked)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds a dependency on a package that may be resolved from any of the
+    /// given registries.
+    ///
+    /// Each registry is tried in order and the dependency is resolved from
+    /// the first registry that succeeds.
+    async fn add_package_with_fallbacks(
+        &mut self,
+        name: &'a registry::PackageName,
+        package: &'a RegistryPackage,
+        registry_names: &[&'a str],
+    ) -> Result<()> {
+        let mut errors = Vec::new();
+        for registry_name in registry_names {
+            // Resolve with a separate resolver so a failure in one registry
+            // leaves no pending state behind
+            let mut resolver = DependencyResolver {
+                terminal: self.terminal,
+                registry_refs: self.registry_refs,
+                warg_config: self.warg_config,
+                lock_file: self.lock_file,
+                registries: Default::default(),
+                resolutions: Default::default(),
+                network_allowed: self.network_allowed,
+                client_configs: self.client_configs,
+                fetch_progress: self.fetch_progress,
+                package_provider: self.package_provider,
+                provided_dir: self.provided_dir.clone(),
+                source_replacement: self.source_replacement,
+                frozen_mode: self.frozen_mode,
+                oci_fetches: Vec::new(),
+                oci_client: self.oci_client.clone(),
+                concurrency: self.concurrency,
+                prefer_cached: self.prefer_cached,
+            };
+
+            let resolution = match resolver.add_package(name, package, registry_name).await {
+                Ok(()) => resolver.resolve().await,
+                Err(e) => Err(e),
+            };
+
+            match resolution {
+                Ok(mut resolutions) => {
+                    let res = resolutions
+                        .remove(name)
+                        .expect("dependency should have been resolved");
+                    let prev = self.resolutions.insert(name.clone(), res);
+                    assert!(prev.is_none());
+                    return Ok(());
+                }
+                Err(e) => {
+                    log::debug!("failed to resolve dependency `{name}` from registry `{registry_name}`: {e:#}");
+                    errors.push(format!("registry `{registry_name}`: {e:#}"));
+                }
+            }
+        }
+
+        bail!(
+            "failed to resolve dependency `{name}` from any of its registries:\n  {errors}",
+            errors = errors.join("\n  ")
+        )
+    }
+
+    /// Resolve all dependencies.
+    ///
+    /// This will download all dependencies that are not already present in client storage.
+    ///
+    /// Returns the dependency resolution map.
+    pub async fn resolve(self) -> Result<DependencyResolutionMap> {
+        let Self {
+            mut registries,
+            mut resolutions,
+            terminal,
+            network_allowed,
+            fetch_progress,
+            oci_fetches,
+            oci_client,
+            concurrency,
+            ..
+        } = self;
+
+        if !oci_fetches.is_empty() {
+            let oci_client = match oci_client {
+                Some(client) => client,
+                None => OciClient::from_env()?,
+            };
+
+            for resolution in Self::fetch_oci_packages(
+                &oci_client,
+                oci_fetches,
+                fetch_progress,
+                network_allowed,
+                concurrency,
+            )
+            .await?
+            {
+                let prev = resolutions.insert(resolution.name().clone(), resolution);
+                assert!(prev.is_none());
+            }
+        }
+
+        // Start by updating the packages that need updating
+        // This will determine the contents that need to be downloaded
+        let downloads = Self::update_packages(&mut registries, terminal, network_allowed).await?;
+
+        // Finally, download and resolve the dependencies
+        for resolution in Self::download_and_resolve(
+            registries,
+            downloads,
+            terminal,
+            fetch_progress,
+            network_allowed,
+            concurrency,
+        )
+        .await?
+        {
+            let prev = resolutions.insert(resolution.name().clone(), resolution);
+            assert!(prev.is_none());
+        }
+
+        Ok(resolutions)
+    }
+
+    /// Fetches the given packages from OCI registries, at most `concurrency`
+    /// at a time.
+    ///
+    /// Errors are reported in the order the dependencies were added,
+    /// regardless of the order the fetches complete in.
+    async fn fetch_oci_packages(
+        oci_client: &OciClient,
+        fetches: Vec<OciFetch<'a>>,
+        fetch_progress: &dyn FetchProgress,
+        network_allowed: bool,
+        concurrency: usize,
+    ) -> Result<Vec<DependencyResolution>> {
+        let mut results: Vec<_> = stream::iter(fetches.into_iter().enumerate())
+            .map(|(index, fetch)| async move {
+                fetch_progress.started(&fetch.package, fetch.registry);
+                let res = oci_client
+                    .fetch_package(
+                        fetch.namespace,
+                        &fetch.package,
+                        fetch.requirement,
+                        fetch.locked.as_ref(),
+                        network_allowed,
+                        fetch.prefer_cached,
+                    )
+                    .await;
+
+                if let Ok(fetched) = &res {
+                    fetch_progress.finished(&fetch.package, fetch.registry, &fetched.version);
+                }
+
+                (index, fetch, res)
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        results.sort_by_key(|(index, ..)| *index);
+        results
+            .into_iter()
+            .map(|(_, fetch, res)| {
+                let fetched = res.with_context(|| {
+                    format!(
+                        "failed to resolve dependency `{name}` from OCI registry `{registry}`",
+                        name = fetch.name,
+                        registry = fetch.registry
+                    )
+                })?;
+
+                Ok(DependencyResolution::Registry(RegistryResolution {
+                    name: fetch.name.clone(),
+                    package: fetch.package,
+                    registry: if fetch.registry == DEFAULT_REGISTRY_NAME {
+                        None
+                    } else {
+                        Some(fetch.registry.to_string())
+                    },
+                    requirement: fetch.requirement.clone(),
+                    version: fetched.version,
+                    digest: fetched.digest,
+                    path: fetched.path,
+                }))
+            })
+            .collect()
+    }
+
+    async fn update_packages(
+        registries: &mut IndexMap<(&'a str, String), Registry<'a>>,
+        terminal: &Terminal,
+        network_allowed: bool,
+    ) -> Result<DownloadMap> {
+        let task_count = registries
+            .iter()
+            .filter(|(_, r)| !r.upserts.is_empty())
+            .count();
+
+        let mut progress = ProgressBar::with_style("Updating", ProgressStyle::Ratio, terminal);
+
+        if task_count > 0 {
+            if !network_allowed {
+                bail!("a component registry update is required but network access is disabled");
+            }
+
+            terminal.status("Updating", "component registry package logs")?;
+            progress.tick_now(0, task_count, "")?;
+        }
+
+        let mut downloads = DownloadMap::new();
+        let mut futures = FuturesUnordered::new();
+        for (index, ((name, _), registry)) in registries.iter_mut().enumerate() {
+            let upserts = std::mem::take(&mut registry.upserts);
+            if upserts.is_empty() {
+                // No upserts needed, add the necessary downloads now
+                registry.add_downloads(name, index, &mut downloads).await?;
+                continue;
+            }
+
+            log::info!("updating package logs for registry `{name}`");
+
+            let client = registry.client.clone();
+            let policy = registry.policy;
+            futures.push(tokio::spawn(async move {
+                (index, policy.run(|| client.upsert(upserts.iter())).await)
+            }))
+        }
+
+        assert_eq!(futures.len(), task_count);
+
+        let mut finished = 0;
+        while let Some(res) = futures.next().await {
+            let (index, res) = res.context("failed to join registry update task")?;
+            let ((name, _), registry) = registries
                 .get_index_mut(index)
                 .expect("out of bounds registry index");
 
-            res.with_context(|| {
-                format!("failed to update package logs for component registry `{name}`")
-            })?;
+            res.with_context(|| {
+                format!("failed to update package logs for component registry `{name}`")
+            })?;
+
+            log::info!("package logs successfully updated for component registry `{name}`");
+            finished += 1;
+            progress.tick_now(finished, task_count, ": updated `{name}`")?;
+            registry.add_downloads(name, index, &mut downloads).await?;
+        }
+
+        assert_eq!(finished, task_count);
+
+        progress.clear();
+
+        Ok(downloads)
+    }
+
+    async fn download_and_resolve(
+        mut registries: IndexMap<(&'a str, String), Registry<'a>>,
+        downloads: DownloadMap,
+        terminal: &Terminal,
+        fetch_progress: &dyn FetchProgress,
+        network_allowed: bool,
+        concurrency: usize,
+    ) -> Result<impl Iterator<Item = DependencyResolution> + 'a> {
+        if !downloads.is_empty() {
+            if !network_allowed {
+                bail!("a component package download is required but network access is disabled");
+            }
+
+            terminal.status("Downloading", "component registry packages")?;
+
+            let mut progress =
+                ProgressBar::with_style("Downloading", ProgressStyle::Ratio, terminal);
+
+            let count = downloads.len();
+            progress.tick_now(0, count, "")?;
+
+            let tasks: Vec<_> = downloads
+                .into_iter()
+                .map(|((registry_index, name, version), deps)| {
+                    let ((registry_name, _), registry) =
+                        registries.get_index(registry_index).unwrap();
+                    let client = registry.client.clone();
+                    (
+                        *registry_name,
+                        registry.policy,
+                        client,
+                        registry_index,
+                        name,
+                        version,
+                        deps,
+                    )
+                })
+                .collect();
+
+            let mut futures = stream::iter(tasks)
+                .map(
+                    |(registry_name, policy, client, registry_index, name, version, deps)| {
+                        log::info!("downloading content for package `{name}` from component registry `{registry_name}`");
+                        fetch_progress.started(&name, registry_name);
+
+                        tokio::spawn(async move {
+                            let res = policy.run(|| client.download_exact(&name, &version)).await;
+                            (registry_index, name, version, deps, res)
+                        })
+                    },
+                )
+                .buffer_unordered(concurrency);
+
+            let mut downloaded = Vec::with_capacity(count);
+            let mut finished = 0;
+            while let Some(res) = futures.next().await {
+                let (registry_index, name, version, deps, res) =
+                    res.context("failed to join content download task")?;
+
+                if res.is_ok() {
+                    let ((registry_name, _), _) = registries
+                        .get_index(registry_index)
+                        .expect("out of bounds registry index");
+
+                    log::info!(
+                        "downloaded contents of package `{name}` (v{version}) from component registry `{registry_name}`"
+                    );
+
+                    finished += 1;
+                    fetch_progress.finished(&name, registry_name, &version);
+                    fetch_progress.progress(finished, count);
+                    progress.tick_now(
+                        finished,
+                        count,
+                        &format!(": downloaded `{name}` (v{version})"),
+                    )?;
+                }
+
+                downloaded.push((registry_index, name, version, deps, res));
+            }
+
+            // Report errors in a consistent order, regardless of the order
+            // the downloads completed in
+            downloaded.sort_by_key(|(registry_index, name, version, ..)| {
+                (*registry_index, name.to_string(), version.clone())
+            });
+
+            for (registry_index, name, version, deps, res) in downloaded {
+                let ((registry_name, _), registry) = registries
+                    .get_index_mut(registry_index)
+                    .expect("out of bounds registry index");
+
+                let download = res.with_context(|| {
+                    format!("failed to download package `{name}` (v{version}) from component registry `{registry_name}`")
+                })?;
+
+                for index in deps {
+                    let dependency = &mut registry.dependencies[index];
+                    assert!(dependency.resolution.is_none());
+                    dependency.resolution = Some(RegistryResolution {
+                        name: dependency.name.clone(),
+                        package: dependency.package.clone(),
+                        registry: if *registry_name == DEFAULT_REGISTRY_NAME {
+                            None
+                        } else {
+                            Some(registry_name.to_string())
+                        },
+                        requirement: dependency.version.clone(),
+                        version: download.version.clone(),
+                        digest: download.digest.clone(),
+                        path: download.path.clone(),
+                    });
+                }
+            }
+
+            assert_eq!(finished, count);
+
+            progress.clear();
+        }
+
+        Ok(registries
+            .into_values()
+            .flat_map(|r| r.dependencies.into_iter())
+            .map(|d| {
+                DependencyResolution::Registry(
+                    d.resolution.expect("dependency should have been resolved"),
+                )
+            }))
+    }
+}
+
+struct Registry<'a> {
+    client: Arc<FileSystemClient>,
+    policy: RegistryClientConfig,
+    packages: HashMap<registry::PackageName, PackageInfo>,
+    dependencies: Vec<RegistryDependency<'a>>,
+    upserts: HashSet<registry::PackageName>,
+    prefer_cached: bool,
+}
+
+impl<'a> Registry<'a> {
+    async fn add_dependency(
+        &mut self,
+        name: &'a registry::PackageName,
+        package: registry::PackageName,
+        version: &'a VersionReq,
+        registry: &str,
+        locked: Option<&LockedPackageVersion>,
+    ) -> Result<()> {
+        let dep = RegistryDependency {
+            name,
+            package: package.clone(),
+            version,
+            locked: locked.map(|l| (l.version.clone(), l.digest.clone())),
+            resolution: None,
+        };
+
+        self.dependencies.push(dep);
+
+        let mut needs_upsert = true;
+        if let Some(locked) = locked {
+            if let Some(package) =
+                Self::load_package(&self.client, &mut self.packages, package.clone()).await?
+            {
+                if package
+                    .state
+                    .release(&locked.version)
+                    .and_then(|r| r.content())
+                    .is_some()
+                {
+                    // Don't need to upsert this package as it is present
+                    // in the lock file and in client storage.
+                    needs_upsert = false;
+                }
+            }
+        } else if self.prefer_cached {
+            if let Some(package) =
+                Self::load_package(&self.client, &mut self.packages, package.clone()).await?
+            {
+                // Don't need to upsert this package as a satisfying version
+                // is already in client storage
+                needs_upsert =
+                    Self::latest_cached_release(&self.client, package, version).is_none();
+            }
+        }
+
+        if needs_upsert && self.upserts.insert(package.clone()) {
+            log::info!(
+                "package `{package}` from component registry `{registry}` needs to be updated"
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn add_downloads(
+        &mut self,
+        registry: &'a str,
+        registry_index: usize,
+        downloads: &mut DownloadMap,
+    ) -> Result<()> {
+        let Self {
+            dependencies,
+            packages,
+            client,
+            prefer_cached,
+            ..
+        } = self;
+
+        for (index, dependency) in dependencies.iter_mut().enumerate() {
+            let package = Self::load_package(client, packages, dependency.package.clone())
+                .await?
+                .with_context(|| {
+                    format!(
+                        "package `{name}` was not found in component registry `{registry}`",
+                        name = dependency.package
+                    )
+                })?;
+
+            let release = match &dependency.locked {
+                // Ignore a locked version that no longer satisfies the requirement,
+                // such as after changing an exact (`=x.y.z`) requirement
+                Some((version, digest)) if dependency.version.matches(version) => {
+                    // The dependency had a lock file entry, so attempt to do an exact match first
+                    let exact_req = VersionReq {
+                        comparators: vec![Comparator {
+                            op: Op::Exact,
+                            major: version.major,
+                            minor: Some(version.minor),
+                            patch: Some(version.patch),
+                            pre: version.pre.clone(),
+                        }],
+                    };
+
+                    // If an exact match can't be found, fallback to the latest release to
+                    // satisfy the version requirement; this can happen when packages are yanked
+                    package.state.find_latest_release(&exact_req).map(|r| {
+                        // Exact match, verify the content digests match
+                        let content = r.content().expect("release must have content");
+                        if content != digest {
+                            bail!(
+                                "component registry package `{name}` (v`{version}`) has digest `{content}` but the lock file specifies digest `{digest}`",
+                                name = dependency.package,
+                            );
+                        }
+                        Ok(r)
+                    }).transpose()?.or_else(|| package.state.find_latest_release(dependency.version))
+                }
+                _ if *prefer_cached => Self::latest_cached_release(client, package, dependency.version)
+                    .or_else(|| package.state.find_latest_release(dependency.version)),
+                _ => package.state.find_latest_release(dependency.version),
+            }.ok_or_else(|| {
+                no_matching_release(
+                    &dependency.package,
+                    dependency.version,
+                    package.state.releases().filter(|r| !r.yanked()).map(|r| &r.version),
+                )
+            })?;
+
+            let digest = release.content().expect("release must have content");
+            match client.content().content_location(digest) {
+                Some(path) => {
+                    // Content is already present, set the resolution
+                    assert!(dependency.resolution.is_none());
+                    dependency.resolution = Some(RegistryResolution {
+                        name: dependency.name.clone(),
+                        package: dependency.package.clone(),
+                        registry: if registry == DEFAULT_REGISTRY_NAME {
+                            None
+                        } else {
+                            Some(registry.to_string())
+                        },
+                        requirement: dependency.version.clone(),
+                        version: release.version.clone(),
+                        digest: digest.clone(),
+                        path,
+                    });
+
+                    log::info!(
+                        "version {version} of registry package `{name}` from registry `{registry}` is already in client storage",
+                        name = dependency.package,
+                        version = release.version,
+                    );
+                }
+                None => {
+                    // Content needs to be downloaded
+                    let indexes = downloads
+                        .entry((
+                            registry_index,
+                            dependency.package.clone(),
+                            release.version.clone(),
+                        ))
+                        .or_default();
+
+                    if indexes.is_empty() {
+                        log::info!(
+                            "version {version} of registry package `{name}` from registry `{registry}` needs to be downloaded",
+                            name = dependency.package,
+                            version = release.version,
+                        );
+                    }
 
-            log::info!("package logs successfully updated for component registry `{name}`");
-            finished += 1;
-            progress.tick_now(finished, task_count, ": updated `{name}`")?;
-            registry.add_downloads(name, &mut downloads).await?;
+                    indexes.push(index);
+                }
+            }
         }
 
-        assert_eq!(finished, task_count);
+        Ok(())
+    }
 
-        progress.clear();
+    /// Gets the latest release of a package satisfying the given requirement
+    /// whose content is in client storage.
+    fn latest_cached_release<'b>(
+        client: &FileSystemClient,
+        package: &'b PackageInfo,
+        requirement: &VersionReq,
+    ) -> Option<&'b Release> {
+        package
+            .state
+            .releases()
+            .filter(|r| !r.yanked() && requirement.matches(&r.version))
+            .filter(|r| {
+                r.content()
+                    .and_then(|digest| client.content().content_location(digest))
+                    .is_some()
+            })
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
 
-        Ok(downloads)
+    async fn load_package<'b>(
+        client: &FileSystemClient,
+        packages: &'b mut HashMap<registry::PackageName, PackageInfo>,
+        name: registry::PackageName,
+    ) -> Result<Option<&'b PackageInfo>> {
+        match packages.entry(name) {
+            hash_map::Entry::Occupied(e) => Ok(Some(e.into_mut())),
+            hash_map::Entry::Vacant(e) => match client
+                .registry()
+                .load_package(client.get_warg_registry(), e.key())
+                .await?
+            {
+                Some(p) => Ok(Some(e.insert(p))),
+                None => Ok(None),
+            },
+        }
     }
+}
 
-    async fn download_and_resolve(
-        mut registries: IndexMap<&'a str, Registry<'a>>,
-        downloads: DownloadMap<'a>,
-        terminal: &Terminal,
-        network_allowed: bool,
-    ) -> Result<impl Iterator<Item = DependencyResolution> + 'a> {
-        if !downloads.is_empty() {
-            if !network_allowed {
-                bail!("a component package download is required but network access is disabled");
+/// Determines if the given requirement matches exactly one version
+/// (e.g. `=1.2.3`).
+fn is_exact(requirement: &VersionReq) -> bool {
+    matches!(
+        requirement.comparators.as_slice(),
+        [Comparator {
+            op: Op::Exact,
+            minor: Some(_),
+            patch: Some(_),
+            ..
+        }]
+    )
+}
+
+/// Creates the error for a registry package with no release matching a
+/// version requirement.
+///
+/// As with Cargo, pre-releases only match requirements that include a
+/// pre-release; when a stable requirement is otherwise satisfied only by
+/// pre-releases, the error lists them.
+fn no_matching_release<'b>(
+    name: &registry::PackageName,
+    requirement: &VersionReq,
+    versions: impl Iterator<Item = &'b Version>,
+) -> anyhow::Error {
+    let versions: Vec<_> = versions.collect();
+    let mut pre_releases: Vec<_> = if requirement.comparators.iter().all(|c| c.pre.is_empty()) {
+        versions
+            .iter()
+            .copied()
+            .filter(|v| {
+                !v.pre.is_empty() && requirement.matches(&Version::new(v.major, v.minor, v.patch))
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    pre_releases.sort();
+    let latest = match pre_releases.last() {
+        Some(latest) => latest,
+        None if is_exact(requirement) => {
+            let mut available = versions;
+            available.sort();
+            available.dedup();
+
+            if available.is_empty() {
+                return anyhow!(
+                    "component registry package `{name}` has no release matching exact version requirement `{requirement}`; \
+                     the package has no releases"
+                );
+            }
+
+            return anyhow!(
+                "component registry package `{name}` has no release matching exact version requirement `{requirement}`\n\n\
+                 the following versions are available: {available}",
+                available = available
+                    .iter()
+                    .map(|v| format!("`{v}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        None => {
+            return anyhow!(
+                "component registry package `{name}` has no release matching version requirement `{requirement}`"
+            )
+        }
+    };
+
+    anyhow!(
+        "component registry package `{name}` has no stable release matching version requirement `{requirement}`\n\n\
+         only the following pre-releases match, which require a pre-release version requirement: {versions}\n\n\
+         specify a pre-release requirement such as `={latest}` to use one",
+        versions = pre_releases
+            .iter()
+            .map(|v| format!("`{v}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// The key of a download: the index of the registry, the package name, and
+/// the package version.
+type DownloadMapKey = (usize, registry::PackageName, Version);
+
+/// A package to fetch from an OCI registry.
+struct OciFetch<'a> {
+    /// The name of the dependency being resolved.
+    name: &'a registry::PackageName,
+    /// The name of the package in the registry.
+    package: registry::PackageName,
+    /// The name of the registry.
+    registry: &'a str,
+    /// The namespace of the registry's packages.
+    namespace: &'a str,
+    requirement: &'a VersionReq,
+    locked: Option<LockedPackageVersion>,
+    prefer_cached: bool,
+}
+type DownloadMap = HashMap<DownloadMapKey, Vec<usize>>;
+
+struct RegistryDependency<'a> {
+    /// The package name assigned in the configuration file.
+    name: &'a registry::PackageName,
+    /// The package name of the registry package.
+    package: registry::PackageName,
+    version: &'a VersionReq,
+    locked: Option<(Version, AnyHash)>,
+    resolution: Option<RegistryResolution>,
+}
+
+/// Represents a map of dependency resolutions.
+///
+/// The key to the map is the package name of the dependency.
+pub type DependencyResolutionMap = HashMap<registry::PackageName, DependencyResolution>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        lock::{LockFile, LockedPackage},
+        terminal::{Color, Verbosity},
+    };
+    use std::sync::OnceLock;
+    use tempfile::tempdir;
+
+    /// Gets the OCI cache directory shared by the tests of this module.
+    fn oci_cache() -> &'static Path {
+        static DIR: OnceLock<tempfile::TempDir> = OnceLock::new();
+        DIR.get_or_init(|| tempdir().unwrap()).path()
+    }
+
+    /// Gets an OCI client using the shared OCI cache directory.
+    fn oci_client() -> OciClient {
+        OciClient::new(oci_cache().to_path_buf(), Default::default())
+    }
+
+    /// Writes the given contents to the OCI cache, returning their digest.
+    fn cache_oci_contents(contents: &str) -> AnyHash {
+        let digest: AnyHash = format!(
+            "sha256:{digest}",
+            digest = Sha256::digest(contents)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        )
+        .parse()
+        .unwrap();
+        fs::write(
+            oci_cache().join(format!("{}.wasm", digest.to_string().replace(':', "-"))),
+            contents,
+        )
+        .unwrap();
+        digest
+    }
+
+    #[test]
+    fn it_normalizes_package_map_keys() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "deserialize_package_map")]
+            dependencies: HashMap<registry::PackageName, Dependency>,
+        }
+
+        let config: Config = toml_edit::de::from_str(
+            r#"
+[dependencies]
+"WASI:http" = "0.2.0"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.dependencies.len(), 1);
+        assert!(config
+            .dependencies
+            .contains_key(&"wasi:http".parse::<registry::PackageName>().unwrap()));
+
+        let err = toml_edit::de::from_str::<Config>(
+            r#"
+[dependencies]
+"WASI:http" = "0.2.0"
+"wasi:http" = "0.2.1"
+"#,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains(
+            "package names `WASI:http` and `wasi:http` refer to the same package `wasi:http`"
+        ));
+    }
+
+    #[test]
+    fn it_finds_registry_urls_from_the_environment() {
+        let urls = HashMap::from_iter([(
+            "other".to_string(),
+            Url::parse("https://other.example.com").unwrap().into(),
+        )]);
+
+        assert_eq!(
+            find_url_with_env(None, &urls, Some("https://home.example.com"), None).unwrap(),
+            "https://home.example.com"
+        );
+
+        let env_url = Some("https://env.example.com".to_string());
+        assert_eq!(
+            find_url_with_env(
+                None,
+                &urls,
+                Some("https://home.example.com"),
+                env_url.clone()
+            )
+            .unwrap(),
+            "https://env.example.com"
+        );
+        assert_eq!(
+            find_url_with_env(Some("other"), &urls, None, env_url).unwrap(),
+            "https://other.example.com/"
+        );
+
+        assert!(
+            find_url_with_env(None, &urls, None, Some("not a url".to_string()))
+                .unwrap_err()
+                .to_string()
+                .contains("invalid registry URL `not a url`")
+        );
+    }
+
+    #[test]
+    fn it_serializes_sorted_maps() {
+        #[derive(Serialize, Deserialize)]
+        struct Config {
+            #[serde(
+                serialize_with = "serialize_sorted_map",
+                deserialize_with = "deserialize_package_map"
+            )]
+            dependencies: HashMap<registry::PackageName, Dependency>,
+            #[serde(serialize_with = "serialize_sorted_map")]
+            registries: HashMap<String, RegistryRef>,
+        }
+
+        let config: Config = toml_edit::de::from_str(
+            r#"
+[dependencies]
+"foo:zed" = "1.0.0"
+"foo:bar" = { path = "bar" }
+"baz:qux" = "0.1.0"
+
+[registries]
+zeta = "https://zeta.example.com"
+alpha = { oci = "ghcr.io/alpha" }
+mid = "https://mid.example.com"
+"#,
+        )
+        .unwrap();
+
+        let serialized = toml_edit::ser::to_string(&config).unwrap();
+        let position = |key: &str| serialized.find(key).unwrap();
+        assert!(position("baz:qux") < position("foo:bar"));
+        assert!(position("foo:bar") < position("foo:zed"));
+        assert!(position("alpha") < position("mid"));
+        assert!(position("mid") < position("zeta"));
+    }
+
+    #[test]
+    fn it_deserializes_registry_refs() {
+        #[derive(Deserialize)]
+        struct Config {
+            registries: HashMap<String, RegistryRef>,
+        }
+
+        let config: Config = toml_edit::de::from_str(
+            r#"
+[registries]
+bare = "https://bare.example.com"
+warg = { warg = "https://warg.example.com" }
+oci = { oci = "ghcr.io/example/" }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.registries["bare"],
+            RegistryRef::from(Url::parse("https://bare.example.com").unwrap())
+        );
+        assert_eq!(
+            config.registries["warg"],
+            RegistryRef::from(Url::parse("https://warg.example.com").unwrap())
+        );
+        assert_eq!(
+            config.registries["oci"],
+            RegistryRef::Oci("ghcr.io/example".to_string())
+        );
+
+        assert!(find_url(Some("oci"), &config.registries, None)
+            .unwrap_err()
+            .to_string()
+            .contains("is an OCI registry"));
+
+        for (toml, message) in [
+            (
+                r#"registries = { both = { warg = "https://example.com", oci = "ghcr.io/example" } }"#,
+                "cannot specify both `warg` and `oci` fields",
+            ),
+            (
+                "registries = { neither = {} }",
+                "must specify either a `warg` or an `oci` field",
+            ),
+            (
+                "registries = { invalid = \"not a url\" }",
+                "relative URL without a base",
+            ),
+        ] {
+            let err = match toml_edit::de::from_str::<Config>(toml) {
+                Ok(_) => panic!("expected `{toml}` to fail to deserialize"),
+                Err(e) => e,
+            };
+            assert!(err.to_string().contains(message), "{err}");
+        }
+    }
+
+    #[test]
+    fn it_maps_registry_protocols() {
+        #[derive(Deserialize, Serialize)]
+        struct Config {
+            registries: HashMap<String, RegistryRef>,
+        }
+
+        let config: Config = toml_edit::de::from_str(
+            r#"
+[registries]
+default = "https://default.example.com"
+pinned = { warg = "https://pinned.example.com", protocol = 1 }
+future = { warg = "https://future.example.com", protocol = 2 }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.registries["pinned"],
+            RegistryRef::Warg {
+                url: Url::parse("https://pinned.example.com").unwrap(),
+                protocol: Some(1),
+            }
+        );
+        assert_eq!(find_protocol(None, &config.registries), None);
+        assert_eq!(find_protocol(Some("pinned"), &config.registries), Some(1));
+        assert_eq!(find_protocol(Some("missing"), &config.registries), None);
+
+        assert_eq!(
+            select_protocol("https://default.example.com", None).unwrap(),
+            WARG_PROTOCOL_VERSION
+        );
+        assert_eq!(
+            select_protocol(
+                "https://pinned.example.com",
+                find_protocol(Some("pinned"), &config.registries)
+            )
+            .unwrap(),
+            1
+        );
+        assert_eq!(
+            select_protocol(
+                "https://future.example.com",
+                find_protocol(Some("future"), &config.registries)
+            )
+            .unwrap_err()
+            .to_string(),
+            "component registry `https://future.example.com` uses warg protocol version 2, \
+             which is incompatible with the supported version 1"
+        );
+
+        let serialized = toml_edit::ser::to_string(&config).unwrap();
+        let config: Config = toml_edit::de::from_str(&serialized).unwrap();
+        assert_eq!(find_protocol(Some("future"), &config.registries), Some(2));
+
+        let err = match toml_edit::de::from_str::<Config>(
+            r#"registries = { oci = { oci = "ghcr.io/example", protocol = 1 } }"#,
+        ) {
+            Ok(_) => panic!("expected a protocol for an OCI registry to fail"),
+            Err(e) => e,
+        };
+        assert!(
+            err.to_string()
+                .contains("the `protocol` field is only supported for `warg` registries"),
+            "{err}"
+        );
+    }
+
+    /// Starts a registry over plain HTTP that serves the checkpoint endpoint
+    /// of only the given warg protocol version, returning its URL.
+    ///
+    /// If a token is given, requests without it as a bearer token are
+    /// answered with `404 Not Found`, as a private registry might.
+    fn start_protocol_registry(protocol: u8, token: Option<&'static str>) -> String {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{addr}", addr = listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut authorized = token.is_none();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+
+                    if let (Some(token), Some((name, value))) = (token, line.split_once(':')) {
+                        authorized |= name.eq_ignore_ascii_case("authorization")
+                            && value.trim() == format!("Bearer {token}");
+                    }
+                }
+
+                let path = request.split(' ').nth(1).unwrap_or_default();
+                let status = if authorized && path == format!("/v{protocol}/fetch/checkpoint") {
+                    "200 OK"
+                } else {
+                    "404 Not Found"
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn it_checks_protocols_with_the_registry() {
+        let url = start_protocol_registry(1, None);
+        check_protocol(&url, 1, None).await.unwrap();
+
+        // The probe is sent with the registry's auth token
+        let url = start_protocol_registry(1, Some("secret"));
+        check_protocol(&url, 1, Some(&Secret::new("secret".to_string())))
+            .await
+            .unwrap();
+        assert!(check_protocol(&url, 1, None).await.is_err());
+
+        let url = start_protocol_registry(2, None);
+        let err = check_protocol(&url, 1, None).await.unwrap_err().to_string();
+        assert!(
+            err.contains(&format!(
+                "component registry `{url}` does not serve warg protocol version 1"
+            )),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn it_expands_templated_registry_urls() {
+        let urls = HashMap::from([(
+            "cdn".to_string(),
+            Url::parse("https://cdn.example.com/{name}/{version}")
+                .unwrap()
+                .into(),
+        )]);
+        let url = find_url(Some("cdn"), &urls, None).unwrap();
+
+        let http = "wasi:http".parse().unwrap();
+        let io = "wasi:io".parse().unwrap();
+        assert_eq!(
+            expand_url(&url, &http, &template_version(&"0.2.0".parse().unwrap())).unwrap(),
+            "https://cdn.example.com/wasi:http/0.2.0"
+        );
+        assert_eq!(
+            expand_url(&url, &io, &template_version(&"=0.2.1".parse().unwrap())).unwrap(),
+            "https://cdn.example.com/wasi:io/0.2.1"
+        );
+
+        // Plain URLs are unchanged
+        assert!(matches!(
+            expand_url("https://example.com/", &http, "0.2.0").unwrap(),
+            Cow::Borrowed("https://example.com/")
+        ));
+
+        assert!(expand_url("https://{name}.example.com/", &http, "0.2.0")
+            .unwrap_err()
+            .to_string()
+            .contains("expanded to invalid URL `https://wasi:http.example.com/`"));
+    }
+
+    #[test]
+    fn it_selects_pre_releases_only_when_requested() {
+        let package: RegistryPackage = "0.2.0-alpha.1".parse().unwrap();
+        assert!(package
+            .version
+            .matches(&"0.2.0-alpha.2".parse::<Version>().unwrap()));
+        assert!(package.version.matches(&Version::new(0, 2, 0)));
+
+        let stable: RegistryPackage = "0.2.0".parse().unwrap();
+        assert!(!stable
+            .version
+            .matches(&"0.2.1-alpha.1".parse::<Version>().unwrap()));
+    }
+
+    #[test]
+    fn it_lists_pre_releases_for_stable_requirements() {
+        let name = "wasi:http".parse().unwrap();
+        let versions: Vec<Version> = ["0.2.0-rc.1", "0.2.0-rc.2", "0.1.0-alpha.1"]
+            .iter()
+            .map(|v| v.parse().unwrap())
+            .collect();
+
+        let message =
+            no_matching_release(&name, &"0.2.0".parse().unwrap(), versions.iter()).to_string();
+        assert!(message.contains("has no stable release matching version requirement `^0.2.0`"));
+        assert!(message.contains("`0.2.0-rc.1`, `0.2.0-rc.2`"));
+        assert!(!message.contains("0.1.0-alpha.1"));
+        assert!(message.contains("`=0.2.0-rc.2`"));
+
+        let message = no_matching_release(&name, &"=0.3.0-rc.1".parse().unwrap(), versions.iter())
+            .to_string();
+        assert!(message.contains("has no release matching exact version requirement `=0.3.0-rc.1`"));
+    }
+
+    #[test]
+    fn it_lists_available_versions_for_exact_requirements() {
+        let name = "foo:bar".parse().unwrap();
+        let package: RegistryPackage = "=1.2.3".parse().unwrap();
+        assert_eq!(package.version.to_string(), "=1.2.3");
+        assert!(is_exact(&package.version));
+        assert!(package.version.matches(&Version::new(1, 2, 3)));
+        assert!(!package.version.matches(&Version::new(1, 2, 4)));
+        assert!(!is_exact(&"1.2.3".parse().unwrap()));
+        assert!(!is_exact(&"=1.2".parse().unwrap()));
+
+        // A fake index that only has other versions
+        let versions: Vec<Version> = ["1.2.4", "1.3.0", "1.2.2"]
+            .iter()
+            .map(|v| v.parse().unwrap())
+            .collect();
+        assert!(versions.iter().all(|v| !package.version.matches(v)));
+
+        let message = no_matching_release(&name, &package.version, versions.iter()).to_string();
+        assert!(message.contains(
+            "component registry package `foo:bar` has no release matching exact version requirement `=1.2.3`"
+        ));
+        assert!(message.contains("the following versions are available: `1.2.2`, `1.2.4`, `1.3.0`"));
+
+        let message = no_matching_release(&name, &package.version, std::iter::empty()).to_string();
+        assert!(message.contains("the package has no releases"));
+    }
+
+    #[test]
+    fn it_selects_proxies() {
+        let env = ProxyEnv {
+            http_proxy: Some("http://http-proxy:3128".to_string()),
+            https_proxy: Some("http://https-proxy:3128".to_string()),
+            no_proxy: Some("localhost, .internal.example.com".to_string()),
+        };
+
+        let select = |url| {
+            env.select(url)
+                .unwrap()
+                .map(|url: Url| url.host_str().unwrap().to_string())
+        };
+
+        assert_eq!(
+            select("https://registry.example.com").as_deref(),
+            Some("https-proxy")
+        );
+        assert_eq!(
+            select("registry.example.com").as_deref(),
+            Some("https-proxy")
+        );
+        assert_eq!(
+            select("http://registry.example.com").as_deref(),
+            Some("http-proxy")
+        );
+        assert_eq!(select("http://localhost:8090"), None);
+        assert_eq!(select("https://warg.internal.example.com"), None);
+        assert_eq!(select("https://internal.example.com"), None);
+        assert_eq!(
+            select("https://notinternal.example.com").as_deref(),
+            Some("https-proxy")
+        );
+
+        let env = ProxyEnv::default();
+        assert_eq!(env.select("https://registry.example.com").unwrap(), None);
+    }
+
+    #[test]
+    fn it_digests_local_directories() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("deps/foo")).unwrap();
+        fs::write(dir.path().join("world.wit"), "package foo:bar;\n").unwrap();
+        fs::write(dir.path().join("deps/foo/types.wit"), "package foo:foo;\n").unwrap();
+
+        let resolution = LocalResolution {
+            name: "foo:bar".parse().unwrap(),
+            path: dir.path().to_path_buf(),
+        };
+        let digest = resolution.digest().unwrap();
+        assert_eq!(digest, resolution.digest().unwrap());
+
+        // The digest is independent of the directory's location
+        let other = tempdir().unwrap();
+        fs::create_dir_all(other.path().join("deps/foo")).unwrap();
+        fs::write(other.path().join("world.wit"), "package foo:bar;\n").unwrap();
+        fs::write(
+            other.path().join("deps/foo/types.wit"),
+            "package foo:foo;\n",
+        )
+        .unwrap();
+        let copy = LocalResolution {
+            name: "foo:bar".parse().unwrap(),
+            path: other.path().to_path_buf(),
+        };
+        assert_eq!(copy.digest().unwrap(), digest);
+
+        // Changing a nested file changes the digest
+        fs::write(dir.path().join("deps/foo/types.wit"), "package foo:baz;\n").unwrap();
+        assert_ne!(resolution.digest().unwrap(), digest);
+
+        // Renaming a file changes the digest
+        fs::write(dir.path().join("deps/foo/types.wit"), "package foo:foo;\n").unwrap();
+        fs::rename(dir.path().join("world.wit"), dir.path().join("other.wit")).unwrap();
+        assert_ne!(resolution.digest().unwrap(), digest);
+    }
+
+    #[test]
+    fn it_checks_local_dependency_names() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("types.wit"), "package foo:bar@1.0.0;\n").unwrap();
+
+        let resolution = |name: &str| {
+            DependencyResolution::Local(LocalResolution {
+                name: name.parse().unwrap(),
+                path: dir.path().to_path_buf(),
+            })
+        };
+
+        // The version of the declared package is not compared
+        resolution("foo:bar").decode().unwrap();
+
+        let message = format!("{e:#}", e = resolution("foo:baz").decode().err().unwrap());
+        assert!(message.contains("local dependency `foo:baz`"));
+        assert!(message.contains("declares package `foo:bar@1.0.0`"));
+    }
+
+    #[test]
+    fn it_renames_remapped_packages() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("fork.wit");
+        fs::write(
+            &path,
+            "package my-org:http@0.2.0;\n\ninterface types {\n  type field = string;\n}\n",
+        )
+        .unwrap();
+
+        let mut resolution = RegistryResolution {
+            name: "wasi:http".parse().unwrap(),
+            package: "my-org:http".parse().unwrap(),
+            registry: None,
+            requirement: "0.2.0".parse().unwrap(),
+            version: Version::new(0, 2, 0),
+            digest: "sha256:0000000000000000000000000000000000000000000000000000000000000000"
+                .parse()
+                .unwrap(),
+            path,
+        };
+
+        let dependency = DependencyResolution::Registry(resolution.clone());
+        assert_eq!(
+            dependency.renamed().map(ToString::to_string).as_deref(),
+            Some("wasi:http")
+        );
+        let decoded = dependency.decode().unwrap();
+        assert_eq!(decoded.package_name().to_string(), "wasi:http@0.2.0");
+
+        resolution.name = resolution.package.clone();
+        let dependency = DependencyResolution::Registry(resolution);
+        assert!(dependency.renamed().is_none());
+        let decoded = dependency.decode().unwrap();
+        assert_eq!(decoded.package_name().to_string(), "my-org:http@0.2.0");
+    }
+
+    #[test]
+    fn it_selects_registry_tokens_from_credentials() {
+        let credentials = Credentials::parse(
+            PathBuf::from("credentials.toml"),
+            r#"
+[registries."https://example.com/"]
+token = "secret"
+
+[registries."https://private.example.com"]
+protected = true
+"#,
+        )
+        .unwrap();
+
+        let token = |url, env: Option<(&str, &str)>, keyring: Option<&str>| {
+            credentials
+                .token_with(
+                    url,
+                    env.map(|(token, _)| token.to_string()),
+                    env.map(|(_, url)| url.to_string()),
+                    || Ok(keyring.map(|t| Secret::new(t.to_string()))),
+                )
+                .map(|t| t.map(|t| t.expose_secret().clone()))
+        };
+
+        assert_eq!(
+            token("https://example.com", None, None).unwrap().as_deref(),
+            Some("secret")
+        );
+        assert_eq!(
+            token(
+                "https://example.com",
+                Some(("env", "https://example.com/")),
+                None
+            )
+            .unwrap()
+            .as_deref(),
+            Some("env")
+        );
+        assert_eq!(
+            token("https://other.example.com", None, None).unwrap(),
+            None
+        );
+
+        // The environment token is only sent to the registry it is for
+        assert_eq!(
+            token(
+                "https://other.example.com",
+                Some(("env", "https://example.com")),
+                None
+            )
+            .unwrap(),
+            None
+        );
+        assert_eq!(
+            token("https://example.com", Some(("env", "")), None)
+                .unwrap()
+                .as_deref(),
+            Some("secret")
+        );
+        assert_eq!(
+            token(
+                "https://private.example.com",
+                Some(("env", "https://private.example.com")),
+                None
+            )
+            .unwrap()
+            .as_deref(),
+            Some("env")
+        );
+
+        // Protected registries fall back to the keyring
+        assert_eq!(
+            token("https://private.example.com", None, Some("keyring"))
+                .unwrap()
+                .as_deref(),
+            Some("keyring")
+        );
+        assert!(token(
+            "https://private.example.com",
+            Some(("env", "https://example.com")),
+            None
+        )
+        .unwrap_err()
+        .to_string()
+        .contains("authentication required for component registry `https://private.example.com`"));
+
+        // Tokens are never included in debug output
+        assert!(!format!("{credentials:?}").contains("secret"));
+    }
+
+    #[test]
+    fn it_maps_registry_client_configs() {
+        let configs = HashMap::from([(
+            "flaky".to_string(),
+            RegistryClientConfig {
+                timeout_secs: 5,
+                max_retries: 4,
+            },
+        )]);
+
+        let config = RegistryClientConfig::for_registry(&configs, "flaky");
+        assert_eq!(config.timeout(), Duration::from_secs(5));
+        assert_eq!(config.max_retries, 4);
+        assert_eq!(
+            RegistryClientConfig::for_registry(&configs, DEFAULT_REGISTRY_NAME),
+            RegistryClientConfig::default()
+        );
+
+        assert_eq!(config.backoff(0), Duration::from_millis(250));
+        assert_eq!(config.backoff(2), Duration::from_millis(1000));
+    }
+
+    #[tokio::test]
+    async fn it_stops_retrying_after_max_retries() {
+        let config = RegistryClientConfig {
+            timeout_secs: 5,
+            max_retries: 2,
+        };
+
+        let mut attempts = 0;
+        let err = config
+            .run(|| {
+                attempts += 1;
+                async { Err::<(), _>(anyhow!("registry unavailable")) }
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "registry unavailable");
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let value = config
+            .run(|| {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt < 2 {
+                        Err(anyhow!("registry unavailable"))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(value, 2);
+    }
+
+    #[test]
+    fn it_replaces_sources_with_mirrors() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join(SOURCE_REPLACEMENT_FILE_NAME),
+            r#"
+[source-replacement]
+"wasi:" = "https://mirror.example.com"
+"wasi:http" = "https://http-mirror.example.com"
+"https://registry.example.com/" = "https://registry-mirror.example.com"
+"#,
+        )
+        .unwrap();
+
+        let nested = dir.path().join("nested/project");
+        fs::create_dir_all(&nested).unwrap();
+        let replacement = SourceReplacement::find(&nested).unwrap();
+
+        let replace = |package: &str, url: &str| {
+            replacement
+                .replace(&package.parse().unwrap(), url)
+                .map(Url::to_string)
+        };
+
+        assert_eq!(
+            replace("wasi:io", "https://other.example.com").as_deref(),
+            Some("https://mirror.example.com/")
+        );
+        assert_eq!(
+            replace("wasi:http", "https://other.example.com").as_deref(),
+            Some("https://http-mirror.example.com/")
+        );
+        assert_eq!(
+            replace("wasi:http", "https://registry.example.com").as_deref(),
+            Some("https://registry-mirror.example.com/")
+        );
+        assert_eq!(replace("foo:bar", "https://other.example.com"), None);
+
+        let empty = tempdir().unwrap();
+        assert_eq!(
+            SourceReplacement::find(empty.path()).unwrap(),
+            SourceReplacement::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_resolves_in_frozen_modes() {
+        // The namespace is not a valid OCI reference, so any attempt to fetch
+        // fails without contacting a registry
+        let registries = HashMap::from([(
+            "oci".to_string(),
+            RegistryRef::Oci("Invalid Namespace".into()),
+        )]);
+        let config = Config::default();
+        let terminal = Terminal::new(Verbosity::Quiet, Color::Never);
+        let name: registry::PackageName = "foo:bar".parse().unwrap();
+        let dependency = Dependency::Package(RegistryPackage {
+            name: None,
+            version: "1.0.0".parse().unwrap(),
+            registry: Some("oci".to_string()),
+            registries: Vec::new(),
+        });
+
+        // The locked contents are not in the cache; the lock files are leaked
+        // so the futures of `resolve` may borrow them
+        let lock_file: &'static LockFile =
+            Box::leak(Box::new(LockFile::new(vec![LockedPackage {
+                name: name.clone(),
+                registry: Some("oci".to_string()),
+                versions: vec![LockedPackageVersion {
+                    requirement: "^1.0.0".to_string(),
+                    version: Version::new(1, 0, 0),
+                    digest:
+                        "sha256:0000000000000000000000000000000000000000000000000000000000000000"
+                            .parse()
+                            .unwrap(),
+                }],
+            }])));
+        let other: &'static LockFile = Box::leak(Box::new(LockFile::new(Vec::new())));
+
+        let resolve = |mode, lock_file: Option<&'static LockFile>| {
+            let mut resolver = DependencyResolver::new(
+                &config,
+                &registries,
+                lock_file.map(LockFileResolver::new),
+                &terminal,
+                true,
+            )
+            .unwrap()
+            .with_frozen_mode(mode);
+            let name = &name;
+            let dependency = &dependency;
+            async move {
+                let res = match resolver.add_dependency(name, dependency).await {
+                    Ok(()) => resolver.resolve().await.map(|_| ()),
+                    Err(e) => Err(e),
+                };
+
+                match res {
+                    Ok(()) => String::new(),
+                    Err(e) => format!("{e:#}"),
+                }
+            }
+        };
+
+        // A missing package is fetched
+        assert!(resolve(FrozenMode::Normal, None)
+            .await
+            .contains("invalid OCI reference"));
+
+        // A missing package requires network access when offline, even when locked
+        for lock_file in [None, Some(lock_file)] {
+            assert!(resolve(FrozenMode::Offline, lock_file)
+                .await
+                .contains("network access is disabled"));
+        }
+
+        // A frozen resolution requires the lock file and the cached package
+        assert!(resolve(FrozenMode::Frozen, None)
+            .await
+            .contains("the lock file does not exist"));
+        assert!(resolve(FrozenMode::Frozen, Some(lock_file))
+            .await
+            .contains("network access is disabled"));
+
+        assert!(resolve(FrozenMode::Frozen, Some(other))
+            .await
+            .contains("is not in the lock file"));
+    }
+
+    #[tokio::test]
+    async fn it_reports_fetch_progress() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<String>>);
+
+        impl FetchProgress for Recorder {
+            fn started(&self, package: &registry::PackageName, registry: &str) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("started {package} from {registry}"));
             }
 
-            terminal.status("Downloading", "component registry packages")?;
+            fn finished(&self, package: &registry::PackageName, registry: &str, version: &Version) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("finished {package}@{version} from {registry}"));
+            }
+        }
 
-            let mut progress =
-                ProgressBar::with_style("Downloading", ProgressStyle::Ratio, terminal);
+        // Locked packages already in the OCI cache are fetched without a network
+        let mut packages = Vec::new();
+        for (name, contents) in [("foo:bar", "bar"), ("foo:baz", "baz")] {
+            packages.push(LockedPackage {
+                name: name.parse().unwrap(),
+                registry: Some("oci".to_string()),
+                versions: vec![LockedPackageVersion {
+                    requirement: "^1.0.0".to_string(),
+                    version: Version::new(1, 0, 0),
+                    digest: cache_oci_contents(contents),
+                }],
+            });
+        }
+        let lock_file = LockFile::new(packages);
 
-            let count = downloads.len();
-            progress.tick_now(0, count, "")?;
+        let registries = HashMap::from([(
+            "oci".to_string(),
+            RegistryRef::Oci("ghcr.io/example".into()),
+        )]);
+        let config = Config::default();
+        let terminal = Terminal::new(Verbosity::Quiet, Color::Never);
+        let recorder = Recorder::default();
+        let mut resolver = DependencyResolver::new(
+            &config,
+            &registries,
+            Some(LockFileResolver::new(&lock_file)),
+            &terminal,
+            false,
+        )
+        .unwrap()
+        .with_oci_client(oci_client())
+        .with_fetch_progress(&recorder);
 
-            let mut futures = FuturesUnordered::new();
-            for ((registry_name, name, version), deps) in downloads {
-                let registry_index = registries.get_index_of(registry_name).unwrap();
-                let (_, registry) = registries.get_index(registry_index).unwrap();
+        let names: Vec<registry::PackageName> =
+            vec!["foo:bar".parse().unwrap(), "foo:baz".parse().unwrap()];
+        let dependency = Dependency::Package(RegistryPackage {
+            name: None,
+            version: "1.0.0".parse().unwrap(),
+            registry: Some("oci".to_string()),
+            registries: Vec::new(),
+        });
+        for name in &names {
+            resolver.add_dependency(name, &dependency).await.unwrap();
+        }
 
-                log::info!("downloading content for package `{name}` from component registry `{registry_name}`");
+        let resolutions = resolver.resolve().await.unwrap();
+        assert_eq!(resolutions.len(), 2);
 
-                let client = registry.client.clone();
-                futures.push(tokio::spawn(async move {
-                    let res = client.download_exact(&name, &version).await;
-                    (registry_index, name, version, deps, res)
-                }))
-            }
+        // Packages are fetched concurrently, so only the order of the events
+        // of each package is known
+        let events = recorder.0.lock().unwrap().clone();
+        for name in ["foo:bar", "foo:baz"] {
+            let started = events
+                .iter()
+                .position(|e| *e == format!("started {name} from oci"))
+                .unwrap();
+            let finished = events
+                .iter()
+                .position(|e| *e == format!("finished {name}@1.0.0 from oci"))
+                .unwrap();
+            assert!(started < finished);
+        }
+        assert_eq!(events.len(), 4);
+    }
 
-            assert_eq!(futures.len(), count);
+    #[tokio::test]
+    async fn it_fetches_packages_concurrently() {
+        use crate::oci::test::{start_test_registry, TestPackage};
 
-            let mut finished = 0;
-            while let Some(res) = futures.next().await {
-                let (registry_index, name, version, deps, res) =
-                    res.context("failed to join content download task")?;
-                let (registry_name, registry) = registries
-                    .get_index_mut(registry_index)
-                    .expect("out of bounds registry index");
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<String>>);
 
-                let download = res.with_context(|| {
-                    format!("failed to download package `{name}` (v{version}) from component registry `{registry_name}`")
-                })?;
+        impl FetchProgress for Recorder {
+            fn started(&self, _: &registry::PackageName, _: &str) {}
 
-                log::info!(
-                    "downloaded contents of package `{name}` (v{version}) from component registry `{registry_name}`"
-                );
+            fn finished(&self, package: &registry::PackageName, _: &str, _: &Version) {
+                self.0.lock().unwrap().push(package.to_string());
+            }
+        }
+
+        // Packages added earlier take longer to fetch, so concurrent fetches
+        // complete in the reverse of the order the packages were added
+        let names: Vec<registry::PackageName> = (0..4)
+            .map(|i| format!("foo:pkg{i}").parse().unwrap())
+            .collect();
+        let packages: Vec<_> = names
+            .iter()
+            .map(|name| TestPackage {
+                repository: format!("test/foo/{name}", name = name.name()),
+                version: "1.0.0".to_string(),
+                contents: format!("contents of {name}").into_bytes(),
+            })
+            .collect();
+        let mut delays: HashMap<_, _> = packages
+            .iter()
+            .enumerate()
+            .map(|(i, package)| {
+                (
+                    package.repository.clone(),
+                    Duration::from_millis(50 * (names.len() - i) as u64),
+                )
+            })
+            .collect();
+        delays.insert("test/foo/missing1".to_string(), Duration::from_millis(200));
+        let addr = start_test_registry(&packages, &delays, None);
 
-                finished += 1;
-                progress.tick_now(
-                    finished,
-                    count,
-                    &format!(": downloaded `{name}` (v{version})"),
-                )?;
+        let registries =
+            HashMap::from([("oci".to_string(), RegistryRef::Oci(format!("{addr}/test")))]);
+        let config = Config::default();
+        let terminal = Terminal::new(Verbosity::Quiet, Color::Never);
+        let dependency = Dependency::Package(RegistryPackage {
+            name: None,
+            version: "1.0.0".parse().unwrap(),
+            registry: Some("oci".to_string()),
+            registries: Vec::new(),
+        });
 
-                for index in deps {
-                    let dependency = &mut registry.dependencies[index];
-                    assert!(dependency.resolution.is_none());
-                    dependency.resolution = Some(RegistryResolution {
-                        name: dependency.name.clone(),
-                        package: dependency.package.clone(),
-                        registry: if *registry_name == DEFAULT_REGISTRY_NAME {
-                            None
-                        } else {
-                            Some(registry_name.to_string())
-                        },
-                        requirement: dependency.version.clone(),
-                        version: download.version.clone(),
-                        digest: download.digest.clone(),
-                        path: download.path.clone(),
-                    });
-                }
+        for concurrency in [0, 1, DEFAULT_FETCH_CONCURRENCY] {
+            let cache = tempfile::tempdir().unwrap();
+            let recorder = Recorder::default();
+            let mut resolver = DependencyResolver::new(&config, &registries, None, &terminal, true)
+                .unwrap()
+                .with_oci_client(OciClient::new(
+                    cache.path().to_path_buf(),
+                    Default::default(),
+                ))
+                .with_fetch_progress(&recorder)
+                .with_concurrency(concurrency);
+            for name in &names {
+                resolver.add_dependency(name, &dependency).await.unwrap();
             }
 
-            assert_eq!(finished, count);
+            // Each package resolves to its own contents regardless of the
+            // order the fetches complete in
+            let resolutions = resolver.resolve().await.unwrap();
+            assert_eq!(resolutions.len(), names.len());
+            for name in &names {
+                assert_eq!(
+                    fs::read_to_string(resolutions[name].path()).unwrap(),
+                    format!("contents of {name}")
+                );
+            }
 
-            progress.clear();
+            let mut expected: Vec<_> = names.iter().map(ToString::to_string).collect();
+            if concurrency > 1 {
+                expected.reverse();
+            }
+            assert_eq!(*recorder.0.lock().unwrap(), expected, "{concurrency}");
         }
 
-        Ok(registries
-            .into_values()
-            .flat_map(|r| r.dependencies.into_iter())
-            .map(|d| {
-                DependencyResolution::Registry(
-                    d.resolution.expect("dependency should have been resolved"),
-                )
-            }))
+        // Errors are reported for the first failing dependency added, even
+        // though its fetch fails last
+        let missing: Vec<registry::PackageName> = vec![
+            "foo:missing1".parse().unwrap(),
+            "foo:missing0".parse().unwrap(),
+        ];
+        let cache = tempfile::tempdir().unwrap();
+        let mut resolver = DependencyResolver::new(&config, &registries, None, &terminal, true)
+            .unwrap()
+            .with_oci_client(OciClient::new(
+                cache.path().to_path_buf(),
+                Default::default(),
+            ));
+        for name in missing.iter().chain(&names) {
+            resolver.add_dependency(name, &dependency).await.unwrap();
+        }
+
+        let e = resolver.resolve().await.unwrap_err();
+        assert!(e.to_string().contains("dependency `foo:missing1`"), "{e}");
     }
-}
 
-struct Registry<'a> {
-    client: Arc<FileSystemClient>,
-    packages: HashMap<registry::PackageName, PackageInfo>,
-    dependencies: Vec<RegistryDependency<'a>>,
-    upserts: HashSet<registry::PackageName>,
-}
+    #[tokio::test]
+    async fn it_falls_back_to_other_registries() {
+        let dependencies: HashMap<String, Dependency> = toml_edit::de::from_str(
+            r#"
+"foo:bar" = { version = "1.0.0", registries = ["primary", "mirror"] }
+"#,
+        )
+        .unwrap();
+        let dependency = &dependencies["foo:bar"];
+        let Dependency::Package(package) = dependency else {
+            panic!("expected a registry package");
+        };
+        assert_eq!(package.candidate_registries(), ["primary", "mirror"]);
 
-impl<'a> Registry<'a> {
-    async fn add_dependency(
-        &mut self,
-        name: &'a registry::PackageName,
-        package: registry::PackageName,
-        version: &'a VersionReq,
-        registry: &str,
-        locked: Option<&LockedPackageVersion>,
-    ) -> Result<()> {
-        let dep = RegistryDependency {
-            name,
-            package: package.clone(),
-            version,
-            locked: locked.map(|l| (l.version.clone(), l.digest.clone())),
-            resolution: None,
+        for (toml, error) in [
+            (
+                r#"{ version = "1.0.0", registry = "primary", registries = ["mirror"] }"#,
+                "cannot specify both `registry` and `registries`",
+            ),
+            (
+                r#"{ version = "1.0.0", registries = [] }"#,
+                "cannot be empty",
+            ),
+        ] {
+            let e = toml_edit::de::from_str::<HashMap<String, Dependency>>(&format!(
+                "\"foo:bar\" = {toml}"
+            ))
+            .unwrap_err();
+            assert!(e.to_string().contains(error), "{e}");
+        }
+
+        // The primary registry is unavailable, but the mirror has the
+        // locked package cached
+        let name: registry::PackageName = "foo:bar".parse().unwrap();
+        let locked = |registry: &str, digest| LockedPackage {
+            name: name.clone(),
+            registry: Some(registry.to_string()),
+            versions: vec![LockedPackageVersion {
+                requirement: "^1.0.0".to_string(),
+                version: Version::new(1, 0, 0),
+                digest,
+            }],
         };
+        let lock_file = LockFile::new(vec![
+            locked(
+                "primary",
+                "sha256:0000000000000000000000000000000000000000000000000000000000000000"
+                    .parse()
+                    .unwrap(),
+            ),
+            locked("mirror", cache_oci_contents("mirrored")),
+        ]);
 
-        self.dependencies.push(dep);
+        let registries = HashMap::from([
+            (
+                "primary".to_string(),
+                RegistryRef::Oci("ghcr.io/primary".into()),
+            ),
+            (
+                "mirror".to_string(),
+                RegistryRef::Oci("ghcr.io/mirror".into()),
+            ),
+        ]);
+        let config = Config::default();
+        let terminal = Terminal::new(Verbosity::Quiet, Color::Never);
+        let mut resolver = DependencyResolver::new(
+            &config,
+            &registries,
+            Some(LockFileResolver::new(&lock_file)),
+            &terminal,
+            false,
+        )
+        .unwrap()
+        .with_oci_client(oci_client());
+        resolver.add_dependency(&name, dependency).await.unwrap();
 
-        let mut needs_upsert = true;
-        if let Some(locked) = locked {
-            if let Some(package) =
-                Self::load_package(&self.client, &mut self.packages, package.clone()).await?
-            {
-                if package
-                    .state
-                    .release(&locked.version)
-                    .and_then(|r| r.content())
-                    .is_some()
-                {
-                    // Don't need to upsert this package as it is present
-                    // in the lock file and in client storage.
-                    needs_upsert = false;
-                }
+        let resolutions = resolver.resolve().await.unwrap();
+        match &resolutions[&name] {
+            DependencyResolution::Registry(res) => {
+                assert_eq!(res.registry.as_deref(), Some("mirror"));
+                assert_eq!(fs::read_to_string(&res.path).unwrap(), "mirrored");
             }
+            _ => panic!("expected a registry resolution"),
         }
 
-        if needs_upsert && self.upserts.insert(package.clone()) {
-            log::info!(
-                "package `{package}` from component registry `{registry}` needs to be updated"
-            );
+        // Errors from every registry are reported when none succeed
+        let mut resolver =
+            DependencyResolver::new(&config, &registries, None, &terminal, false).unwrap();
+        let e = resolver
+            .add_dependency(&name, dependency)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("from any of its registries"), "{e}");
+        assert!(e.contains("registry `primary`"), "{e}");
+        assert!(e.contains("registry `mirror`"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn it_prefers_cached_versions() {
+        // The namespace is not a valid OCI reference, so any attempt to
+        // fetch the newer versions from the registry fails
+        let namespace = "Invalid Namespace";
+        let registries = HashMap::from([("oci".to_string(), RegistryRef::Oci(namespace.into()))]);
+        let config = Config::default();
+        let terminal = Terminal::new(Verbosity::Quiet, Color::Never);
+        let name: registry::PackageName = "prefer:cached".parse().unwrap();
+        let dependency = Dependency::Package(RegistryPackage {
+            name: None,
+            version: "1.0.0".parse().unwrap(),
+            registry: Some("oci".to_string()),
+            registries: Vec::new(),
+        });
+
+        // The contents of 1.2.0 are no longer cached, so 1.1.0 is the latest
+        // cached version
+        for (version, contents) in [
+            ("1.0.0", Some("one")),
+            ("1.1.0", Some("one-one")),
+            ("1.2.0", None),
+        ] {
+            let version: Version = version.parse().unwrap();
+            let digest = match contents {
+                Some(contents) => cache_oci_contents(contents).to_string(),
+                None => "sha256:0000000000000000000000000000000000000000000000000000000000000000"
+                    .to_string(),
+            };
+            let path = oci_client().cached_version_path(namespace, &name, &version);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, digest).unwrap();
         }
 
-        Ok(())
+        let resolve = |prefer_cached| {
+            let mut resolver = DependencyResolver::new(&config, &registries, None, &terminal, true)
+                .unwrap()
+                .with_oci_client(oci_client())
+                .with_prefer_cached(prefer_cached);
+            let name = &name;
+            let dependency = &dependency;
+            async move {
+                resolver.add_dependency(name, dependency).await?;
+                resolver.resolve().await
+            }
+        };
+
+        let resolutions = resolve(true).await.unwrap();
+        match &resolutions[&name] {
+            DependencyResolution::Registry(resolution) => {
+                assert_eq!(resolution.version, Version::new(1, 1, 0));
+                assert_eq!(fs::read_to_string(&resolution.path).unwrap(), "one-one");
+            }
+            DependencyResolution::Local(_) => panic!("expected a registry resolution"),
+        }
+
+        // Otherwise, the registry is contacted for the latest version
+        let e = resolve(false).await.unwrap_err();
+        assert!(format!("{e:#}").contains("invalid OCI reference"), "{e:#}");
     }
 
-    async fn add_downloads(
-        &mut self,
-        registry: &'a str,
-        downloads: &mut DownloadMap<'a>,
-    ) -> Result<()> {
-        let Self {
-            dependencies,
-            packages,
-            client,
-            ..
-        } = self;
+    #[tokio::test]
+    async fn it_resolves_locked_versions_out_of_range_again() {
+        let namespace = "Invalid Namespace";
+        let registries = HashMap::from([("oci".to_string(), RegistryRef::Oci(namespace.into()))]);
+        let config = Config::default();
+        let terminal = Terminal::new(Verbosity::Quiet, Color::Never);
+        let name: registry::PackageName = "stale:locked".parse().unwrap();
+        let dependency = Dependency::Package(RegistryPackage {
+            name: None,
+            version: "1.0.0".parse().unwrap(),
+            registry: Some("oci".to_string()),
+            registries: Vec::new(),
+        });
 
-        for (index, dependency) in dependencies.iter_mut().enumerate() {
-            let package = Self::load_package(client, packages, dependency.package.clone())
-                .await?
-                .with_context(|| {
-                    format!(
-                        "package `{name}` was not found in component registry `{registry}`",
-                        name = dependency.package
-                    )
-                })?;
+        // The lock file pins a version outside of the manifest requirement
+        let lock_file = LockFile::new([LockedPackage {
+            name: name.clone(),
+            registry: Some("oci".to_string()),
+            versions: vec![LockedPackageVersion {
+                requirement: "^1.0.0".to_string(),
+                version: Version::new(2, 0, 0),
+                digest: cache_oci_contents("two"),
+            }],
+        }]);
 
-            let release = match &dependency.locked {
-                Some((version, digest)) => {
-                    // The dependency had a lock file entry, so attempt to do an exact match first
-                    let exact_req = VersionReq {
-                        comparators: vec![Comparator {
-                            op: Op::Exact,
-                            major: version.major,
-                            minor: Some(version.minor),
-                            patch: Some(version.patch),
-                            pre: version.pre.clone(),
-                        }],
-                    };
+        let path = oci_client().cached_version_path(namespace, &name, &Version::new(1, 0, 0));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, cache_oci_contents("one").to_string()).unwrap();
 
-                    // If an exact match can't be found, fallback to the latest release to
-                    // satisfy the version requirement; this can happen when packages are yanked
-                    package.state.find_latest_release(&exact_req).map(|r| {
-                        // Exact match, verify the content digests match
-                        let content = r.content().expect("release must have content");
-                        if content != digest {
-                            bail!(
-                                "component registry package `{name}` (v`{version}`) has digest `{content}` but the lock file specifies digest `{digest}`",
-                                name = dependency.package,
-                            );
-                        }
-                        Ok(r)
-                    }).transpose()?.or_else(|| package.state.find_latest_release(dependency.version))
-                }
-                None => package.state.find_latest_release(dependency.version),
-            }.with_context(|| format!("component registry package `{name}` has no release matching version requirement `{version}`", name = dependency.package, version = dependency.version))?;
+        let resolve = |mode| {
+            let mut resolver = DependencyResolver::new(
+                &config,
+                &registries,
+                Some(LockFileResolver::new(&lock_file)),
+                &terminal,
+                true,
+            )
+            .unwrap()
+            .with_oci_client(oci_client())
+            .with_frozen_mode(mode)
+            .with_prefer_cached(true);
+            let name = &name;
+            let dependency = &dependency;
+            async move {
+                resolver.add_dependency(name, dependency).await?;
+                resolver.resolve().await
+            }
+        };
 
-            let digest = release.content().expect("release must have content");
-            match client.content().content_location(digest) {
-                Some(path) => {
-                    // Content is already present, set the resolution
-                    assert!(dependency.resolution.is_none());
-                    dependency.resolution = Some(RegistryResolution {
-                        name: dependency.name.clone(),
-                        package: dependency.package.clone(),
-                        registry: if registry == DEFAULT_REGISTRY_NAME {
-                            None
-                        } else {
-                            Some(registry.to_string())
-                        },
-                        requirement: dependency.version.clone(),
-                        version: release.version.clone(),
-                        digest: digest.clone(),
-                        path,
-                    });
+        let resolutions = resolve(FrozenMode::Normal).await.unwrap();
+        match &resolutions[&name] {
+            DependencyResolution::Registry(resolution) => {
+                assert_eq!(resolution.version, Version::new(1, 0, 0));
+                assert_eq!(fs::read_to_string(&resolution.path).unwrap(), "one");
+            }
+            DependencyResolution::Local(_) => panic!("expected a registry resolution"),
+        }
 
-                    log::info!(
-                        "version {version} of registry package `{name}` from registry `{registry}` is already in client storage",
-                        name = dependency.package,
-                        version = release.version,
-                    );
-                }
-                None => {
-                    // Content needs to be downloaded
-                    let indexes = downloads
-                        .entry((
-                            registry,
-                            dependency.package.clone(),
-                            release.version.clone(),
-                        ))
-                        .or_default();
+        let e = resolve(FrozenMode::Frozen).await.unwrap_err();
+        assert!(
+            format!("{e:#}").contains(
+                "locked version 2.0.0 of dependency `stale:locked` does not satisfy requirement `^1.0.0`"
+            ),
+            "{e:#}"
+        );
+    }
 
-                    if indexes.is_empty() {
-                        log::info!(
-                            "version {version} of registry package `{name}` from registry `{registry}` needs to be downloaded",
-                            name = dependency.package,
-                            version = release.version,
-                        );
-                    }
+    #[tokio::test]
+    async fn it_resolves_provided_packages() {
+        struct InMemoryProvider(HashMap<registry::PackageName, (Version, &'static str)>);
 
-                    indexes.push(index);
-                }
+        impl PackageProvider for InMemoryProvider {
+            fn provide(
+                &self,
+                package: &registry::PackageName,
+                requirement: &VersionReq,
+            ) -> Result<Option<ProvidedPackage>> {
+                Ok(self
+                    .0
+                    .get(package)
+                    .filter(|(version, _)| requirement.matches(version))
+                    .map(|(version, contents)| ProvidedPackage {
+                        version: version.clone(),
+                        contents: contents.as_bytes().to_vec(),
+                    }))
             }
         }
 
-        Ok(())
-    }
+        let provider = InMemoryProvider(HashMap::from([
+            (
+                "fake:app".parse().unwrap(),
+                (
+                    Version::new(1, 2, 0),
+                    "package fake:app@1.2.0;\n\ninterface run {\n    use fake:types/types@0.1.0.{id};\n    run: func(id: id);\n}\n",
+                ),
+            ),
+            (
+                "fake:types".parse().unwrap(),
+                (
+                    Version::new(0, 1, 0),
+                    "package fake:types@0.1.0;\n\ninterface types {\n    type id = u32;\n}\n",
+                ),
+            ),
+        ]));
 
-    async fn load_package<'b>(
-        client: &FileSystemClient,
-        packages: &'b mut HashMap<registry::PackageName, PackageInfo>,
-        name: registry::PackageName,
-    ) -> Result<Option<&'b PackageInfo>> {
-        match packages.entry(name) {
-            hash_map::Entry::Occupied(e) => Ok(Some(e.into_mut())),
-            hash_map::Entry::Vacant(e) => match client
-                .registry()
-                .load_package(client.get_warg_registry(), e.key())
-                .await?
-            {
-                Some(p) => Ok(Some(e.insert(p))),
-                None => Ok(None),
-            },
+        let dependencies: Vec<(registry::PackageName, Dependency)> = vec![
+            ("fake:app".parse().unwrap(), "1.0.0".parse().unwrap()),
+            ("fake:types".parse().unwrap(), "0.1.0".parse().unwrap()),
+        ];
+
+        // No registries are configured, so only provided packages resolve
+        let registries = HashMap::new();
+        let config = Config::default();
+        let terminal = Terminal::new(Verbosity::Quiet, Color::Never);
+        let mut resolver = DependencyResolver::new(&config, &registries, None, &terminal, false)
+            .unwrap()
+            .with_package_provider(&provider);
+
+        for (name, dependency) in &dependencies {
+            resolver.add_dependency(name, dependency).await.unwrap();
         }
-    }
-}
 
-type DownloadMapKey<'a> = (&'a str, registry::PackageName, Version);
-type DownloadMap<'a> = HashMap<DownloadMapKey<'a>, Vec<usize>>;
+        let resolutions = resolver.resolve().await.unwrap();
+        for (name, version) in [("fake:app", "1.2.0"), ("fake:types", "0.1.0")] {
+            let resolution = &resolutions[&name.parse::<registry::PackageName>().unwrap()];
+            assert_eq!(resolution.version(), Some(&version.parse().unwrap()));
+            assert_eq!(
+                resolution.decode().unwrap().package_name().to_string(),
+                format!("{name}@{version}")
+            );
+        }
 
-struct RegistryDependency<'a> {
-    /// The package name assigned in the configuration file.
-    name: &'a registry::PackageName,
-    /// The package name of the registry package.
-    package: registry::PackageName,
-    version: &'a VersionReq,
-    locked: Option<(Version, AnyHash)>,
-    resolution: Option<RegistryResolution>,
-}
+        // Provided packages are written to a directory unique to the resolver
+        let dirs: HashSet<_> = resolutions
+            .values()
+            .map(|resolution| resolution.path().parent().unwrap().to_path_buf())
+            .collect();
+        assert_eq!(dirs.len(), 1);
+        let dir = dirs.into_iter().next().unwrap();
+        assert!(dir
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("cargo-component-provided-"));
 
-/// Represents a map of dependency resolutions.
-///
-/// The key to the map is the package name of the dependency.
-pub type DependencyResolutionMap = HashMap<registry::PackageName, DependencyResolution>;
+        // A version outside of the requirement is not provided
+        let name: registry::PackageName = "fake:types".parse().unwrap();
+        let package = RegistryPackage {
+            name: None,
+            version: "0.2.0".parse().unwrap(),
+            registry: None,
+            registries: Vec::new(),
+        };
+        let mut resolver = DependencyResolver::new(&config, &registries, None, &terminal, false)
+            .unwrap()
+            .with_package_provider(&provider);
+        assert!(!resolver.add_provided_package(&name, &package).unwrap());
+    }
+}
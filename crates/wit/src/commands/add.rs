@@ -28,6 +28,7 @@ async fn resolve_version(
             .unwrap_or(&VersionReq::STAR)
             .clone(),
         registry: registry.clone(),
+        registries: Vec::new(),
     });
 
     resolver.add_dependency(&package.name, &dependency).await?;
@@ -115,6 +116,7 @@ impl AddCommand {
                     name: self.name.is_some().then(|| self.package.name.clone()),
                     version: version.parse().expect("expected a valid version"),
                     registry: self.registry,
+                    registries: Vec::new(),
                 };
 
                 config
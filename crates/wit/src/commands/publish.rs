@@ -3,7 +3,10 @@ use crate::{
     publish_wit_package, PublishOptions,
 };
 use anyhow::{Context, Result};
-use cargo_component_core::{command::CommonOptions, registry::find_url};
+use cargo_component_core::{
+    command::CommonOptions,
+    registry::{find_protocol, find_url},
+};
 use clap::Args;
 use warg_credentials::keyring::get_signing_key;
 use warg_crypto::signing::PrivateKey;
@@ -68,7 +71,8 @@ impl PublishCommand {
                 config: &config,
                 config_path: &config_path,
                 warg_config: &warg_config,
-                url,
+                url: &url,
+                protocol: find_protocol(self.registry.as_deref(), &config.registries),
                 signing_key: &signing_key,
                 package: self.package.as_ref(),
                 init: self.init,
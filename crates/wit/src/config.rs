@@ -1,7 +1,9 @@
 //! Module for WIT package configuration.
 
 use anyhow::{Context, Result};
-use cargo_component_core::registry::Dependency;
+use cargo_component_core::registry::{
+    deserialize_package_map, serialize_sorted_map, Dependency, RegistryRef,
+};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -35,7 +37,7 @@ fn find_config(cwd: &Path) -> Option<PathBuf> {
 #[derive(Default)]
 pub struct ConfigBuilder {
     version: Option<Version>,
-    registries: HashMap<String, Url>,
+    registries: HashMap<String, RegistryRef>,
 }
 
 impl ConfigBuilder {
@@ -52,7 +54,7 @@ impl ConfigBuilder {
 
     /// Adds a registry to the configuration.
     pub fn with_registry(mut self, name: impl Into<String>, url: Url) -> Self {
-        self.registries.insert(name.into(), url);
+        self.registries.insert(name.into(), url.into());
         self
     }
 
@@ -79,11 +81,20 @@ pub struct Config {
     /// The current package version.
     pub version: Version,
     /// The package dependencies.
-    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map",
+        deserialize_with = "deserialize_package_map"
+    )]
     pub dependencies: HashMap<PackageName, Dependency>,
     /// The registries to use for sourcing packages.
-    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
-    pub registries: HashMap<String, Url>,
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    pub registries: HashMap<String, RegistryRef>,
     /// The authors of the package.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub authors: Vec<String>,
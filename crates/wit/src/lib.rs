@@ -256,6 +256,7 @@ struct PublishOptions<'a> {
     config_path: &'a Path,
     warg_config: &'a warg_client::Config,
     url: &'a str,
+    protocol: Option<u8>,
     signing_key: &'a PrivateKey,
     package: Option<&'a registry::PackageName>,
     init: bool,
@@ -328,7 +329,8 @@ async fn publish_wit_package(options: PublishOptions<'_>, terminal: &Terminal) -
 
     let bytes = add_registry_metadata(options.config, &bytes)?;
     let name = options.package.unwrap_or(&name);
-    let mut client = create_client(options.warg_config, options.url, terminal)?;
+    let mut client =
+        create_client(options.warg_config, options.url, options.protocol, terminal).await?;
     client.refresh_namespace(name.namespace()).await?;
 
     let content = client
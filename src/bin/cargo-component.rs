@@ -157,13 +157,15 @@ async fn main() -> Result<()> {
                     }
                 },
                 cargo_args.color.unwrap_or_default(),
-            ))?;
+            ))?
+            .with_prefer_cached(cargo_args.prefer_cached);
 
             let metadata = load_metadata(cargo_args.manifest_path.as_deref())?;
             let packages = load_component_metadata(
                 &metadata,
                 cargo_args.packages.iter(),
                 cargo_args.workspace,
+                Some(cargo_args.profile()),
             )?;
 
             if packages.is_empty() {
@@ -1,17 +1,29 @@
 //! Module for bindings generation.
 
 use crate::{
-    last_modified_time,
-    metadata::{ComponentMetadata, Ownership},
+    generator, last_modified_time,
+    metadata::{
+        matches_pattern, Bindings, ComponentMetadata, Format, Ownership, OwnershipModel,
+        WithMapping, INTERFACE_SKIP_PREFIX, WORLD_ENV_VAR,
+    },
     registry::PackageDependencyResolution,
+    target::rustc_version,
 };
-use anyhow::{bail, Context, Result};
-use cargo_component_core::registry::DecodedDependency;
+use anyhow::{anyhow, bail, Context, Result};
+use cargo_component_core::{
+    features::{self, FeatureSet},
+    registry::{DecodedDependency, DependencyResolution},
+};
+use heck::{ToSnakeCase, ToUpperCamelCase};
 use indexmap::{IndexMap, IndexSet};
+use proc_macro2::TokenStream;
 use semver::Version;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    io::Write,
     path::{Path, PathBuf},
+    process::{Command, Stdio},
     time::SystemTime,
 };
 use warg_protocol::registry;
@@ -19,10 +31,33 @@ use wit_bindgen_core::Files;
 use wit_bindgen_rust::Opts;
 use wit_component::DecodedWasm;
 use wit_parser::{
-    Interface, Package, PackageName, Resolve, Type, TypeDefKind, TypeOwner, UnresolvedPackage,
-    World, WorldId, WorldItem, WorldKey,
+    AstItem, FunctionKind, Interface, Package, PackageId, PackageName, Resolve, Type, TypeDefKind,
+    TypeOwner, UnresolvedPackage, World, WorldId, WorldItem, WorldKey,
 };
 
+/// The kinds of fields that prevent a derive from applying to a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum FieldKind {
+    /// A floating-point number.
+    Float,
+    /// A string or list, which is allocated on the heap.
+    Heap,
+    /// A handle to a resource.
+    Handle,
+}
+
+impl FieldKind {
+    /// Gets the field kinds the given derive cannot be applied to.
+    fn underivable(derive: &str) -> &'static [Self] {
+        match derive.rsplit("::").next().unwrap_or(derive) {
+            "Debug" => &[],
+            "Eq" | "Ord" | "Hash" => &[Self::Float, Self::Handle],
+            "Copy" => &[Self::Heap, Self::Handle],
+            _ => &[Self::Handle],
+        }
+    }
+}
+
 // Used to format `unlocked-dep` import names for dependencies on
 // other components.
 fn format_dep_import(package: &Package, name: Option<&str>, version: Option<&Version>) -> String {
@@ -54,6 +89,103 @@ fn format_dep_import(package: &Package, name: Option<&str>, version: Option<&Ver
     }
 }
 
+/// Gets the path of the file storing the hash of the bindings at the given
+/// path.
+///
+/// See [`ComponentMetadata::bindings_hash`].
+pub fn bindings_hash_path(bindings_path: &Path) -> PathBuf {
+    let mut path = bindings_path.as_os_str().to_owned();
+    path.push(".hash");
+    path.into()
+}
+
+/// The version of `wit-bindgen-rt` that generated bindings crates depend on.
+const WIT_BINDGEN_RT_VERSION: &str = "0.24.0";
+
+/// Writes a crate containing the given bindings to the given directory.
+///
+/// The crate consists of a `Cargo.toml` for a crate of the given name and
+/// version and a `src/lib.rs` containing the bindings.
+pub fn write_bindings_crate(
+    dir: &Path,
+    name: &str,
+    version: &Version,
+    std_feature: bool,
+    bindings: &str,
+) -> Result<()> {
+    let mut manifest = format!(
+        "[package]\n\
+         name = \"{name}\"\n\
+         version = \"{version}\"\n\
+         edition = \"2021\"\n\
+         \n\
+         [dependencies]\n\
+         wit-bindgen-rt = {{ version = \"{WIT_BINDGEN_RT_VERSION}\", features = [\"bitflags\"] }}\n"
+    );
+
+    if std_feature {
+        manifest += "\n[features]\nstd = []\n";
+    }
+
+    let src_dir = dir.join("src");
+    fs::create_dir_all(&src_dir).with_context(|| {
+        format!(
+            "failed to create bindings crate directory `{path}`",
+            path = src_dir.display()
+        )
+    })?;
+
+    for (path, contents) in [
+        (dir.join("Cargo.toml"), manifest.as_str()),
+        (src_dir.join("lib.rs"), bindings),
+    ] {
+        fs::write(&path, contents).with_context(|| {
+            format!(
+                "failed to write bindings crate file `{path}`",
+                path = path.display()
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Writes the stub implementations returned by `generate` to the given path,
+/// creating any parent directories.
+///
+/// An existing file is only overwritten if `force` is true; otherwise the
+/// file is left as is and `generate` is not called.
+///
+/// Returns whether or not the file was written.
+pub fn write_stubs(
+    path: &Path,
+    force: bool,
+    generate: impl FnOnce() -> Result<String>,
+) -> Result<bool> {
+    if path.exists() && !force {
+        return Ok(false);
+    }
+
+    let stubs = generate()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| {
+            format!(
+                "failed to create stub output directory `{path}`",
+                path = parent.display()
+            )
+        })?;
+    }
+
+    fs::write(path, stubs).with_context(|| {
+        format!(
+            "failed to write stub output file `{path}`",
+            path = path.display()
+        )
+    })?;
+
+    Ok(true)
+}
+
 /// A generator for bindings.
 ///
 /// This type is responsible for generating the bindings
@@ -62,7 +194,7 @@ pub struct BindingsGenerator<'a> {
     resolution: &'a PackageDependencyResolution<'a>,
     resolve: Resolve,
     world: WorldId,
-    source_files: Vec<PathBuf>,
+    settings: Bindings,
 }
 
 impl<'a> BindingsGenerator<'a> {
@@ -74,8 +206,8 @@ impl<'a> BindingsGenerator<'a> {
         resolution: &'a PackageDependencyResolution<'a>,
     ) -> Result<(Self, HashMap<String, String>)> {
         let mut import_name_map = Default::default();
-        let (resolve, world, source_files) =
-            Self::create_target_world(resolution, &mut import_name_map).with_context(|| {
+        let (resolve, world) = Self::create_target_world(resolution, &mut import_name_map)
+            .with_context(|| {
                 format!(
                     "failed to create a target world for package `{name}` ({path})",
                     name = resolution.metadata.name,
@@ -88,7 +220,7 @@ impl<'a> BindingsGenerator<'a> {
                 resolution,
                 resolve,
                 world,
-                source_files,
+                settings: resolution.metadata.section.bindings.clone(),
             },
             import_name_map,
         ))
@@ -99,6 +231,18 @@ impl<'a> BindingsGenerator<'a> {
         self.resolution.metadata
     }
 
+    /// Gets the bindings settings in effect for the generator.
+    pub fn settings(&self) -> &Bindings {
+        &self.settings
+    }
+
+    /// Applies the conditional bindings settings matching a build target with
+    /// the given `cfg` settings.
+    pub fn with_target_cfg(mut self, target_cfg: &[(String, Option<String>)]) -> Result<Self> {
+        self.settings = self.settings.for_target_cfg(target_cfg)?;
+        Ok(self)
+    }
+
     /// Gets the reason for generating the bindings.
     ///
     /// If this returns `Ok(None)`, then the bindings are up-to-date and
@@ -116,21 +260,29 @@ impl<'a> BindingsGenerator<'a> {
         let metadata = self.metadata();
         let exe_modified = last_modified_exe > last_modified_output;
         let manifest_modified = metadata.modified_at > last_modified_output;
-        let target_modified = if let Some(path) = metadata.target_path() {
-            last_modified_time(&path)? > last_modified_output
-        } else {
-            false
+        let target_modified = metadata
+            .local_wit_files()?
+            .modified_since(last_modified_output)?;
+
+        // Regenerating the bindings causes the component to be rebuilt and
+        // encoded with the modified adapter
+        let adapter_modified = match metadata.adapter_path() {
+            Some(path) => last_modified_time(&path)? > last_modified_output,
+            None => false,
         };
 
         if exe_modified
             || manifest_modified
             || target_modified
+            || adapter_modified
             || self.dependencies_are_newer(last_modified_output)?
         {
             Ok(Some(if manifest_modified {
                 "the manifest was modified"
             } else if target_modified {
                 "the target WIT package was modified"
+            } else if adapter_modified {
+                "the WASI adapter was modified"
             } else if exe_modified {
                 "the cargo-component executable was modified"
             } else {
@@ -141,32 +293,230 @@ impl<'a> BindingsGenerator<'a> {
         }
     }
 
+    /// Computes the hash of the inputs to generating the bindings.
+    ///
+    /// See [`ComponentMetadata::bindings_hash`].
+    pub fn bindings_hash(&self) -> Result<String> {
+        self.metadata()
+            .bindings_hash(&self.settings, &self.resolve, self.world)
+    }
+
+    /// Gets the `only` entries of the bindings settings that do not match any
+    /// item in the target world.
+    pub fn unmatched_only(&self) -> Vec<&str> {
+        Self::unmatched_patterns(&self.resolve, self.world, &self.settings.only)
+    }
+
+    /// Gets the `features` entries of the bindings settings that are not
+    /// declared by a feature gate of the target package or its dependencies.
+    pub fn unknown_features(&self) -> Result<Vec<&str>> {
+        if self.settings.features.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths = Vec::new();
+        if let Some(path) = self.metadata().target_path()? {
+            // Nested packages may be in the target directory or in the `deps`
+            // directory beside a target file
+            match path.parent() {
+                _ if path.is_dir() => Self::collect_nested_dirs(&path, &mut paths)?,
+                Some(dir) if dir.join("deps").is_dir() => {
+                    Self::collect_nested_dirs(&dir.join("deps"), &mut paths)?
+                }
+                _ => {}
+            }
+
+            paths.push(path.into_owned());
+        }
+
+        paths.extend(
+            self.resolution
+                .target_resolutions
+                .values()
+                .map(|r| r.path().to_path_buf()),
+        );
+
+        let mut declared = BTreeSet::new();
+        for path in paths {
+            declared.extend(features::declared_path_features(&path)?);
+        }
+
+        Ok(self
+            .settings
+            .features
+            .iter()
+            .filter(|f| !declared.contains(*f))
+            .map(String::as_str)
+            .collect())
+    }
+
+    /// Gets the `interface:` entries of the `skip` setting that do not match
+    /// any interface in the target world.
+    pub fn unmatched_interface_skips(&self) -> Vec<&str> {
+        let world = &self.resolve.worlds[self.world];
+        let names: Vec<_> = world
+            .imports
+            .iter()
+            .chain(world.exports.iter())
+            .filter(|(_, item)| matches!(item, WorldItem::Interface(_)))
+            .flat_map(|(key, _)| Self::item_names(&self.resolve, key))
+            .collect();
+
+        self.settings
+            .skip
+            .iter()
+            .filter(|entry| {
+                entry
+                    .strip_prefix(INTERFACE_SKIP_PREFIX)
+                    .is_some_and(|p| !names.iter().any(|n| matches_pattern(p, n)))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Gets the additional derives that are skipped for generated types.
+    ///
+    /// Returns the names of the types and the derives skipped for them;
+    /// derives are only skipped if `additional_derives_ignore_errors` is set.
+    pub fn ignored_derives(&self) -> Vec<(String, String)> {
+        let mut ignored: Vec<_> = Self::underivable_types(&self.resolve, &self.settings)
+            .into_iter()
+            .flat_map(|(ty, derives)| derives.into_iter().map(move |d| (ty.clone(), d)))
+            .collect();
+        ignored.sort();
+        ignored
+    }
+
+    /// Determines if `rustfmt` should be run on the generated bindings.
+    ///
+    /// Returns `Ok(false)` if formatting is enabled by default but `rustfmt`
+    /// is not available; returns an error if formatting was explicitly
+    /// requested but `rustfmt` is not available.
+    pub fn rustfmt(&self) -> Result<bool> {
+        let format = &self.settings.format;
+        if !format.enabled() {
+            return Ok(false);
+        }
+
+        let available = Command::new("rustfmt")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+
+        format.check_rustfmt(available)
+    }
+
     /// Generates the bindings source for a package.
-    pub fn generate(self) -> Result<String> {
-        let settings = &self.resolution.metadata.section.bindings;
+    ///
+    /// The bindings are formatted with `rustfmt` only if `rustfmt` is true.
+    pub fn generate(mut self, rustfmt: bool) -> Result<String> {
+        let settings = &self.settings;
+        settings.check_rust_version(rustc_version()?)?;
+
+        let source = Self::generate_source(
+            &mut self.resolve,
+            self.world,
+            settings,
+            self.resolution.metadata.section.bindings_crate.is_some(),
+            rustfmt,
+        )?;
+
+        if rustfmt && (settings.format.options().is_some() || Self::edits_derives(settings)) {
+            return Self::format(&source, &settings.format);
+        }
+
+        Ok(source)
+    }
+
+    /// Generates the stub implementations of the exports of the world for
+    /// the `stub_output` setting.
+    ///
+    /// The stubs are formatted with `rustfmt` only if `rustfmt` is true.
+    pub fn generate_stubs(&self, rustfmt: bool) -> Result<String> {
+        // The stubs must only implement the traits the bindings will define
+        let mut resolve = self.resolve.clone();
+        Self::filter_world(
+            &mut resolve,
+            self.world,
+            &self.settings.only,
+            &self.settings.skip,
+        );
+
+        generator::generate_stubs(&resolve, self.world, rustfmt)
+    }
+
+    /// Generates the bindings for a package as a token stream.
+    ///
+    /// This is intended for build scripts and procedural macros that embed
+    /// the bindings directly; the `format` setting has no effect on the
+    /// returned tokens.
+    pub fn generate_tokens(mut self) -> Result<TokenStream> {
+        let settings = &self.settings;
+
+        let source = Self::generate_source(
+            &mut self.resolve,
+            self.world,
+            settings,
+            self.resolution.metadata.section.bindings_crate.is_some(),
+            false,
+        )?;
+
+        Self::tokens(&source)
+    }
+
+    /// Parses the given bindings source into a token stream.
+    fn tokens(source: &str) -> Result<TokenStream> {
+        source
+            .parse()
+            .map_err(|e| anyhow!("failed to parse generated bindings: {e}"))
+    }
+
+    /// Generates the unformatted bindings source for the given world.
+    ///
+    /// Formatting with `rustfmt` is left to `wit-bindgen` only if `rustfmt`
+    /// is true and no explicit formatting options are set.
+    fn generate_source(
+        resolve: &mut Resolve,
+        world: WorldId,
+        settings: &Bindings,
+        pub_export_macro: bool,
+        rustfmt: bool,
+    ) -> Result<String> {
+        Self::filter_world(resolve, world, &settings.only, &settings.skip);
+
+        let aliases = Self::rename_aliases(resolve, world, &settings.rename)?;
+        let runtime_path = settings.runtime_path.as_deref().unwrap_or("wit_bindgen_rt");
+
         let opts = Opts {
-            rustfmt: settings.format,
-            ownership: match settings.ownership {
-                Ownership::Owning => wit_bindgen_rust::Ownership::Owning,
-                Ownership::Borrowing => wit_bindgen_rust::Ownership::Borrowing {
-                    duplicate_if_necessary: false,
-                },
-                Ownership::BorrowingDuplicateIfNecessary => {
-                    wit_bindgen_rust::Ownership::Borrowing {
-                        duplicate_if_necessary: true,
-                    }
-                }
-            },
+            // Explicit formatting options are handled by running `rustfmt` separately,
+            // as is filtering derives from the unformatted source
+            rustfmt: rustfmt
+                && settings.format.options().is_none()
+                && !Self::edits_derives(settings),
+            ownership: Self::ownership(&settings.ownership)?,
             additional_derive_attributes: settings.derives.clone(),
-            std_feature: settings.std_feature,
-            runtime_path: Some("wit_bindgen_rt".to_string()),
-            bitflags_path: Some("wit_bindgen_rt::bitflags".to_string()),
+            // Items requiring `std` are gated and then removed for `no_std`
+            std_feature: settings.std_feature || settings.no_std,
+            export_prefix: settings.export_prefix.clone(),
+            skip: Self::skipped_functions(resolve, world, &settings.skip),
+            with: Self::with_mappings(resolve, world, &settings.with),
+            type_section_suffix: settings.type_section_suffix.clone(),
+            runtime_path: Some(runtime_path.to_string()),
+            bitflags_path: Some(format!("{runtime_path}::bitflags")),
+            // The `export!` macro must be usable from the component crate
+            pub_export_macro: pub_export_macro
+                || settings.export_macro_visibility.as_deref() == Some("pub"),
+            // Stubs written to a separate file are generated by `generate_stubs`
+            stubs: settings.stubs && settings.stub_output.is_none(),
             ..Default::default()
         };
 
         let mut files = Files::default();
         opts.build()
-            .generate(&self.resolve, self.world, &mut files)
+            .generate(resolve, world, &mut files)
             .context("failed to generate bindings")?;
 
         let sources: Vec<_> = files
@@ -178,465 +528,2581 @@ impl<'a> BindingsGenerator<'a> {
             "expected exactly one source file to be generated"
         );
 
-        Ok(sources[0].to_string())
-    }
+        let mut source = sources[0].to_string();
+        if Self::filters_derives(settings) {
+            source = Self::remove_derives(&source, &Self::underivable_types(resolve, settings));
+        }
 
-    fn dependencies_are_newer(&self, last_modified_output: SystemTime) -> Result<bool> {
-        for dep in &self.source_files {
-            if last_modified_time(dep)? > last_modified_output {
-                log::debug!(
-                    "target source file `{path}` has been modified",
-                    path = dep.display()
-                );
-                return Ok(true);
+        if !settings.derives_feature.is_empty() {
+            source = Self::add_feature_derives(
+                &source,
+                &settings.derives_feature,
+                &Self::derivable_type_names(resolve),
+            );
+        }
+
+        if !settings.emit_type_section {
+            source = Self::strip_type_section(&source);
+        }
+
+        if let Some(visibility) = &settings.export_macro_visibility {
+            if !pub_export_macro && visibility != "pub" {
+                source = Self::set_export_macro_visibility(&source, visibility);
             }
         }
 
-        for (_, dep) in self.resolution.all() {
-            if last_modified_time(dep.path())? > last_modified_output {
-                log::debug!(
-                    "dependency `{path}` has been modified",
-                    path = dep.path().display()
-                );
-                return Ok(true);
+        if settings.no_std {
+            source = Self::remove_std_items(&source);
+        }
+
+        source += &aliases;
+        Ok(source)
+    }
+
+    /// Sets the visibility of the re-export of the `export!` macro.
+    fn set_export_macro_visibility(source: &str, visibility: &str) -> String {
+        let mut result = String::with_capacity(source.len());
+        for line in source.split_inclusive('\n') {
+            let trimmed = line.trim_start();
+            match trimmed.strip_prefix("pub(crate) use __export_") {
+                Some(rest) => {
+                    let indent = &line[..line.len() - trimmed.len()];
+                    result.push_str(&format!("{indent}{visibility} use __export_{rest}"));
+                }
+                None => result.push_str(line),
             }
         }
 
-        Ok(false)
+        result
     }
 
-    fn create_target_world(
-        resolution: &PackageDependencyResolution,
-        import_name_map: &mut HashMap<String, String>,
-    ) -> Result<(Resolve, WorldId, Vec<PathBuf>)> {
-        log::debug!(
-            "creating target world for package `{name}` ({path})",
-            name = resolution.metadata.name,
-            path = resolution.metadata.manifest_path.display()
-        );
+    /// Determines if additional derives are filtered from the generated types.
+    fn filters_derives(settings: &Bindings) -> bool {
+        settings.additional_derives_ignore_errors && !settings.derives.is_empty()
+    }
 
-        let (mut merged, world_id, source_files) =
-            if let Some(name) = resolution.metadata.target_package() {
-                Self::target_package(resolution, name, resolution.metadata.target_world())?
-            } else if let Some(path) = resolution.metadata.target_path() {
-                Self::target_local_path(resolution, &path, resolution.metadata.target_world())?
-            } else {
-                let (merged, world) = Self::target_empty_world(resolution);
-                (merged, world, Vec::new())
-            };
+    /// Determines if the derives of the generated types are edited after
+    /// generation, in which case the bindings are formatted afterwards.
+    fn edits_derives(settings: &Bindings) -> bool {
+        Self::filters_derives(settings) || !settings.derives_feature.is_empty()
+    }
 
-        // Merge all component dependencies as interface imports
-        for (id, dependency) in &resolution.resolutions {
-            log::debug!("importing component dependency `{id}`");
+    /// Gets the Rust names of the generated types that additional derives
+    /// apply to (records, variants, enums, and flags).
+    fn derivable_type_names(resolve: &Resolve) -> HashSet<String> {
+        resolve
+            .types
+            .iter()
+            .filter(|(_, def)| {
+                matches!(
+                    def.kind,
+                    TypeDefKind::Record(_)
+                        | TypeDefKind::Variant(_)
+                        | TypeDefKind::Enum(_)
+                        | TypeDefKind::Flags(_)
+                )
+            })
+            .filter_map(|(_, def)| Some(def.name.as_ref()?.to_upper_camel_case()))
+            .collect()
+    }
 
-            let (mut resolve, component_world_id) = dependency
-                .decode()?
-                .into_component_world()
-                .with_context(|| format!("failed to decode component dependency `{id}`"))?;
+    /// Adds the derives of each Cargo feature to the definitions of the given
+    /// generated types, gated with `cfg_attr` on the feature.
+    fn add_feature_derives(
+        source: &str,
+        derives_feature: &HashMap<String, Vec<String>>,
+        types: &HashSet<String>,
+    ) -> String {
+        let mut features: Vec<_> = derives_feature
+            .iter()
+            .filter(|(_, derives)| !derives.is_empty())
+            .collect();
+        features.sort_by_key(|(feature, _)| *feature);
+
+        let mut output = String::with_capacity(source.len());
+        for line in source.lines() {
+            let definition = line.trim_start();
+            let name: String = definition
+                .strip_prefix("pub struct ")
+                .or_else(|| definition.strip_prefix("pub enum "))
+                .unwrap_or_default()
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+
+            if types.contains(&name) {
+                let indent = &line[..line.len() - definition.len()];
+                for (feature, derives) in &features {
+                    output.push_str(&format!(
+                        "{indent}#[cfg_attr(feature = \"{feature}\", derive({derives}))]\n",
+                        derives = derives.join(", ")
+                    ));
+                }
+            }
 
-            // Set the world name as currently it defaults to "root"
-            // For now, set it to the name from the id
-            let world = &mut resolve.worlds[component_world_id];
-            world.name = id.name().to_string();
+            output.push_str(line);
+            output.push('\n');
+        }
 
-            let pkg = &mut resolve.packages[world.package.unwrap()];
-            pkg.name.namespace = id.namespace().to_string();
-            pkg.name.name = id.name().to_string();
+        output
+    }
 
-            let source = merged
-                .merge(resolve)
-                .with_context(|| format!("failed to merge world of dependency `{id}`"))?
-                .worlds[component_world_id.index()];
-            Self::import_world(
-                &mut merged,
-                source,
-                world_id,
-                dependency.version(),
-                import_name_map,
-            )?;
+    /// Gets the additional derives that cannot apply to each generated type,
+    /// keyed by the Rust name of the type.
+    ///
+    /// Types of the same name from different interfaces are combined, so a
+    /// derive is skipped for all of them if any cannot derive it.
+    fn underivable_types(resolve: &Resolve, settings: &Bindings) -> HashMap<String, Vec<String>> {
+        let mut types = HashMap::new();
+        if !settings.additional_derives_ignore_errors {
+            return types;
+        }
+
+        for (_, def) in resolve.types.iter() {
+            let Some(name) = &def.name else {
+                continue;
+            };
+
+            let mut kinds = HashSet::new();
+            match &def.kind {
+                TypeDefKind::Record(r) => {
+                    for field in &r.fields {
+                        Self::field_kinds(resolve, &field.ty, &mut kinds);
+                    }
+                }
+                TypeDefKind::Variant(v) => {
+                    for ty in v.cases.iter().filter_map(|c| c.ty.as_ref()) {
+                        Self::field_kinds(resolve, ty, &mut kinds);
+                    }
+                }
+                _ => continue,
+            }
+
+            for derive in &settings.derives {
+                if !FieldKind::underivable(derive)
+                    .iter()
+                    .any(|k| kinds.contains(k))
+                {
+                    continue;
+                }
+
+                let derives: &mut Vec<String> =
+                    types.entry(name.to_upper_camel_case()).or_default();
+                if !derives.contains(derive) {
+                    derives.push(derive.clone());
+                }
+            }
         }
 
-        Ok((merged, world_id, source_files))
+        types
     }
 
-    fn target_package(
-        resolution: &PackageDependencyResolution,
-        name: &registry::PackageName,
-        world: Option<&str>,
-    ) -> Result<(Resolve, WorldId, Vec<PathBuf>)> {
-        // We must have resolved a target package dependency at this point
-        assert_eq!(resolution.target_resolutions.len(), 1);
+    /// Collects the kinds of fields contained in the given type.
+    fn field_kinds(resolve: &Resolve, ty: &Type, kinds: &mut HashSet<FieldKind>) {
+        let id = match ty {
+            Type::F32 | Type::F64 => {
+                kinds.insert(FieldKind::Float);
+                return;
+            }
+            Type::String => {
+                kinds.insert(FieldKind::Heap);
+                return;
+            }
+            Type::Id(id) => *id,
+            _ => return,
+        };
 
-        // Decode the target package dependency
-        let dependency = resolution.target_resolutions.values().next().unwrap();
-        let (resolve, pkg, source_files) = dependency.decode()?.resolve().with_context(|| {
-            format!(
-                "failed to resolve target package `{name}`",
-                name = dependency.name()
-            )
-        })?;
+        match &resolve.types[id].kind {
+            TypeDefKind::Record(r) => {
+                for field in &r.fields {
+                    Self::field_kinds(resolve, &field.ty, kinds);
+                }
+            }
+            TypeDefKind::Variant(v) => {
+                for ty in v.cases.iter().filter_map(|c| c.ty.as_ref()) {
+                    Self::field_kinds(resolve, ty, kinds);
+                }
+            }
+            TypeDefKind::Tuple(t) => {
+                for ty in &t.types {
+                    Self::field_kinds(resolve, ty, kinds);
+                }
+            }
+            TypeDefKind::Option(ty) | TypeDefKind::Type(ty) => {
+                Self::field_kinds(resolve, ty, kinds)
+            }
+            TypeDefKind::Result(r) => {
+                for ty in r.ok.iter().chain(r.err.iter()) {
+                    Self::field_kinds(resolve, ty, kinds);
+                }
+            }
+            TypeDefKind::List(ty) => {
+                kinds.insert(FieldKind::Heap);
+                Self::field_kinds(resolve, ty, kinds);
+            }
+            TypeDefKind::Handle(_) | TypeDefKind::Resource => {
+                kinds.insert(FieldKind::Handle);
+            }
+            _ => {}
+        }
+    }
 
-        let world = resolve
-            .select_world(pkg, world)
-            .with_context(|| format!("failed to select world from target package `{name}`"))?;
+    /// Removes the given additional derives from the generated types they
+    /// cannot apply to.
+    ///
+    /// This expects unformatted bindings, where the derives of a type are
+    /// combined on a line of their own preceding the type's definition.
+    fn remove_derives(source: &str, underivable: &HashMap<String, Vec<String>>) -> String {
+        let lines: Vec<_> = source.lines().collect();
+        let mut output = String::with_capacity(source.len());
+        for (i, line) in lines.iter().enumerate() {
+            let skipped = line
+                .trim()
+                .strip_prefix("#[derive(")
+                .and_then(|l| l.strip_suffix(")]"))
+                .and_then(|derives| {
+                    // Find the name of the type the attribute applies to
+                    let definition = lines[i + 1..]
+                        .iter()
+                        .map(|l| l.trim())
+                        .find(|l| !l.starts_with("#[") && !l.starts_with("//"))?;
+                    let name: String = definition
+                        .strip_prefix("pub struct ")
+                        .or_else(|| definition.strip_prefix("pub enum "))?
+                        .chars()
+                        .take_while(|c| c.is_alphanumeric() || *c == '_')
+                        .collect();
+                    Some((derives, underivable.get(&name)?))
+                });
+
+            match skipped {
+                Some((derives, skipped)) => {
+                    let remaining: Vec<_> = derives
+                        .split(", ")
+                        .filter(|d| !skipped.iter().any(|s| s == d))
+                        .collect();
+                    if !remaining.is_empty() {
+                        let indent = &line[..line.len() - line.trim_start().len()];
+                        output.push_str(&format!(
+                            "{indent}#[derive({remaining})]\n",
+                            remaining = remaining.join(", ")
+                        ));
+                    }
+                }
+                None => {
+                    output.push_str(line);
+                    output.push('\n');
+                }
+            }
+        }
 
-        Ok((resolve, world, source_files))
+        output
     }
 
-    fn target_local_path(
-        resolution: &PackageDependencyResolution,
-        path: &Path,
-        world: Option<&str>,
-    ) -> Result<(Resolve, WorldId, Vec<PathBuf>)> {
-        let mut merged = Resolve::default();
+    /// Removes the items gated on the `std` feature from the generated bindings.
+    fn remove_std_items(source: &str) -> String {
+        source.replace("#[cfg(feature = \"std\")]", "#[cfg(any())]")
+    }
 
-        // Start by decoding all of the target dependencies
-        let mut deps = IndexMap::new();
-        for (id, resolution) in &resolution.target_resolutions {
-            let decoded = resolution.decode()?;
-            let name = decoded.package_name();
+    /// Removes the component type custom section from the generated bindings.
+    fn strip_type_section(source: &str) -> String {
+        let mut output = String::with_capacity(source.len());
+        let mut lines = source.lines();
+        while let Some(line) = lines.next() {
+            if !line
+                .trim_start()
+                .starts_with("#[link_section = \"component-type:")
+            {
+                output.push_str(line);
+                output.push('\n');
+                continue;
+            }
 
-            if let Some(prev) = deps.insert(name.clone(), decoded) {
-                bail!("duplicate definitions of package `{name}` found while decoding target dependency `{id}`", name = prev.package_name());
+            // The static is preceded by a `cfg` attribute for the wasm target
+            if output
+                .lines()
+                .last()
+                .map(|l| l.trim() == "#[cfg(target_arch = \"wasm32\")]")
+                .unwrap_or(false)
+            {
+                let len = output
+                    .trim_end_matches('\n')
+                    .rfind('\n')
+                    .map_or(0, |i| i + 1);
+                output.truncate(len);
+            }
+
+            // Skip the remaining attributes and the encoded bytes of the static
+            for line in lines.by_ref() {
+                if line.trim_end().ends_with("\";") {
+                    break;
+                }
             }
         }
 
-        // Parse the target package itself
-        let root = if path.is_dir() {
-            UnresolvedPackage::parse_dir(path).with_context(|| {
-                format!(
-                    "failed to parse local target from directory `{}`",
-                    path.display()
-                )
-            })?
-        } else {
-            UnresolvedPackage::parse_file(path).with_context(|| {
-                format!(
-                    "failed to parse local target `{path}`",
-                    path = path.display()
-                )
-            })?
-        };
+        output
+    }
 
-        let mut source_files: Vec<_> = root.source_files().map(Path::to_path_buf).collect();
+    /// Gets the module name wit-bindgen uses for the given WIT name.
+    fn module_name(name: &str) -> String {
+        match name {
+            "as" | "break" | "const" | "continue" | "crate" | "else" | "enum" | "extern"
+            | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" | "mod"
+            | "move" | "mut" | "pub" | "ref" | "return" | "self" | "static" | "struct"
+            | "super" | "trait" | "true" | "type" | "unsafe" | "use" | "where" | "while"
+            | "async" | "await" | "dyn" | "abstract" | "become" | "box" | "do" | "final"
+            | "macro" | "override" | "priv" | "typeof" | "unsized" | "virtual" | "yield"
+            | "try" => format!("{name}_"),
+            name => name.to_snake_case(),
+        }
+    }
 
-        // Do a topological sort of the dependencies
-        let mut order = IndexSet::new();
-        let mut visiting = HashSet::new();
-        for dep in deps.values() {
-            visit(dep, &deps, &mut order, &mut visiting)?;
+    /// Gets the path of the module generated for the given world interface.
+    fn module_path(resolve: &Resolve, key: &WorldKey, export: bool) -> Vec<String> {
+        let mut path = Vec::new();
+        if export {
+            path.push("exports".to_string());
         }
 
-        assert!(visiting.is_empty());
+        match key {
+            WorldKey::Name(name) => path.push(Self::module_name(name)),
+            WorldKey::Interface(id) => {
+                let interface = &resolve.interfaces[*id];
+                if let Some(package) = interface.package {
+                    let name = &resolve.packages[package].name;
+                    path.push(Self::module_name(&name.namespace));
+                    path.push(Self::module_name(&name.name));
+                }
 
-        // Merge all of the dependencies first
-        for name in order {
-            match deps.swap_remove(&name).unwrap() {
-                DecodedDependency::Wit {
-                    resolution,
-                    package,
-                } => {
-                    source_files.extend(package.source_files().map(Path::to_path_buf));
-                    merged.push(package).with_context(|| {
-                        format!(
-                            "failed to merge target dependency `{name}`",
-                            name = resolution.name()
-                        )
-                    })?;
-                }
-                DecodedDependency::Wasm {
-                    resolution,
-                    decoded,
-                } => {
-                    let resolve = match decoded {
-                        DecodedWasm::WitPackage(resolve, _) => resolve,
-                        DecodedWasm::Component(resolve, _) => resolve,
-                    };
+                path.push(Self::module_name(
+                    interface
+                        .name
+                        .as_deref()
+                        .expect("interface should be named"),
+                ));
+            }
+        }
 
-                    merged.merge(resolve).with_context(|| {
-                        format!(
-                            "failed to merge world of target dependency `{name}`",
-                            name = resolution.name()
-                        )
-                    })?;
+        path
+    }
+
+    /// Gets the interface mappings to pass to wit-bindgen for the `with` setting.
+    ///
+    /// A `*` entry is expanded to every imported interface that does not have
+    /// an entry of its own.
+    fn with_mappings(
+        resolve: &Resolve,
+        world: WorldId,
+        with: &HashMap<String, WithMapping>,
+    ) -> Vec<(String, String)> {
+        let mut mappings: Vec<_> = with
+            .iter()
+            .filter_map(|(name, mapping)| match mapping {
+                WithMapping::Path(path) if name != "*" => Some((name.clone(), path.clone())),
+                _ => None,
+            })
+            .collect();
+
+        if let Some(WithMapping::Path(root)) = with.get("*") {
+            for key in resolve.worlds[world].imports.keys() {
+                let name = match key {
+                    WorldKey::Interface(id) => match resolve.id_of(*id) {
+                        Some(name) => name,
+                        None => continue,
+                    },
+                    WorldKey::Name(_) => continue,
+                };
+
+                // Explicit entries take precedence over the wildcard
+                if Self::item_names(resolve, key)
+                    .iter()
+                    .any(|n| with.contains_key(n))
+                {
+                    continue;
                 }
+
+                let path = Self::module_path(resolve, key, false).join("::");
+                mappings.push((name, format!("{root}::{path}")));
             }
         }
 
-        let package = merged.push(root).with_context(|| {
-            format!(
-                "failed to merge local target `{path}`",
-                path = path.display()
-            )
-        })?;
+        mappings.sort();
+        mappings
+    }
 
-        let world = merged
-            .select_world(package, world)
-            .with_context(|| match world {
-                Some(world) => {
-                    format!(
-                        "failed to select the specified world `{world}` for local target `{path}`",
-                        path = path.display()
-                    )
-                }
-                None => format!(
-                    "failed to select the default world to use for local target `{path}`",
-                    path = path.display()
-                ),
+    /// Gets the `pub use` aliases for the interface modules renamed by the
+    /// `rename` setting.
+    fn rename_aliases(
+        resolve: &Resolve,
+        world: WorldId,
+        rename: &HashMap<String, String>,
+    ) -> Result<String> {
+        let world = &resolve.worlds[world];
+        let interfaces: Vec<_> = world
+            .imports
+            .iter()
+            .map(|item| (item, false))
+            .chain(world.exports.iter().map(|item| (item, true)))
+            .filter(|((_, item), _)| matches!(item, WorldItem::Interface(_)))
+            .map(|((key, _), export)| (key, export))
+            .collect();
+
+        let roots: HashSet<_> = interfaces
+            .iter()
+            .map(|(key, export)| Self::module_path(resolve, key, *export).remove(0))
+            .collect();
+
+        let mut renames: Vec<_> = rename.iter().collect();
+        renames.sort();
+
+        let mut aliases = String::new();
+        for (name, module) in renames {
+            let mut matches = interfaces
+                .iter()
+                .filter(|(key, _)| Self::item_names(resolve, key).contains(name));
+
+            let (key, export) = matches.next().with_context(|| {
+                format!("bindings `rename` entry `{name}` does not match an interface in the target world")
             })?;
 
-        return Ok((merged, world, source_files));
+            if matches.next().is_some() {
+                bail!("bindings `rename` entry `{name}` matches more than one interface in the target world");
+            }
 
-        fn visit<'a>(
-            dep: &'a DecodedDependency<'a>,
-            deps: &'a IndexMap<PackageName, DecodedDependency>,
-            order: &mut IndexSet<PackageName>,
-            visiting: &mut HashSet<&'a PackageName>,
-        ) -> Result<()> {
-            if order.contains(dep.package_name()) {
-                return Ok(());
+            if roots.contains(module) {
+                bail!(
+                    "bindings `rename` entry `{name}` conflicts with generated module `{module}`"
+                );
             }
 
-            // Visit any unresolved foreign dependencies
-            match dep {
-                DecodedDependency::Wit {
-                    package,
-                    resolution,
-                } => {
-                    for name in package.foreign_deps.keys() {
-                        if !visiting.insert(name) {
-                            bail!("foreign dependency `{name}` forms a dependency cycle while parsing target dependency `{other}`", other = resolution.name());
-                        }
+            aliases.push_str(&format!(
+                "\npub use self::{path} as {module};\n",
+                path = Self::module_path(resolve, key, *export).join("::")
+            ));
+        }
 
-                        // Only visit known dependencies
-                        // wit-parser will error on unknown foreign dependencies when
-                        // the package is resolved
-                        if let Some(dep) = deps.get(name) {
-                            visit(dep, deps, order, visiting)?
-                        }
+        Ok(aliases)
+    }
 
-                        assert!(visiting.remove(name));
+    /// Gets the names that a world item may be matched by.
+    fn item_names(resolve: &Resolve, key: &WorldKey) -> Vec<String> {
+        match key {
+            WorldKey::Name(name) => vec![name.clone()],
+            WorldKey::Interface(id) => match resolve.id_of(*id) {
+                Some(name) => {
+                    // Allow matching the interface without its version
+                    let unversioned = name.split('@').next().unwrap().to_string();
+                    if unversioned == name {
+                        vec![name]
+                    } else {
+                        vec![name, unversioned]
                     }
                 }
-                DecodedDependency::Wasm { .. } => {
-                    // No unresolved foreign dependencies for decoded wasm files
-                }
-            }
+                None => Vec::new(),
+            },
+        }
+    }
 
-            assert!(order.insert(dep.package_name().clone()));
+    /// Gets the patterns that do not match any item in the given world.
+    fn unmatched_patterns<'b>(
+        resolve: &Resolve,
+        world: WorldId,
+        patterns: &'b [String],
+    ) -> Vec<&'b str> {
+        let world = &resolve.worlds[world];
+        let names: Vec<_> = world
+            .imports
+            .keys()
+            .chain(world.exports.keys())
+            .flat_map(|key| Self::item_names(resolve, key))
+            .collect();
 
-            Ok(())
-        }
+        patterns
+            .iter()
+            .filter(|p| !names.iter().any(|n| matches_pattern(p, n)))
+            .map(String::as_str)
+            .collect()
     }
 
-    fn target_empty_world(resolution: &PackageDependencyResolution) -> (Resolve, WorldId) {
-        let mut resolve = Resolve::default();
-        let name = resolution.metadata.name.clone();
-        let pkg_name = PackageName {
-            namespace: "component".to_string(),
-            name: name.clone(),
-            version: None,
+    /// Removes the items from the world that are excluded by the `only` or
+    /// `skip` patterns.
+    ///
+    /// Imported interfaces that are used by retained items are kept.
+    pub(crate) fn filter_world(
+        resolve: &mut Resolve,
+        world: WorldId,
+        only: &[String],
+        skip: &[String],
+    ) {
+        if only.is_empty() && skip.is_empty() {
+            return;
+        }
+
+        let included = |key: &WorldKey, item: &WorldItem| {
+            if let WorldItem::Type(_) = item {
+                return true;
+            }
+
+            let names = Self::item_names(resolve, key);
+            let matches = |p: &String| names.iter().any(|n| matches_pattern(p, n));
+            let skipped = |p: &String| match p.strip_prefix(INTERFACE_SKIP_PREFIX) {
+                Some(p) => {
+                    matches!(item, WorldItem::Interface(_))
+                        && names.iter().any(|n| matches_pattern(p, n))
+                }
+                None => matches(p),
+            };
+            !skip.iter().any(skipped) && (only.is_empty() || only.iter().any(matches))
         };
 
-        let package = resolve.packages.alloc(Package {
-            name: pkg_name.clone(),
-            interfaces: Default::default(),
-            worlds: Default::default(),
-            docs: Default::default(),
-        });
+        let w = &resolve.worlds[world];
+        let removed: HashSet<WorldKey> = w
+            .imports
+            .iter()
+            .chain(w.exports.iter())
+            .filter(|(key, item)| !included(key, item))
+            .map(|(key, _)| key.clone())
+            .collect();
 
-        resolve.package_names.insert(pkg_name, package);
+        // Find all interfaces required by the retained items
+        let mut required = HashSet::new();
+        let mut stack: Vec<_> = w
+            .imports
+            .iter()
+            .chain(w.exports.iter())
+            .filter(|(key, _)| !removed.contains(*key))
+            .filter_map(|(_, item)| match item {
+                WorldItem::Interface(id) => Some(*id),
+                WorldItem::Type(ty) => match resolve.types[*ty].kind {
+                    TypeDefKind::Type(Type::Id(ty)) => match resolve.types[ty].owner {
+                        TypeOwner::Interface(id) => Some(id),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                WorldItem::Function(_) => None,
+            })
+            .collect();
 
-        let world = resolve.worlds.alloc(World {
-            name: name.clone(),
-            docs: Default::default(),
-            imports: Default::default(),
-            exports: Default::default(),
-            package: Some(package),
-            includes: Default::default(),
-            include_names: Default::default(),
-        });
+        while let Some(id) = stack.pop() {
+            if required.insert(id) {
+                stack.extend(resolve.interface_direct_deps(id));
+            }
+        }
 
-        resolve.packages[package].worlds.insert(name, world);
+        let w = &mut resolve.worlds[world];
+        let retain = |key: &WorldKey, _: &mut WorldItem| match key {
+            WorldKey::Interface(id) if required.contains(id) => true,
+            key => !removed.contains(key),
+        };
 
-        (resolve, world)
+        w.imports.retain(retain);
+        w.exports.retain(retain);
     }
 
-    /// This function imports in the target world the exports of the source world.
-    ///
-    /// This is used for dependencies on other components so that their exports may
-    /// be imported by the component being built.
+    /// Gets the names of the functions to skip when generating bindings.
     ///
-    /// This also populates the import name map, which is used to map import names
-    /// that the bindings supports to `unlocked-dep` import names used in the output
-    /// component.
-    fn import_world(
-        resolve: &mut Resolve,
-        source_id: WorldId,
-        target_id: WorldId,
-        version: Option<&Version>,
-        import_name_map: &mut HashMap<String, String>,
-    ) -> Result<()> {
-        let mut functions = IndexMap::default();
-        let mut used = IndexMap::new();
-        let mut interfaces = IndexMap::new();
+    /// This is the given skip entries, plus the names of the functions of
+    /// any resource matched by an entry and of any interface matched by an
+    /// `interface:` entry.
+    fn skipped_functions(resolve: &Resolve, world: WorldId, skip: &[String]) -> Vec<String> {
+        let (interfaces, skip): (Vec<_>, Vec<_>) = skip
+            .iter()
+            .partition(|p| p.starts_with(INTERFACE_SKIP_PREFIX));
+        let mut skipped: Vec<String> = skip.iter().map(|p| p.to_string()).collect();
+        let w = &resolve.worlds[world];
 
-        // Check for directly used types from the component's world
-        // Add any used interfaces to the `used` map
-        for item in resolve.worlds[source_id].imports.values() {
-            if let WorldItem::Type(ty) = &item {
-                if let TypeDefKind::Type(Type::Id(ty)) = resolve.types[*ty].kind {
-                    if let TypeOwner::Interface(id) = resolve.types[ty].owner {
-                        log::debug!(
-                            "importing interface `{iface}` for used type `{ty}`",
-                            iface = resolve.id_of(id).as_deref().unwrap_or("<unnamed>"),
-                            ty = resolve.types[ty].name.as_deref().unwrap_or("<unnamed>")
-                        );
+        for (key, item) in w.imports.iter().chain(w.exports.iter()) {
+            let WorldItem::Interface(id) = item else {
+                continue;
+            };
 
-                        used.insert(WorldKey::Interface(id), WorldItem::Interface(id));
-                    }
+            let names = Self::item_names(resolve, key);
+            if !interfaces.iter().any(|p| {
+                let p = &p[INTERFACE_SKIP_PREFIX.len()..];
+                names.iter().any(|n| matches_pattern(p, n))
+            }) {
+                continue;
+            }
+
+            for function in resolve.interfaces[*id].functions.values() {
+                if !skipped.contains(&function.name) {
+                    skipped.push(function.name.clone());
                 }
             }
         }
 
-        // Add imports for all exported items
-        for (key, item) in &resolve.worlds[source_id].exports {
-            match item {
-                WorldItem::Function(f) => {
-                    log::debug!("importing function `{name}`", name = f.name);
-                    functions.insert(key.clone().unwrap_name(), f.clone());
-                }
-                WorldItem::Interface(id) => {
-                    let name = match key {
-                        WorldKey::Name(name) => name.clone(),
-                        WorldKey::Interface(id) => {
-                            let iface = &resolve.interfaces[*id];
-                            let name = iface.name.as_deref().expect("interface has no name");
-                            match iface.package {
-                                Some(pkg) => {
-                                    let pkg = &resolve.packages[pkg];
-                                    format!(
-                                        "{ns}-{pkg}-{name}",
-                                        ns = pkg.name.namespace,
-                                        pkg = pkg.name.name
-                                    )
-                                }
-                                None => name.to_string(),
-                            }
-                        }
-                    };
+        if skip.is_empty() {
+            return skipped;
+        }
 
-                    // Check for used types from this interface
-                    // Add any used interfaces to the `used` map
-                    for (_, ty) in &resolve.interfaces[*id].types {
-                        if let TypeDefKind::Type(Type::Id(ty)) = resolve.types[*ty].kind {
-                            if let TypeOwner::Interface(other) = resolve.types[ty].owner {
-                                if other != *id {
-                                    log::debug!(
-                                        "importing interface `{iface}` for used type `{ty}`",
-                                        iface =
-                                            resolve.id_of(other).as_deref().unwrap_or("<unnamed>"),
-                                        ty = resolve.types[ty]
-                                            .name
-                                            .as_deref()
-                                            .unwrap_or("<unnamed>")
-                                    );
+        let functions = w
+            .imports
+            .values()
+            .chain(w.exports.values())
+            .flat_map(|item| match item {
+                WorldItem::Interface(id) => resolve.interfaces[*id].functions.values().collect(),
+                WorldItem::Function(f) => vec![f],
+                WorldItem::Type(_) => Vec::new(),
+            });
 
-                                    used.insert(
-                                        WorldKey::Interface(other),
-                                        WorldItem::Interface(other),
-                                    );
-                                }
-                            }
-                        }
-                    }
+        for function in functions {
+            let resource = match function.kind {
+                FunctionKind::Method(id)
+                | FunctionKind::Static(id)
+                | FunctionKind::Constructor(id) => id,
+                FunctionKind::Freestanding => continue,
+            };
 
-                    log::debug!(
-                        "importing interface `{iface}`",
-                        iface = resolve.id_of(*id).as_ref().unwrap_or(&name),
-                    );
-                    interfaces.insert(name, *id);
+            let ty = &resolve.types[resource];
+            let Some(name) = &ty.name else {
+                continue;
+            };
+
+            let mut names = vec![name.clone()];
+            if let TypeOwner::Interface(id) = ty.owner {
+                if let Some(interface) = resolve.id_of(id) {
+                    let unversioned = interface.split('@').next().unwrap();
+                    names.push(format!("{unversioned}#{name}"));
+                    if unversioned != interface {
+                        names.push(format!("{interface}#{name}"));
+                    }
                 }
-                _ => continue,
+            }
+
+            if skip
+                .iter()
+                .any(|p| names.iter().any(|n| matches_pattern(p, n)))
+                && !skipped.contains(&function.name)
+            {
+                skipped.push(function.name.clone());
             }
         }
 
-        // Import the used interfaces
-        resolve.worlds[target_id].imports.extend(used);
+        skipped
+    }
 
-        // Import the exported interfaces
-        for (name, id) in interfaces {
-            // Alloc an interface that will just serve as a name
-            // for the import.
-            let package = resolve.worlds[source_id].package;
-            let name_id = resolve.interfaces.alloc(Interface {
-                name: Some(name.clone()),
-                types: Default::default(),
-                functions: Default::default(),
-                docs: Default::default(),
-                package,
-            });
+    fn ownership(ownership: &Ownership) -> Result<wit_bindgen_rust::Ownership> {
+        // The bindings generator always uses owning types for exports unless a
+        // borrowing type is shared with an import and not duplicated
+        match (ownership.imports(), ownership.exports()) {
+            (OwnershipModel::Owning, OwnershipModel::Owning) => {
+                Ok(wit_bindgen_rust::Ownership::Owning)
+            }
+            (OwnershipModel::Owning, _) => {
+                bail!("borrowing ownership for exports requires borrowing ownership for imports")
+            }
+            (OwnershipModel::Borrowing, OwnershipModel::Borrowing) => {
+                Ok(wit_bindgen_rust::Ownership::Borrowing {
+                    duplicate_if_necessary: false,
+                })
+            }
+            // Duplicate any types shared with exports so that exports remain owning
+            (OwnershipModel::Borrowing, _) | (OwnershipModel::BorrowingDuplicateIfNecessary, _) => {
+                Ok(wit_bindgen_rust::Ownership::Borrowing {
+                    duplicate_if_necessary: true,
+                })
+            }
+        }
+    }
 
-            let import_name =
-                format_dep_import(&resolve.packages[package.unwrap()], Some(&name), version);
-            import_name_map.insert(resolve.id_of(name_id).unwrap(), import_name);
+    fn format(source: &str, format: &Format) -> Result<String> {
+        let mut cmd = Command::new("rustfmt");
+        cmd.args(format.rustfmt_args())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
 
-            if resolve.worlds[target_id]
-                .imports
-                .insert(WorldKey::Interface(name_id), WorldItem::Interface(id))
-                .is_some()
-            {
-                let iface = &resolve.interfaces[id];
-                let package = &resolve.packages[iface.package.expect("interface has no package")];
-                let id = package
-                    .name
-                    .interface_id(iface.name.as_deref().expect("interface has no name"));
-                bail!("cannot import dependency `{id}` because it conflicts with an import in the target world");
+        log::debug!("spawning command {:?}", cmd);
+
+        let mut child = cmd.spawn().context("failed to spawn `rustfmt`")?;
+        child
+            .stdin
+            .take()
+            .expect("no stdin")
+            .write_all(source.as_bytes())
+            .context("failed to write bindings to `rustfmt`")?;
+
+        let output = child
+            .wait_with_output()
+            .context("failed to wait for `rustfmt` to finish")?;
+
+        if !output.status.success() {
+            bail!("`rustfmt` failed to format the bindings");
+        }
+
+        String::from_utf8(output.stdout).context("`rustfmt` produced output that is not UTF-8")
+    }
+
+    fn dependencies_are_newer(&self, last_modified_output: SystemTime) -> Result<bool> {
+        for (_, dep) in self.resolution.all() {
+            if last_modified_time(dep.path())? > last_modified_output {
+                log::debug!(
+                    "dependency `{path}` has been modified",
+                    path = dep.path().display()
+                );
+                return Ok(true);
             }
         }
 
-        // If the world had functions, insert an interface that contains them.
-        if !functions.is_empty() {
-            let source = &resolve.worlds[source_id];
-            let package = &resolve.packages[source.package.unwrap()];
-            let name = format!(
-                "{ns}-{pkg}",
-                ns = package.name.namespace,
-                pkg = package.name.name
-            );
+        Ok(false)
+    }
 
-            import_name_map.insert(name.clone(), format_dep_import(package, None, version));
+    fn create_target_world(
+        resolution: &PackageDependencyResolution,
+        import_name_map: &mut HashMap<String, String>,
+    ) -> Result<(Resolve, WorldId)> {
+        log::debug!(
+            "creating target world for package `{name}` ({path})",
+            name = resolution.metadata.name,
+            path = resolution.metadata.manifest_path.display()
+        );
 
-            let interface = resolve.interfaces.alloc(Interface {
-                name: Some(name.clone()),
-                docs: source.docs.clone(),
-                types: Default::default(),
-                functions,
-                package: source.package,
-            });
+        let (mut merged, world_id) = match Self::target_resolve(resolution)? {
+            Some((resolve, pkg, _)) => {
+                let world = Self::select_target_world(resolution, &resolve, pkg)?;
+                (resolve, world)
+            }
+            None => Self::target_empty_world(resolution),
+        };
 
-            // Add any types owned by the world to the interface
-            for (id, ty) in resolve.types.iter() {
-                if ty.owner == TypeOwner::World(source_id) {
-                    resolve.interfaces[interface]
-                        .types
-                        .insert(ty.name.clone().expect("type should have a name"), id);
+        Self::replace_imports(&mut merged, world_id, &resolution.metadata.section.replace)?;
+
+        // Merge all component dependencies as interface imports
+        for (id, dependency) in &resolution.resolutions {
+            log::debug!("importing component dependency `{id}`");
+
+            let (mut resolve, component_world_id) =
+                Self::decode_dependency(resolution, id, dependency)?
+                    .into_component_world()
+                    .with_context(|| format!("failed to decode component dependency `{id}`"))?;
+
+            // Set the world name as currently it defaults to "root"
+            // For now, set it to the name from the id
+            let world = &mut resolve.worlds[component_world_id];
+            world.name = id.name().to_string();
+
+            let pkg = &mut resolve.packages[world.package.unwrap()];
+            pkg.name.namespace = id.namespace().to_string();
+            pkg.name.name = id.name().to_string();
+
+            let source = merged
+                .merge(resolve)
+                .with_context(|| format!("failed to merge world of dependency `{id}`"))?
+                .worlds[component_world_id.index()];
+            Self::import_world(
+                &mut merged,
+                source,
+                world_id,
+                dependency.version(),
+                import_name_map,
+            )?;
+        }
+
+        Ok((merged, world_id))
+    }
+
+    /// Replaces the interface imports of the world from the packages of the
+    /// `replace` setting.
+    fn replace_imports(
+        resolve: &mut Resolve,
+        world: WorldId,
+        replace: &HashMap<registry::PackageName, registry::PackageName>,
+    ) -> Result<()> {
+        if replace.is_empty() {
+            return Ok(());
+        }
+
+        let imports = std::mem::take(&mut resolve.worlds[world].imports);
+        let mut replaced = IndexMap::with_capacity(imports.len());
+        for (key, item) in imports {
+            let replacement = match &item {
+                WorldItem::Interface(id) => {
+                    let interface = &resolve.interfaces[*id];
+                    interface
+                        .package
+                        .and_then(|pkg| {
+                            let name = &resolve.packages[pkg].name;
+                            replace.iter().find(|(from, _)| {
+                                from.namespace() == name.namespace && from.name() == name.name
+                            })
+                        })
+                        .map(|(from, to)| (from, to, interface.name.clone().unwrap_or_default()))
                 }
+                _ => None,
+            };
+
+            let Some((from, to, name)) = replacement else {
+                replaced.insert(key, item);
+                continue;
+            };
+
+            let pkg = resolve
+                .packages
+                .iter()
+                .find(|(_, p)| p.name.namespace == to.namespace() && p.name.name == to.name())
+                .map(|(_, p)| p)
+                .with_context(|| {
+                    format!("replacement package `{to}` for package `{from}` was not found")
+                })?;
+
+            let id = *pkg.interfaces.get(&name).with_context(|| {
+                format!(
+                    "replacement package `{to}` does not provide interface `{name}` imported from package `{from}`"
+                )
+            })?;
+
+            log::debug!("replacing import of interface `{from}/{name}` with `{to}/{name}`");
+            replaced.insert(WorldKey::Interface(id), WorldItem::Interface(id));
+        }
+
+        resolve.worlds[world].imports = replaced;
+        Ok(())
+    }
+
+    /// Creates the resolve containing the target package of the component.
+    ///
+    /// Returns the resolve, the id of the target package, and the source
+    /// files of the target.
+    ///
+    /// Returns `Ok(None)` if the component has no target.
+    pub fn target_resolve(
+        resolution: &PackageDependencyResolution,
+    ) -> Result<Option<(Resolve, PackageId, Vec<PathBuf>)>> {
+        if let Some(name) = resolution.metadata.target_package() {
+            Self::target_package(resolution, name).map(Some)
+        } else if let Some(path) = resolution.metadata.target_path()? {
+            Self::target_local_path(resolution, &path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Selects the target world from the given target package.
+    pub fn select_target_world(
+        resolution: &PackageDependencyResolution,
+        resolve: &Resolve,
+        pkg: PackageId,
+    ) -> Result<WorldId> {
+        let world = resolution.metadata.target_world();
+        let world = world.as_deref();
+        if let Ok(name) = std::env::var(WORLD_ENV_VAR) {
+            if !name.is_empty() {
+                log::info!(
+                    "using world `{name}` from environment variable `{WORLD_ENV_VAR}` for package `{package}`",
+                    package = resolution.metadata.name
+                );
             }
+        }
 
-            // Finally, insert the interface into the target world
-            if resolve.worlds[target_id]
-                .imports
-                .insert(
-                    WorldKey::Name(name.clone()),
-                    WorldItem::Interface(interface),
+        if let (Some(_), Some(world)) = (resolution.metadata.target_package(), world) {
+            Self::check_package_world(resolve, pkg, world)?;
+        }
+
+        let path = resolution.metadata.target_path()?.unwrap_or_default();
+        if world.is_none() {
+            if let Some(message) = Self::ambiguous_worlds(resolve, pkg) {
+                bail!(message);
+            }
+        }
+
+        resolve.select_world(pkg, world).with_context(|| {
+            match (resolution.metadata.target_package(), world) {
+                (Some(name), _) => format!("failed to select world from target package `{name}`"),
+                (None, Some(world)) => format!(
+                    "failed to select the specified world `{world}` for local target `{path}`",
+                    path = path.display()
+                ),
+                (None, None) => format!(
+                    "failed to select the default world to use for local target `{path}`",
+                    path = path.display()
+                ),
+            }
+        })
+    }
+
+    /// Checks that a target package contains the given world.
+    ///
+    /// The error lists the worlds the package does contain.
+    fn check_package_world(resolve: &Resolve, pkg: PackageId, world: &str) -> Result<()> {
+        let package = &resolve.packages[pkg];
+        if package.worlds.contains_key(world) {
+            return Ok(());
+        }
+
+        if package.worlds.is_empty() {
+            bail!(
+                "target package `{name}` does not contain world `{world}`; the package contains no worlds",
+                name = package.name
+            );
+        }
+
+        bail!(
+            "target package `{name}` does not contain world `{world}`; the available worlds are: {worlds}",
+            name = package.name,
+            worlds = package
+                .worlds
+                .keys()
+                .map(|w| format!("`{w}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// Gets an error message listing the candidate worlds when the given
+    /// package has more than one world and no world was specified.
+    fn ambiguous_worlds(resolve: &Resolve, pkg: PackageId) -> Option<String> {
+        let package = &resolve.packages[pkg];
+        if package.worlds.len() < 2 {
+            return None;
+        }
+
+        let mut message = format!(
+            "target package `{name}` contains multiple worlds; specify one with a `world` setting in the `[package.metadata.component.target]` section of `Cargo.toml`:",
+            name = package.name
+        );
+
+        for name in package.worlds.keys() {
+            message.push_str(&format!(
+                "\n  world = \"{id}\"",
+                id = package.name.interface_id(name)
+            ));
+        }
+
+        Some(message)
+    }
+
+    fn target_package(
+        resolution: &PackageDependencyResolution,
+        name: &registry::PackageName,
+    ) -> Result<(Resolve, PackageId, Vec<PathBuf>)> {
+        // We must have resolved a target package dependency at this point
+        assert_eq!(resolution.target_resolutions.len(), 1);
+
+        // Decode the target package dependency
+        let dependency = resolution.target_resolutions.values().next().unwrap();
+        dependency
+            .decode_with_features(&resolution.metadata.section.bindings.feature_set())?
+            .resolve()
+            .with_context(|| format!("failed to resolve target package `{name}`"))
+    }
+
+    /// Decodes a dependency with the WIT features selected for it.
+    ///
+    /// Returns an error if the dependency entry selects a feature that the
+    /// dependency does not declare.
+    fn decode_dependency<'b>(
+        resolution: &PackageDependencyResolution,
+        id: &registry::PackageName,
+        dependency: &'b DependencyResolution,
+    ) -> Result<DecodedDependency<'b>> {
+        let section = &resolution.metadata.section;
+        if let Some(selected) = section.dependency_features.get(id) {
+            let declared = features::declared_path_features(dependency.path())?;
+            if let Some(feature) = selected.features.iter().find(|f| !declared.contains(*f)) {
+                bail!(
+                    "feature `{feature}` of dependency `{id}` is not declared by the dependency{available}",
+                    available = if declared.is_empty() {
+                        "; the dependency declares no features".to_string()
+                    } else {
+                        format!(
+                            "; the declared features are: {}",
+                            declared
+                                .iter()
+                                .map(|f| format!("`{f}`"))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    }
+                );
+            }
+        }
+
+        dependency.decode_with_features(&section.dependency_feature_set(id))
+    }
+
+    fn target_local_path(
+        resolution: &PackageDependencyResolution,
+        path: &Path,
+    ) -> Result<(Resolve, PackageId, Vec<PathBuf>)> {
+        let mut merged = Resolve::default();
+        let features = resolution.metadata.section.bindings.feature_set();
+
+        // Start by decoding all of the target dependencies
+        let mut deps = IndexMap::new();
+        for (id, dependency) in &resolution.target_resolutions {
+            let decoded = Self::decode_dependency(resolution, id, dependency)?;
+            let name = decoded.package_name();
+
+            if let Some(prev) = deps.insert(name.clone(), decoded) {
+                bail!("duplicate definitions of package `{name}` found while decoding target dependency `{id}`", name = prev.package_name());
+            }
+        }
+
+        // Parse the target package itself
+        let root = features::parse_path(path, &features).with_context(|| {
+            if path.is_dir() {
+                format!(
+                    "failed to parse local target from directory `{}`",
+                    path.display()
                 )
-                .is_some()
-            {
-                bail!("cannot import dependency `{name}` because it conflicts with an import in the target world");
+            } else {
+                format!(
+                    "failed to parse local target `{path}`",
+                    path = path.display()
+                )
+            }
+        })?;
+
+        let mut source_files: Vec<_> = root.source_files().map(Path::to_path_buf).collect();
+
+        // Collect the packages in nested directories of a target directory or
+        // in the `deps` directory beside a target file
+        let nested = if path.is_dir() {
+            if resolution.metadata.section.recursive_wit {
+                Self::nested_packages(path, &root, &features)?
+            } else {
+                Vec::new()
+            }
+        } else {
+            match path.parent().map(|dir| dir.join("deps")) {
+                Some(deps) if deps.is_dir() => Self::nested_packages(&deps, &root, &features)?,
+                _ => Vec::new(),
             }
+        };
+
+        // Do a topological sort of the dependencies
+        let mut order = IndexSet::new();
+        let mut visiting = HashSet::new();
+        for dep in deps.values() {
+            visit(dep, &deps, &mut order, &mut visiting)?;
         }
 
-        Ok(())
+        assert!(visiting.is_empty());
+
+        // Merge all of the dependencies first
+        for name in order {
+            match deps.swap_remove(&name).unwrap() {
+                DecodedDependency::Wit {
+                    resolution,
+                    package,
+                } => {
+                    source_files.extend(package.source_files().map(Path::to_path_buf));
+                    Self::check_world_includes(&merged, &package)?;
+                    merged.push(package).with_context(|| {
+                        format!(
+                            "failed to merge target dependency `{name}`",
+                            name = resolution.name()
+                        )
+                    })?;
+                }
+                DecodedDependency::Wasm {
+                    resolution,
+                    decoded,
+                } => {
+                    let resolve = match decoded {
+                        DecodedWasm::WitPackage(resolve, _) => resolve,
+                        DecodedWasm::Component(resolve, _) => resolve,
+                    };
+
+                    merged.merge(resolve).with_context(|| {
+                        format!(
+                            "failed to merge world of target dependency `{name}`",
+                            name = resolution.name()
+                        )
+                    })?;
+                }
+            }
+        }
+
+        for package in nested {
+            source_files.extend(package.source_files().map(Path::to_path_buf));
+            let name = package.name.clone();
+            Self::check_world_includes(&merged, &package)?;
+            merged.push(package).with_context(|| {
+                format!(
+                    "failed to merge nested package `{name}` of local target `{path}`",
+                    path = path.display()
+                )
+            })?;
+        }
+
+        Self::check_world_includes(&merged, &root)?;
+        let package = merged.push(root).with_context(|| {
+            format!(
+                "failed to merge local target `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        return Ok((merged, package, source_files));
+
+        fn visit<'a>(
+            dep: &'a DecodedDependency<'a>,
+            deps: &'a IndexMap<PackageName, DecodedDependency>,
+            order: &mut IndexSet<PackageName>,
+            visiting: &mut HashSet<&'a PackageName>,
+        ) -> Result<()> {
+            if order.contains(dep.package_name()) {
+                return Ok(());
+            }
+
+            // Visit any unresolved foreign dependencies
+            match dep {
+                DecodedDependency::Wit {
+                    package,
+                    resolution,
+                } => {
+                    for name in package.foreign_deps.keys() {
+                        if !visiting.insert(name) {
+                            bail!("foreign dependency `{name}` forms a dependency cycle while parsing target dependency `{other}`", other = resolution.name());
+                        }
+
+                        // Only visit known dependencies
+                        // wit-parser will error on unknown foreign dependencies when
+                        // the package is resolved
+                        if let Some(dep) = deps.get(name) {
+                            visit(dep, deps, order, visiting)?
+                        }
+
+                        assert!(visiting.remove(name));
+                    }
+                }
+                DecodedDependency::Wasm { .. } => {
+                    // No unresolved foreign dependencies for decoded wasm files
+                }
+            }
+
+            assert!(order.insert(dep.package_name().clone()));
+
+            Ok(())
+        }
+    }
+
+    /// Checks that the worlds of other packages included by the worlds of the
+    /// given package are defined in the given resolve.
+    ///
+    /// Includes are expanded when the package is pushed to the resolve; this
+    /// names the missing world rather than just its package.
+    fn check_world_includes(resolve: &Resolve, package: &UnresolvedPackage) -> Result<()> {
+        for (name, items) in &package.foreign_deps {
+            for (world, item) in items {
+                if !matches!(item, AstItem::World(_)) {
+                    continue;
+                }
+
+                let Some(id) = resolve.package_names.get(name) else {
+                    bail!(
+                        "world `{id}` included by package `{package}` cannot be resolved: package `{name}` was not found",
+                        id = name.interface_id(world),
+                        package = package.name
+                    );
+                };
+
+                let dependency = &resolve.packages[*id];
+                if !dependency.worlds.contains_key(world) {
+                    let worlds = dependency
+                        .worlds
+                        .keys()
+                        .map(|w| format!("`{w}`"))
+                        .collect::<Vec<_>>();
+                    bail!(
+                        "world `{id}` included by package `{package}` cannot be resolved: package `{name}` does not contain world `{world}`{available}",
+                        id = name.interface_id(world),
+                        package = package.name,
+                        available = if worlds.is_empty() {
+                            "; the package contains no worlds".to_string()
+                        } else {
+                            format!("; the available worlds are: {}", worlds.join(", "))
+                        }
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the packages in the nested directories of a local target
+    /// directory.
+    ///
+    /// Every nested directory containing WIT files is parsed as a separate
+    /// package; the packages are returned in an order that satisfies their
+    /// dependencies on each other.
+    fn nested_packages(
+        dir: &Path,
+        root: &UnresolvedPackage,
+        features: &FeatureSet,
+    ) -> Result<Vec<UnresolvedPackage>> {
+        let mut dirs = Vec::new();
+        Self::collect_nested_dirs(dir, &mut dirs)?;
+        dirs.sort();
+
+        let mut locations = HashMap::from([(root.name.clone(), dir.to_path_buf())]);
+        let mut pending = Vec::new();
+        for dir in dirs {
+            let package = features::parse_path(&dir, features).with_context(|| {
+                format!(
+                    "failed to parse nested package from directory `{path}`",
+                    path = dir.display()
+                )
+            })?;
+
+            if let Some(prev) = locations.insert(package.name.clone(), dir.clone()) {
+                bail!(
+                    "duplicate definitions of package `{name}` found in `{first}` and `{second}`",
+                    name = package.name,
+                    first = prev.display(),
+                    second = dir.display()
+                );
+            }
+
+            pending.push(package);
+        }
+
+        // Order the packages so that each follows the nested packages it uses
+        let mut ordered = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            let names: HashSet<_> = pending.iter().map(|p| p.name.clone()).collect();
+            let (ready, rest): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|p| !p.foreign_deps.keys().any(|n| names.contains(n)));
+
+            if ready.is_empty() {
+                bail!(
+                    "nested packages {names} of local target `{path}` form a dependency cycle",
+                    names = rest
+                        .iter()
+                        .map(|p| format!("`{name}`", name = p.name))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    path = dir.display()
+                );
+            }
+
+            ordered.extend(ready);
+            pending = rest;
+        }
+
+        Ok(ordered)
+    }
+
+    /// Collects the nested directories of the given directory that contain
+    /// WIT files.
+    fn collect_nested_dirs(dir: &Path, dirs: &mut Vec<PathBuf>) -> Result<()> {
+        let entries = fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory `{path}`", path = dir.display()))?;
+
+        for entry in entries {
+            let path = entry
+                .with_context(|| {
+                    format!("failed to read directory `{path}`", path = dir.display())
+                })?
+                .path();
+            if !path.is_dir() {
+                continue;
+            }
+
+            let has_wit = fs::read_dir(&path)
+                .with_context(|| {
+                    format!("failed to read directory `{path}`", path = path.display())
+                })?
+                .filter_map(|e| e.ok())
+                .any(|e| {
+                    let path = e.path();
+                    path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("wit")
+                });
+            if has_wit {
+                dirs.push(path.clone());
+            }
+
+            Self::collect_nested_dirs(&path, dirs)?;
+        }
+
+        Ok(())
+    }
+
+    fn target_empty_world(resolution: &PackageDependencyResolution) -> (Resolve, WorldId) {
+        let mut resolve = Resolve::default();
+        let name = resolution.metadata.name.clone();
+        let pkg_name = PackageName {
+            namespace: "component".to_string(),
+            name: name.clone(),
+            version: None,
+        };
+
+        let package = resolve.packages.alloc(Package {
+            name: pkg_name.clone(),
+            interfaces: Default::default(),
+            worlds: Default::default(),
+            docs: Default::default(),
+        });
+
+        resolve.package_names.insert(pkg_name, package);
+
+        let world = resolve.worlds.alloc(World {
+            name: name.clone(),
+            docs: Default::default(),
+            imports: Default::default(),
+            exports: Default::default(),
+            package: Some(package),
+            includes: Default::default(),
+            include_names: Default::default(),
+        });
+
+        resolve.packages[package].worlds.insert(name, world);
+
+        (resolve, world)
+    }
+
+    /// This function imports in the target world the exports of the source world.
+    ///
+    /// This is used for dependencies on other components so that their exports may
+    /// be imported by the component being built.
+    ///
+    /// This also populates the import name map, which is used to map import names
+    /// that the bindings supports to `unlocked-dep` import names used in the output
+    /// component.
+    fn import_world(
+        resolve: &mut Resolve,
+        source_id: WorldId,
+        target_id: WorldId,
+        version: Option<&Version>,
+        import_name_map: &mut HashMap<String, String>,
+    ) -> Result<()> {
+        let mut functions = IndexMap::default();
+        let mut used = IndexMap::new();
+        let mut interfaces = IndexMap::new();
+
+        // Check for directly used types from the component's world
+        // Add any used interfaces to the `used` map
+        for item in resolve.worlds[source_id].imports.values() {
+            if let WorldItem::Type(ty) = &item {
+                if let TypeDefKind::Type(Type::Id(ty)) = resolve.types[*ty].kind {
+                    if let TypeOwner::Interface(id) = resolve.types[ty].owner {
+                        log::debug!(
+                            "importing interface `{iface}` for used type `{ty}`",
+                            iface = resolve.id_of(id).as_deref().unwrap_or("<unnamed>"),
+                            ty = resolve.types[ty].name.as_deref().unwrap_or("<unnamed>")
+                        );
+
+                        used.insert(WorldKey::Interface(id), WorldItem::Interface(id));
+                    }
+                }
+            }
+        }
+
+        // Add imports for all exported items
+        for (key, item) in &resolve.worlds[source_id].exports {
+            match item {
+                WorldItem::Function(f) => {
+                    log::debug!("importing function `{name}`", name = f.name);
+                    functions.insert(key.clone().unwrap_name(), f.clone());
+                }
+                WorldItem::Interface(id) => {
+                    let name = match key {
+                        WorldKey::Name(name) => name.clone(),
+                        WorldKey::Interface(id) => {
+                            let iface = &resolve.interfaces[*id];
+                            let name = iface.name.as_deref().expect("interface has no name");
+                            match iface.package {
+                                Some(pkg) => {
+                                    let pkg = &resolve.packages[pkg];
+                                    format!(
+                                        "{ns}-{pkg}-{name}",
+                                        ns = pkg.name.namespace,
+                                        pkg = pkg.name.name
+                                    )
+                                }
+                                None => name.to_string(),
+                            }
+                        }
+                    };
+
+                    // Check for used types from this interface
+                    // Add any used interfaces to the `used` map
+                    for (_, ty) in &resolve.interfaces[*id].types {
+                        if let TypeDefKind::Type(Type::Id(ty)) = resolve.types[*ty].kind {
+                            if let TypeOwner::Interface(other) = resolve.types[ty].owner {
+                                if other != *id {
+                                    log::debug!(
+                                        "importing interface `{iface}` for used type `{ty}`",
+                                        iface =
+                                            resolve.id_of(other).as_deref().unwrap_or("<unnamed>"),
+                                        ty = resolve.types[ty]
+                                            .name
+                                            .as_deref()
+                                            .unwrap_or("<unnamed>")
+                                    );
+
+                                    used.insert(
+                                        WorldKey::Interface(other),
+                                        WorldItem::Interface(other),
+                                    );
+                                }
+                            }
+                        }
+                    }
+
+                    log::debug!(
+                        "importing interface `{iface}`",
+                        iface = resolve.id_of(*id).as_ref().unwrap_or(&name),
+                    );
+                    interfaces.insert(name, *id);
+                }
+                _ => continue,
+            }
+        }
+
+        // Import the used interfaces
+        resolve.worlds[target_id].imports.extend(used);
+
+        // Import the exported interfaces
+        for (name, id) in interfaces {
+            // Alloc an interface that will just serve as a name
+            // for the import.
+            let package = resolve.worlds[source_id].package;
+            let name_id = resolve.interfaces.alloc(Interface {
+                name: Some(name.clone()),
+                types: Default::default(),
+                functions: Default::default(),
+                docs: Default::default(),
+                package,
+            });
+
+            let import_name =
+                format_dep_import(&resolve.packages[package.unwrap()], Some(&name), version);
+            import_name_map.insert(resolve.id_of(name_id).unwrap(), import_name);
+
+            if resolve.worlds[target_id]
+                .imports
+                .insert(WorldKey::Interface(name_id), WorldItem::Interface(id))
+                .is_some()
+            {
+                let iface = &resolve.interfaces[id];
+                let package = &resolve.packages[iface.package.expect("interface has no package")];
+                let id = package
+                    .name
+                    .interface_id(iface.name.as_deref().expect("interface has no name"));
+                bail!("cannot import dependency `{id}` because it conflicts with an import in the target world");
+            }
+        }
+
+        // If the world had functions, insert an interface that contains them.
+        if !functions.is_empty() {
+            let source = &resolve.worlds[source_id];
+            let package = &resolve.packages[source.package.unwrap()];
+            let name = format!(
+                "{ns}-{pkg}",
+                ns = package.name.namespace,
+                pkg = package.name.name
+            );
+
+            import_name_map.insert(name.clone(), format_dep_import(package, None, version));
+
+            let interface = resolve.interfaces.alloc(Interface {
+                name: Some(name.clone()),
+                docs: source.docs.clone(),
+                types: Default::default(),
+                functions,
+                package: source.package,
+            });
+
+            // Add any types owned by the world to the interface
+            for (id, ty) in resolve.types.iter() {
+                if ty.owner == TypeOwner::World(source_id) {
+                    resolve.interfaces[interface]
+                        .types
+                        .insert(ty.name.clone().expect("type should have a name"), id);
+                }
+            }
+
+            // Finally, insert the interface into the target world
+            if resolve.worlds[target_id]
+                .imports
+                .insert(
+                    WorldKey::Name(name.clone()),
+                    WorldItem::Interface(interface),
+                )
+                .is_some()
+            {
+                bail!("cannot import dependency `{name}` because it conflicts with an import in the target world");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::metadata::ComponentSection;
+    use cargo_component_core::registry::LocalResolution;
+    use std::fs;
+
+    fn world(source: &str) -> (Resolve, WorldId) {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(UnresolvedPackage::parse(Path::new("test.wit"), source).unwrap())
+            .unwrap();
+        let world = resolve.select_world(pkg, None).unwrap();
+        (resolve, world)
+    }
+
+    fn names(resolve: &Resolve, world: WorldId) -> Vec<String> {
+        let world = &resolve.worlds[world];
+        world
+            .imports
+            .keys()
+            .chain(world.exports.keys())
+            .map(|key| resolve.name_world_key(key))
+            .collect()
+    }
+
+    #[test]
+    fn it_filters_world_items() {
+        let (mut resolve, world) = world(
+            r#"package foo:bar;
+
+interface types {
+    record point { x: u32, y: u32 }
+}
+
+interface graphics {
+    use types.{point};
+    draw: func(p: point);
+}
+
+interface audio {
+    play: func();
+}
+
+world example {
+    import graphics;
+    import audio;
+    export run: func();
+}
+"#,
+        );
+
+        BindingsGenerator::filter_world(
+            &mut resolve,
+            world,
+            &["foo:bar/graphics".to_string()],
+            &[],
+        );
+
+        // The `types` interface is retained as it is used by `graphics`
+        assert_eq!(
+            names(&resolve, world),
+            ["foo:bar/types", "foo:bar/graphics"]
+        );
+    }
+
+    #[test]
+    fn it_reports_unmatched_only_entries() {
+        let (mut resolve, world) = world(
+            r#"package foo:bar;
+
+interface audio {
+    play: func();
+}
+
+world example {
+    import audio;
+    export run: func();
+}
+"#,
+        );
+
+        let only = ["run".to_string(), "missing:*".to_string()];
+        assert_eq!(
+            BindingsGenerator::unmatched_patterns(&resolve, world, &only),
+            ["missing:*"]
+        );
+
+        BindingsGenerator::filter_world(&mut resolve, world, &only, &[]);
+        assert_eq!(names(&resolve, world), ["run"]);
+    }
+
+    #[test]
+    fn it_renames_interface_modules() {
+        let (resolve, world) = world(
+            r#"package foo:bar;
+
+interface audio {
+    play: func();
+}
+
+interface graphics {
+    draw: func();
+}
+
+world example {
+    import audio;
+    export graphics;
+}
+"#,
+        );
+
+        let rename = HashMap::from([("foo:bar/audio".to_string(), "sound".to_string())]);
+        assert_eq!(
+            BindingsGenerator::rename_aliases(&resolve, world, &rename).unwrap(),
+            "\npub use self::foo::bar::audio as sound;\n"
+        );
+
+        // The aliased module must match the module that is generated
+        let mut files = Files::default();
+        Opts::default()
+            .build()
+            .generate(&resolve, world, &mut files)
+            .unwrap();
+        let (_, source) = files.iter().next().unwrap();
+        let source = std::str::from_utf8(source).unwrap();
+        assert!(source.contains("pub mod foo {"));
+        assert!(source.contains("pub mod audio {"));
+
+        let rename = HashMap::from([("foo:bar/video".to_string(), "video".to_string())]);
+        assert!(BindingsGenerator::rename_aliases(&resolve, world, &rename)
+            .unwrap_err()
+            .to_string()
+            .contains("does not match an interface in the target world"));
+
+        let rename = HashMap::from([("foo:bar/audio".to_string(), "exports".to_string())]);
+        assert!(BindingsGenerator::rename_aliases(&resolve, world, &rename)
+            .unwrap_err()
+            .to_string()
+            .contains("conflicts with generated module `exports`"));
+    }
+
+    #[test]
+    fn it_forwards_with_mappings() {
+        let (resolve, world) = world(
+            r#"package foo:bar;
+
+interface audio {
+    play: func();
+}
+
+interface graphics {
+    draw: func();
+}
+
+interface input {
+    poll: func();
+}
+
+world example {
+    import audio;
+    import graphics;
+    import input;
+}
+"#,
+        );
+
+        // A generating wildcard only forwards the explicit entries
+        let with = HashMap::from([
+            ("*".to_string(), WithMapping::Generate),
+            (
+                "foo:bar/audio".to_string(),
+                WithMapping::Path("crate::audio".to_string()),
+            ),
+        ]);
+        assert_eq!(
+            BindingsGenerator::with_mappings(&resolve, world, &with),
+            [("foo:bar/audio".to_string(), "crate::audio".to_string())]
+        );
+
+        // Explicit entries take precedence over a remapping wildcard
+        let with = HashMap::from([
+            ("*".to_string(), WithMapping::Path("other".to_string())),
+            ("foo:bar/audio".to_string(), WithMapping::Generate),
+            (
+                "foo:bar/input".to_string(),
+                WithMapping::Path("crate::input".to_string()),
+            ),
+        ]);
+        assert_eq!(
+            BindingsGenerator::with_mappings(&resolve, world, &with),
+            [
+                (
+                    "foo:bar/graphics".to_string(),
+                    "other::foo::bar::graphics".to_string()
+                ),
+                ("foo:bar/input".to_string(), "crate::input".to_string()),
+            ]
+        );
+
+        // Paths with generic arguments are forwarded unchanged
+        let with = HashMap::from([(
+            "foo:bar/audio".to_string(),
+            WithMapping::Path("my_crate::Thing::<()>".to_string()),
+        )]);
+        assert_eq!(
+            BindingsGenerator::with_mappings(&resolve, world, &with),
+            [(
+                "foo:bar/audio".to_string(),
+                "my_crate::Thing::<()>".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn it_strips_the_type_section() {
+        let (resolve, world) = world(
+            r#"package foo:bar;
+
+world example {
+    export run: func();
+}
+"#,
+        );
+
+        let mut files = Files::default();
+        Opts::default()
+            .build()
+            .generate(&resolve, world, &mut files)
+            .unwrap();
+        let (_, source) = files.iter().next().unwrap();
+        let source = std::str::from_utf8(source).unwrap();
+        assert!(source.contains("component-type:"));
+
+        let stripped = BindingsGenerator::strip_type_section(source);
+        assert!(!stripped.contains("component-type:"));
+        assert!(!stripped.contains("__WIT_BINDGEN_COMPONENT_TYPE"));
+        assert!(stripped.contains("fn run()"));
+    }
+
+    #[test]
+    fn it_skips_resources() {
+        let (resolve, world) = world(
+            r#"package foo:bar;
+
+interface types {
+    resource blob {
+        constructor(size: u32);
+        read: func(len: u32) -> list<u8>;
+        merge: static func(a: borrow<blob>, b: borrow<blob>) -> blob;
+    }
+
+    resource other {
+        write: func(bytes: list<u8>);
+    }
+
+    size: func() -> u32;
+}
+
+world example {
+    import types;
+}
+"#,
+        );
+
+        let skip = BindingsGenerator::skipped_functions(&resolve, world, &["blob".to_string()]);
+        assert_eq!(
+            skip,
+            [
+                "blob",
+                "[constructor]blob",
+                "[method]blob.read",
+                "[static]blob.merge"
+            ]
+        );
+        assert_eq!(
+            BindingsGenerator::skipped_functions(
+                &resolve,
+                world,
+                &["foo:bar/types#blob".to_string()]
+            )
+            .len(),
+            4
+        );
+
+        let mut files = Files::default();
+        Opts {
+            skip,
+            ..Default::default()
+        }
+        .build()
+        .generate(&resolve, world, &mut files)
+        .unwrap();
+        let (_, source) = files.iter().next().unwrap();
+        let source = std::str::from_utf8(source).unwrap();
+        assert!(!source.contains(r#"link_name = "[constructor]blob""#));
+        assert!(!source.contains(r#"link_name = "[method]blob.read""#));
+        assert!(!source.contains(r#"link_name = "[static]blob.merge""#));
+        assert!(source.contains(r#"link_name = "[method]other.write""#));
+        assert!(source.contains("fn size("));
+    }
+
+    #[test]
+    fn it_skips_interfaces() {
+        let (mut resolve, world) = world(
+            r#"package foo:bar;
+
+interface streams {
+    resource output-stream;
+    read: func(len: u32) -> list<u8>;
+    write: func(bytes: list<u8>);
+    flush: func();
+}
+
+interface files {
+    use streams.{output-stream};
+    open: func(path: string) -> output-stream;
+}
+
+world example {
+    import streams;
+    import files;
+    export run: func();
+}
+"#,
+        );
+
+        let skip = ["interface:foo:bar/streams".to_string()];
+        assert_eq!(
+            BindingsGenerator::skipped_functions(&resolve, world, &skip),
+            ["read", "write", "flush"]
+        );
+
+        // Only interfaces are matched by `interface:` entries
+        assert!(BindingsGenerator::skipped_functions(
+            &resolve,
+            world,
+            &["interface:run".to_string()]
+        )
+        .is_empty());
+
+        // The interface is kept for the types used by `files`, but none of
+        // its functions are generated
+        BindingsGenerator::filter_world(&mut resolve, world, &[], &skip);
+        let skip = BindingsGenerator::skipped_functions(&resolve, world, &skip);
+        let mut files = Files::default();
+        Opts {
+            skip,
+            ..Default::default()
+        }
+        .build()
+        .generate(&resolve, world, &mut files)
+        .unwrap();
+        let (_, source) = files.iter().next().unwrap();
+        let source = std::str::from_utf8(source).unwrap();
+        assert!(source.contains("pub struct OutputStream"));
+        assert!(!source.contains("fn read("));
+        assert!(!source.contains("fn write("));
+        assert!(!source.contains("fn flush("));
+        assert!(source.contains("fn open("));
+    }
+
+    #[test]
+    fn it_removes_std_items() {
+        let (resolve, world) = world(
+            r#"package foo:bar;
+
+interface types {
+    enum error { failed }
+    run: func() -> result<_, error>;
+}
+
+world example {
+    import types;
+}
+"#,
+        );
+
+        let mut files = Files::default();
+        Opts {
+            std_feature: true,
+            ..Default::default()
+        }
+        .build()
+        .generate(&resolve, world, &mut files)
+        .unwrap();
+        let (_, source) = files.iter().next().unwrap();
+        let source = std::str::from_utf8(source).unwrap();
+        assert!(source.contains("#[cfg(feature = \"std\")]"));
+
+        let source = BindingsGenerator::remove_std_items(source);
+        assert!(!source.contains("#[cfg(feature = \"std\")]"));
+        assert!(source.contains("#[cfg(any())]"));
+    }
+
+    #[test]
+    fn it_replaces_imported_packages() {
+        let mut resolve = Resolve::default();
+        for source in [
+            "package compat:old;\n\ninterface http {\n  get: func(url: string) -> string;\n}\n",
+            "package compat:new;\n\ninterface http {\n  get: func(url: string) -> string;\n}\n",
+            "package compat:partial;\n\ninterface sockets {}\n",
+        ] {
+            resolve
+                .push(UnresolvedPackage::parse(Path::new("test.wit"), source).unwrap())
+                .unwrap();
+        }
+
+        let pkg = resolve
+            .push(
+                UnresolvedPackage::parse(
+                    Path::new("test.wit"),
+                    "package foo:bar;\n\nworld example {\n  import compat:old/http;\n  export run: func();\n}\n",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let world = resolve.select_world(pkg, None).unwrap();
+
+        let mut replaced = resolve.clone();
+        BindingsGenerator::replace_imports(
+            &mut replaced,
+            world,
+            &[("compat:old".parse().unwrap(), "compat:new".parse().unwrap())].into(),
+        )
+        .unwrap();
+        assert_eq!(names(&replaced, world), ["compat:new/http", "run"]);
+
+        let err = BindingsGenerator::replace_imports(
+            &mut resolve,
+            world,
+            &[(
+                "compat:old".parse().unwrap(),
+                "compat:partial".parse().unwrap(),
+            )]
+            .into(),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("does not provide interface `http`"));
+    }
+
+    #[test]
+    fn it_uses_a_custom_runtime_path() {
+        let (mut resolve, world) = world(
+            r#"package foo:bar;
+
+interface types {
+    flags permissions { read, write }
+    check: func(p: permissions) -> list<string>;
+}
+
+world example {
+    import types;
+    export run: func();
+}
+"#,
+        );
+
+        let settings = Bindings {
+            runtime_path: Some("my_crate::wit_bindgen::rt".to_string()),
+            ..Default::default()
+        };
+        settings.validate().unwrap();
+
+        let source =
+            BindingsGenerator::generate_source(&mut resolve, world, &settings, false, false)
+                .unwrap();
+        assert!(source.contains("my_crate::wit_bindgen::rt::"));
+        assert!(source.contains("my_crate::wit_bindgen::rt::bitflags"));
+        assert!(!source.contains("wit_bindgen_rt::"));
+
+        let settings = Bindings {
+            runtime_path: Some("my_crate::wit-bindgen".to_string()),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn it_sets_the_export_macro_visibility() {
+        let source = r#"package foo:bar;
+
+world example {
+    export run: func();
+}
+"#;
+
+        let (mut resolve, world) = self::world(source);
+        let settings = Bindings {
+            export_macro_visibility: Some("pub(crate)".to_string()),
+            ..Default::default()
+        };
+        settings.validate().unwrap();
+
+        let generated =
+            BindingsGenerator::generate_source(&mut resolve, world, &settings, false, false)
+                .unwrap();
+        assert!(generated.contains("pub(crate) use __export_"));
+
+        let (mut resolve, world) = self::world(source);
+        let settings = Bindings {
+            export_macro_visibility: Some("pub(in crate::component)".to_string()),
+            ..Default::default()
+        };
+        let generated =
+            BindingsGenerator::generate_source(&mut resolve, world, &settings, false, false)
+                .unwrap();
+        assert!(generated.contains("pub(in crate::component) use __export_"));
+        assert!(!generated.contains("pub(crate) use __export_"));
+    }
+
+    #[test]
+    fn it_ignores_underivable_derives() {
+        let (mut resolve, world) = world(
+            r#"package foo:bar;
+
+interface types {
+    record point { x: f64, y: f64 }
+    record id { value: u32 }
+    locate: func(id: id) -> point;
+}
+
+world example {
+    import types;
+}
+"#,
+        );
+
+        let settings = Bindings {
+            derives: vec!["Hash".to_string(), "PartialEq".to_string()],
+            additional_derives_ignore_errors: true,
+            ..Default::default()
+        };
+
+        let source =
+            BindingsGenerator::generate_source(&mut resolve, world, &settings, false, false)
+                .unwrap();
+        let attributes = |ty: &str| {
+            let index = source.find(&format!("pub struct {ty}")).unwrap();
+            source[..index]
+                .lines()
+                .rev()
+                .take_while(|l| l.trim().starts_with("#[") || l.trim().is_empty())
+                .map(str::trim)
+                .collect::<Vec<_>>()
+        };
+        assert!(attributes("Point").contains(&"#[derive(Clone, Copy, PartialEq)]"));
+        assert!(attributes("Id").contains(&"#[derive(Clone, Copy, Hash, PartialEq)]"));
+
+        assert_eq!(
+            BindingsGenerator::underivable_types(&resolve, &settings),
+            HashMap::from([("Point".to_string(), vec!["Hash".to_string()])])
+        );
+
+        // Without the setting, the derives are left as requested
+        let settings = Bindings {
+            additional_derives_ignore_errors: false,
+            ..settings
+        };
+        assert!(BindingsGenerator::underivable_types(&resolve, &settings).is_empty());
+    }
+
+    #[test]
+    fn it_checks_included_worlds() {
+        let mut resolve = Resolve::default();
+        resolve
+            .push(
+                UnresolvedPackage::parse(
+                    Path::new("deps/shared/base.wit"),
+                    r#"package dep:shared@1.0.0;
+
+world base {
+    import log: func(message: string);
+}
+"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let target = |include: &str| {
+            UnresolvedPackage::parse(
+                Path::new("app.wit"),
+                &format!(
+                    r#"package foo:app;
+
+world app {{
+    include {include};
+    export run: func();
+}}
+"#
+                ),
+            )
+            .unwrap()
+        };
+
+        // The included world is expanded into the target world
+        let root = target("dep:shared/base@1.0.0");
+        BindingsGenerator::check_world_includes(&resolve, &root).unwrap();
+        let pkg = resolve.push(root).unwrap();
+        let world = resolve.select_world(pkg, Some("app")).unwrap();
+        let world = &resolve.worlds[world];
+        assert!(world
+            .imports
+            .contains_key(&WorldKey::Name("log".to_string())));
+        assert!(world
+            .exports
+            .contains_key(&WorldKey::Name("run".to_string())));
+
+        let err =
+            BindingsGenerator::check_world_includes(&resolve, &target("dep:shared/missing@1.0.0"))
+                .unwrap_err()
+                .to_string();
+        assert!(
+            err.contains("package `dep:shared@1.0.0` does not contain world `missing`; the available worlds are: `base`"),
+            "{err}"
+        );
+
+        let err = BindingsGenerator::check_world_includes(&resolve, &target("dep:other/base"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("world `dep:other/base` included by package `foo:app` cannot be resolved: package `dep:other` was not found"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn it_gates_derives_on_features() {
+        let (mut resolve, world) = world(
+            r#"package foo:bar;
+
+interface types {
+    record point { x: f64, y: f64 }
+    enum color { red, green }
+    resource blob;
+    locate: func(color: color) -> point;
+}
+
+world example {
+    import types;
+}
+"#,
+        );
+
+        let settings = Bindings {
+            derives: vec!["PartialEq".to_string()],
+            derives_feature: HashMap::from([
+                (
+                    "debug".to_string(),
+                    vec!["Clone".to_string(), "Debug".to_string()],
+                ),
+                ("serde".to_string(), vec!["serde::Serialize".to_string()]),
+            ]),
+            ..Default::default()
+        };
+
+        let source =
+            BindingsGenerator::generate_source(&mut resolve, world, &settings, false, false)
+                .unwrap();
+        let attributes = |definition: &str| {
+            let index = source.find(definition).unwrap();
+            source[..index]
+                .lines()
+                .rev()
+                .take_while(|l| l.trim().starts_with("#[") || l.trim().is_empty())
+                .map(str::trim)
+                .collect::<Vec<_>>()
+        };
+
+        for definition in ["pub struct Point", "pub enum Color"] {
+            let attributes = attributes(definition);
+            assert!(attributes.contains(&"#[cfg_attr(feature = \"debug\", derive(Clone, Debug))]"));
+            assert!(
+                attributes.contains(&"#[cfg_attr(feature = \"serde\", derive(serde::Serialize))]")
+            );
+        }
+
+        // Resources do not take additional derives
+        assert!(!attributes("pub struct Blob")
+            .iter()
+            .any(|a| a.starts_with("#[cfg_attr(feature")));
+    }
+
+    #[test]
+    fn it_generates_token_streams() {
+        let (mut resolve, world) = world(
+            r#"package foo:bar;
+
+interface types {
+    record point { x: u32, y: u32 }
+    draw: func(p: point);
+}
+
+interface logging {
+    log: func(message: string);
+}
+
+world example {
+    import types;
+    import logging;
+    export run: func();
+}
+"#,
+        );
+
+        let settings = Bindings {
+            with: [(
+                "foo:bar/logging".to_string(),
+                WithMapping::Path("crate::logging".to_string()),
+            )]
+            .into(),
+            ..Default::default()
+        };
+
+        let source =
+            BindingsGenerator::generate_source(&mut resolve, world, &settings, false, false)
+                .unwrap();
+        let tokens = BindingsGenerator::tokens(&source).unwrap();
+        let file: syn::File = syn::parse2(tokens).unwrap();
+
+        let module = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Mod(m) if m.ident == "foo" => Some(m),
+                _ => None,
+            })
+            .expect("expected a `foo` module");
+        let source = quote::quote!(#module).to_string();
+        assert!(source.contains("types"));
+        assert!(source.contains("draw"));
+        assert!(!source.contains("pub mod logging"));
+    }
+
+    #[test]
+    fn it_checks_target_package_worlds() {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(
+                UnresolvedPackage::parse(
+                    Path::new("test.wit"),
+                    "package wasi:http@0.2.0;
+
+world imports {}
+
+world handler {}
+",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        BindingsGenerator::check_package_world(&resolve, pkg, "handler").unwrap();
+
+        let err = BindingsGenerator::check_package_world(&resolve, pkg, "proxy").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "target package `wasi:http@0.2.0` does not contain world `proxy`; the available worlds are: `imports`, `handler`"
+        );
+
+        let pkg = resolve
+            .push(
+                UnresolvedPackage::parse(
+                    Path::new("test.wit"),
+                    "package foo:bar;
+
+interface baz {}
+",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert!(
+            BindingsGenerator::check_package_world(&resolve, pkg, "proxy")
+                .unwrap_err()
+                .to_string()
+                .contains("the package contains no worlds")
+        );
+    }
+
+    #[test]
+    fn it_applies_wit_feature_gates() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("wit")).unwrap();
+        fs::write(
+            dir.path().join("wit/world.wit"),
+            "package foo:bar;
+
+interface stable {
+    run: func();
+}
+
+@unstable(feature = fancy)
+interface fancy {
+    go: func();
+}
+
+world example {
+    import stable;
+    @unstable(feature = fancy)
+    import fancy;
+    import foo:dep/api;
+}
+",
+        )
+        .unwrap();
+
+        let dep = dir.path().join("dep");
+        fs::create_dir_all(&dep).unwrap();
+        fs::write(
+            dep.join("api.wit"),
+            "package foo:dep;
+
+interface api {
+    call: func();
+    @unstable(feature = fancy)
+    call-fancy: func();
+}
+",
+        )
+        .unwrap();
+
+        let generate = |features: &str| {
+            let section: ComponentSection =
+                toml_edit::de::from_str(&format!("bindings = {{ {features} }}")).unwrap();
+            let metadata =
+                ComponentMetadata::for_test("component", dir.path().join("Cargo.toml"), section);
+            let name: registry::PackageName = "foo:dep".parse().unwrap();
+            let resolution = PackageDependencyResolution {
+                metadata: &metadata,
+                target_resolutions: [(
+                    name.clone(),
+                    DependencyResolution::Local(LocalResolution {
+                        name,
+                        path: dep.clone(),
+                    }),
+                )]
+                .into_iter()
+                .collect(),
+                resolutions: Default::default(),
+            };
+
+            let (generator, _) = BindingsGenerator::new(&resolution).unwrap();
+            let functions: Vec<_> = generator
+                .resolve
+                .interfaces
+                .iter()
+                .flat_map(|(_, i)| i.functions.keys().cloned())
+                .collect();
+            let unknown: Vec<_> = generator
+                .unknown_features()
+                .unwrap()
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            (
+                names(&generator.resolve, generator.world),
+                functions,
+                unknown,
+            )
+        };
+
+        // The gated interface and function are generated only when enabled
+        let (names, functions, unknown) = generate("");
+        assert_eq!(names, ["foo:bar/stable", "foo:dep/api"]);
+        assert!(!functions.contains(&"call-fancy".to_string()));
+        assert!(unknown.is_empty());
+
+        let (names, functions, unknown) = generate("features = [\"fancy\", \"typo\"]");
+        assert_eq!(names, ["foo:bar/stable", "foo:bar/fancy", "foo:dep/api"]);
+        assert!(functions.contains(&"call-fancy".to_string()));
+        assert_eq!(unknown, ["typo"]);
+
+        let (names, ..) = generate("all_features = true");
+        assert_eq!(names, ["foo:bar/stable", "foo:bar/fancy", "foo:dep/api"]);
+    }
+
+    #[test]
+    fn it_selects_dependency_features() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        fs::write(&manifest_path, "").unwrap();
+        fs::create_dir_all(dir.path().join("wit")).unwrap();
+        fs::write(
+            dir.path().join("wit/world.wit"),
+            "package foo:bar;
+
+world example {
+    import foo:one/api;
+    import foo:two/api;
+}
+",
+        )
+        .unwrap();
+
+        for name in ["one", "two"] {
+            fs::write(
+                dir.path().join(format!("{name}.wit")),
+                format!(
+                    "package foo:{name};
+
+interface api {{
+    call: func();
+    @unstable(feature = fancy)
+    call-fancy: func();
+}}
+"
+                ),
+            )
+            .unwrap();
+        }
+
+        let generate = |features: serde_json::Value| {
+            let package: cargo_metadata::Package = serde_json::from_value(serde_json::json!({
+                "name": "component",
+                "version": "0.1.0",
+                "id": "component 0.1.0 (path+file:///component)",
+                "dependencies": [],
+                "targets": [],
+                "features": {},
+                "manifest_path": manifest_path,
+                "metadata": {}
+            }))
+            .unwrap();
+            let metadata = ComponentMetadata::from_manifest_and_component(
+                &package,
+                serde_json::json!({
+                    "target": {
+                        "path": "wit",
+                        "dependencies": {
+                            "foo:one": { "path": "one.wit", "features": features },
+                            "foo:two": { "path": "two.wit" }
+                        }
+                    }
+                }),
+            )
+            .unwrap();
+
+            let resolution = PackageDependencyResolution {
+                metadata: &metadata,
+                target_resolutions: ["one", "two"]
+                    .into_iter()
+                    .map(|name| {
+                        let name: registry::PackageName = format!("foo:{name}").parse().unwrap();
+                        let path = dir.path().join(format!("{}.wit", name.name()));
+                        (
+                            name.clone(),
+                            DependencyResolution::Local(LocalResolution { name, path }),
+                        )
+                    })
+                    .collect(),
+                resolutions: Default::default(),
+            };
+
+            BindingsGenerator::new(&resolution).map(|(generator, _)| {
+                generator
+                    .resolve
+                    .interfaces
+                    .iter()
+                    .map(|(_, i)| {
+                        let package = &generator.resolve.packages[i.package.unwrap()];
+                        (package.name.name.clone(), i.functions.len())
+                    })
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        // The feature is enabled for `foo:one` while `foo:two` keeps the default
+        assert_eq!(
+            generate(serde_json::json!(["fancy"])).unwrap(),
+            [("one".to_string(), 2), ("two".to_string(), 1)]
+        );
+        assert_eq!(
+            generate(serde_json::json!([])).unwrap(),
+            [("one".to_string(), 1), ("two".to_string(), 1)]
+        );
+
+        let err = generate(serde_json::json!(["typo"])).unwrap_err();
+        assert!(format!("{err:#}").contains(
+            "feature `typo` of dependency `foo:one` is not declared by the dependency; the declared features are: `fancy`"
+        ));
+    }
+
+    #[test]
+    fn it_writes_bindings_crates() {
+        let dir = tempfile::tempdir().unwrap();
+        let bindings = "// Generated by `wit-bindgen`\npub fn run() {}\n";
+        write_bindings_crate(
+            dir.path(),
+            "shared-bindings",
+            &Version::new(1, 2, 3),
+            false,
+            bindings,
+        )
+        .unwrap();
+
+        let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+        let manifest: toml_edit::DocumentMut = manifest.parse().unwrap();
+        assert_eq!(
+            manifest["package"]["name"].as_str(),
+            Some("shared-bindings")
+        );
+        assert_eq!(manifest["package"]["version"].as_str(), Some("1.2.3"));
+        assert!(manifest["dependencies"].get("wit-bindgen-rt").is_some());
+        assert!(manifest.get("features").is_none());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("src/lib.rs")).unwrap(),
+            bindings
+        );
+
+        write_bindings_crate(
+            dir.path(),
+            "shared-bindings",
+            &Version::new(1, 2, 3),
+            true,
+            bindings,
+        )
+        .unwrap();
+        let manifest = fs::read_to_string(dir.path().join("Cargo.toml")).unwrap();
+        let manifest: toml_edit::DocumentMut = manifest.parse().unwrap();
+        assert!(manifest["features"].get("std").is_some());
+    }
+
+    #[test]
+    fn it_does_not_overwrite_stubs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("src/component/stubs.rs");
+
+        // Parent directories are created for a new file
+        assert!(write_stubs(&path, false, || Ok("// generated\n".to_string())).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "// generated\n");
+
+        // An existing file is kept without generating the stubs
+        fs::write(&path, "// edited\n").unwrap();
+        assert!(!write_stubs(&path, false, || panic!("stubs should not be generated")).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "// edited\n");
+
+        // Unless forced
+        assert!(write_stubs(&path, true, || Ok("// regenerated\n".to_string())).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "// regenerated\n");
+    }
+
+    #[test]
+    fn it_lists_ambiguous_worlds() {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(
+                UnresolvedPackage::parse(
+                    Path::new("test.wit"),
+                    r#"package foo:bar@1.0.0;
+
+world first {}
+world second {}
+"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let message = BindingsGenerator::ambiguous_worlds(&resolve, pkg).unwrap();
+        assert!(message.contains("world = \"foo:bar/first@1.0.0\""));
+        assert!(message.contains("world = \"foo:bar/second@1.0.0\""));
+
+        let (resolve, world) = world("package foo:bar;\n\nworld example {}\n");
+        let pkg = resolve.worlds[world].package.unwrap();
+        assert!(BindingsGenerator::ambiguous_worlds(&resolve, pkg).is_none());
     }
 }
@@ -75,14 +75,17 @@ impl AddCommand {
         let PackageComponentMetadata { package, metadata }: PackageComponentMetadata<'_> =
             match &spec {
                 Some(spec) => {
-                    let pkgs = load_component_metadata(&metadata, std::iter::once(spec), false)?;
+                    let pkgs =
+                        load_component_metadata(&metadata, std::iter::once(spec), false, None)?;
                     assert!(pkgs.len() == 1, "one package should be present");
                     pkgs.into_iter().next().unwrap()
                 }
                 None => PackageComponentMetadata::new(
+                    &metadata,
                     metadata
                         .root_package()
                         .context("no root package found in metadata")?,
+                    None,
                 )?,
             };
 
@@ -140,6 +143,7 @@ impl AddCommand {
                 .unwrap_or(&VersionReq::STAR)
                 .clone(),
             registry: self.registry.clone(),
+            registries: Vec::new(),
         });
 
         resolver.add_dependency(name, &dependency).await?;
@@ -266,7 +270,7 @@ impl AddCommand {
                 Target::Package { .. } => {
                     bail!("cannot add dependency `{name}` to a registry package target")
                 }
-                Target::Local { dependencies, .. } => {
+                Target::Local { dependencies, .. } | Target::Archive { dependencies, .. } => {
                     if dependencies.contains_key(name) {
                         bail!("cannot add dependency `{name}` as it conflicts with an existing dependency");
                     }
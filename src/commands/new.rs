@@ -2,7 +2,9 @@ use crate::{config::Config, generator::SourceGenerator, metadata, metadata::DEFA
 use anyhow::{bail, Context, Result};
 use cargo_component_core::{
     command::CommonOptions,
-    registry::{Dependency, DependencyResolution, DependencyResolver, RegistryResolution},
+    registry::{
+        Dependency, DependencyResolution, DependencyResolver, RegistryRef, RegistryResolution,
+    },
 };
 use clap::Args;
 use heck::ToKebabCase;
@@ -14,7 +16,7 @@ use std::{
     path::{Path, PathBuf},
     process::Command,
 };
-use toml_edit::{table, value, DocumentMut, Item, Table, Value};
+use toml_edit::{table, value, DocumentMut, InlineTable, Item, Table, Value};
 use url::Url;
 
 const WIT_BINDGEN_RT_CRATE: &str = "wit-bindgen-rt";
@@ -222,7 +224,7 @@ impl NewCommand {
         config: &Config,
         name: &PackageName,
         out_dir: &Path,
-        registries: &HashMap<String, Url>,
+        registries: &HashMap<String, RegistryRef>,
         target: &Option<(RegistryResolution, Option<String>)>,
     ) -> Result<()> {
         let manifest_path = out_dir.join("Cargo.toml");
@@ -275,8 +277,27 @@ impl NewCommand {
 
         if !registries.is_empty() {
             let mut table = Table::new();
-            for (name, url) in registries {
-                table[name] = value(url.as_str());
+            for (name, registry) in registries {
+                table[name] = match registry {
+                    RegistryRef::Warg {
+                        url,
+                        protocol: None,
+                    } => value(url.as_str()),
+                    RegistryRef::Warg {
+                        url,
+                        protocol: Some(protocol),
+                    } => {
+                        let mut entry = InlineTable::new();
+                        entry.insert("warg", url.as_str().into());
+                        entry.insert("protocol", i64::from(*protocol).into());
+                        value(entry)
+                    }
+                    RegistryRef::Oci(namespace) => {
+                        let mut entry = InlineTable::new();
+                        entry.insert("oci", namespace.as_str().into());
+                        value(entry)
+                    }
+                };
             }
             component["registries"] = Item::Table(table);
         }
@@ -512,7 +533,7 @@ world example {{
     async fn resolve_target(
         &self,
         config: &Config,
-        registries: &HashMap<String, Url>,
+        registries: &HashMap<String, RegistryRef>,
         target: Option<metadata::Target>,
         network_allowed: bool,
     ) -> Result<Option<(RegistryResolution, Option<String>)>> {
@@ -549,13 +570,13 @@ world example {{
         }
     }
 
-    fn registries(&self) -> Result<HashMap<String, Url>> {
+    fn registries(&self) -> Result<HashMap<String, RegistryRef>> {
         let mut registries = HashMap::new();
 
         if let Some(url) = self.registry.as_deref() {
             registries.insert(
                 "default".to_string(),
-                url.parse().context("failed to parse registry URL")?,
+                RegistryRef::from(url.parse::<Url>().context("failed to parse registry URL")?),
             );
         }
 
@@ -4,7 +4,10 @@ use crate::{
     PublishOptions,
 };
 use anyhow::{bail, Context, Result};
-use cargo_component_core::{command::CommonOptions, registry::find_url};
+use cargo_component_core::{
+    command::CommonOptions,
+    registry::{expand_url, find_protocol, find_url, DEFAULT_REGISTRY_NAME},
+};
 use clap::Args;
 use std::path::PathBuf;
 use warg_credentials::keyring::get_signing_key;
@@ -93,7 +96,7 @@ impl PublishCommand {
             Some(spec) => Some(spec.clone()),
             None => CargoPackageSpec::find_current_package_spec(&metadata),
         };
-        let packages = [PackageComponentMetadata::new(if let Some(spec) = &spec {
+        let package = if let Some(spec) = &spec {
             metadata
                 .packages
                 .iter()
@@ -111,24 +114,31 @@ impl PublishCommand {
             metadata
                 .root_package()
                 .context("no root package found in manifest")?
-        })?];
+        };
+        let packages = [PackageComponentMetadata::new(
+            &metadata,
+            package,
+            Some("release"),
+        )?];
 
         let package = packages[0].package;
         let component_metadata = &packages[0].metadata;
 
-        let name = component_metadata.section.package.as_ref().with_context(|| {
-            format!(
-                "package `{name}` is missing a `package.metadata.component.package` setting in manifest `{path}`",
-                name = package.name,
-                path = package.manifest_path
-            )
-        })?;
+        let name = component_metadata.publish_name()?;
+        let name = name.as_ref();
 
         let registry_url = find_url(
             self.registry.as_deref(),
             &component_metadata.section.registries,
             config.warg().home_url.as_deref(),
         )?;
+        let registry_url =
+            expand_url(&registry_url, name, &component_metadata.version.to_string())?;
+
+        component_metadata.section.check_registry_url(
+            self.registry.as_deref().unwrap_or(DEFAULT_REGISTRY_NAME),
+            &registry_url,
+        )?;
 
         let signing_key = if let Ok(key) = std::env::var("CARGO_COMPONENT_PUBLISH_KEY") {
             PrivateKey::decode(key).context("failed to parse signing key from `CARGO_COMPONENT_PUBLISH_KEY` environment variable")?
@@ -151,9 +161,13 @@ impl PublishCommand {
             frozen: self.frozen,
             locked: self.locked,
             release: true,
+            profile: None,
             offline: self.offline,
             workspace: false,
             packages: self.cargo_package.clone().into_iter().collect(),
+            require_pinned_targets: false,
+            force: false,
+            prefer_cached: false,
         };
 
         let spawn_args = self.build_args()?;
@@ -175,7 +189,11 @@ impl PublishCommand {
 
         let options = PublishOptions {
             package,
-            registry_url,
+            registry_url: &registry_url,
+            registry_protocol: find_protocol(
+                self.registry.as_deref(),
+                &component_metadata.section.registries,
+            ),
             init: self.init,
             name,
             version: &component_metadata.version,
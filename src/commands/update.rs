@@ -39,7 +39,7 @@ impl UpdateCommand {
         log::debug!("executing update command");
         let config = Config::new(self.common.new_terminal())?;
         let metadata = load_metadata(self.manifest_path.as_deref())?;
-        let packages = load_component_metadata(&metadata, [].iter(), true)?;
+        let packages = load_component_metadata(&metadata, [].iter(), true, None)?;
 
         let network_allowed = !self.frozen && !self.offline;
         let lock_update_allowed = !self.frozen && !self.locked;
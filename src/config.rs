@@ -18,8 +18,12 @@
 //! that `cargo` supports which are necessary for `cargo-component`
 //! to function.
 
+use crate::diagnostics::{Diagnostic, Diagnostics};
 use anyhow::{anyhow, bail, Context, Result};
-use cargo_component_core::terminal::{Color, Terminal};
+use cargo_component_core::{
+    registry::FrozenMode,
+    terminal::{Color, Terminal},
+};
 use cargo_metadata::Metadata;
 use parse_arg::{iter_short, match_arg};
 use semver::Version;
@@ -382,20 +386,62 @@ pub struct CargoArguments {
     pub locked: bool,
     /// The --release argument.
     pub release: bool,
+    /// The --profile argument.
+    pub profile: Option<String>,
     /// The --offline argument.
     pub offline: bool,
     /// The --workspace argument.
     pub workspace: bool,
     /// The --package argument.
     pub packages: Vec<CargoPackageSpec>,
+    /// The --require-pinned-targets argument.
+    ///
+    /// This argument is specific to cargo-component and is not passed to cargo.
+    pub require_pinned_targets: bool,
+    /// The --force argument.
+    ///
+    /// This argument is specific to cargo-component and is not passed to cargo;
+    /// it allows overwriting an existing `stub_output` file.
+    pub force: bool,
+    /// The --prefer-cached argument.
+    ///
+    /// This argument is specific to cargo-component and is not passed to cargo;
+    /// with it, cached package versions are preferred over newer ones.
+    pub prefer_cached: bool,
 }
 
 impl CargoArguments {
+    /// The arguments specific to cargo-component that are not passed to cargo.
+    pub const COMPONENT_FLAGS: &'static [&'static str] =
+        &["--require-pinned-targets", "--force", "--prefer-cached"];
+
     /// Determines if network access is allowed based on the configuration.
     pub fn network_allowed(&self) -> bool {
         !self.frozen && !self.offline
     }
 
+    /// Gets the mode in which dependencies are resolved.
+    pub fn frozen_mode(&self) -> FrozenMode {
+        if self.frozen {
+            FrozenMode::Frozen
+        } else if self.offline {
+            FrozenMode::Offline
+        } else {
+            FrozenMode::Normal
+        }
+    }
+
+    /// Gets the name of the build profile in use.
+    ///
+    /// Defaults to `release` when `--release` is given, otherwise `dev`.
+    pub fn profile(&self) -> &str {
+        match &self.profile {
+            Some(profile) => profile,
+            None if self.release => "release",
+            None => "dev",
+        }
+    }
+
     /// Determines if an update to the lock file is allowed based on the configuration.
     pub fn lock_update_allowed(&self) -> bool {
         !self.frozen && !self.locked
@@ -415,6 +461,7 @@ impl CargoArguments {
             .single("--color", "WHEN", Some('c'))
             .single("--manifest-path", "PATH", None)
             .single("--message-format", "FMT", None)
+            .single("--profile", "PROFILE-NAME", None)
             .multiple("--package", "SPEC", Some('p'))
             .multiple("--target", "TRIPLE", None)
             .flag("--release", Some('r'))
@@ -423,6 +470,9 @@ impl CargoArguments {
             .flag("--offline", None)
             .flag("--all", None)
             .flag("--workspace", None)
+            .flag("--require-pinned-targets", None)
+            .flag("--force", None)
+            .flag("--prefer-cached", None)
             .counting("--verbose", Some('v'))
             .flag("--quiet", Some('q'))
             .flag("--help", Some('h'));
@@ -469,6 +519,7 @@ impl CargoArguments {
             locked: args.get("--locked").unwrap().count() > 0,
             offline: args.get("--offline").unwrap().count() > 0,
             release: args.get("--release").unwrap().count() > 0,
+            profile: args.get_mut("--profile").unwrap().take_single(),
             workspace: args.get("--workspace").unwrap().count() > 0
                 || args.get("--all").unwrap().count() > 0,
             packages: args
@@ -478,6 +529,9 @@ impl CargoArguments {
                 .into_iter()
                 .map(CargoPackageSpec::new)
                 .collect::<Result<_>>()?,
+            require_pinned_targets: args.get("--require-pinned-targets").unwrap().count() > 0,
+            force: args.get("--force").unwrap().count() > 0,
+            prefer_cached: args.get("--prefer-cached").unwrap().count() > 0,
         })
     }
 }
@@ -491,6 +545,10 @@ pub struct Config {
     pub warg: warg_client::Config,
     /// The terminal to use.
     terminal: Terminal,
+    /// The diagnostics reported so far.
+    diagnostics: Diagnostics,
+    /// Whether dependency resolution prefers cached package versions.
+    prefer_cached: bool,
 }
 
 impl Config {
@@ -499,9 +557,23 @@ impl Config {
         Ok(Self {
             warg: warg_client::Config::from_default_file()?.unwrap_or_default(),
             terminal,
+            diagnostics: Default::default(),
+            prefer_cached: false,
         })
     }
 
+    /// Sets whether dependency resolution prefers cached package versions
+    /// over newer versions in the registry.
+    pub fn with_prefer_cached(mut self, prefer_cached: bool) -> Self {
+        self.prefer_cached = prefer_cached;
+        self
+    }
+
+    /// Determines if dependency resolution prefers cached package versions.
+    pub fn prefer_cached(&self) -> bool {
+        self.prefer_cached
+    }
+
     /// Gets the warg client configuration.
     pub fn warg(&self) -> &warg_client::Config {
         &self.warg
@@ -511,6 +583,16 @@ impl Config {
     pub fn terminal(&self) -> &Terminal {
         &self.terminal
     }
+
+    /// Reports a diagnostic, printing it to the terminal.
+    pub fn report(&self, diagnostic: Diagnostic) -> Result<()> {
+        self.diagnostics.report(&self.terminal, diagnostic)
+    }
+
+    /// Gets the diagnostics reported so far.
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
 }
 
 #[cfg(test)]
@@ -785,11 +867,15 @@ mod test {
                 manifest_path: None,
                 message_format: None,
                 release: false,
+                profile: None,
                 frozen: false,
                 locked: false,
                 offline: false,
                 workspace: true,
                 packages: Vec::new(),
+                require_pinned_targets: false,
+                force: false,
+                prefer_cached: false,
             }
         );
 
@@ -805,6 +891,8 @@ mod test {
                 "--message-format",
                 "json-render-diagnostics",
                 "--release",
+                "--profile",
+                "custom",
                 "--package",
                 "package1",
                 "-p=package2@1.1.1",
@@ -816,6 +904,9 @@ mod test {
                 "--locked",
                 "--offline",
                 "--all",
+                "--require-pinned-targets",
+                "--force",
+                "--prefer-cached",
                 "--not-an-option",
             ]
             .into_iter(),
@@ -832,6 +923,7 @@ mod test {
                 manifest_path: Some("Cargo.toml".into()),
                 message_format: Some("json-render-diagnostics".into()),
                 release: true,
+                profile: Some("custom".to_string()),
                 frozen: true,
                 locked: true,
                 offline: true,
@@ -846,6 +938,9 @@ mod test {
                         version: Some(Version::parse("1.1.1").unwrap())
                     }
                 ],
+                require_pinned_targets: true,
+                force: true,
+                prefer_cached: true,
             }
         );
     }
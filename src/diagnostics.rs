@@ -0,0 +1,217 @@
+//! Module for collecting diagnostics to report to the user.
+//!
+//! Diagnostics are printed to the terminal as they are reported and are
+//! also collected so that they may be emitted as JSON for editors when a
+//! JSON `--message-format` is requested.
+
+use anyhow::Result;
+use cargo_component_core::terminal::Terminal;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    cell::RefCell,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::ImDocument;
+
+/// The severity of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The diagnostic is a warning.
+    Warning,
+    /// The diagnostic is an informational note.
+    Note,
+}
+
+/// The location of a setting in a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManifestSpan {
+    /// The path to the manifest.
+    pub path: PathBuf,
+    /// The 1-based line of the start of the setting.
+    pub line: usize,
+    /// The 1-based column of the start of the setting.
+    pub column: usize,
+    /// The byte offset of the start of the setting.
+    pub start: usize,
+    /// The byte offset of the end of the setting.
+    pub end: usize,
+}
+
+impl ManifestSpan {
+    /// Finds the value of the setting at the given path of keys (e.g.
+    /// `["package", "metadata", "component"]`) in the given manifest.
+    ///
+    /// Returns `None` if the manifest cannot be read or parsed, or if the
+    /// setting is not present.
+    pub fn find(path: &Path, keys: &[&str]) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+        let document = ImDocument::parse(contents.as_str()).ok()?;
+        let (first, rest) = keys.split_first()?;
+        let mut item = document.get(first)?;
+        for key in rest {
+            item = item.get(key)?;
+        }
+
+        let span = item.span()?;
+        let before = &contents[..span.start];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+
+        Some(Self {
+            path: path.to_path_buf(),
+            line,
+            column,
+            start: span.start,
+            end: span.end,
+        })
+    }
+}
+
+/// A diagnostic reported by a command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// The code identifying the kind of diagnostic (e.g. `unmatched-skip`).
+    pub code: &'static str,
+    /// The severity of the diagnostic.
+    pub severity: Severity,
+    /// The message of the diagnostic.
+    pub message: String,
+    /// The location in a manifest the diagnostic refers to, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<ManifestSpan>,
+}
+
+impl Diagnostic {
+    /// Creates a new warning diagnostic with the given code and message.
+    pub fn warning(code: &'static str, message: impl fmt::Display) -> Self {
+        Self {
+            code,
+            severity: Severity::Warning,
+            message: message.to_string(),
+            span: None,
+        }
+    }
+
+    /// Creates a new note diagnostic with the given code and message.
+    pub fn note(code: &'static str, message: impl fmt::Display) -> Self {
+        Self {
+            code,
+            severity: Severity::Note,
+            message: message.to_string(),
+            span: None,
+        }
+    }
+
+    /// Sets the location in a manifest the diagnostic refers to.
+    pub fn with_span(mut self, span: Option<ManifestSpan>) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+/// A collection of reported diagnostics.
+#[derive(Debug, Default)]
+pub struct Diagnostics(RefCell<Vec<Diagnostic>>);
+
+impl Diagnostics {
+    /// Reports a diagnostic, printing it to the given terminal.
+    pub fn report(&self, terminal: &Terminal, diagnostic: Diagnostic) -> Result<()> {
+        match diagnostic.severity {
+            Severity::Warning => terminal.warn(&diagnostic.message)?,
+            Severity::Note => terminal.note(&diagnostic.message)?,
+        }
+
+        self.0.borrow_mut().push(diagnostic);
+        Ok(())
+    }
+
+    /// Gets the diagnostics reported so far, in the order they were reported.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.0.borrow().clone()
+    }
+
+    /// Serializes the diagnostics reported so far as a JSON array.
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(&*self.0.borrow()).expect("diagnostics should serialize")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cargo_component_core::terminal::{Color, Verbosity};
+    use serde_json::json;
+
+    #[test]
+    fn it_serializes_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        fs::write(
+            &manifest,
+            "[package]\nname = \"foo\"\n\n[package.metadata.component.bindings]\nskip = [\"interface:missing\"]\n",
+        )
+        .unwrap();
+
+        let diagnostics = Diagnostics::default();
+        let terminal = Terminal::new(Verbosity::Quiet, Color::Never);
+        diagnostics
+            .report(
+                &terminal,
+                Diagnostic::warning(
+                    "unmatched-skip",
+                    "bindings `skip` entry `interface:missing` does not match any interface",
+                )
+                .with_span(ManifestSpan::find(
+                    &manifest,
+                    &["package", "metadata", "component", "bindings", "skip"],
+                )),
+            )
+            .unwrap();
+        diagnostics
+            .report(
+                &terminal,
+                Diagnostic::warning("unknown-derive", "derive `Hsah` is not commonly used"),
+            )
+            .unwrap();
+        diagnostics
+            .report(
+                &terminal,
+                Diagnostic::note(
+                    "auto-adapter",
+                    "automatically selected the built-in reactor adapter based on the target world",
+                ),
+            )
+            .unwrap();
+
+        assert_eq!(
+            diagnostics.to_json(),
+            json!([
+                {
+                    "code": "unmatched-skip",
+                    "severity": "warning",
+                    "message": "bindings `skip` entry `interface:missing` does not match any interface",
+                    "span": {
+                        "path": manifest,
+                        "line": 5,
+                        "column": 8,
+                        "start": 69,
+                        "end": 90,
+                    },
+                },
+                {
+                    "code": "unknown-derive",
+                    "severity": "warning",
+                    "message": "derive `Hsah` is not commonly used",
+                },
+                {
+                    "code": "auto-adapter",
+                    "severity": "note",
+                    "message": "automatically selected the built-in reactor adapter based on the target world",
+                },
+            ])
+        );
+    }
+}
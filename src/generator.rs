@@ -702,61 +702,12 @@ impl<'a> SourceGenerator<'a> {
     /// Generates the Rust source code for the given world.
     pub fn generate(&self, world: Option<&str>) -> Result<String> {
         let (resolve, world) = self.decode(world)?;
-        let mut names = ReservedNames::default();
-        let generator = ImplementationGenerator::new(&resolve, &resolve.worlds[world], &mut names);
-
-        let mut trie = UseTrie::default();
-        trie.reserve_names(&names);
-
-        let impls = generator.generate(&mut trie)?;
-
-        let mut source = String::new();
-        writeln!(&mut source, "#[allow(warnings)]\nmod bindings;")?;
-        writeln!(&mut source)?;
-        write!(
-            &mut source,
-            "{trie}{nl}",
-            nl = if trie.is_empty() { "" } else { "\n" }
-        )?;
-
-        writeln!(&mut source, "struct {IMPLEMENTER};\n")?;
-
-        for (i, imp) in impls.iter().enumerate() {
-            if i > 0 {
-                source.push('\n');
-            }
-
-            source.push_str(imp);
-        }
-
-        writeln!(
-            &mut source,
-            "\nbindings::export!({IMPLEMENTER} with_types_in bindings);"
-        )?;
-
-        if self.format {
-            let mut child = Command::new("rustfmt")
-                .arg("--edition=2018")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()
-                .context("failed to spawn `rustfmt`")?;
-            std::io::Write::write_all(&mut child.stdin.take().unwrap(), source.as_bytes())
-                .context("failed to write to `rustfmt`")?;
-            source.truncate(0);
-            child
-                .stdout
-                .take()
-                .unwrap()
-                .read_to_string(&mut source)
-                .context("failed to write to `rustfmt`")?;
-            let status = child.wait().context("failed to wait for `rustfmt`")?;
-            if !status.success() {
-                bail!("execution of `rustfmt` returned a non-zero exit code {status}");
-            }
-        }
-
-        Ok(source)
+        implementation_source(
+            &resolve,
+            world,
+            "#[allow(warnings)]\nmod bindings;",
+            self.format,
+        )
     }
 
     fn decode(&self, world: Option<&str>) -> Result<(Resolve, WorldId)> {
@@ -790,3 +741,78 @@ impl<'a> SourceGenerator<'a> {
         }
     }
 }
+
+/// Generates stub implementations of the export traits of the given world.
+///
+/// The stubs are intended for a module of the component crate other than
+/// the crate root, so the bindings are referred to as `crate::bindings`.
+///
+/// If `format` is true, then `rustfmt` will be run on the generated source.
+pub fn generate_stubs(resolve: &Resolve, world: WorldId, format: bool) -> Result<String> {
+    implementation_source(resolve, world, "use crate::bindings;", format)
+}
+
+/// Generates the Rust source code implementing the export traits of the
+/// given world, starting with the given header.
+fn implementation_source(
+    resolve: &Resolve,
+    world: WorldId,
+    header: &str,
+    format: bool,
+) -> Result<String> {
+    let mut names = ReservedNames::default();
+    let generator = ImplementationGenerator::new(resolve, &resolve.worlds[world], &mut names);
+
+    let mut trie = UseTrie::default();
+    trie.reserve_names(&names);
+
+    let impls = generator.generate(&mut trie)?;
+
+    let mut source = String::new();
+    writeln!(&mut source, "{header}")?;
+    writeln!(&mut source)?;
+    write!(
+        &mut source,
+        "{trie}{nl}",
+        nl = if trie.is_empty() { "" } else { "\n" }
+    )?;
+
+    writeln!(&mut source, "struct {IMPLEMENTER};\n")?;
+
+    for (i, imp) in impls.iter().enumerate() {
+        if i > 0 {
+            source.push('\n');
+        }
+
+        source.push_str(imp);
+    }
+
+    writeln!(
+        &mut source,
+        "\nbindings::export!({IMPLEMENTER} with_types_in bindings);"
+    )?;
+
+    if format {
+        let mut child = Command::new("rustfmt")
+            .arg("--edition=2018")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("failed to spawn `rustfmt`")?;
+        std::io::Write::write_all(&mut child.stdin.take().unwrap(), source.as_bytes())
+            .context("failed to write to `rustfmt`")?;
+        source.truncate(0);
+        child
+            .stdout
+            .take()
+            .unwrap()
+            .read_to_string(&mut source)
+            .context("failed to write to `rustfmt`")?;
+        let status = child.wait().context("failed to wait for `rustfmt`")?;
+        if !status.success() {
+            bail!("execution of `rustfmt` returned a non-zero exit code {status}");
+        }
+    }
+
+    Ok(source)
+}
@@ -8,14 +8,16 @@ use bindings::BindingsGenerator;
 use bytes::Bytes;
 use cargo_component_core::{
     lock::{LockFile, LockFileResolver, LockedPackage, LockedPackageVersion},
-    registry::create_client,
+    registry::{create_client, FrozenMode},
     terminal::Colors,
 };
 use cargo_config2::{PathAndArgs, TargetTripleRef};
 use cargo_metadata::{Artifact, Message, Metadata, MetadataCommand, Package};
 use config::{CargoArguments, CargoPackageSpec, Config};
+use diagnostics::{Diagnostic, ManifestSpan};
 use lock::{acquire_lock_file_ro, acquire_lock_file_rw};
-use metadata::ComponentMetadata;
+use metadata::{AdapterKind, ComponentMetadata, ADAPTER_ENV_VAR, TARGET_PATH_ENV_VAR};
+use proc_macro2::TokenStream;
 use registry::{PackageDependencyResolution, PackageResolutionMap};
 use semver::Version;
 use shell_escape::escape;
@@ -36,16 +38,18 @@ use warg_crypto::signing::PrivateKey;
 use warg_protocol::registry::PackageName;
 use wasm_metadata::{Link, LinkType, RegistryMetadata};
 use wasmparser::{Parser, Payload};
-use wit_component::ComponentEncoder;
+use wit_component::{ComponentEncoder, Linker};
 
 mod bindings;
 pub mod commands;
 pub mod config;
+pub mod diagnostics;
 mod generator;
 mod lock;
-mod metadata;
-mod registry;
+pub mod metadata;
+pub mod registry;
 mod target;
+mod wit_deps;
 
 fn is_wasm_target(target: &str) -> bool {
     target == "wasm32-wasi" || target == "wasm32-unknown-unknown"
@@ -61,11 +65,25 @@ pub struct PackageComponentMetadata<'a> {
 }
 
 impl<'a> PackageComponentMetadata<'a> {
-    /// Creates a new package metadata from the given package.
-    pub fn new(package: &'a Package) -> Result<Self> {
+    /// Creates a new package metadata from the given package of the
+    /// workspace described by the given cargo metadata.
+    ///
+    /// The profile selects any profile-specific target of the package.
+    pub fn new(metadata: &Metadata, package: &'a Package, profile: Option<&str>) -> Result<Self> {
         Ok(Self {
             package,
-            metadata: ComponentMetadata::from_package(package)?,
+            metadata: ComponentMetadata::from_workspace_package(
+                package,
+                &metadata.workspace_metadata,
+                metadata.workspace_root.as_std_path(),
+                profile,
+            )
+            .with_context(|| {
+                format!(
+                    "failed to load component metadata of package `{name}`",
+                    name = package.name
+                )
+            })?,
         })
     }
 }
@@ -180,7 +198,7 @@ pub async fn run_cargo_command(
             }
         }
     }
-    cargo.args(args);
+    cargo.args(args.filter(|a| !CargoArguments::COMPONENT_FLAGS.contains(&a.as_str())));
 
     // TODO: consider targets from .cargo/config.toml
 
@@ -243,8 +261,25 @@ pub async fn run_cargo_command(
         packages,
         &import_name_map,
         command,
+        cargo_args,
         output_args,
-    )?;
+    )
+    .await?;
+
+    // Emit the reported diagnostics along with cargo's JSON messages
+    if cargo_args
+        .message_format
+        .as_deref()
+        .is_some_and(|format| format.starts_with("json"))
+    {
+        println!(
+            "{message}",
+            message = serde_json::json!({
+                "reason": "component-diagnostics",
+                "diagnostics": config.diagnostics().to_json(),
+            })
+        );
+    }
 
     if let Some(runner) = runner {
         spawn_outputs(config, &runner, output_args, &outputs, command)?;
@@ -374,13 +409,15 @@ struct Output {
     display: Option<String>,
 }
 
-fn componentize_artifacts(
+#[allow(clippy::too_many_arguments)]
+async fn componentize_artifacts(
     config: &Config,
     cargo_metadata: &Metadata,
     artifacts: &[Artifact],
     packages: &[PackageComponentMetadata<'_>],
     import_name_map: &HashMap<String, HashMap<String, String>>,
     command: CargoCommand,
+    cargo_args: &CargoArguments,
     output_args: &[String],
 ) -> Result<Vec<Output>> {
     let mut outputs = Vec::new();
@@ -388,7 +425,22 @@ fn componentize_artifacts(
         env::current_dir().with_context(|| "couldn't get the current directory of the process")?;
 
     // Acquire the lock file to ensure any other cargo-component process waits for this to complete
-    let _file_lock = acquire_lock_file_ro(config.terminal(), cargo_metadata)?;
+    let file_lock = acquire_lock_file_ro(config.terminal(), cargo_metadata)?;
+    let lock_file = if packages.iter().any(|p| p.metadata.section.verify_world) {
+        file_lock
+            .as_ref()
+            .map(|f| {
+                LockFile::read(f.file()).with_context(|| {
+                    format!(
+                        "failed to read lock file `{path}`",
+                        path = f.path().display()
+                    )
+                })
+            })
+            .transpose()?
+    } else {
+        None
+    };
 
     for artifact in artifacts {
         for path in artifact
@@ -423,6 +475,22 @@ fn componentize_artifacts(
                         &cwd,
                         &bytes,
                     )?;
+
+                    if metadata.section.verify_world {
+                        let component = fs::read(path)
+                            .with_context(|| format!("failed to read output component `{path}`"))?;
+                        metadata
+                            .verify_component_world(
+                                config,
+                                lock_file.as_ref().map(LockFileResolver::new),
+                                cargo_args.network_allowed(),
+                                &component,
+                            )
+                            .await
+                            .with_context(|| {
+                                format!("failed to verify output component `{path}`")
+                            })?;
+                    }
                 }
                 ArtifactKind::Component => {
                     log::debug!("output file `{path}` is already a WebAssembly component");
@@ -677,10 +745,13 @@ pub fn load_metadata(manifest_path: Option<&Path>) -> Result<Metadata> {
 /// Loads the component metadata for the given package specs.
 ///
 /// If `workspace` is true, all workspace packages are loaded.
+///
+/// The profile selects any profile-specific targets of the packages.
 pub fn load_component_metadata<'a>(
     metadata: &'a Metadata,
     specs: impl ExactSizeIterator<Item = &'a CargoPackageSpec>,
     workspace: bool,
+    profile: Option<&str>,
 ) -> Result<Vec<PackageComponentMetadata<'a>>> {
     let pkgs = if workspace {
         metadata.workspace_packages()
@@ -709,7 +780,7 @@ pub fn load_component_metadata<'a>(
     };
 
     pkgs.into_iter()
-        .map(PackageComponentMetadata::new)
+        .map(|p| PackageComponentMetadata::new(metadata, p, profile))
         .collect::<Result<_>>()
 }
 
@@ -736,21 +807,113 @@ async fn generate_bindings(
     let cwd =
         env::current_dir().with_context(|| "couldn't get the current directory of the process")?;
 
+    if cargo_args.require_pinned_targets {
+        for PackageComponentMetadata { metadata, .. } in packages {
+            metadata.check_pinned_versions()?;
+        }
+    }
+
     let resolver = lock_file.as_ref().map(LockFileResolver::new);
     let resolution_map =
-        create_resolution_map(config, packages, resolver, cargo_args.network_allowed()).await?;
+        create_resolution_map(config, packages, resolver, cargo_args.frozen_mode()).await?;
+
+    // Conditional bindings settings are evaluated against the wasm target
+    // being built, which is `wasm32-wasi` unless another is given
+    let target_cfg = if packages
+        .iter()
+        .any(|p| !p.metadata.section.bindings.cfg.is_empty())
+    {
+        let target = cargo_args
+            .targets
+            .iter()
+            .find(|t| is_wasm_target(t))
+            .map(String::as_str)
+            .unwrap_or("wasm32-wasi");
+        Some(target::target_cfg(target)?)
+    } else {
+        None
+    };
+
     let mut import_name_map = HashMap::new();
-    for PackageComponentMetadata { package, .. } in packages {
+    for PackageComponentMetadata {
+        package,
+        metadata: component_metadata,
+    } in packages
+    {
+        // The proxy adapter is only suitable for components targeting the proxy world
+        let section = &component_metadata.section;
+        if section.proxy.is_enabled()
+            && component_metadata.adapter_path().is_none()
+            && !component_metadata
+                .uses_proxy_world(config, resolver, cargo_args.network_allowed())
+                .await?
+        {
+            config.report(
+                Diagnostic::warning(
+                    "proxy-world-mismatch",
+                    format!(
+                        "package `{name}` enables the `proxy` setting but does not target the `wasi:http/proxy` world",
+                        name = package.name
+                    ),
+                )
+                .with_span(ManifestSpan::find(
+                    package.manifest_path.as_std_path(),
+                    &["package", "metadata", "component", "proxy"],
+                )),
+            )?;
+        }
+
+        if let Some(dir) = component_metadata.target_path_override()? {
+            config.report(Diagnostic::warning(
+                "target-path-override",
+                format!(
+                    "using target directory `{dir}` from the `{TARGET_PATH_ENV_VAR}` environment variable in place of the registry package target of package `{name}`",
+                    dir = dir.display(),
+                    name = package.name
+                ),
+            ))?;
+        }
+
         let resolution = resolution_map.get(&package.id).expect("missing resolution");
         import_name_map.insert(
             package.name.clone(),
-            generate_package_bindings(config, resolution, last_modified_exe, &cwd).await?,
+            generate_package_bindings(
+                config,
+                resolution,
+                target_cfg.as_deref(),
+                last_modified_exe,
+                &cwd,
+                cargo_args.force,
+            )
+            .await?,
         );
     }
 
+    let new_lock_file = resolution_map.to_lock_file()?;
+    if let Some(lock_file) = &lock_file {
+        for name in new_lock_file.changed_local_packages(lock_file) {
+            if !cargo_args.lock_update_allowed() {
+                let flag = if cargo_args.locked {
+                    "--locked"
+                } else {
+                    "--frozen"
+                };
+                bail!(
+                    "local dependency `{name}` has changed since the lock file was written \
+                     but {flag} was passed to prevent updating the lock file"
+                );
+            }
+
+            config.terminal().warn(format!(
+                "local dependency `{name}` has changed since the lock file was written"
+            ))?;
+        }
+    }
+
     // Update the lock file if it exists or if the new lock file is non-empty
-    let new_lock_file = resolution_map.to_lock_file();
-    if (lock_file.is_some() || !new_lock_file.packages.is_empty())
+    if (lock_file.is_some()
+        || !new_lock_file.packages.is_empty()
+        || !new_lock_file.local_packages.is_empty())
         && Some(&new_lock_file) != lock_file.as_ref()
     {
         drop(file_lock);
@@ -777,13 +940,13 @@ async fn create_resolution_map<'a>(
     config: &Config,
     packages: &'a [PackageComponentMetadata<'_>],
     lock_file: Option<LockFileResolver<'_>>,
-    network_allowed: bool,
+    mode: FrozenMode,
 ) -> Result<PackageResolutionMap<'a>> {
     let mut map = PackageResolutionMap::default();
 
     for PackageComponentMetadata { package, metadata } in packages {
         let resolution =
-            PackageDependencyResolution::new(config, metadata, lock_file, network_allowed).await?;
+            PackageDependencyResolution::new(config, metadata, lock_file, mode).await?;
 
         map.insert(package.id.clone(), resolution);
     }
@@ -791,13 +954,51 @@ async fn create_resolution_map<'a>(
     Ok(map)
 }
 
+/// Generates the bindings for the given package as a token stream.
+///
+/// No bindings file is written; this is intended for build scripts and
+/// procedural macros that embed the bindings directly.
+pub async fn generate_bindings_tokens(
+    config: &Config,
+    metadata: &Metadata,
+    package: &PackageComponentMetadata<'_>,
+    network_allowed: bool,
+) -> Result<TokenStream> {
+    let file_lock = acquire_lock_file_ro(config.terminal(), metadata)?;
+    let lock_file = file_lock
+        .as_ref()
+        .map(|f| {
+            LockFile::read(f.file()).with_context(|| {
+                format!(
+                    "failed to read lock file `{path}`",
+                    path = f.path().display()
+                )
+            })
+        })
+        .transpose()?;
+
+    let resolver = lock_file.as_ref().map(LockFileResolver::new);
+    let resolution = PackageDependencyResolution::new(
+        config,
+        &package.metadata,
+        resolver,
+        FrozenMode::from_network_allowed(network_allowed),
+    )
+    .await?;
+
+    let (generator, _) = BindingsGenerator::new(&resolution)?;
+    generator.generate_tokens()
+}
+
 async fn generate_package_bindings(
     config: &Config,
     resolution: &PackageDependencyResolution<'_>,
+    target_cfg: Option<&[(String, Option<String>)]>,
     last_modified_exe: SystemTime,
     cwd: &Path,
+    force_stubs: bool,
 ) -> Result<HashMap<String, String>> {
-    if !resolution.metadata.section_present && resolution.metadata.target_path().is_none() {
+    if !resolution.metadata.section_present && resolution.metadata.target_path()?.is_none() {
         log::debug!(
             "skipping generating bindings for package `{name}`",
             name = resolution.metadata.name
@@ -806,13 +1007,17 @@ async fn generate_package_bindings(
     }
 
     // TODO: make the output path configurable
-    let output_dir = resolution
-        .metadata
-        .manifest_path
-        .parent()
-        .unwrap()
-        .join("src");
-    let bindings_path = output_dir.join("bindings.rs");
+    let section = &resolution.metadata.section;
+    let (output_dir, bindings_path) = match &section.bindings_crate_path {
+        Some(path) if section.bindings_crate.is_some() => {
+            (path.clone(), path.join("src").join("lib.rs"))
+        }
+        _ => {
+            let output_dir = resolution.metadata.manifest_dir()?.join("src");
+            let bindings_path = output_dir.join("bindings.rs");
+            (output_dir, bindings_path)
+        }
+    };
 
     let last_modified_output = bindings_path
         .is_file()
@@ -820,8 +1025,49 @@ async fn generate_package_bindings(
         .transpose()?;
 
     let (generator, import_name_map) = BindingsGenerator::new(resolution)?;
+    let generator = match target_cfg {
+        Some(cfg) => generator.with_target_cfg(cfg)?,
+        None => generator,
+    };
+
+    let settings = generator.settings();
+    if let Some(path) = settings.stub_output.as_ref().filter(|_| settings.stubs) {
+        let path = resolution.metadata.manifest_dir()?.join(path);
+        let written = bindings::write_stubs(&path, force_stubs, || {
+            generator.generate_stubs(generator.rustfmt()?)
+        })?;
+
+        let path = path.strip_prefix(cwd).unwrap_or(&path).display();
+        if written {
+            config.terminal().status(
+                "Generating",
+                format!("stubs for {name} ({path})", name = resolution.metadata.name),
+            )?;
+        } else {
+            log::debug!(
+                "not overwriting existing stubs for package `{name}` at `{path}`",
+                name = resolution.metadata.name
+            );
+        }
+    }
+
     match generator.reason(last_modified_exe, last_modified_output)? {
         Some(reason) => {
+            // The bindings are unchanged if nothing they are generated from
+            // has changed since they were last generated
+            let hash_path = bindings::bindings_hash_path(&bindings_path);
+            let hash = generator.bindings_hash()?;
+            if last_modified_output.is_some()
+                && fs::read_to_string(&hash_path).is_ok_and(|prev| prev.trim() == hash)
+            {
+                log::debug!(
+                    "existing bindings for package `{name}` at `{path}` are unchanged even though {reason}",
+                    name = resolution.metadata.name,
+                    path = bindings_path.display(),
+                );
+                return Ok(import_name_map);
+            }
+
             log::debug!(
                 "generating bindings for package `{name}` at `{path}` because {reason}",
                 name = resolution.metadata.name,
@@ -840,18 +1086,120 @@ async fn generate_package_bindings(
                 ),
             )?;
 
-            let bindings = generator.generate()?;
-            fs::create_dir_all(&output_dir).with_context(|| {
-                format!(
-                    "failed to create output directory `{path}`",
-                    path = output_dir.display()
+            let manifest_path = &resolution.metadata.manifest_path;
+            let bindings_span = |setting: &str| {
+                ManifestSpan::find(
+                    manifest_path,
+                    &["package", "metadata", "component", "bindings", setting],
                 )
-            })?;
+            };
+
+            for derive in generator.settings().unknown_derives() {
+                config.report(
+                    Diagnostic::warning(
+                        "unknown-derive",
+                        format!(
+                            "bindings derive `{derive}` of package `{name}` is not a commonly used derive; check it for typos",
+                            name = resolution.metadata.name
+                        ),
+                    )
+                    .with_span(bindings_span("derives")),
+                )?;
+            }
+
+            for feature in generator.unknown_features()? {
+                config.report(
+                    Diagnostic::warning(
+                        "unknown-feature",
+                        format!(
+                            "bindings feature `{feature}` of package `{name}` is not declared by the target world or its dependencies",
+                            name = resolution.metadata.name
+                        ),
+                    )
+                    .with_span(bindings_span("features")),
+                )?;
+            }
+
+            for entry in generator.unmatched_only() {
+                config.report(
+                    Diagnostic::warning(
+                        "unmatched-only",
+                        format!(
+                            "bindings `only` entry `{entry}` does not match any item in the target world of package `{name}`",
+                            name = resolution.metadata.name
+                        ),
+                    )
+                    .with_span(bindings_span("only")),
+                )?;
+            }
+
+            for entry in generator.unmatched_interface_skips() {
+                config.report(
+                    Diagnostic::warning(
+                        "unmatched-skip",
+                        format!(
+                            "bindings `skip` entry `{entry}` does not match any interface in the target world of package `{name}`",
+                            name = resolution.metadata.name
+                        ),
+                    )
+                    .with_span(bindings_span("skip")),
+                )?;
+            }
+
+            for (ty, derive) in generator.ignored_derives() {
+                config.report(
+                    Diagnostic::warning(
+                        "ignored-derive",
+                        format!(
+                            "bindings derive `{derive}` cannot apply to type `{ty}` of package `{name}` and will be skipped for it",
+                            name = resolution.metadata.name
+                        ),
+                    )
+                    .with_span(bindings_span("derives")),
+                )?;
+            }
+
+            let rustfmt = generator.rustfmt()?;
+            if !rustfmt && generator.settings().format.enabled() {
+                config.report(Diagnostic::warning(
+                    "rustfmt-unavailable",
+                    format!(
+                        "`rustfmt` is not available; the bindings for package `{name}` will not be formatted",
+                        name = resolution.metadata.name
+                    ),
+                ))?;
+            }
+
+            let std_feature = generator.settings().std_feature;
+            let bindings = generator.generate(rustfmt)?;
+            if let Some(name) = &section.bindings_crate {
+                bindings::write_bindings_crate(
+                    &output_dir,
+                    name,
+                    &resolution.metadata.version,
+                    std_feature,
+                    &bindings,
+                )?;
+            } else {
+                fs::create_dir_all(&output_dir).with_context(|| {
+                    format!(
+                        "failed to create output directory `{path}`",
+                        path = output_dir.display()
+                    )
+                })?;
 
-            fs::write(&bindings_path, bindings).with_context(|| {
+                fs::write(&bindings_path, bindings).with_context(|| {
+                    format!(
+                        "failed to write bindings file `{path}`",
+                        path = bindings_path.display()
+                    )
+                })?;
+            }
+
+            fs::write(&hash_path, hash).with_context(|| {
                 format!(
-                    "failed to write bindings file `{path}`",
-                    path = bindings_path.display()
+                    "failed to write bindings hash file `{path}`",
+                    path = hash_path.display()
                 )
             })?;
         }
@@ -871,49 +1219,78 @@ fn adapter_bytes(
     config: &Config,
     metadata: &ComponentMetadata,
     is_command: bool,
-) -> Result<Cow<'static, [u8]>> {
-    if let Some(adapter) = &metadata.section.adapter {
-        if metadata.section.proxy {
+    bytes: &[u8],
+) -> Result<Option<Cow<'static, [u8]>>> {
+    if let Some(adapter) = metadata.adapter_path() {
+        if std::env::var_os(ADAPTER_ENV_VAR).is_some_and(|v| !v.is_empty()) {
+            config.terminal().warn(format!(
+                "using module adapter `{path}` from the `{ADAPTER_ENV_VAR}` environment variable in place of the configured adapter",
+                path = adapter.display()
+            ))?;
+        } else if metadata.section.proxy.is_enabled() {
             config.terminal().warn(
                 "ignoring `proxy` setting due to `adapter` setting being present in `Cargo.toml`",
             )?;
         }
 
-        return Ok(fs::read(adapter)
-            .with_context(|| {
-                format!(
-                    "failed to read module adapter `{path}`",
-                    path = adapter.display()
-                )
-            })?
-            .into());
+        return Ok(Some(
+            fs::read(&adapter)
+                .with_context(|| {
+                    format!(
+                        "failed to read module adapter `{path}`",
+                        path = adapter.display()
+                    )
+                })?
+                .into(),
+        ));
     }
 
-    if is_command {
-        if metadata.section.proxy {
+    let kind = if is_command {
+        if metadata.section.proxy.is_enabled() {
             config
                 .terminal()
                 .warn("ignoring `proxy` setting in `Cargo.toml` for command component")?;
         }
 
-        Ok(Cow::Borrowed(include_bytes!(concat!(
-            "../adapters/",
-            env!("WASI_ADAPTER_VERSION"),
-            "/wasi_snapshot_preview1.command.wasm"
-        ))))
-    } else if metadata.section.proxy {
-        Ok(Cow::Borrowed(include_bytes!(concat!(
-            "../adapters/",
-            env!("WASI_ADAPTER_VERSION"),
-            "/wasi_snapshot_preview1.proxy.wasm"
-        ))))
+        AdapterKind::Command
+    } else if metadata.section.proxy.is_enabled() {
+        match metadata.section.proxy.adapter() {
+            Some(kind) => kind,
+            None => {
+                config.terminal().warn(
+                    "no adapter is applied due to `proxy = \"none\"` in `Cargo.toml`; the component may be incomplete",
+                )?;
+                return Ok(None);
+            }
+        }
+    } else if metadata.section.auto_adapter {
+        let kind = match wit_component::metadata::decode(bytes) {
+            Ok((_, bindgen)) => AdapterKind::from_world(&bindgen.resolve, bindgen.world),
+            Err(e) => {
+                log::debug!("failed to decode the component type of the module: {e:#}");
+                AdapterKind::Reactor
+            }
+        };
+
+        config.report(
+            Diagnostic::note(
+                "auto-adapter",
+                format!(
+                    "automatically selected the built-in {kind} adapter based on the target world"
+                ),
+            )
+            .with_span(ManifestSpan::find(
+                &metadata.manifest_path,
+                &["package", "metadata", "component", "auto_adapter"],
+            )),
+        )?;
+        kind
     } else {
-        Ok(Cow::Borrowed(include_bytes!(concat!(
-            "../adapters/",
-            env!("WASI_ADAPTER_VERSION"),
-            "/wasi_snapshot_preview1.reactor.wasm"
-        ))))
-    }
+        AdapterKind::Reactor
+    };
+
+    let adapter = kind.bundled(metadata.section.wasi_version.as_ref())?;
+    Ok(Some(Cow::Borrowed(adapter)))
 }
 
 fn componentize(
@@ -953,25 +1330,66 @@ fn componentize(
         )?;
     }
 
-    let encoder = ComponentEncoder::default()
-        .module(bytes)?
-        .import_name_map(import_name_map.clone())
-        .adapter(
-            "wasi_snapshot_preview1",
-            &adapter_bytes(config, metadata, is_command)?,
+    let adapter = adapter_bytes(config, metadata, is_command, bytes)?;
+    let adapter_context = || {
+        format!(
+            "failed to load adapter module `{path}`",
+            path = metadata
+                .adapter_path()
+                .as_deref()
+                .unwrap_or_else(|| Path::new("<built-in>"))
+                .display()
         )
-        .with_context(|| {
-            format!(
-                "failed to load adapter module `{path}`",
-                path = metadata
-                    .section
-                    .adapter
-                    .as_deref()
-                    .unwrap_or_else(|| Path::new("<built-in>"))
-                    .display()
-            )
-        })?
-        .validate(true);
+    };
+
+    let encoded = if metadata.section.link.is_empty() {
+        let mut encoder = ComponentEncoder::default()
+            .module(bytes)?
+            .import_name_map(import_name_map.clone());
+
+        if let Some(adapter) = &adapter {
+            encoder = encoder
+                .adapter("wasi_snapshot_preview1", adapter)
+                .with_context(adapter_context)?;
+        }
+
+        encoder.validate(true).encode()?
+    } else {
+        let modules = metadata
+            .section
+            .link
+            .iter()
+            .map(|module| {
+                let contents = fs::read(module).with_context(|| {
+                    format!(
+                        "failed to read linked module `{path}`",
+                        path = module.display()
+                    )
+                })?;
+
+                let name = module
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .with_context(|| {
+                        format!(
+                            "invalid linked module path `{path}`",
+                            path = module.display()
+                        )
+                    })?;
+
+                Ok((name.to_string(), contents))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        link_component(
+            &metadata.name,
+            bytes,
+            &modules,
+            adapter.as_deref(),
+            import_name_map,
+        )
+        .context("failed to link the component")?
+    };
 
     let mut producers = wasm_metadata::Producers::empty();
     producers.add(
@@ -980,7 +1398,7 @@ fn componentize(
         option_env!("CARGO_VERSION_INFO").unwrap_or(env!("CARGO_PKG_VERSION")),
     );
 
-    let component = producers.add_to_wasm(&encoder.encode()?).with_context(|| {
+    let component = producers.add_to_wasm(&encoded).with_context(|| {
         format!(
             "failed to add metadata to output component `{path}`",
             path = path.display()
@@ -1013,12 +1431,145 @@ fn componentize(
     Ok(())
 }
 
+/// Links the module built from a crate with the given modules into a
+/// component.
+///
+/// The built module is the primary module of the component: it is given to
+/// the linker first, so its exports take precedence over those of the linked
+/// modules. As the linker only links modules built for dynamic linking, every
+/// module must have a `dylink.0` section.
+///
+/// The linker does not accept an import name map, so the imports of the
+/// linked component are renamed afterwards.
+fn link_component(
+    name: &str,
+    bytes: &[u8],
+    modules: &[(String, Vec<u8>)],
+    adapter: Option<&[u8]>,
+    import_name_map: &HashMap<String, String>,
+) -> Result<Vec<u8>> {
+    if !has_dylink_section(bytes)? {
+        bail!(
+            "the module built from crate `{name}` cannot be linked with the modules of the `link` setting as it was not built for dynamic linking (it has no `dylink.0` section)"
+        );
+    }
+
+    let mut linker =
+        Linker::default()
+            .validate(true)
+            .library(&format!("lib{name}.so"), bytes, false)?;
+
+    for (name, contents) in modules {
+        if !has_dylink_section(contents)? {
+            bail!(
+                "linked module `{name}` was not built for dynamic linking (it has no `dylink.0` section)"
+            );
+        }
+
+        linker = linker.library(name, contents, false)?;
+    }
+
+    if let Some(adapter) = adapter {
+        linker = linker.adapter("wasi_snapshot_preview1", adapter)?;
+    }
+
+    rename_imports(&linker.encode()?, import_name_map)
+}
+
+/// Determines if the given core module has a `dylink.0` section.
+fn has_dylink_section(bytes: &[u8]) -> Result<bool> {
+    for payload in Parser::new(0).parse_all(bytes) {
+        match payload? {
+            Payload::CustomSection(section) if section.name() == "dylink.0" => return Ok(true),
+            // The section must precede all other sections
+            Payload::Version { .. } | Payload::CustomSection(_) => {}
+            _ => return Ok(false),
+        }
+    }
+
+    Ok(false)
+}
+
+/// Renames the imports of the given component according to the given map.
+///
+/// Only the imports of the outermost component are renamed; all other
+/// sections are copied as is.
+fn rename_imports(component: &[u8], map: &HashMap<String, String>) -> Result<Vec<u8>> {
+    use wasm_encoder::{ComponentSectionId, Encode, RawSection, Section};
+
+    if map.is_empty() {
+        return Ok(component.to_vec());
+    }
+
+    let mut output = wasm_encoder::Component::HEADER.to_vec();
+    let mut depth = 0;
+    for payload in Parser::new(0).parse_all(component) {
+        let payload = payload?;
+        match &payload {
+            Payload::Version { .. } => {
+                depth += 1;
+                continue;
+            }
+            Payload::End(_) => {
+                depth -= 1;
+                continue;
+            }
+            _ if depth > 1 => continue,
+            Payload::ComponentImportSection(reader) => {
+                let end = reader.range().end;
+                let imports = reader
+                    .clone()
+                    .into_iter_with_offsets()
+                    .collect::<wasmparser::Result<Vec<_>>>()?;
+
+                let mut data = Vec::new();
+                (imports.len() as u32).encode(&mut data);
+                for (i, (offset, import)) in imports.iter().enumerate() {
+                    // The type of the import follows the discriminator byte
+                    // and the length-prefixed name
+                    let mut name = Vec::new();
+                    import.name.0.encode(&mut name);
+                    let ty = offset + 1 + name.len();
+                    let next = imports.get(i + 1).map(|(o, _)| *o).unwrap_or(end);
+
+                    data.push(0x00);
+                    map.get(import.name.0)
+                        .map(String::as_str)
+                        .unwrap_or(import.name.0)
+                        .encode(&mut data);
+                    data.extend_from_slice(&component[ty..next]);
+                }
+
+                RawSection {
+                    id: ComponentSectionId::Import as u8,
+                    data: &data,
+                }
+                .append_to(&mut output);
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some((id, range)) = payload.as_section() {
+            RawSection {
+                id,
+                data: &component[range],
+            }
+            .append_to(&mut output);
+        }
+    }
+
+    Ok(output)
+}
+
 /// Represents options for a publish operation.
 pub struct PublishOptions<'a> {
     /// The package to publish.
     pub package: &'a Package,
     /// The registry URL to publish to.
     pub registry_url: &'a str,
+    /// The explicit warg protocol version of the registry.
+    pub registry_protocol: Option<u8>,
     /// Whether to initialize the package or not.
     pub init: bool,
     /// The name of the package being published.
@@ -1095,7 +1646,13 @@ pub async fn publish(config: &Config, options: &PublishOptions<'_>) -> Result<()
         return Ok(());
     }
 
-    let mut client = create_client(config.warg(), options.registry_url, config.terminal())?;
+    let mut client = create_client(
+        config.warg(),
+        options.registry_url,
+        options.registry_protocol,
+        config.terminal(),
+    )
+    .await?;
     client.refresh_namespace(options.name.namespace()).await?;
 
     let bytes = fs::read(options.path).with_context(|| {
@@ -1168,7 +1725,13 @@ pub async fn update_lockfile(
     dry_run: bool,
 ) -> Result<()> {
     // Read the current lock file and generate a new one
-    let map = create_resolution_map(config, packages, None, network_allowed).await?;
+    let map = create_resolution_map(
+        config,
+        packages,
+        None,
+        FrozenMode::from_network_allowed(network_allowed),
+    )
+    .await?;
 
     let file_lock = acquire_lock_file_ro(config.terminal(), metadata)?;
     let orig_lock_file = file_lock
@@ -1184,7 +1747,7 @@ pub async fn update_lockfile(
         .transpose()?
         .unwrap_or_default();
 
-    let new_lock_file = map.to_lock_file();
+    let new_lock_file = map.to_lock_file()?;
 
     for old_pkg in &orig_lock_file.packages {
         let new_pkg = match new_lock_file
@@ -1316,3 +1879,103 @@ pub async fn update_lockfile(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wit_component::StringEncoding;
+    use wit_parser::{Resolve, UnresolvedPackage};
+
+    #[test]
+    fn it_links_modules() {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(
+                UnresolvedPackage::parse(
+                    Path::new("test.wit"),
+                    "package test:test;
+
+interface host {
+    get: func(v: s32) -> s32;
+}
+
+interface run {
+    run: func() -> s32;
+}
+
+world main {
+    import host;
+    export run;
+}
+",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let world = resolve.select_world(pkg, None).unwrap();
+
+        let module = |wat: &str| wat::parse_str(wat).unwrap();
+        let mut main = module(
+            r#"(module
+  (@dylink.0
+    (mem-info (memory 0 4))
+    (needed "libhelper.so")
+  )
+  (import "env" "memory" (memory 1))
+  (import "env" "__indirect_function_table" (table 0 funcref))
+  (import "env" "helper" (func $helper (result i32)))
+  (import "test:test/host" "get" (func $get (param i32) (result i32)))
+  (func (export "test:test/run#run") (result i32)
+    call $helper
+    call $get
+  )
+)"#,
+        );
+        wit_component::embed_component_metadata(&mut main, &resolve, world, StringEncoding::UTF8)
+            .unwrap();
+        let helper = module(
+            r#"(module
+  (@dylink.0)
+  (func (export "helper") (result i32)
+    i32.const 42
+  )
+)"#,
+        );
+
+        let modules = [("libhelper.so".to_string(), helper.clone())];
+        let import_name_map = HashMap::from([(
+            "test:test/host".to_string(),
+            "unlocked-dep=<test:test/host@{>=1.0.0 <1.1.0}>".to_string(),
+        )]);
+        let component = link_component("main", &main, &modules, None, &import_name_map).unwrap();
+        wasmparser::Validator::new_with_features(wasmparser::WasmFeatures::all())
+            .validate_all(&component)
+            .unwrap();
+
+        let printed = wasmprinter::print_bytes(&component).unwrap();
+        assert!(printed.contains("(import \"unlocked-dep=<test:test/host@{>=1.0.0 <1.1.0}>\""));
+        assert!(!printed.contains("(import \"test:test/host\" (instance"));
+        assert!(printed.contains("\"test:test/run\" (instance"));
+
+        // Both the primary module and the linked modules must be built for
+        // dynamic linking
+        let err = link_component(
+            "main",
+            &module("(module)"),
+            &modules,
+            None,
+            &import_name_map,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("the module built from crate `main` cannot be linked"));
+
+        let modules = [("libhelper.so".to_string(), module("(module)"))];
+        let err = link_component("main", &main, &modules, None, &import_name_map).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "linked module `libhelper.so` was not built for dynamic linking (it has no `dylink.0` section)"
+        );
+    }
+}
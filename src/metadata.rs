@@ -1,46 +1,176 @@
 //! Module for component metadata representation in `Cargo.toml`.
 
-use anyhow::{bail, Context, Result};
-use cargo_component_core::registry::{Dependency, RegistryPackage};
+use crate::{
+    bindings::BindingsGenerator, config::Config, registry::PackageDependencyResolution,
+    target::cfg_matches,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use cargo_component_core::{
+    archive::extract_archive,
+    features::FeatureSet,
+    lock::LockFileResolver,
+    registry::{
+        deserialize_package_map, expand_url, find_url, Dependency, DependencyResolution,
+        FrozenMode, LocalResolution, RegistryClientConfig, RegistryPackage, RegistryRef,
+        DEFAULT_REGISTRY_NAME,
+    },
+};
 use cargo_metadata::Package;
 use semver::{Version, VersionReq};
 use serde::{
     de::{self, value::MapAccessDeserializer},
-    Deserialize,
+    Deserialize, Serialize,
 };
-use serde_json::from_value;
+use serde_json::{from_value, Value};
+use sha2::{Digest, Sha256};
 use std::{
     borrow::Cow,
-    collections::HashMap,
-    path::{Path, PathBuf},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ffi::OsString,
+    fs,
+    io::Read,
+    path::{Component, Path, PathBuf},
     str::FromStr,
     time::SystemTime,
 };
-use url::Url;
+use toml_edit::de::ValueDeserializer;
+use url::{Host, Url};
 use warg_protocol::registry::PackageName;
+use wit_component::{DecodedWasm, WitPrinter};
+use wit_parser::{
+    Function, FunctionKind, Handle, Resolve, Results, Type, TypeDefKind, WorldId, WorldItem,
+    WorldKey,
+};
 
 /// The default directory to look for a target WIT file.
 pub const DEFAULT_WIT_DIR: &str = "wit";
 
-/// The supported ownership model for generated types.
-#[derive(Default, Debug, Clone, Copy, Deserialize)]
+/// The environment variable that enables overriding bindings settings with
+/// `# cargo-component:` comments in `Cargo.toml`.
+pub const ALLOW_COMMENT_OVERRIDES_ENV: &str = "CARGO_COMPONENT_ALLOW_COMMENT_OVERRIDES";
+
+/// The environment variable that overrides the target world of components.
+pub const WORLD_ENV_VAR: &str = "CARGO_COMPONENT_WORLD";
+
+/// The environment variable that overrides the module adapter of components.
+pub const ADAPTER_ENV_VAR: &str = "CARGO_COMPONENT_ADAPTER";
+
+/// The environment variable that overrides a registry package target of
+/// components with a local directory.
+pub const TARGET_PATH_ENV_VAR: &str = "CARGO_COMPONENT_TARGET_PATH";
+
+/// The prefix of a comment in `Cargo.toml` that overrides a bindings setting.
+const COMMENT_OVERRIDE_PREFIX: &str = "# cargo-component:";
+
+/// An error with the component metadata of a package.
+///
+/// Functions returning [`anyhow::Error`] may wrap this error with context;
+/// use [`anyhow::Error::downcast_ref`] to match on it.
+///
+/// Failures loading the metadata of a manifest that are not described by a
+/// specific variant are reported as [`MetadataError::Manifest`].
+#[derive(Debug)]
+pub enum MetadataError {
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// A version is invalid.
+    InvalidVersion {
+        /// The invalid version.
+        version: String,
+        /// The error parsing the version.
+        source: semver::Error,
+    },
+    /// Two fields cannot be specified together.
+    ConflictingFields {
+        /// The first of the conflicting fields.
+        first: &'static str,
+        /// The second of the conflicting fields.
+        second: &'static str,
+    },
+    /// A path referenced by the metadata does not exist.
+    MissingPath(PathBuf),
+    /// A field has an invalid value.
+    InvalidValue {
+        /// The name of the field.
+        field: &'static str,
+        /// The reason the value is invalid.
+        reason: String,
+    },
+    /// The component metadata of a manifest could not be loaded.
+    Manifest {
+        /// The path of the manifest.
+        path: PathBuf,
+        /// The error loading the metadata.
+        source: anyhow::Error,
+    },
+}
+
+impl MetadataError {
+    /// Converts an error loading the metadata of the given manifest.
+    ///
+    /// The error is returned as is if it is (or was given context on) a
+    /// metadata error.
+    fn manifest(path: &Path, error: anyhow::Error) -> Self {
+        match error.downcast::<Self>() {
+            Ok(error) => error,
+            Err(source) => Self::Manifest {
+                path: path.to_path_buf(),
+                source,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "missing required field `{field}`"),
+            Self::InvalidVersion { version, .. } => write!(f, "invalid version `{version}`"),
+            Self::ConflictingFields { first, second } => {
+                write!(f, "`{first}` cannot be combined with `{second}`")
+            }
+            Self::MissingPath(path) => {
+                write!(f, "path `{path}` does not exist", path = path.display())
+            }
+            Self::InvalidValue { field, reason } => {
+                write!(f, "invalid `{field}` setting: {reason}")
+            }
+            Self::Manifest { path, .. } => write!(
+                f,
+                "failed to load component metadata from `{path}`",
+                path = path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidVersion { source, .. } => Some(source),
+            Self::Manifest { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// The ownership model for generated types in a single direction (imports
+/// or exports).
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
-pub enum Ownership {
-    /// Generated types will be composed entirely of owning fields, regardless
-    /// of whether they are used as parameters to imports or not.
+pub enum OwnershipModel {
+    /// Generated types will be composed entirely of owning fields.
     #[default]
     Owning,
-    /// Generated types used as parameters to imports will be "deeply
-    /// borrowing", i.e. contain references rather than owned values when
-    /// applicable.
+    /// Generated types will be "deeply borrowing", i.e. contain references
+    /// rather than owned values when applicable.
     Borrowing,
-    /// Generate "duplicate" type definitions for a single WIT type, if necessary.
-    /// For example if it's used as both an import and an export, or if it's used
-    /// both as a parameter to an import and a return value from an import.
+    /// Generated types will be borrowing, with "duplicate" type definitions
+    /// generated for a single WIT type, if necessary.
     BorrowingDuplicateIfNecessary,
 }
 
-impl FromStr for Ownership {
+impl FromStr for OwnershipModel {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -56,135 +186,69 @@ impl FromStr for Ownership {
     }
 }
 
-/// Configuration for bindings generation.
-#[derive(Debug, Clone, Deserialize)]
-#[serde(default)]
-pub struct Bindings {
-    /// Whether or not to run `rustfmt` on the bindings; defaults to true.
-    pub format: bool,
-    /// The ownership model for generated types.
-    pub ownership: Ownership,
-    /// Additional derives to apply to generated binding types.
-    pub derives: Vec<String>,
-    /// If true, code generation should qualify any features that depend on
-    /// `std` with `cfg(feature = "std")`.
-    pub std_feature: bool,
-}
-
-impl Default for Bindings {
-    fn default() -> Self {
-        Self {
-            format: true,
-            ownership: Default::default(),
-            derives: Default::default(),
-            std_feature: false,
-        }
-    }
-}
-
-/// The target of a component.
-///
-/// The target defines the world of the component being developed.
-#[derive(Debug, Clone)]
-pub enum Target {
-    /// The target is a world from a registry package.
-    Package {
-        /// The name of the target package (e.g. `wasi:http`).
-        name: PackageName,
-        /// The registry package being targeted.
-        package: RegistryPackage,
-        /// The name of the world being targeted.
-        ///
-        /// [Resolve::select_world][select-world] will be used
-        /// to select world.
-        ///
-        /// [select-world]: https://docs.rs/wit-parser/latest/wit_parser/struct.Resolve.html#method.select_world
-        world: Option<String>,
-    },
-    /// The target is a world from a local wit document.
-    Local {
-        /// The path to the wit document defining the world.
-        ///
-        /// Defaults to the `wit` directory.
-        path: Option<PathBuf>,
-        /// The name of the world being targeted.
-        ///
-        /// [Resolve::select_world][select-world] will be used
-        /// to select world.
-        ///
-        /// [select-world]: https://docs.rs/wit-parser/latest/wit_parser/struct.Resolve.html#method.select_world
-        world: Option<String>,
-        /// The dependencies of the wit document being targeted.
-        dependencies: HashMap<PackageName, Dependency>,
+/// The supported ownership model for generated types.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Ownership {
+    /// Generated types will be composed entirely of owning fields, regardless
+    /// of whether they are used as parameters to imports or not.
+    #[default]
+    Owning,
+    /// Generated types used as parameters to imports will be "deeply
+    /// borrowing", i.e. contain references rather than owned values when
+    /// applicable.
+    Borrowing,
+    /// Generate "duplicate" type definitions for a single WIT type, if necessary.
+    /// For example if it's used as both an import and an export, or if it's used
+    /// both as a parameter to an import and a return value from an import.
+    BorrowingDuplicateIfNecessary,
+    /// Use separate ownership models for imports and exports.
+    Split {
+        /// The ownership model for types used by imports.
+        imports: OwnershipModel,
+        /// The ownership model for types used by exports.
+        exports: OwnershipModel,
     },
 }
 
-impl Target {
-    /// Gets the dependencies of the target.
-    pub fn dependencies(&self) -> Cow<HashMap<PackageName, Dependency>> {
+impl Ownership {
+    /// Gets the ownership model for types used by imports.
+    pub fn imports(&self) -> OwnershipModel {
         match self {
-            Self::Package { name, package, .. } => Cow::Owned(HashMap::from_iter([(
-                name.clone(),
-                Dependency::Package(package.clone()),
-            )])),
-            Self::Local { dependencies, .. } => Cow::Borrowed(dependencies),
+            Self::Owning => OwnershipModel::Owning,
+            Self::Borrowing => OwnershipModel::Borrowing,
+            Self::BorrowingDuplicateIfNecessary => OwnershipModel::BorrowingDuplicateIfNecessary,
+            Self::Split { imports, .. } => *imports,
         }
     }
 
-    /// Gets the target world, if any.
-    pub fn world(&self) -> Option<&str> {
+    /// Gets the ownership model for types used by exports.
+    pub fn exports(&self) -> OwnershipModel {
         match self {
-            Self::Package { world, .. } | Self::Local { world, .. } => world.as_deref(),
+            Self::Split { exports, .. } => *exports,
+            _ => self.imports(),
         }
     }
 }
 
-impl Default for Target {
-    fn default() -> Self {
-        Self::Local {
-            path: None,
-            world: None,
-            dependencies: HashMap::new(),
+impl From<OwnershipModel> for Ownership {
+    fn from(model: OwnershipModel) -> Self {
+        match model {
+            OwnershipModel::Owning => Self::Owning,
+            OwnershipModel::Borrowing => Self::Borrowing,
+            OwnershipModel::BorrowingDuplicateIfNecessary => Self::BorrowingDuplicateIfNecessary,
         }
     }
 }
 
-impl FromStr for Target {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        let (name, version) = match s.split_once('@') {
-            Some((name, version)) => (
-                name,
-                version
-                    .parse()
-                    .with_context(|| format!("invalid target version `{version}`"))?,
-            ),
-            None => bail!("expected target format `<package-name>[/<world>]@<version>`"),
-        };
-
-        let (name, world) = match name.split_once('/') {
-            Some((name, world)) => {
-                wit_parser::validate_id(world)
-                    .with_context(|| format!("invalid target world name `{world}`"))?;
-                (name, Some(world.to_string()))
-            }
-            None => (name, None),
-        };
+impl FromStr for Ownership {
+    type Err = String;
 
-        Ok(Self::Package {
-            name: name.parse()?,
-            package: RegistryPackage {
-                name: None,
-                version,
-                registry: None,
-            },
-            world,
-        })
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<OwnershipModel>().map(Into::into)
     }
 }
 
-impl<'de> Deserialize<'de> for Target {
+impl<'de> Deserialize<'de> for Ownership {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
@@ -192,7 +256,7 @@ impl<'de> Deserialize<'de> for Target {
         struct Visitor;
 
         impl<'de> de::Visitor<'de> for Visitor {
-            type Value = Target;
+            type Value = Ownership;
 
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
                 write!(formatter, "a string or a table")
@@ -202,7 +266,7 @@ impl<'de> Deserialize<'de> for Target {
             where
                 E: de::Error,
             {
-                Target::from_str(s).map_err(de::Error::custom)
+                Ownership::from_str(s).map_err(de::Error::custom)
             }
 
             fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
@@ -212,59 +276,15 @@ impl<'de> Deserialize<'de> for Target {
                 #[derive(Default, Deserialize)]
                 #[serde(default, deny_unknown_fields)]
                 struct Entry {
-                    package: Option<String>,
-                    version: Option<VersionReq>,
-                    world: Option<String>,
-                    registry: Option<String>,
-                    path: Option<PathBuf>,
-                    dependencies: HashMap<PackageName, Dependency>,
+                    imports: OwnershipModel,
+                    exports: OwnershipModel,
                 }
 
                 let entry = Entry::deserialize(MapAccessDeserializer::new(map))?;
-
-                match (entry.path, entry.package) {
-                    (None, Some(package)) => {
-                        for (present, name) in [(!entry.dependencies.is_empty(), "dependencies")] {
-                            if present {
-                                return Err(de::Error::custom(
-                                    format!("cannot specify both `{name}` and `package` fields in a target entry"),
-                                ));
-                            }
-                        }
-
-                        Ok(Target::Package {
-                            name: package.parse().map_err(de::Error::custom)?,
-                            package: RegistryPackage {
-                                name: None,
-                                version: entry
-                                    .version
-                                    .ok_or_else(|| de::Error::missing_field("version"))?,
-                                registry: entry.registry,
-                            },
-                            world: entry.world,
-                        })
-                    }
-                    (path, None) => {
-                        for (present, name) in [
-                            (entry.version.is_some(), "version"),
-                            (entry.registry.is_some(), "registry"),
-                        ] {
-                            if present {
-                                return Err(de::Error::custom(
-                                    format!("cannot specify both `{name}` and `path` fields in a target entry"),
-                                ));
-                            }
-                        }
-                        Ok(Target::Local {
-                            path,
-                            world: entry.world,
-                            dependencies: entry.dependencies,
-                        })
-                    }
-                    (Some(_), Some(_)) => Err(de::Error::custom(
-                        "cannot specify both `path` and `package` fields in a target entry",
-                    )),
-                }
+                Ok(Ownership::Split {
+                    imports: entry.imports,
+                    exports: entry.exports,
+                })
             }
         }
 
@@ -272,157 +292,6444 @@ impl<'de> Deserialize<'de> for Target {
     }
 }
 
-/// Represents the `package.metadata.component` section in `Cargo.toml`.
-#[derive(Default, Debug, Clone, Deserialize)]
+/// The options for running `rustfmt` on generated bindings.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(default, deny_unknown_fields)]
-pub struct ComponentSection {
-    /// The package name of the component, for publishing.
-    pub package: Option<PackageName>,
-    /// The world targeted by the component.
-    pub target: Target,
-    /// The path to the WASI adapter to use.
-    pub adapter: Option<PathBuf>,
-    /// The dependencies of the component.
-    pub dependencies: HashMap<PackageName, Dependency>,
-    /// The registries to use for the component.
-    pub registries: HashMap<String, Url>,
-    /// The configuration for bindings generation.
-    pub bindings: Bindings,
-    /// Whether to use the built-in `wasi:http/proxy` adapter for the component.
-    ///
-    /// This should only be `true` when `adapter` is None.
-    pub proxy: bool,
+pub struct FormatOptions {
+    /// The Rust edition to pass to `rustfmt`.
+    pub edition: Option<String>,
+    /// The path to the `rustfmt` configuration file to use.
+    pub config_path: Option<PathBuf>,
 }
 
-/// Represents cargo metadata for a WebAssembly component.
-#[derive(Debug, Clone)]
-pub struct ComponentMetadata {
-    /// The crate name.
-    pub name: String,
-    /// The version of the crate.
-    pub version: Version,
-    /// The path to the cargo manifest file.
-    pub manifest_path: PathBuf,
-    /// The last modified time of the manifest file.
-    pub modified_at: SystemTime,
-    /// The component section in `Cargo.toml`.
-    pub section: ComponentSection,
-    /// Whether the component section was present in `Cargo.toml`.
-    pub section_present: bool,
+/// The formatting configuration for generated bindings.
+///
+/// This is either a boolean or a table of `rustfmt` options.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Format {
+    /// Whether or not to run `rustfmt` with its default options.
+    ///
+    /// This is `None` when not explicitly specified, in which case `rustfmt`
+    /// is run only if it is available.
+    Enabled(Option<bool>),
+    /// Run `rustfmt` with the given options.
+    Options(FormatOptions),
 }
 
-impl ComponentMetadata {
-    /// Creates a new component metadata for the given cargo package.
-    pub fn from_package(package: &Package) -> Result<Self> {
-        log::debug!(
-            "searching for component metadata in manifest `{path}`",
-            path = package.manifest_path
-        );
+impl Format {
+    /// Determines if `rustfmt` should be run on the bindings.
+    pub fn enabled(&self) -> bool {
+        match self {
+            Self::Enabled(enabled) => enabled.unwrap_or(true),
+            Self::Options(_) => true,
+        }
+    }
 
-        let mut section_present = false;
-        let mut section: ComponentSection = match package.metadata.get("component").cloned() {
-            Some(component) => {
-                section_present = true;
-                from_value(component).with_context(|| {
-                    format!(
-                        "failed to deserialize component metadata from `{path}`",
-                        path = package.manifest_path
-                    )
-                })?
+    /// Determines if running `rustfmt` was explicitly requested.
+    pub fn explicit(&self) -> bool {
+        match self {
+            Self::Enabled(enabled) => enabled.unwrap_or(false),
+            Self::Options(_) => true,
+        }
+    }
+
+    /// Determines if `rustfmt` should be run given whether it is available.
+    ///
+    /// Returns an error if `rustfmt` was explicitly requested but is not
+    /// available.
+    pub fn check_rustfmt(&self, available: bool) -> Result<bool> {
+        if !self.enabled() {
+            return Ok(false);
+        }
+
+        if !available && self.explicit() {
+            bail!(
+                "formatting of bindings was requested but `rustfmt` is not available\n\n\
+                 install it with `rustup component add rustfmt` or set `format = false` \
+                 in the `[package.metadata.component.bindings]` section of `Cargo.toml`"
+            );
+        }
+
+        Ok(available)
+    }
+
+    /// Gets the explicit `rustfmt` options, if any.
+    pub fn options(&self) -> Option<&FormatOptions> {
+        match self {
+            Self::Enabled(_) => None,
+            Self::Options(options) => Some(options),
+        }
+    }
+
+    /// Gets the arguments to pass to `rustfmt`.
+    pub fn rustfmt_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        if let Some(options) = self.options() {
+            if let Some(edition) = &options.edition {
+                args.push("--edition".into());
+                args.push(edition.into());
             }
-            None => {
-                log::debug!(
-                    "manifest `{path}` has no component metadata",
-                    path = package.manifest_path
-                );
-                Default::default()
+
+            if let Some(path) = &options.config_path {
+                args.push("--config-path".into());
+                args.push(path.into());
             }
-        };
+        }
 
-        let manifest_dir = package
-            .manifest_path
-            .parent()
-            .map(|p| p.as_std_path())
-            .with_context(|| {
-                format!(
-                    "manifest path `{path}` has no parent directory",
-                    path = package.manifest_path
-                )
-            })?;
-        let modified_at = crate::last_modified_time(package.manifest_path.as_std_path())?;
+        args
+    }
+}
 
-        // Make all paths stored in the metadata relative to the manifest directory.
-        if let Target::Local {
-            path, dependencies, ..
-        } = &mut section.target
-        {
-            if let Some(path) = path {
-                *path = manifest_dir.join(path.as_path());
+impl Default for Format {
+    fn default() -> Self {
+        Self::Enabled(None)
+    }
+}
+
+impl<'de> Deserialize<'de> for Format {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Format;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "a boolean or a table")
             }
 
-            for dependency in dependencies.values_mut() {
-                if let Dependency::Local(path) = dependency {
-                    *path = manifest_dir.join(path.as_path());
-                }
+            fn visit_bool<E>(self, b: bool) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Format::Enabled(Some(b)))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                Ok(Format::Options(FormatOptions::deserialize(
+                    MapAccessDeserializer::new(map),
+                )?))
             }
         }
 
-        for dependency in section.dependencies.values_mut() {
-            if let Dependency::Local(path) = dependency {
-                *path = manifest_dir.join(path.as_path());
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// How bindings are provided for an interface in the `with` setting.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String")]
+pub enum WithMapping {
+    /// Bindings are generated for the interface; specified as `generate`.
+    Generate,
+    /// The interface is mapped to the existing module at the given Rust path.
+    Path(String),
+}
+
+impl From<String> for WithMapping {
+    fn from(s: String) -> Self {
+        if s == "generate" {
+            Self::Generate
+        } else {
+            Self::Path(s)
+        }
+    }
+}
+
+/// Configuration for bindings generation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Bindings {
+    /// Whether or not to run `rustfmt` on the bindings; defaults to true.
+    ///
+    /// A table may be specified to pass an explicit edition or configuration
+    /// file to `rustfmt`.
+    pub format: Format,
+    /// The ownership model for generated types.
+    pub ownership: Ownership,
+    /// Additional derives to apply to generated binding types.
+    ///
+    /// Each derive is a name (e.g. `Hash`) or a path (e.g. `serde::Serialize`).
+    pub derives: Vec<String>,
+    /// Additional derives to apply to generated binding types only when a
+    /// Cargo feature of the crate is enabled.
+    ///
+    /// Keys are feature names and values are derives; each derive is gated
+    /// with `cfg_attr(feature = "...")`.
+    pub derives_feature: HashMap<String, Vec<String>>,
+    /// If true, a derive that cannot apply to a generated type (e.g. `Hash`
+    /// for a record with a floating-point field) is skipped for that type
+    /// rather than failing the build.
+    pub additional_derives_ignore_errors: bool,
+    /// If true, code generation should qualify any features that depend on
+    /// `std` with `cfg(feature = "std")`.
+    pub std_feature: bool,
+    /// If true, the generated bindings only use `core` and `alloc`, regardless
+    /// of any `std` feature of the crate.
+    ///
+    /// This cannot be combined with `std_feature`.
+    pub no_std: bool,
+    /// The prefix to apply to the names of exported items.
+    ///
+    /// The prefix must be a valid WIT identifier, optionally followed by a
+    /// trailing `-` separator.
+    pub export_prefix: Option<String>,
+    /// The visibility of the generated `export!` macro (e.g. `pub(crate)` or
+    /// `pub(in crate::component)`).
+    ///
+    /// Defaults to `pub(crate)`; a bindings crate always exports the macro
+    /// with `pub` visibility.
+    pub export_macro_visibility: Option<String>,
+    /// The world items (interfaces or functions) to skip generating bindings for.
+    ///
+    /// An entry naming a resource (e.g. `blob` or `foo:bar/types#blob`) skips
+    /// the constructor, methods, and static functions of the resource.
+    ///
+    /// An entry prefixed with `interface:` (e.g. `interface:wasi:io/streams`)
+    /// matches only interfaces and skips all of their functions, even when the
+    /// interface is kept because other items use its types.
+    ///
+    /// Entries may contain `*` wildcards.
+    pub skip: Vec<String>,
+    /// The world items (interfaces or functions) to restrict generating bindings to.
+    ///
+    /// If empty, bindings are generated for the entire world. Entries may
+    /// contain `*` wildcards.
+    pub only: Vec<String>,
+    /// The new names of the modules generated for interfaces in the world.
+    ///
+    /// Keys are interface names (e.g. `wasi:clocks/monotonic-clock`) and values
+    /// are Rust module names; each renamed module is made available at the
+    /// root of the bindings.
+    ///
+    /// Unlike `with`, which maps an interface to an existing module elsewhere,
+    /// the bindings for a renamed interface are still generated.
+    pub rename: HashMap<String, String>,
+    /// The mapping of imported interfaces to existing Rust modules.
+    ///
+    /// A `*` key applies to every interface without its own entry; for a
+    /// path, each interface is mapped to its usual module path under it.
+    pub with: HashMap<String, WithMapping>,
+    /// Whether to embed the component type custom section in the bindings;
+    /// defaults to true.
+    ///
+    /// This should only be `false` when the component type is provided to
+    /// the component encoder by other means.
+    pub emit_type_section: bool,
+    /// The suffix to append to the name of the component type custom section.
+    ///
+    /// This cannot be specified when `emit_type_section` is `false`.
+    pub type_section_suffix: Option<String>,
+    /// The Rust path of the `wit-bindgen` runtime referenced by the bindings.
+    ///
+    /// Defaults to `wit_bindgen_rt`; set this when the runtime is re-exported
+    /// from another crate (e.g. `my_crate::wit_bindgen::rt`).
+    pub runtime_path: Option<String>,
+    /// Whether to generate stub implementations of the exported functions,
+    /// interfaces, and resources of the world.
+    ///
+    /// The stubs are generated inline with the bindings unless `stub_output`
+    /// is set.
+    pub stubs: bool,
+    /// The path, relative to the manifest, of a file to write the stub
+    /// implementations to instead of generating them inline.
+    ///
+    /// An existing file is not overwritten unless `--force` is given, so
+    /// the stubs may be edited and checked in. This requires `stubs`.
+    pub stub_output: Option<PathBuf>,
+    /// The settings that apply only when building for a matching target.
+    ///
+    /// Keys are cargo `cfg` expressions (e.g. `target_os = "wasi"`); the
+    /// settings of each entry matching the build target are merged over
+    /// these settings.
+    pub cfg: HashMap<String, Bindings>,
+    /// The minimum Rust version the generated bindings must support.
+    ///
+    /// Bindings features requiring a newer Rust version than this (or the
+    /// active `rustc`, if older) will result in an error.
+    pub min_rust: Option<Version>,
+    /// The WIT features to enable for the target world and its dependencies.
+    ///
+    /// Items gated with `@unstable(feature = ...)` are only included when
+    /// their feature is enabled.
+    pub features: Vec<String>,
+    /// Whether to enable all WIT features; this overrides `features`.
+    pub all_features: bool,
+}
+
+/// A bindings feature name, a predicate for whether the feature is used, and
+/// the minimum Rust version (major, minor, patch) it requires.
+type RustVersionRequirement = (&'static str, fn(&Bindings) -> bool, (u64, u64, u64));
+
+/// Determines if the bindings are formatted with the given `rustfmt` edition.
+fn formats_edition(bindings: &Bindings, edition: &str) -> bool {
+    bindings.format.options().and_then(|o| o.edition.as_deref()) == Some(edition)
+}
+
+/// The derives that are commonly applied to generated binding types.
+const KNOWN_DERIVES: &[&str] = &[
+    "Clone",
+    "Copy",
+    "Debug",
+    "Default",
+    "Eq",
+    "Hash",
+    "Ord",
+    "PartialEq",
+    "PartialOrd",
+    "serde::Deserialize",
+    "serde::Serialize",
+];
+
+/// Determines if the given string is a valid Rust identifier.
+fn is_rust_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && s != "_"
+}
+
+/// Determines if the given string is a Rust path (e.g. `serde::Serialize`),
+/// optionally with a leading `::`.
+fn is_rust_path(s: &str) -> bool {
+    s.strip_prefix("::")
+        .unwrap_or(s)
+        .split("::")
+        .all(is_rust_identifier)
+}
+
+/// Determines if the given string is a Rust path that may have generic
+/// arguments (e.g. `my_crate::Thing::<()>`).
+///
+/// Generic arguments are only checked for balanced brackets.
+fn is_rust_type_path(s: &str) -> bool {
+    let mut depth = 0usize;
+    let mut path = String::with_capacity(s.len());
+    let mut args = String::new();
+    for c in s.chars() {
+        match c {
+            '<' => {
+                if depth == 0 {
+                    args.clear();
+                }
+                depth += 1;
+            }
+            '>' if depth == 0 => return false,
+            '>' => {
+                depth -= 1;
+                if depth == 0 && args.trim().is_empty() {
+                    return false;
+                }
             }
+            _ if depth > 0 => args.push(c),
+            _ => path.push(c),
         }
+    }
 
-        if let Some(adapter) = section.adapter.as_mut() {
-            *adapter = manifest_dir.join(adapter.as_path());
+    // A turbofish leaves a trailing separator
+    depth == 0 && is_rust_path(path.strip_suffix("::").unwrap_or(&path))
+}
+
+/// Determines if the given string is a Rust visibility (e.g. `pub(crate)`).
+fn is_rust_visibility(s: &str) -> bool {
+    let Some(rest) = s.strip_prefix("pub") else {
+        return false;
+    };
+
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return true;
+    }
+
+    let Some(scope) = rest
+        .strip_prefix('(')
+        .and_then(|r| r.strip_suffix(')'))
+        .map(str::trim)
+    else {
+        return false;
+    };
+
+    match scope.strip_prefix("in ") {
+        Some(path) => {
+            let path = path.trim();
+            ["crate", "self", "super"]
+                .iter()
+                .any(|root| path == *root || path.starts_with(&format!("{root}::")))
+                && path.split("::").all(|segment| {
+                    matches!(segment, "crate" | "self" | "super") || is_rust_identifier(segment)
+                })
         }
+        None => matches!(scope, "crate" | "self" | "super"),
+    }
+}
 
-        Ok(Self {
-            name: package.name.clone(),
-            version: package.version.clone(),
-            manifest_path: package.manifest_path.clone().into(),
-            modified_at,
-            section,
-            section_present,
-        })
+/// The prefix of `skip` entries that match only interfaces.
+pub const INTERFACE_SKIP_PREFIX: &str = "interface:";
+
+/// Determines if the given name matches a pattern that may contain `*` wildcards.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = parts.peekable();
+    if parts.peek().is_none() {
+        return rest.is_empty();
     }
 
-    /// Gets the target package name.
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+impl Bindings {
+    /// The names of the settings that may be overridden with comments.
+    const FIELDS: &'static [&'static str] = &[
+        "format",
+        "ownership",
+        "derives",
+        "derives_feature",
+        "additional_derives_ignore_errors",
+        "std_feature",
+        "no_std",
+        "export_prefix",
+        "export_macro_visibility",
+        "skip",
+        "only",
+        "rename",
+        "with",
+        "emit_type_section",
+        "type_section_suffix",
+        "runtime_path",
+        "stubs",
+        "stub_output",
+        "min_rust",
+        "features",
+        "all_features",
+    ];
+
+    /// The bindings features that require a minimum Rust version.
+    const REQUIRED_RUST_VERSIONS: &'static [RustVersionRequirement] = &[
+        // `rustfmt` accepts an edition only from the release stabilizing it
+        (
+            "format.edition = \"2021\"",
+            |b| formats_edition(b, "2021"),
+            (1, 56, 0),
+        ),
+        (
+            "format.edition = \"2024\"",
+            |b| formats_edition(b, "2024"),
+            (1, 85, 0),
+        ),
+    ];
+
+    /// Gets the settings for a build target with the given `cfg` settings.
     ///
-    /// Returns `None` if the target is not a registry package.
-    pub fn target_package(&self) -> Option<&PackageName> {
-        match &self.section.target {
-            Target::Package { name, .. } => Some(name),
-            _ => None,
+    /// The `cfg` entries matching the target are merged over these settings
+    /// in order of their expressions.
+    pub fn for_target_cfg(&self, target_cfg: &[(String, Option<String>)]) -> Result<Self> {
+        let mut settings = self.clone();
+        let cfg = std::mem::take(&mut settings.cfg);
+
+        let mut entries: Vec<_> = cfg.iter().collect();
+        entries.sort_by_key(|(expr, _)| *expr);
+        for (expr, entry) in entries {
+            if cfg_matches(expr, target_cfg)? {
+                log::debug!("applying bindings settings for `cfg({expr})`");
+                settings.merge(entry);
+            }
         }
+
+        Ok(settings)
     }
 
-    /// Gets the path to a local target.
+    /// Merges the given settings over these settings.
     ///
-    /// Returns `None` if the target is a registry package or
-    /// if a path is not specified and the default path does not exist.
-    pub fn target_path(&self) -> Option<Cow<Path>> {
-        match &self.section.target {
-            Target::Local {
-                path: Some(path), ..
-            } => Some(path.into()),
-            Target::Local { path: None, .. } => {
-                let path = self.manifest_path.parent().unwrap().join(DEFAULT_WIT_DIR);
+    /// Lists and tables are extended, flags are enabled, and other settings
+    /// are replaced when they differ from their defaults.
+    fn merge(&mut self, other: &Self) {
+        let default = Self::default();
+        if other.format != default.format {
+            self.format = other.format.clone();
+        }
 
-                if path.exists() {
-                    Some(path.into())
-                } else {
-                    None
+        if other.ownership != default.ownership {
+            self.ownership = other.ownership;
+        }
+
+        for derive in &other.derives {
+            if !self.derives.contains(derive) {
+                self.derives.push(derive.clone());
+            }
+        }
+
+        for (feature, derives) in &other.derives_feature {
+            let existing = self.derives_feature.entry(feature.clone()).or_default();
+            for derive in derives {
+                if !existing.contains(derive) {
+                    existing.push(derive.clone());
                 }
             }
-            Target::Package { .. } => None,
         }
+
+        self.additional_derives_ignore_errors |= other.additional_derives_ignore_errors;
+        self.stubs |= other.stubs;
+        self.std_feature |= other.std_feature;
+        self.no_std |= other.no_std;
+        self.emit_type_section &= other.emit_type_section;
+
+        for (setting, value) in [
+            (&mut self.export_prefix, &other.export_prefix),
+            (
+                &mut self.export_macro_visibility,
+                &other.export_macro_visibility,
+            ),
+            (&mut self.type_section_suffix, &other.type_section_suffix),
+            (&mut self.runtime_path, &other.runtime_path),
+        ] {
+            if value.is_some() {
+                *setting = value.clone();
+            }
+        }
+
+        if other.stub_output.is_some() {
+            self.stub_output = other.stub_output.clone();
+        }
+
+        if other.min_rust.is_some() {
+            self.min_rust = other.min_rust.clone();
+        }
+
+        for feature in &other.features {
+            if !self.features.contains(feature) {
+                self.features.push(feature.clone());
+            }
+        }
+
+        self.all_features |= other.all_features;
+
+        self.skip.extend(other.skip.iter().cloned());
+        self.only.extend(other.only.iter().cloned());
+        self.rename
+            .extend(other.rename.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.with
+            .extend(other.with.iter().map(|(k, v)| (k.clone(), v.clone())));
     }
 
-    /// Gets the target world.
+    /// Gets a representation of the settings for hashing.
     ///
-    /// Returns `None` if there is no target world.
-    pub fn target_world(&self) -> Option<&str> {
-        self.section.target.world()
+    /// The settings are serialized as JSON with sorted keys, so the
+    /// representation does not depend on the iteration order of the tables
+    /// in the settings.
+    fn canonical_string(&self) -> Result<String> {
+        canonical_json(self)
+    }
+
+    /// Gets the WIT features enabled by the settings.
+    ///
+    /// See [`ComponentSection::dependency_feature_set`] for the features of
+    /// a dependency.
+    pub fn feature_set(&self) -> FeatureSet {
+        FeatureSet {
+            all: self.all_features,
+            features: self.features.iter().cloned().collect(),
+        }
+    }
+
+    /// Gets the derives that are not among the commonly used derives.
+    ///
+    /// Paths into `std` or `core` are always considered known.
+    pub fn unknown_derives(&self) -> Vec<&str> {
+        self.derives
+            .iter()
+            .filter(|derive| {
+                let path = derive.trim_start_matches("::");
+                !KNOWN_DERIVES.contains(&path)
+                    && !path.starts_with("std::")
+                    && !path.starts_with("core::")
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Validates the bindings settings.
+    pub fn validate(&self) -> Result<(), MetadataError> {
+        if let Some(prefix) = &self.export_prefix {
+            // The prefix is joined directly with an identifier, so a trailing
+            // separator is permitted
+            let id = prefix.strip_suffix('-').unwrap_or(prefix);
+            wit_parser::validate_id(id).map_err(|e| MetadataError::InvalidValue {
+                field: "bindings.export_prefix",
+                reason: format!("invalid bindings export prefix `{prefix}`: {e}"),
+            })?;
+        }
+
+        if let Some(visibility) = &self.export_macro_visibility {
+            if !is_rust_visibility(visibility) {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.export_macro_visibility",
+                    reason: format!("invalid Rust visibility `{visibility}`"),
+                });
+            }
+        }
+
+        for (name, mapping) in &self.with {
+            let WithMapping::Path(path) = mapping else {
+                continue;
+            };
+
+            // Interface modules are appended to the path of a wildcard entry,
+            // so it cannot have generic arguments
+            let valid = if name == "*" {
+                is_rust_path(path)
+            } else {
+                is_rust_type_path(path)
+            };
+
+            if !valid {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.with",
+                    reason: format!("entry `{name}` has invalid Rust path `{path}`"),
+                });
+            }
+        }
+
+        if self.no_std && self.std_feature {
+            return Err(MetadataError::ConflictingFields {
+                first: "bindings.no_std",
+                second: "bindings.std_feature",
+            });
+        }
+
+        if !self.emit_type_section && self.type_section_suffix.is_some() {
+            // The suffix only applies to an emitted type section
+            return Err(MetadataError::ConflictingFields {
+                first: "bindings.type_section_suffix",
+                second: "bindings.emit_type_section = false",
+            });
+        }
+
+        if !self.stubs && self.stub_output.is_some() {
+            // The output path only applies to generated stubs
+            return Err(MetadataError::ConflictingFields {
+                first: "bindings.stub_output",
+                second: "bindings.stubs = false",
+            });
+        }
+
+        for entry in &self.skip {
+            if entry.strip_prefix(INTERFACE_SKIP_PREFIX) == Some("") {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.skip",
+                    reason: format!("entry `{entry}` does not name an interface"),
+                });
+            }
+        }
+
+        for entry in &self.only {
+            if let Some(pattern) = self.skip.iter().find(|p| matches_pattern(p, entry)) {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.only",
+                    reason: format!("entry `{entry}` is excluded by `skip` entry `{pattern}`"),
+                });
+            }
+        }
+
+        for derive in &self.derives {
+            if !is_rust_path(derive) {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.derives",
+                    reason: format!("`{derive}` is not a valid derive path"),
+                });
+            }
+        }
+
+        for (feature, derives) in &self.derives_feature {
+            let valid_feature = !feature.is_empty()
+                && feature
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.'));
+            if !valid_feature {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.derives_feature",
+                    reason: format!("`{feature}` is not a valid Cargo feature name"),
+                });
+            }
+
+            if let Some(derive) = derives.iter().find(|d| !is_rust_path(d)) {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.derives_feature",
+                    reason: format!("`{derive}` of feature `{feature}` is not a valid derive path"),
+                });
+            }
+        }
+
+        if let Some(path) = &self.runtime_path {
+            if !is_rust_path(path) {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.runtime_path",
+                    reason: format!("`{path}` is not a valid Rust path"),
+                });
+            }
+        }
+
+        let mut modules = HashMap::new();
+        for (name, module) in &self.rename {
+            if !is_rust_identifier(module) {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.rename",
+                    reason: format!("entry `{name}` has invalid module name `{module}`"),
+                });
+            }
+
+            if let Some(other) = modules.insert(module, name) {
+                let (first, second) = if other < name {
+                    (other, name)
+                } else {
+                    (name, other)
+                };
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.rename",
+                    reason: format!(
+                        "entries `{first}` and `{second}` both rename to module `{module}`"
+                    ),
+                });
+            }
+        }
+
+        for (expr, entry) in &self.cfg {
+            // Check the expression is well-formed by evaluating it against an
+            // empty set of settings
+            cfg_matches(expr, &[]).map_err(|e| MetadataError::InvalidValue {
+                field: "bindings.cfg",
+                reason: format!("{e:#}"),
+            })?;
+
+            if !entry.cfg.is_empty() {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.cfg",
+                    reason: format!("entry `{expr}` cannot contain nested `cfg` entries"),
+                });
+            }
+
+            if !entry.features.is_empty() || entry.all_features {
+                // The target world is resolved once for all build targets
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings.cfg",
+                    reason: format!("entry `{expr}` cannot select WIT features"),
+                });
+            }
+
+            entry.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Gets the names of the settings that differ from the given settings.
+    pub fn changed_settings(&self, other: &Self) -> Vec<&'static str> {
+        [
+            ("format", self.format != other.format),
+            ("ownership", self.ownership != other.ownership),
+            ("derives", self.derives != other.derives),
+            (
+                "derives_feature",
+                self.derives_feature != other.derives_feature,
+            ),
+            (
+                "additional_derives_ignore_errors",
+                self.additional_derives_ignore_errors != other.additional_derives_ignore_errors,
+            ),
+            ("std_feature", self.std_feature != other.std_feature),
+            ("no_std", self.no_std != other.no_std),
+            ("export_prefix", self.export_prefix != other.export_prefix),
+            (
+                "export_macro_visibility",
+                self.export_macro_visibility != other.export_macro_visibility,
+            ),
+            ("skip", self.skip != other.skip),
+            ("only", self.only != other.only),
+            ("rename", self.rename != other.rename),
+            ("with", self.with != other.with),
+            (
+                "emit_type_section",
+                self.emit_type_section != other.emit_type_section,
+            ),
+            (
+                "type_section_suffix",
+                self.type_section_suffix != other.type_section_suffix,
+            ),
+            ("runtime_path", self.runtime_path != other.runtime_path),
+            ("stubs", self.stubs != other.stubs),
+            ("stub_output", self.stub_output != other.stub_output),
+            ("cfg", self.cfg != other.cfg),
+            ("min_rust", self.min_rust != other.min_rust),
+            ("features", self.features != other.features),
+            ("all_features", self.all_features != other.all_features),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    /// Checks that the bindings features in use are supported by both the
+    /// active Rust version and the configured minimum Rust version.
+    pub fn check_rust_version(&self, active: &Version) -> Result<()> {
+        // Ignore any pre-release of the active toolchain (e.g. `-nightly`)
+        let active = Version::new(active.major, active.minor, active.patch);
+        let (version, source) = match &self.min_rust {
+            Some(min) if *min < active => (min.clone(), "the configured `min_rust` version"),
+            _ => (active, "the active `rustc`"),
+        };
+
+        for (feature, used, (major, minor, patch)) in Self::REQUIRED_RUST_VERSIONS {
+            let required = Version::new(*major, *minor, *patch);
+            if used(self) && version < required {
+                bail!(
+                    "bindings feature `{feature}` requires Rust {required} but {source} is Rust {version}\n\n\
+                     raise `min_rust` to at least {required} (updating the toolchain if necessary) \
+                     or remove `{feature}` from the `[package.metadata.component.bindings]` section of `Cargo.toml`"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            format: Default::default(),
+            ownership: Default::default(),
+            derives: Default::default(),
+            derives_feature: Default::default(),
+            additional_derives_ignore_errors: false,
+            std_feature: false,
+            no_std: false,
+            export_prefix: None,
+            export_macro_visibility: None,
+            skip: Default::default(),
+            only: Default::default(),
+            rename: Default::default(),
+            with: Default::default(),
+            emit_type_section: true,
+            type_section_suffix: None,
+            runtime_path: None,
+            stubs: false,
+            stub_output: None,
+            cfg: Default::default(),
+            min_rust: None,
+            features: Default::default(),
+            all_features: false,
+        }
+    }
+}
+
+/// The target of a component.
+///
+/// The target defines the world of the component being developed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Target {
+    /// The target is a world from a registry package.
+    Package {
+        /// The name of the target package (e.g. `wasi:http`).
+        name: PackageName,
+        /// The registry package being targeted.
+        package: RegistryPackage,
+        /// The name of the world being targeted.
+        ///
+        /// [Resolve::select_world][select-world] will be used
+        /// to select world.
+        ///
+        /// [select-world]: https://docs.rs/wit-parser/latest/wit_parser/struct.Resolve.html#method.select_world
+        world: Option<String>,
+    },
+    /// The target is a world from a local wit document.
+    Local {
+        /// The path to the wit document defining the world.
+        ///
+        /// Defaults to the `wit` directory.
+        path: Option<PathBuf>,
+        /// The name of the world being targeted.
+        ///
+        /// [Resolve::select_world][select-world] will be used
+        /// to select world.
+        ///
+        /// [select-world]: https://docs.rs/wit-parser/latest/wit_parser/struct.Resolve.html#method.select_world
+        world: Option<String>,
+        /// The dependencies of the wit document being targeted.
+        dependencies: HashMap<PackageName, Dependency>,
+    },
+    /// The target is a world from an archive of WIT files.
+    ///
+    /// The archive is extracted into a cache directory and the given
+    /// subdirectory of it is used as a local target.
+    Archive {
+        /// The path to the archive (`.tar.gz`, `.tgz`, or `.zip`).
+        path: PathBuf,
+        /// The subdirectory of the archive containing the WIT package.
+        subdir: Option<PathBuf>,
+        /// The name of the world being targeted.
+        ///
+        /// [Resolve::select_world][select-world] will be used
+        /// to select world.
+        ///
+        /// [select-world]: https://docs.rs/wit-parser/latest/wit_parser/struct.Resolve.html#method.select_world
+        world: Option<String>,
+        /// The dependencies of the wit document being targeted.
+        dependencies: HashMap<PackageName, Dependency>,
+    },
+}
+
+impl Target {
+    /// Gets the dependencies of the target.
+    pub fn dependencies(&self) -> Cow<HashMap<PackageName, Dependency>> {
+        match self {
+            Self::Package { name, package, .. } => Cow::Owned(HashMap::from_iter([(
+                name.clone(),
+                Dependency::Package(package.clone()),
+            )])),
+            Self::Local { dependencies, .. } | Self::Archive { dependencies, .. } => {
+                Cow::Borrowed(dependencies)
+            }
+        }
+    }
+
+    /// Gets the target world, if any.
+    pub fn world(&self) -> Option<&str> {
+        match self {
+            Self::Package { world, .. }
+            | Self::Local { world, .. }
+            | Self::Archive { world, .. } => world.as_deref(),
+        }
+    }
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Self::Local {
+            path: None,
+            world: None,
+            dependencies: HashMap::new(),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, version) = match s.split_once('@') {
+            Some((name, version)) => (
+                name,
+                version
+                    .parse()
+                    .map_err(|source| MetadataError::InvalidVersion {
+                        version: version.to_string(),
+                        source,
+                    })?,
+            ),
+            None => {
+                return Err(MetadataError::InvalidValue {
+                    field: "target",
+                    reason: "expected target format `<package-name>[/<world>]@<version>`"
+                        .to_string(),
+                })
+            }
+        };
+
+        let (name, world) = match name.split_once('/') {
+            Some((name, world)) => {
+                wit_parser::validate_id(world).map_err(|e| MetadataError::InvalidValue {
+                    field: "target",
+                    reason: format!("invalid target world name `{world}`: {e}"),
+                })?;
+                (name, Some(world.to_string()))
+            }
+            None => (name, None),
+        };
+
+        Ok(Self::Package {
+            name: name.parse().map_err(|e| MetadataError::InvalidValue {
+                field: "target",
+                reason: format!("invalid target package name `{name}`: {e}"),
+            })?,
+            package: RegistryPackage {
+                name: None,
+                version,
+                registry: None,
+                registries: Vec::new(),
+            },
+            world,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Target {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Target;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "a string or a table")
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Target::from_str(s).map_err(de::Error::custom)
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                #[derive(Default, Deserialize)]
+                #[serde(default, deny_unknown_fields)]
+                struct Entry {
+                    package: Option<String>,
+                    version: Option<VersionReq>,
+                    world: Option<String>,
+                    registry: Option<String>,
+                    path: Option<PathBuf>,
+                    archive: Option<PathBuf>,
+                    subdir: Option<PathBuf>,
+                    #[serde(deserialize_with = "deserialize_package_map")]
+                    dependencies: HashMap<PackageName, Dependency>,
+                }
+
+                let entry = Entry::deserialize(MapAccessDeserializer::new(map))?;
+
+                if let Some(path) = entry.archive {
+                    for (present, name) in [
+                        (entry.path.is_some(), "path"),
+                        (entry.package.is_some(), "package"),
+                        (entry.version.is_some(), "version"),
+                        (entry.registry.is_some(), "registry"),
+                    ] {
+                        if present {
+                            return Err(de::Error::custom(format!(
+                                "cannot specify both `{name}` and `archive` fields in a target entry"
+                            )));
+                        }
+                    }
+
+                    return Ok(Target::Archive {
+                        path,
+                        subdir: entry.subdir,
+                        world: entry.world,
+                        dependencies: entry.dependencies,
+                    });
+                }
+
+                if entry.subdir.is_some() {
+                    return Err(de::Error::custom(
+                        "the `subdir` field requires the `archive` field in a target entry",
+                    ));
+                }
+
+                match (entry.path, entry.package) {
+                    (None, Some(package)) => {
+                        for (present, name) in [(!entry.dependencies.is_empty(), "dependencies")] {
+                            if present {
+                                return Err(de::Error::custom(
+                                    format!("cannot specify both `{name}` and `package` fields in a target entry"),
+                                ));
+                            }
+                        }
+
+                        Ok(Target::Package {
+                            name: package.parse().map_err(de::Error::custom)?,
+                            package: RegistryPackage {
+                                name: None,
+                                version: entry
+                                    .version
+                                    .ok_or_else(|| de::Error::missing_field("version"))?,
+                                registry: entry.registry,
+                                registries: Vec::new(),
+                            },
+                            world: entry.world,
+                        })
+                    }
+                    (path, None) => {
+                        for (present, name) in [
+                            (entry.version.is_some(), "version"),
+                            (entry.registry.is_some(), "registry"),
+                        ] {
+                            if present {
+                                return Err(de::Error::custom(
+                                    format!("cannot specify both `{name}` and `path` fields in a target entry"),
+                                ));
+                            }
+                        }
+                        Ok(Target::Local {
+                            path,
+                            world: entry.world,
+                            dependencies: entry.dependencies,
+                        })
+                    }
+                    (Some(_), Some(_)) => Err(de::Error::custom(
+                        "cannot specify both `path` and `package` fields in a target entry",
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// The bundled WASI adapters for a WASI release.
+struct BundledAdapters {
+    /// The WASI version implemented by the adapters.
+    wasi_version: &'static str,
+    /// The adapter for command components.
+    command: &'static [u8],
+    /// The adapter for reactor components.
+    reactor: &'static [u8],
+    /// The adapter for `wasi:http/proxy` components.
+    proxy: &'static [u8],
+}
+
+/// The bundled WASI adapters, ordered from oldest to newest WASI release.
+const BUNDLED_ADAPTERS: &[BundledAdapters] = &[BundledAdapters {
+    wasi_version: "0.2.0",
+    command: include_bytes!(concat!(
+        "../adapters/",
+        env!("WASI_ADAPTER_VERSION"),
+        "/wasi_snapshot_preview1.command.wasm"
+    )),
+    reactor: include_bytes!(concat!(
+        "../adapters/",
+        env!("WASI_ADAPTER_VERSION"),
+        "/wasi_snapshot_preview1.reactor.wasm"
+    )),
+    proxy: include_bytes!(concat!(
+        "../adapters/",
+        env!("WASI_ADAPTER_VERSION"),
+        "/wasi_snapshot_preview1.proxy.wasm"
+    )),
+}];
+
+/// Gets the WASI versions of the bundled adapters, from oldest to newest.
+pub fn available_bundled_wasi_versions() -> Vec<Version> {
+    BUNDLED_ADAPTERS
+        .iter()
+        .map(|a| {
+            a.wasi_version
+                .parse()
+                .expect("invalid bundled WASI version")
+        })
+        .collect()
+}
+
+/// The kind of a bundled WASI adapter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdapterKind {
+    /// The adapter for command components.
+    Command,
+    /// The adapter for reactor components.
+    Reactor,
+    /// The adapter for `wasi:http/proxy` components.
+    Proxy,
+}
+
+/// The interfaces the `wasi:http/proxy` world may import.
+const PROXY_WORLD_IMPORTS: &[&str] = &[
+    "wasi:cli/stderr",
+    "wasi:cli/stdin",
+    "wasi:cli/stdout",
+    "wasi:clocks/monotonic-clock",
+    "wasi:clocks/wall-clock",
+    "wasi:http/outgoing-handler",
+    "wasi:http/types",
+    "wasi:io/error",
+    "wasi:io/poll",
+    "wasi:io/streams",
+    "wasi:random/random",
+];
+
+/// Determines if the given world matches the `wasi:http/proxy` world.
+///
+/// A matching world exports only `wasi:http/incoming-handler` and imports
+/// only interfaces of the proxy world; the versions of the interfaces are
+/// not considered.
+pub fn is_proxy_world(resolve: &Resolve, world: WorldId) -> bool {
+    let interface = |(key, item): (&WorldKey, &WorldItem)| match (key, item) {
+        (WorldKey::Interface(_), WorldItem::Interface(_)) => Some(unversioned_name(resolve, key)),
+        _ => None,
+    };
+
+    let world = &resolve.worlds[world];
+    let mut exports = world.exports.iter();
+    match (exports.next().map(interface), exports.next()) {
+        (Some(Some(name)), None) if name == "wasi:http/incoming-handler" => {}
+        _ => return false,
+    }
+
+    world.imports.iter().all(|(key, item)| match item {
+        WorldItem::Type(_) => true,
+        _ => {
+            interface((key, item)).is_some_and(|name| PROXY_WORLD_IMPORTS.contains(&name.as_str()))
+        }
+    })
+}
+
+impl AdapterKind {
+    /// Selects the bundled adapter that matches the given world.
+    ///
+    /// Worlds exporting `wasi:http/incoming-handler` use the proxy adapter
+    /// and worlds exporting `wasi:cli/run` use the command adapter; all other
+    /// worlds use the reactor adapter.
+    pub fn from_world(resolve: &Resolve, world: WorldId) -> Self {
+        let exports = |package: &str, interface: &str| {
+            resolve.worlds[world]
+                .exports
+                .values()
+                .any(|item| match item {
+                    WorldItem::Interface(id) => {
+                        let iface = &resolve.interfaces[*id];
+                        iface.name.as_deref() == Some(interface)
+                            && iface
+                                .package
+                                .map(|id| {
+                                    let name = &resolve.packages[id].name;
+                                    format!("{ns}:{name}", ns = name.namespace, name = name.name)
+                                        == package
+                                })
+                                .unwrap_or(false)
+                    }
+                    _ => false,
+                })
+        };
+
+        if exports("wasi:http", "incoming-handler") {
+            Self::Proxy
+        } else if exports("wasi:cli", "run") {
+            Self::Command
+        } else {
+            Self::Reactor
+        }
+    }
+
+    /// Gets the bundled adapter of this kind for the given WASI version.
+    ///
+    /// The adapter for the newest WASI version is used if no version is
+    /// given; an error is returned if no bundled adapter matches the version.
+    pub fn bundled(self, wasi_version: Option<&Version>) -> Result<&'static [u8]> {
+        let adapters = match wasi_version {
+            Some(version) => BUNDLED_ADAPTERS
+                .iter()
+                .find(|a| a.wasi_version == version.to_string())
+                .with_context(|| {
+                    format!(
+                        "WASI version `{version}` is not supported by the bundled adapters \
+                         (available versions: {versions})",
+                        versions = available_bundled_wasi_versions()
+                            .iter()
+                            .map(|v| format!("`{v}`"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })?,
+            None => BUNDLED_ADAPTERS.last().expect("no bundled adapters"),
+        };
+
+        Ok(match self {
+            Self::Command => adapters.command,
+            Self::Reactor => adapters.reactor,
+            Self::Proxy => adapters.proxy,
+        })
+    }
+}
+
+impl std::fmt::Display for AdapterKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Command => write!(f, "command"),
+            Self::Reactor => write!(f, "reactor"),
+            Self::Proxy => write!(f, "proxy"),
+        }
+    }
+}
+
+/// The setting for the built-in `wasi:http/proxy` adapter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum ProxyAdapter {
+    /// The component does not target the proxy world.
+    #[default]
+    Disabled,
+    /// The component uses the built-in proxy adapter.
+    Enabled,
+    /// The component targets the proxy world, but no adapter is applied.
+    ///
+    /// This is specified as `proxy = "none"`.
+    None,
+}
+
+impl ProxyAdapter {
+    /// Determines if the component targets the proxy world.
+    pub fn is_enabled(self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+
+    /// Gets the kind of the bundled adapter to apply, if any.
+    pub fn adapter(self) -> Option<AdapterKind> {
+        match self {
+            Self::Enabled => Some(AdapterKind::Proxy),
+            Self::Disabled | Self::None => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ProxyAdapter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = ProxyAdapter;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "a boolean or the string \"none\"")
+            }
+
+            fn visit_bool<E>(self, b: bool) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(if b {
+                    ProxyAdapter::Enabled
+                } else {
+                    ProxyAdapter::Disabled
+                })
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                match s {
+                    "none" => Ok(ProxyAdapter::None),
+                    _ => Err(de::Error::invalid_value(de::Unexpected::Str(s), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// The WIT features selected for a dependency.
+///
+/// These are the `features` and `default_features` fields of a dependency
+/// entry.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DependencyFeatures {
+    /// The WIT features to enable for the dependency.
+    pub features: Vec<String>,
+    /// Whether the features of the `bindings` settings are also enabled for
+    /// the dependency; defaults to true.
+    pub default_features: bool,
+}
+
+impl Default for DependencyFeatures {
+    fn default() -> Self {
+        Self {
+            features: Default::default(),
+            default_features: true,
+        }
+    }
+}
+
+/// Represents the `package.metadata.component` section in `Cargo.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ComponentSection {
+    /// The package name of the component, for publishing.
+    ///
+    /// Defaults to the crate name in the `namespace` namespace.
+    pub package: Option<PackageName>,
+    /// The namespace of the package name derived from the crate name when
+    /// `package` is not set.
+    pub namespace: Option<String>,
+    /// The package name to publish the component as, in place of `package`.
+    ///
+    /// This allows a fork to be published under another name; it has no
+    /// effect on building the component.
+    pub publish_as: Option<PackageName>,
+    /// The world targeted by the component.
+    pub target: Target,
+    /// The path to the WASI adapter to use.
+    pub adapter: Option<PathBuf>,
+    /// The dependencies of the component.
+    #[serde(deserialize_with = "deserialize_package_map")]
+    pub dependencies: HashMap<PackageName, Dependency>,
+    /// The registries to use for the component.
+    ///
+    /// A registry is either a warg registry URL or a table with a `warg` URL
+    /// or an `oci` registry namespace.
+    pub registries: HashMap<String, RegistryRef>,
+    /// The registries to use for the packages of each namespace (e.g.
+    /// `wasi`).
+    ///
+    /// A registry is either the name of a configured registry or a warg
+    /// registry URL; it is used for registry package dependencies that do
+    /// not specify their own registry.
+    pub namespace_registries: HashMap<String, String>,
+    /// The configuration for bindings generation.
+    pub bindings: Bindings,
+    /// Whether to use the built-in `wasi:http/proxy` adapter for the component.
+    ///
+    /// This should only be enabled when `adapter` is None; `"none"` targets
+    /// the proxy world without applying any adapter.
+    pub proxy: ProxyAdapter,
+    /// The WASI version of the bundled adapter to use.
+    ///
+    /// Defaults to the newest WASI version of the bundled adapters.
+    pub wasi_version: Option<Version>,
+    /// Whether to select the built-in adapter matching the target world when
+    /// neither `adapter` nor `proxy` is set.
+    pub auto_adapter: bool,
+    /// The names of registries that are permitted to use plain `http` URLs.
+    pub insecure_registries: Vec<String>,
+    /// Whether plain `http` URLs referring to the local host are permitted;
+    /// defaults to true.
+    pub allow_insecure_localhost: bool,
+    /// The retry and timeout policies of registry clients, keyed by
+    /// registry name.
+    pub registry_config: HashMap<String, RegistryClientConfig>,
+    /// The per-profile overrides of the target.
+    ///
+    /// Keys are Cargo build profile names (e.g. `dev` or `release`).
+    pub profiles: HashMap<String, Target>,
+    /// Whether to record the digests of local dependencies in the lock file.
+    ///
+    /// When set, a change to a local dependency's contents since the lock
+    /// file was written is reported on build.
+    pub lock_local_dependencies: bool,
+    /// The overridden sources of WIT packages.
+    ///
+    /// A patch replaces the source of a dependency with the same name, or,
+    /// for a local target, supplies a package required by a dependency.
+    #[serde(deserialize_with = "deserialize_package_map")]
+    pub patch: HashMap<PackageName, Dependency>,
+    /// The replaced WIT packages.
+    ///
+    /// Imports of interfaces from a key package are replaced with imports of
+    /// the interfaces with the same names from the value package.
+    pub replace: HashMap<PackageName, PackageName>,
+    /// The name of a separate crate to generate the bindings into.
+    ///
+    /// When set, a crate is generated instead of `src/bindings.rs`.
+    pub bindings_crate: Option<String>,
+    /// The directory of the bindings crate.
+    ///
+    /// Defaults to a directory named after the crate in the manifest directory.
+    pub bindings_crate_path: Option<PathBuf>,
+    /// Whether the nested directories of a local target directory are
+    /// searched for additional WIT packages.
+    ///
+    /// Each nested directory containing WIT files is a separate package.
+    pub recursive_wit: bool,
+    /// The packages to remove from the resolved dependencies.
+    ///
+    /// This prunes packages that are resolved but not used by the component;
+    /// excluding a package that is used is an error.
+    pub exclude_dependencies: Vec<PackageName>,
+    /// The paths to additional WebAssembly core modules to link into the
+    /// component.
+    ///
+    /// The modules are linked with the module built from the crate as shared
+    /// libraries, so the crate and each module must be built for dynamic
+    /// linking (with a `dylink.0` section). The exports of the module built
+    /// from the crate take precedence over those of the linked modules.
+    pub link: Vec<PathBuf>,
+    /// Whether to verify that each encoded component exports exactly the
+    /// exports of the target world.
+    ///
+    /// This catches code generation and linking mistakes after the build.
+    pub verify_world: bool,
+    /// The maximum number of packages fetched from registries concurrently.
+    ///
+    /// Defaults to [`DEFAULT_FETCH_CONCURRENCY`]; a limit of zero is treated
+    /// as a limit of one.
+    ///
+    /// [`DEFAULT_FETCH_CONCURRENCY`]: cargo_component_core::registry::DEFAULT_FETCH_CONCURRENCY
+    pub fetch_concurrency: Option<usize>,
+    /// The WIT features selected in the dependency entries of the section.
+    ///
+    /// These are taken from the dependency tables before the dependencies
+    /// are deserialized.
+    #[serde(skip_deserializing)]
+    pub dependency_features: HashMap<PackageName, DependencyFeatures>,
+}
+
+impl Default for ComponentSection {
+    fn default() -> Self {
+        Self {
+            package: None,
+            namespace: None,
+            publish_as: None,
+            target: Default::default(),
+            adapter: None,
+            dependencies: Default::default(),
+            registries: Default::default(),
+            namespace_registries: Default::default(),
+            bindings: Default::default(),
+            proxy: ProxyAdapter::Disabled,
+            wasi_version: None,
+            auto_adapter: false,
+            insecure_registries: Default::default(),
+            allow_insecure_localhost: true,
+            registry_config: Default::default(),
+            profiles: Default::default(),
+            lock_local_dependencies: false,
+            patch: Default::default(),
+            replace: Default::default(),
+            bindings_crate: None,
+            bindings_crate_path: None,
+            recursive_wit: false,
+            exclude_dependencies: Default::default(),
+            link: Default::default(),
+            verify_world: false,
+            fetch_concurrency: None,
+            dependency_features: Default::default(),
+        }
+    }
+}
+
+impl ComponentSection {
+    /// Gets the WIT features enabled for the given dependency.
+    ///
+    /// Unless the dependency disables `default_features`, the features of
+    /// the `bindings` settings are enabled along with its own features.
+    pub fn dependency_feature_set(&self, name: &PackageName) -> FeatureSet {
+        let Some(selected) = self.dependency_features.get(name) else {
+            return self.bindings.feature_set();
+        };
+
+        let mut set = if selected.default_features {
+            self.bindings.feature_set()
+        } else {
+            FeatureSet::default()
+        };
+        set.features.extend(selected.features.iter().cloned());
+        set
+    }
+
+    /// Validates the section and applies the target of the given profile.
+    ///
+    /// All paths in the section are made relative to the directory of the
+    /// given manifest path.
+    fn prepare(&mut self, manifest_path: &Path, profile: Option<&str>) -> Result<()> {
+        self.bindings.validate().with_context(|| {
+            format!(
+                "invalid bindings configuration in `{path}`",
+                path = manifest_path.display()
+            )
+        })?;
+
+        let invalid = || {
+            format!(
+                "invalid component metadata in `{path}`",
+                path = manifest_path.display()
+            )
+        };
+
+        if let Some(name) = &self.bindings_crate {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(MetadataError::InvalidValue {
+                    field: "bindings_crate",
+                    reason: format!("invalid crate name `{name}`"),
+                })
+                .with_context(invalid);
+            }
+        } else if self.bindings_crate_path.is_some() {
+            return Err(MetadataError::MissingField("bindings_crate")).with_context(invalid);
+        }
+
+        if let Some((name, target)) =
+            profile.and_then(|name| Some((name, self.profiles.get(name)?)))
+        {
+            log::debug!("using the target of profile `{name}`");
+            self.target = target.clone();
+        }
+
+        let manifest_dir = manifest_path.parent().with_context(|| {
+            format!(
+                "manifest path `{path}` has no parent directory",
+                path = manifest_path.display()
+            )
+        })?;
+
+        let namespace_registries = self.namespace_registries().with_context(invalid)?;
+
+        // Make all paths stored in the metadata relative to the manifest directory.
+        for target in std::iter::once(&mut self.target).chain(self.profiles.values_mut()) {
+            let dependencies = match target {
+                Target::Local {
+                    path, dependencies, ..
+                } => {
+                    if let Some(path) = path {
+                        *path = manifest_dir.join(path.as_path());
+                    }
+
+                    dependencies
+                }
+                Target::Archive {
+                    path, dependencies, ..
+                } => {
+                    *path = manifest_dir.join(path.as_path());
+                    dependencies
+                }
+                Target::Package { name, package, .. } => {
+                    apply_namespace_registry(&namespace_registries, name, package);
+                    continue;
+                }
+            };
+
+            for (name, dependency) in dependencies.iter_mut() {
+                join_dependency_path(manifest_dir, dependency);
+                if let Dependency::Package(package) = dependency {
+                    apply_namespace_registry(&namespace_registries, name, package);
+                }
+            }
+        }
+
+        for (name, dependency) in self.dependencies.iter_mut().chain(self.patch.iter_mut()) {
+            join_dependency_path(manifest_dir, dependency);
+            if let Dependency::Package(package) = dependency {
+                apply_namespace_registry(&namespace_registries, name, package);
+            }
+        }
+
+        if let Some(name) = &self.bindings_crate {
+            let path = self.bindings_crate_path.get_or_insert_with(|| name.into());
+            *path = manifest_dir.join(path.as_path());
+        }
+
+        if let Format::Options(FormatOptions {
+            config_path: Some(path),
+            ..
+        }) = &mut self.bindings.format
+        {
+            *path = manifest_dir.join(path.as_path());
+            if !path.exists() {
+                return Err(MetadataError::MissingPath(path.clone())).with_context(invalid);
+            }
+        }
+
+        if let Some(adapter) = self.adapter.as_mut() {
+            *adapter = manifest_dir.join(adapter.as_path());
+        }
+
+        for path in &mut self.link {
+            *path = manifest_dir.join(path.as_path());
+            if !path.is_file() {
+                return Err(MetadataError::MissingPath(path.clone())).with_context(invalid);
+            }
+
+            let mut header = [0; 8];
+            let is_core_wasm = fs::File::open(&*path)
+                .and_then(|mut file| file.read_exact(&mut header))
+                .map(|_| wasmparser::Parser::is_core_wasm(&header))
+                .unwrap_or(false);
+            if !is_core_wasm {
+                return Err(MetadataError::InvalidValue {
+                    field: "link",
+                    reason: format!(
+                        "`{path}` is not a WebAssembly core module",
+                        path = path.display()
+                    ),
+                })
+                .with_context(invalid);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets a representation of the section for hashing.
+    ///
+    /// The section, including the WIT features selected for dependencies, is
+    /// serialized as JSON with sorted keys, so the representation does not
+    /// depend on the iteration order of the tables in the section; paths are
+    /// made relative to the given manifest directory so that it also does not
+    /// depend on where the package is located.
+    fn canonical_string(&self, manifest_dir: &Path) -> Result<String> {
+        let relative = |path: &mut PathBuf| *path = relative_path(manifest_dir, path);
+        let dependencies = |dependencies: &mut HashMap<PackageName, Dependency>| {
+            for dependency in dependencies.values_mut() {
+                if let Dependency::Local(path) | Dependency::Archive { path, .. } = dependency {
+                    relative(path);
+                }
+            }
+        };
+        let target = |target: &mut Target| match target {
+            Target::Package { .. } => {}
+            Target::Local {
+                path,
+                dependencies: deps,
+                ..
+            } => {
+                path.iter_mut().for_each(relative);
+                dependencies(deps);
+            }
+            Target::Archive {
+                path,
+                dependencies: deps,
+                ..
+            } => {
+                relative(path);
+                dependencies(deps);
+            }
+        };
+
+        let mut section = self.clone();
+        target(&mut section.target);
+        section.profiles.values_mut().for_each(target);
+        dependencies(&mut section.dependencies);
+        dependencies(&mut section.patch);
+
+        if let Format::Options(FormatOptions {
+            config_path: Some(path),
+            ..
+        }) = &mut section.bindings.format
+        {
+            relative(path);
+        }
+
+        section
+            .adapter
+            .iter_mut()
+            .chain(section.bindings_crate_path.iter_mut())
+            .chain(section.bindings.stub_output.iter_mut())
+            .chain(section.link.iter_mut())
+            .for_each(relative);
+
+        canonical_json(&section)
+    }
+
+    /// Overrides a registry package target with the local WIT directory at
+    /// the given path, keeping the world of the target.
+    ///
+    /// This allows testing an unpublished version of the target package.
+    ///
+    /// Returns `false` if the target is not a registry package.
+    fn override_target_path(&mut self, dir: &Path) -> bool {
+        let Target::Package { world, .. } = &mut self.target else {
+            return false;
+        };
+
+        self.target = Target::Local {
+            path: Some(dir.to_path_buf()),
+            world: world.take(),
+            dependencies: Default::default(),
+        };
+        true
+    }
+
+    /// Gets the name of the registry to use for each namespace in
+    /// `namespace_registries`.
+    ///
+    /// A registry given as a URL is added to `registries` under the URL as
+    /// its name.
+    fn namespace_registries(&mut self) -> Result<HashMap<String, String>, MetadataError> {
+        let mut namespaces = HashMap::with_capacity(self.namespace_registries.len());
+        for (namespace, registry) in &self.namespace_registries {
+            if registry.contains("://") {
+                let url = Url::parse(registry).map_err(|e| MetadataError::InvalidValue {
+                    field: "namespace_registries",
+                    reason: format!(
+                        "invalid registry URL `{registry}` for namespace `{namespace}`: {e}"
+                    ),
+                })?;
+                self.registries
+                    .entry(registry.clone())
+                    .or_insert_with(|| url.into());
+            } else if registry != DEFAULT_REGISTRY_NAME && !self.registries.contains_key(registry) {
+                return Err(MetadataError::InvalidValue {
+                    field: "namespace_registries",
+                    reason: format!(
+                        "registry `{registry}` for namespace `{namespace}` does not exist in the configuration"
+                    ),
+                });
+            }
+
+            namespaces.insert(namespace.clone(), registry.clone());
+        }
+
+        Ok(namespaces)
+    }
+
+    /// Checks that the URL of the given registry is permitted to be used.
+    ///
+    /// Plain `http` URLs are rejected unless the registry is listed in
+    /// `insecure_registries` or the URL refers to the local host and
+    /// `allow_insecure_localhost` is set.
+    pub fn check_registry_url(&self, name: &str, url: &str) -> Result<()> {
+        let parsed = match Url::parse(url) {
+            Ok(url) if url.scheme() == "http" => url,
+            _ => return Ok(()),
+        };
+
+        if self.insecure_registries.iter().any(|r| r == name) {
+            return Ok(());
+        }
+
+        let is_localhost = match parsed.host() {
+            Some(Host::Domain(domain)) => domain == "localhost",
+            Some(Host::Ipv4(addr)) => addr.is_loopback(),
+            Some(Host::Ipv6(addr)) => addr.is_loopback(),
+            None => false,
+        };
+
+        if is_localhost && self.allow_insecure_localhost {
+            return Ok(());
+        }
+
+        bail!(
+            "component registry `{name}` uses insecure URL `{url}`\n\n\
+             to allow it, add `{name}` to the `insecure_registries` setting in the \
+             `[package.metadata.component]` section of `Cargo.toml`"
+        )
+    }
+
+    /// Checks that the URL of the registry used by the given dependency is
+    /// permitted to be used.
+    ///
+    /// Local dependencies are always permitted.
+    pub fn check_dependency_registry(
+        &self,
+        dependency: &Dependency,
+        default: Option<&str>,
+    ) -> Result<()> {
+        match dependency {
+            Dependency::Package(package) => {
+                for name in package.candidate_registries() {
+                    if let Some(RegistryRef::Oci(_)) = self.registries.get(name) {
+                        continue;
+                    }
+
+                    let url = find_url(Some(name), &self.registries, default)?;
+                    self.check_registry_url(name, &url)?;
+                }
+
+                Ok(())
+            }
+            Dependency::Local(_) | Dependency::Archive { .. } => Ok(()),
+        }
+    }
+
+    /// Gets the registry used by each registry package dependency.
+    ///
+    /// This includes the dependencies of the component, a registry package
+    /// target, and the dependencies of a local target; patches take
+    /// precedence over dependencies of the same name.
+    ///
+    /// The registry of a dependency is found as it is when resolving the
+    /// dependency, with `default` being the URL of the configured default
+    /// registry.
+    pub fn resolved_registries(
+        &self,
+        default: Option<&str>,
+    ) -> Result<HashMap<PackageName, RegistryRef>> {
+        let mut packages: HashMap<&PackageName, Option<&RegistryPackage>> = HashMap::new();
+        match &self.target {
+            Target::Package { name, package, .. } => {
+                packages.insert(name, Some(package));
+            }
+            Target::Local { dependencies, .. } | Target::Archive { dependencies, .. } => {
+                packages.extend(
+                    dependencies
+                        .iter()
+                        .map(|(name, dependency)| match dependency {
+                            Dependency::Package(package) => (name, Some(package)),
+                            Dependency::Local(_) | Dependency::Archive { .. } => (name, None),
+                        }),
+                );
+            }
+        }
+
+        for (name, dependency) in self.dependencies.iter().chain(&self.patch) {
+            packages.insert(
+                name,
+                match dependency {
+                    Dependency::Package(package) => Some(package),
+                    Dependency::Local(_) | Dependency::Archive { .. } => None,
+                },
+            );
+        }
+
+        let mut packages: Vec<_> = packages
+            .into_iter()
+            .filter_map(|(name, package)| Some((name, package?)))
+            .collect();
+        packages.sort_by_key(|(name, _)| name.to_string());
+
+        let mut registries = HashMap::with_capacity(packages.len());
+        for (name, package) in packages {
+            // Fallback registries are only used when the first is unavailable
+            let registry = package.candidate_registries()[0];
+            let registry = match self.registries.get(registry) {
+                Some(oci @ RegistryRef::Oci(_)) => oci.clone(),
+                _ => find_url(Some(registry), &self.registries, default)
+                    .and_then(|url| {
+                        Url::parse(&url).with_context(|| format!("invalid registry URL `{url}`"))
+                    })
+                    .with_context(|| format!("failed to find the registry of dependency `{name}`"))?
+                    .into(),
+            };
+            registries.insert(name.clone(), registry);
+        }
+
+        Ok(registries)
+    }
+}
+
+/// Makes the path of a local or archive dependency relative to the given
+/// directory.
+fn join_dependency_path(dir: &Path, dependency: &mut Dependency) {
+    match dependency {
+        Dependency::Local(path) | Dependency::Archive { path, .. } => {
+            *path = dir.join(path.as_path());
+        }
+        Dependency::Package(_) => {}
+    }
+}
+
+/// Appends a suggestion of the nearest valid field to an unknown field error.
+///
+/// Other errors are returned unchanged.
+fn suggest_unknown_field(error: serde_json::Error) -> anyhow::Error {
+    let message = error.to_string();
+    let Some(rest) = message.strip_prefix("unknown field `") else {
+        return error.into();
+    };
+    let Some((field, expected)) = rest.split_once('`') else {
+        return error.into();
+    };
+
+    // Each expected field name is enclosed in backticks
+    let suggestion = expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|candidate| (edit_distance(field, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (field.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance);
+
+    match suggestion {
+        Some((_, candidate)) => anyhow!("{message}; did you mean `{candidate}`?"),
+        None => error.into(),
+    }
+}
+
+/// Computes the Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+
+    prev[b.len()]
+}
+
+/// Sets the registry of a registry package to the registry of its namespace
+/// when the package does not specify a registry.
+fn apply_namespace_registry(
+    namespaces: &HashMap<String, String>,
+    name: &PackageName,
+    package: &mut RegistryPackage,
+) {
+    if package.registry.is_some() || !package.registries.is_empty() {
+        return;
+    }
+
+    let name = package.name.as_ref().unwrap_or(name);
+    if let Some(registry) = namespaces.get(name.namespace()) {
+        package.registry = Some(registry.clone());
+    }
+}
+
+/// Applies any `# cargo-component: bindings.<setting> = <value>` comments
+/// found in the given manifest over the component section.
+fn apply_comment_overrides(component: &mut Option<Value>, manifest: &str) -> Result<()> {
+    for line in manifest.lines() {
+        let Some(directive) = line.trim().strip_prefix(COMMENT_OVERRIDE_PREFIX) else {
+            continue;
+        };
+
+        let (path, value) = directive
+            .split_once('=')
+            .with_context(|| format!("expected `<path> = <value>` in override `{directive}`"))?;
+        let path = path.trim();
+
+        let field = match path.split_once('.') {
+            Some(("bindings", field)) if Bindings::FIELDS.contains(&field) => field,
+            _ => {
+                bail!("unknown override path `{path}`; only `bindings` settings may be overridden")
+            }
+        };
+
+        let value = Value::deserialize(value.trim().parse::<ValueDeserializer>()?)
+            .with_context(|| format!("invalid value for override `{path}`"))?;
+
+        log::debug!("overriding component metadata setting `{path}` with `{value}`");
+
+        let bindings = component
+            .get_or_insert_with(|| Value::Object(Default::default()))
+            .as_object_mut()
+            .context("component metadata is not a table")?
+            .entry("bindings")
+            .or_insert_with(|| Value::Object(Default::default()))
+            .as_object_mut()
+            .context("`bindings` setting is not a table")?;
+
+        bindings.insert(field.to_string(), value);
+    }
+
+    Ok(())
+}
+
+/// Replaces any `{ workspace = true }` entries in the dependency tables of
+/// the component section with the definitions from the
+/// `[workspace.metadata.component.dependencies]` table.
+///
+/// Paths of inherited local dependencies are relative to the workspace root.
+fn inherit_workspace_dependencies(
+    component: &mut Option<Value>,
+    workspace_metadata: &Value,
+    workspace_root: &Path,
+) -> Result<()> {
+    let Some(component) = component.as_mut() else {
+        return Ok(());
+    };
+
+    let inherited = workspace_metadata
+        .get("component")
+        .and_then(|c| c.get("dependencies"));
+
+    for pointer in ["/target/dependencies", "/dependencies"] {
+        let Some(table) = component
+            .pointer_mut(pointer)
+            .and_then(Value::as_object_mut)
+        else {
+            continue;
+        };
+
+        for (name, dependency) in table.iter_mut() {
+            let is_workspace = dependency
+                .as_object()
+                .map(|entry| entry.len() == 1 && entry.get("workspace") == Some(&Value::Bool(true)))
+                .unwrap_or(false);
+            if !is_workspace {
+                continue;
+            }
+
+            let mut definition = inherited
+                .and_then(|deps| deps.get(name))
+                .cloned()
+                .with_context(|| {
+                    format!(
+                        "dependency `{name}` is inherited from the workspace but \
+                         `[workspace.metadata.component.dependencies]` does not define it"
+                    )
+                })?;
+
+            for key in ["path", "archive"] {
+                if let Some(Value::String(path)) = definition.get_mut(key) {
+                    *path = workspace_root.join(path.as_str()).display().to_string();
+                }
+            }
+
+            log::debug!("inheriting dependency `{name}` from the workspace");
+            *dependency = definition;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the `features` and `default_features` fields from the dependency
+/// tables of the component section.
+///
+/// The dependency tables are those of the section, its target, and the
+/// targets of its profiles.
+fn take_dependency_features(
+    component: &mut Value,
+) -> Result<HashMap<PackageName, DependencyFeatures>> {
+    let mut pointers = vec![
+        "/target/dependencies".to_string(),
+        "/dependencies".to_string(),
+    ];
+    if let Some(profiles) = component.get("profiles").and_then(Value::as_object) {
+        pointers.extend(
+            profiles
+                .keys()
+                .map(|name| format!("/profiles/{name}/dependencies")),
+        );
+    }
+
+    let mut selected: HashMap<PackageName, DependencyFeatures> = HashMap::new();
+    for pointer in pointers {
+        let Some(table) = component
+            .pointer_mut(&pointer)
+            .and_then(Value::as_object_mut)
+        else {
+            continue;
+        };
+
+        for (name, dependency) in table.iter_mut() {
+            let Some(entry) = dependency.as_object_mut() else {
+                continue;
+            };
+
+            let fields: serde_json::Map<_, _> = ["features", "default_features"]
+                .into_iter()
+                .filter_map(|key| entry.remove(key).map(|value| (key.to_string(), value)))
+                .collect();
+            if fields.is_empty() {
+                continue;
+            }
+
+            let name: PackageName = name
+                .parse()
+                .with_context(|| format!("invalid dependency name `{name}`"))?;
+            let features: DependencyFeatures = from_value(Value::Object(fields))
+                .with_context(|| format!("invalid features of dependency `{name}`"))?;
+
+            match selected.get(&name) {
+                Some(prev) if *prev != features => bail!(
+                    "dependency `{name}` selects different features in different dependency tables"
+                ),
+                _ => {
+                    selected.insert(name, features);
+                }
+            }
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Used to construct component metadata without a `Cargo.toml`.
+///
+/// The metadata is validated and its paths are made relative to the manifest
+/// directory, as with [`ComponentMetadata::from_package`].
+#[derive(Default)]
+pub struct ComponentMetadataBuilder {
+    name: Option<String>,
+    version: Option<Version>,
+    manifest_path: Option<PathBuf>,
+    section: ComponentSection,
+}
+
+impl ComponentMetadataBuilder {
+    /// Creates a new component metadata builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the crate name of the component.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the crate version of the component; defaults to `0.1.0`.
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Sets the path of the manifest that relative paths are resolved against.
+    ///
+    /// The manifest file does not need to exist.
+    pub fn with_manifest_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.manifest_path = Some(path.into());
+        self
+    }
+
+    /// Sets the target of the component.
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.section.target = target;
+        self
+    }
+
+    /// Adds a dependency to the component.
+    pub fn with_dependency(mut self, name: PackageName, dependency: Dependency) -> Self {
+        self.section.dependencies.insert(name, dependency);
+        self
+    }
+
+    /// Sets the bindings generation configuration of the component.
+    pub fn with_bindings(mut self, bindings: Bindings) -> Self {
+        self.section.bindings = bindings;
+        self
+    }
+
+    /// Adds a registry to the component.
+    pub fn with_registry(
+        mut self,
+        name: impl Into<String>,
+        registry: impl Into<RegistryRef>,
+    ) -> Self {
+        self.section.registries.insert(name.into(), registry.into());
+        self
+    }
+
+    /// Builds the component metadata.
+    ///
+    /// Returns an error if the name or manifest path is not set or if the
+    /// metadata is invalid.
+    pub fn build(self) -> Result<ComponentMetadata> {
+        let name = self.name.ok_or(MetadataError::MissingField("name"))?;
+        let manifest_path = self
+            .manifest_path
+            .ok_or(MetadataError::MissingField("manifest_path"))?;
+
+        let mut section = self.section;
+        section.prepare(&manifest_path, None)?;
+
+        let modified_at = if manifest_path.is_file() {
+            crate::last_modified_time(&manifest_path)?
+        } else {
+            SystemTime::now()
+        };
+
+        Ok(ComponentMetadata {
+            name,
+            version: self.version.unwrap_or_else(|| Version::new(0, 1, 0)),
+            manifest_path,
+            modified_at,
+            section,
+            section_present: true,
+        })
+    }
+}
+
+/// The kind of an item imported or exported by a world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldItemKind {
+    /// The item is an interface.
+    Interface,
+    /// The item is a bare function.
+    Function,
+}
+
+/// An item imported or exported by the target world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldItemInfo {
+    /// The fully-qualified name of the item (e.g. `wasi:io/streams@0.2.0`).
+    pub name: String,
+    /// The kind of the item.
+    pub kind: WorldItemKind,
+}
+
+/// A resource exported by the target world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedResource {
+    /// The fully-qualified name of the interface defining the resource.
+    pub interface: String,
+    /// The name of the resource.
+    pub name: String,
+    /// Whether or not the resource has a constructor.
+    pub constructor: bool,
+    /// The names of the methods of the resource.
+    pub methods: Vec<String>,
+    /// The names of the static functions of the resource.
+    pub static_functions: Vec<String>,
+}
+
+/// A breaking change between a previous and the current version of a world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakingChange {
+    /// An exported interface or function was removed.
+    RemovedExport(String),
+    /// A function was removed from an exported interface.
+    RemovedFunction {
+        /// The name of the exported interface.
+        interface: String,
+        /// The name of the removed function.
+        function: String,
+    },
+    /// The signature of an exported function changed.
+    ChangedSignature {
+        /// The name of the function, qualified by its interface, if any.
+        function: String,
+        /// The previous signature of the function.
+        previous: String,
+        /// The current signature of the function.
+        current: String,
+    },
+}
+
+impl std::fmt::Display for BreakingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RemovedExport(name) => write!(f, "export `{name}` was removed"),
+            Self::RemovedFunction {
+                interface,
+                function,
+            } => write!(
+                f,
+                "function `{function}` was removed from exported interface `{interface}`"
+            ),
+            Self::ChangedSignature {
+                function,
+                previous,
+                current,
+            } => write!(
+                f,
+                "signature of function `{function}` changed from `{previous}` to `{current}`"
+            ),
+        }
+    }
+}
+
+/// Gets the name of a world item, without any package version.
+fn unversioned_name(resolve: &Resolve, key: &WorldKey) -> String {
+    match key {
+        WorldKey::Name(name) => name.clone(),
+        WorldKey::Interface(id) => {
+            let interface = &resolve.interfaces[*id];
+            let name = interface.name.as_deref().unwrap_or("<unnamed>");
+            match interface.package {
+                Some(pkg) => {
+                    let pkg = &resolve.packages[pkg].name;
+                    format!("{ns}:{pkg}/{name}", ns = pkg.namespace, pkg = pkg.name)
+                }
+                None => name.to_string(),
+            }
+        }
+    }
+}
+
+/// Renders a type for comparison between resolves.
+fn type_signature(resolve: &Resolve, ty: &Type) -> String {
+    let id = match ty {
+        Type::Id(id) => *id,
+        ty => return format!("{ty:?}").to_lowercase(),
+    };
+
+    let def = &resolve.types[id];
+    if let Some(name) = &def.name {
+        return name.clone();
+    }
+
+    let optional = |ty: &Option<Type>| match ty {
+        Some(ty) => type_signature(resolve, ty),
+        None => "_".to_string(),
+    };
+
+    match &def.kind {
+        TypeDefKind::Type(ty) => type_signature(resolve, ty),
+        TypeDefKind::List(ty) => format!("list<{}>", type_signature(resolve, ty)),
+        TypeDefKind::Option(ty) => format!("option<{}>", type_signature(resolve, ty)),
+        TypeDefKind::Result(r) => format!("result<{}, {}>", optional(&r.ok), optional(&r.err)),
+        TypeDefKind::Tuple(t) => format!(
+            "tuple<{}>",
+            t.types
+                .iter()
+                .map(|ty| type_signature(resolve, ty))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        TypeDefKind::Handle(Handle::Own(id)) => {
+            format!("own<{}>", type_signature(resolve, &Type::Id(*id)))
+        }
+        TypeDefKind::Handle(Handle::Borrow(id)) => {
+            format!("borrow<{}>", type_signature(resolve, &Type::Id(*id)))
+        }
+        kind => kind.as_str().to_string(),
+    }
+}
+
+/// Renders the signature of a function for comparison between resolves.
+fn function_signature(resolve: &Resolve, function: &Function) -> String {
+    let params = function
+        .params
+        .iter()
+        .map(|(name, ty)| format!("{name}: {}", type_signature(resolve, ty)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match &function.results {
+        Results::Anon(ty) => format!("func({params}) -> {}", type_signature(resolve, ty)),
+        Results::Named(results) if results.is_empty() => format!("func({params})"),
+        Results::Named(results) => format!(
+            "func({params}) -> ({})",
+            results
+                .iter()
+                .map(|(name, ty)| format!("{name}: {}", type_signature(resolve, ty)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Finds the breaking changes to the exports of a world between a previous
+/// and the current version.
+///
+/// Exported interfaces are matched by name regardless of package version.
+pub fn breaking_changes(
+    (resolve, world): (&Resolve, WorldId),
+    (previous, previous_world): (&Resolve, WorldId),
+) -> Vec<BreakingChange> {
+    let exports: HashMap<_, _> = resolve.worlds[world]
+        .exports
+        .iter()
+        .map(|(key, item)| (unversioned_name(resolve, key), item))
+        .collect();
+
+    let mut changes = Vec::new();
+    for (key, item) in &previous.worlds[previous_world].exports {
+        let name = unversioned_name(previous, key);
+        let current = match exports.get(&name) {
+            Some(item) => *item,
+            None => {
+                changes.push(BreakingChange::RemovedExport(name));
+                continue;
+            }
+        };
+
+        match (item, current) {
+            (WorldItem::Function(prev), WorldItem::Function(cur)) => {
+                let previous = function_signature(previous, prev);
+                let current = function_signature(resolve, cur);
+                if previous != current {
+                    changes.push(BreakingChange::ChangedSignature {
+                        function: name,
+                        previous,
+                        current,
+                    });
+                }
+            }
+            (WorldItem::Interface(prev), WorldItem::Interface(cur)) => {
+                let functions = &resolve.interfaces[*cur].functions;
+                for (function, prev) in &previous.interfaces[*prev].functions {
+                    let Some(cur) = functions.get(function) else {
+                        changes.push(BreakingChange::RemovedFunction {
+                            interface: name.clone(),
+                            function: function.clone(),
+                        });
+                        continue;
+                    };
+
+                    let previous = function_signature(previous, prev);
+                    let current = function_signature(resolve, cur);
+                    if previous != current {
+                        changes.push(BreakingChange::ChangedSignature {
+                            function: format!("{name}#{function}"),
+                            previous,
+                            current,
+                        });
+                    }
+                }
+            }
+            _ => changes.push(BreakingChange::RemovedExport(name)),
+        }
+    }
+
+    changes
+}
+
+/// The local WIT files contributing to the target world of a component.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalWitFiles {
+    /// Whether the target is a local WIT package.
+    ///
+    /// When `false`, the target is a registry package (or there is no
+    /// target) and there are no local WIT files.
+    pub local: bool,
+    /// The sorted paths of the WIT files.
+    pub paths: Vec<PathBuf>,
+}
+
+impl LocalWitFiles {
+    /// Determines if any of the files were modified after the given time.
+    pub fn modified_since(&self, time: SystemTime) -> Result<bool> {
+        for path in &self.paths {
+            if crate::last_modified_time(path)? > time {
+                log::debug!("WIT file `{path}` has been modified", path = path.display());
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// Serializes the given value as JSON with the keys of every object sorted.
+fn canonical_json(value: &impl Serialize) -> Result<String> {
+    fn sort(value: Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(k, v)| (k, sort(v)))
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .collect(),
+            ),
+            Value::Array(values) => Value::Array(values.into_iter().map(sort).collect()),
+            value => value,
+        }
+    }
+
+    Ok(sort(serde_json::to_value(value)?).to_string())
+}
+
+/// Updates the given hasher with the length-prefixed bytes.
+fn update(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+/// Collects the WIT files at the given path, recursing into directories.
+fn collect_wit_files(path: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        if path.is_file() {
+            paths.push(path.to_path_buf());
+        }
+
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(path)
+        .with_context(|| format!("failed to read directory `{path}`", path = path.display()))?;
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("failed to read directory `{path}`", path = path.display()))?
+            .path();
+        if path.is_dir() {
+            collect_wit_files(&path, paths)?;
+        } else if path.extension().and_then(|e| e.to_str()) == Some("wit") {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The differences between two component metadata instances.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetadataDiff {
+    /// The dependencies that are only present in the new metadata.
+    pub added_dependencies: Vec<PackageName>,
+    /// The dependencies that are only present in the old metadata.
+    pub removed_dependencies: Vec<PackageName>,
+    /// The dependencies present in both but with a different source.
+    pub changed_dependencies: Vec<PackageName>,
+    /// The old and new targets, if the target changed.
+    pub target: Option<(Target, Target)>,
+    /// The names of the bindings settings that changed.
+    pub bindings: Vec<&'static str>,
+}
+
+impl MetadataDiff {
+    /// Determines if there are no differences.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Represents cargo metadata for a WebAssembly component.
+#[derive(Debug, Clone)]
+pub struct ComponentMetadata {
+    /// The crate name.
+    pub name: String,
+    /// The version of the crate.
+    pub version: Version,
+    /// The path to the cargo manifest file.
+    pub manifest_path: PathBuf,
+    /// The last modified time of the manifest file.
+    pub modified_at: SystemTime,
+    /// The component section in `Cargo.toml`.
+    pub section: ComponentSection,
+    /// Whether the component section was present in `Cargo.toml`.
+    pub section_present: bool,
+}
+
+impl ComponentMetadata {
+    /// Creates a new component metadata for the given cargo package.
+    ///
+    /// If a profile is given and the component section has a target override
+    /// for it, the override is used in place of the base target.
+    pub fn from_package(package: &Package, profile: Option<&str>) -> Result<Self, MetadataError> {
+        Self::from_workspace_package(package, &Value::Null, Path::new(""), profile)
+    }
+
+    /// Creates a new component metadata for the given cargo package of a
+    /// workspace.
+    ///
+    /// Dependencies specified as `{ workspace = true }` are inherited from
+    /// the `[workspace.metadata.component.dependencies]` table of the given
+    /// workspace metadata.
+    pub fn from_workspace_package(
+        package: &Package,
+        workspace_metadata: &Value,
+        workspace_root: &Path,
+        profile: Option<&str>,
+    ) -> Result<Self, MetadataError> {
+        Self::load_workspace_package(package, workspace_metadata, workspace_root, profile)
+            .map_err(|e| MetadataError::manifest(package.manifest_path.as_std_path(), e))
+    }
+
+    fn load_workspace_package(
+        package: &Package,
+        workspace_metadata: &Value,
+        workspace_root: &Path,
+        profile: Option<&str>,
+    ) -> Result<Self> {
+        log::debug!(
+            "searching for component metadata in manifest `{path}`",
+            path = package.manifest_path
+        );
+
+        let mut component = package.metadata.get("component").cloned();
+        if std::env::var_os(ALLOW_COMMENT_OVERRIDES_ENV).is_some() {
+            let manifest = fs::read_to_string(&package.manifest_path).with_context(|| {
+                format!(
+                    "failed to read manifest `{path}`",
+                    path = package.manifest_path
+                )
+            })?;
+
+            apply_comment_overrides(&mut component, &manifest).with_context(|| {
+                format!(
+                    "failed to apply comment overrides from `{path}`",
+                    path = package.manifest_path
+                )
+            })?;
+        }
+
+        inherit_workspace_dependencies(&mut component, workspace_metadata, workspace_root)
+            .with_context(|| {
+                format!(
+                    "failed to inherit workspace dependencies in `{path}`",
+                    path = package.manifest_path
+                )
+            })?;
+
+        Self::from_section(package, component, profile)
+    }
+
+    /// Creates a new component metadata for the given cargo package with a
+    /// component section from an external source.
+    ///
+    /// The section is used in place of `package.metadata.component`; paths in
+    /// the section are still relative to the directory of the package's
+    /// manifest.
+    pub fn from_manifest_and_component(
+        package: &Package,
+        component_override: Value,
+    ) -> Result<Self, MetadataError> {
+        log::debug!(
+            "using an external component section for manifest `{path}`",
+            path = package.manifest_path
+        );
+
+        Self::from_section(package, Some(component_override), None)
+            .map_err(|e| MetadataError::manifest(package.manifest_path.as_std_path(), e))
+    }
+
+    fn from_section(
+        package: &Package,
+        component: Option<Value>,
+        profile: Option<&str>,
+    ) -> Result<Self> {
+        let mut section_present = false;
+        let mut dependency_features = HashMap::new();
+        let mut section: ComponentSection = match component {
+            Some(mut component) => {
+                section_present = true;
+                dependency_features =
+                    take_dependency_features(&mut component).with_context(|| {
+                        format!(
+                            "failed to read dependency features from `{path}`",
+                            path = package.manifest_path
+                        )
+                    })?;
+                from_value(component)
+                    .map_err(suggest_unknown_field)
+                    .with_context(|| {
+                        format!(
+                            "failed to deserialize component metadata from `{path}`",
+                            path = package.manifest_path
+                        )
+                    })?
+            }
+            None => {
+                log::debug!(
+                    "manifest `{path}` has no component metadata",
+                    path = package.manifest_path
+                );
+                Default::default()
+            }
+        };
+
+        section.dependency_features = dependency_features;
+        section.prepare(package.manifest_path.as_std_path(), profile)?;
+        if let Some(dir) = target_path_override(std::env::var_os(TARGET_PATH_ENV_VAR))? {
+            if section.override_target_path(&dir) {
+                log::debug!(
+                    "overriding the target of package `{name}` with directory `{dir}`",
+                    name = package.name,
+                    dir = dir.display()
+                );
+            }
+        }
+
+        let modified_at = crate::last_modified_time(package.manifest_path.as_std_path())?;
+
+        Ok(Self {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            manifest_path: package.manifest_path.clone().into(),
+            modified_at,
+            section,
+            section_present,
+        })
+    }
+
+    /// Compares this metadata with the given newer metadata.
+    ///
+    /// Both the component and target dependencies are compared; dependency
+    /// names in the result are sorted.
+    pub fn diff(&self, other: &Self) -> MetadataDiff {
+        fn dependencies(metadata: &ComponentMetadata) -> HashMap<PackageName, Dependency> {
+            let mut dependencies = metadata.section.target.dependencies().into_owned();
+            dependencies.extend(
+                metadata
+                    .section
+                    .dependencies
+                    .iter()
+                    .map(|(name, dep)| (name.clone(), dep.clone())),
+            );
+            dependencies
+        }
+
+        let old = dependencies(self);
+        let new = dependencies(other);
+        let mut diff = MetadataDiff::default();
+
+        for (name, dependency) in &new {
+            match old.get(name) {
+                Some(prev) if prev != dependency => diff.changed_dependencies.push(name.clone()),
+                Some(_) => {}
+                None => diff.added_dependencies.push(name.clone()),
+            }
+        }
+
+        diff.removed_dependencies = old
+            .keys()
+            .filter(|name| !new.contains_key(name))
+            .cloned()
+            .collect();
+
+        for names in [
+            &mut diff.added_dependencies,
+            &mut diff.removed_dependencies,
+            &mut diff.changed_dependencies,
+        ] {
+            names.sort_by_key(|name| name.to_string());
+        }
+
+        if self.section.target != other.section.target {
+            diff.target = Some((self.section.target.clone(), other.section.target.clone()));
+        }
+
+        diff.bindings = self
+            .section
+            .bindings
+            .changed_settings(&other.section.bindings);
+
+        diff
+    }
+
+    /// Gets the target package name.
+    ///
+    /// Returns `None` if the target is not a registry package.
+    pub fn target_package(&self) -> Option<&PackageName> {
+        match &self.section.target {
+            Target::Package { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Gets every registry package referenced by the component.
+    ///
+    /// This includes the target package and any registry packages in the
+    /// target and component dependency maps; duplicate references are
+    /// only returned once.
+    pub fn target_packages(&self) -> Vec<(PackageName, RegistryPackage)> {
+        let target = self.section.target.dependencies();
+        let mut packages: Vec<(PackageName, RegistryPackage)> = Vec::new();
+
+        for (name, dependency) in target.iter().chain(self.section.dependencies.iter()) {
+            if let Dependency::Package(package) = dependency {
+                if !packages.iter().any(|(n, p)| n == name && p == package) {
+                    packages.push((name.clone(), package.clone()));
+                }
+            }
+        }
+
+        packages
+    }
+
+    /// Checks that every registry package referenced by the component has an
+    /// explicit version requirement.
+    ///
+    /// A wildcard (`*`) requirement silently resolves to the latest version,
+    /// so an error naming each such package is returned.
+    pub fn check_pinned_versions(&self) -> Result<()> {
+        let mut unpinned: Vec<_> = self
+            .target_packages()
+            .into_iter()
+            .filter(|(_, package)| package.version.comparators.is_empty())
+            .map(|(name, _)| format!("`{name}`"))
+            .collect();
+
+        if unpinned.is_empty() {
+            return Ok(());
+        }
+
+        unpinned.sort();
+        unpinned.dedup();
+        bail!(
+            "package `{name}` has registry packages without an explicit version requirement: {unpinned}\n\n\
+             specify a version range for each package in the `[package.metadata.component]` section of `Cargo.toml` \
+             or build without `--require-pinned-targets`",
+            name = self.name,
+            unpinned = unpinned.join(", ")
+        )
+    }
+
+    /// Gets the fully-qualified names of all worlds in the target package.
+    ///
+    /// Each name is paired with whether or not the world is currently
+    /// selected as the target world.
+    pub async fn available_worlds(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<Vec<(String, bool)>> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+
+        let (resolve, pkg, _) = match BindingsGenerator::target_resolve(&resolution)? {
+            Some(target) => target,
+            None => return Ok(Vec::new()),
+        };
+
+        let selected = BindingsGenerator::select_target_world(&resolution, &resolve, pkg).ok();
+        let package = &resolve.packages[pkg];
+
+        Ok(package
+            .worlds
+            .iter()
+            .map(|(name, id)| (package.name.interface_id(name), Some(*id) == selected))
+            .collect())
+    }
+
+    /// Determines if the target world imports any `wasi` interface.
+    pub async fn uses_wasi(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<bool> {
+        Ok(!self
+            .wasi_imports(config, lock_file, network_allowed)
+            .await?
+            .is_empty())
+    }
+
+    /// Determines if the target world is the `wasi:http/proxy` world.
+    ///
+    /// See [`is_proxy_world`] for how the world is matched.
+    ///
+    /// Returns `Ok(false)` if the component has no target.
+    pub async fn uses_proxy_world(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<bool> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+
+        let (resolve, pkg, _) = match BindingsGenerator::target_resolve(&resolution)? {
+            Some(target) => target,
+            None => return Ok(false),
+        };
+
+        let world = BindingsGenerator::select_target_world(&resolution, &resolve, pkg)?;
+        Ok(is_proxy_world(&resolve, world))
+    }
+
+    /// Verifies that the given encoded component exports exactly the exports
+    /// of the target world.
+    ///
+    /// Exports excluded from the bindings by the `only` and `skip` bindings
+    /// settings are not expected.
+    pub async fn verify_component_world(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+        component: &[u8],
+    ) -> Result<()> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+
+        let (mut resolve, pkg, _) = match BindingsGenerator::target_resolve(&resolution)? {
+            Some(target) => target,
+            None => return Ok(()),
+        };
+
+        let world = BindingsGenerator::select_target_world(&resolution, &resolve, pkg)?;
+        let bindings = &self.section.bindings;
+        BindingsGenerator::filter_world(&mut resolve, world, &bindings.only, &bindings.skip);
+        check_component_exports(&resolve, world, component)
+    }
+
+    /// Gets the names of the `wasi` packages imported by the target world.
+    ///
+    /// The names are sorted and contain no duplicates.
+    pub async fn wasi_imports(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<Vec<wit_parser::PackageName>> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+
+        let (resolve, pkg, _) = match BindingsGenerator::target_resolve(&resolution)? {
+            Some(target) => target,
+            None => return Ok(Vec::new()),
+        };
+
+        let world = BindingsGenerator::select_target_world(&resolution, &resolve, pkg)?;
+        let mut names: Vec<_> = resolve.worlds[world]
+            .imports
+            .values()
+            .filter_map(|item| match item {
+                WorldItem::Interface(id) => resolve.interfaces[*id].package,
+                _ => None,
+            })
+            .map(|id| &resolve.packages[id].name)
+            .filter(|name| name.namespace == "wasi")
+            .cloned()
+            .collect();
+
+        names.sort_by_key(|name| name.to_string());
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Gets the minimum WASI version required by the target world.
+    ///
+    /// This is the highest version of the `wasi` packages imported by the
+    /// target world; returns `Ok(None)` if the world imports no versioned
+    /// `wasi` packages.
+    pub async fn min_wasi_version(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<Option<Version>> {
+        Ok(self
+            .wasi_imports(config, lock_file, network_allowed)
+            .await?
+            .into_iter()
+            .filter_map(|name| name.version)
+            .max())
+    }
+
+    /// Gets the interfaces and functions imported by the target world.
+    ///
+    /// Returns an empty list if the component has no target.
+    pub async fn imports(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<Vec<WorldItemInfo>> {
+        self.world_items(config, lock_file, network_allowed, false)
+            .await
+    }
+
+    /// Gets the interfaces and functions exported by the target world.
+    ///
+    /// Returns an empty list if the component has no target.
+    pub async fn exports(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<Vec<WorldItemInfo>> {
+        self.world_items(config, lock_file, network_allowed, true)
+            .await
+    }
+
+    /// Gets the resources exported by the target world.
+    ///
+    /// Resources are listed in the order of the exported interfaces defining
+    /// them.
+    ///
+    /// Returns an empty list if the component has no target.
+    pub async fn exported_resources(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<Vec<ExportedResource>> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+
+        let (resolve, pkg, _) = match BindingsGenerator::target_resolve(&resolution)? {
+            Some(target) => target,
+            None => return Ok(Vec::new()),
+        };
+
+        let world =
+            &resolve.worlds[BindingsGenerator::select_target_world(&resolution, &resolve, pkg)?];
+
+        let mut resources = Vec::new();
+        for (key, item) in &world.exports {
+            let WorldItem::Interface(id) = item else {
+                continue;
+            };
+
+            let interface = &resolve.interfaces[*id];
+            for (name, ty) in &interface.types {
+                if !matches!(resolve.types[*ty].kind, TypeDefKind::Resource) {
+                    continue;
+                }
+
+                let mut resource = ExportedResource {
+                    interface: resolve.name_world_key(key),
+                    name: name.clone(),
+                    constructor: false,
+                    methods: Vec::new(),
+                    static_functions: Vec::new(),
+                };
+
+                for function in interface.functions.values() {
+                    match function.kind {
+                        FunctionKind::Constructor(id) if id == *ty => resource.constructor = true,
+                        FunctionKind::Method(id) if id == *ty => {
+                            resource.methods.push(function.item_name().to_string())
+                        }
+                        FunctionKind::Static(id) if id == *ty => resource
+                            .static_functions
+                            .push(function.item_name().to_string()),
+                        _ => {}
+                    }
+                }
+
+                resources.push(resource);
+            }
+        }
+
+        Ok(resources)
+    }
+
+    async fn world_items(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+        exports: bool,
+    ) -> Result<Vec<WorldItemInfo>> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+
+        let (resolve, pkg, _) = match BindingsGenerator::target_resolve(&resolution)? {
+            Some(target) => target,
+            None => return Ok(Vec::new()),
+        };
+
+        let world =
+            &resolve.worlds[BindingsGenerator::select_target_world(&resolution, &resolve, pkg)?];
+
+        let items = if exports {
+            &world.exports
+        } else {
+            &world.imports
+        };
+
+        Ok(items
+            .iter()
+            .filter_map(|(key, item)| {
+                let kind = match item {
+                    WorldItem::Interface(_) => WorldItemKind::Interface,
+                    WorldItem::Function(_) => WorldItemKind::Function,
+                    WorldItem::Type(_) => return None,
+                };
+
+                Some(WorldItemInfo {
+                    name: resolve.name_world_key(key),
+                    kind,
+                })
+            })
+            .collect())
+    }
+
+    /// Checks the target world for breaking changes against a previous version.
+    ///
+    /// The previous version is given as a resolve, such as one decoded from a
+    /// published package or parsed from WIT; its world is the one with the
+    /// same name in a package of the same name as the target world.
+    ///
+    /// Returns an empty list if the component has no target.
+    pub async fn check_compatibility(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+        previous: &Resolve,
+    ) -> Result<Vec<BreakingChange>> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+
+        let (resolve, pkg, _) = match BindingsGenerator::target_resolve(&resolution)? {
+            Some(target) => target,
+            None => return Ok(Vec::new()),
+        };
+
+        let world = BindingsGenerator::select_target_world(&resolution, &resolve, pkg)?;
+        let name = &resolve.packages[pkg].name;
+        let world_name = &resolve.worlds[world].name;
+        let previous_world = previous
+            .packages
+            .iter()
+            .filter(|(_, p)| p.name.namespace == name.namespace && p.name.name == name.name)
+            .find_map(|(_, p)| p.worlds.get(world_name))
+            .with_context(|| {
+                format!(
+                    "the previous version does not contain world `{world_name}` of package `{ns}:{pkg}`",
+                    ns = name.namespace,
+                    pkg = name.name
+                )
+            })?;
+
+        Ok(breaking_changes(
+            (&resolve, world),
+            (previous, *previous_world),
+        ))
+    }
+
+    /// Prints the target world and its transitive dependencies as WIT.
+    ///
+    /// Each package is printed as its own document starting with its
+    /// `package` declaration, with dependencies preceding their dependents.
+    /// Only the selected world of the target package is printed.
+    ///
+    /// Returns `Ok(None)` if the component has no target.
+    pub async fn world_wit(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<Option<String>> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+
+        let (mut resolve, pkg, _) = match BindingsGenerator::target_resolve(&resolution)? {
+            Some(target) => target,
+            None => return Ok(None),
+        };
+
+        let world = BindingsGenerator::select_target_world(&resolution, &resolve, pkg)?;
+        resolve.packages[pkg].worlds.retain(|_, id| *id == world);
+
+        let mut required = HashSet::new();
+        let mut stack = vec![pkg];
+        while let Some(id) = stack.pop() {
+            if required.insert(id) {
+                stack.extend(resolve.package_direct_deps(id));
+            }
+        }
+
+        let mut wit = String::new();
+        for id in resolve.topological_packages() {
+            if !required.contains(&id) {
+                continue;
+            }
+
+            let printed = WitPrinter::default().print(&resolve, id).with_context(|| {
+                format!(
+                    "failed to print WIT package `{name}`",
+                    name = resolve.packages[id].name
+                )
+            })?;
+
+            if !wit.is_empty() {
+                wit.push('\n');
+            }
+
+            wit.push_str(&printed);
+        }
+
+        Ok(Some(wit))
+    }
+
+    /// Computes a fingerprint of everything that affects the generated bindings.
+    ///
+    /// The fingerprint is a hex-encoded SHA-256 digest of the effective
+    /// component section, the WASI adapter, the digests of the resolved target
+    /// and component dependencies, the resolved target world, and the contents
+    /// of the target's WIT files.
+    ///
+    /// The digests of registry component dependencies are taken from the
+    /// given lock file; an error is returned if a dependency is not locked.
+    ///
+    /// Paths are hashed relative to the manifest directory so that the
+    /// fingerprint does not depend on where the package is located.
+    pub async fn fingerprint(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<String> {
+        let resolution = self
+            .target_resolution(config, lock_file, network_allowed)
+            .await?;
+        let manifest_dir = self.manifest_dir()?;
+        let mut hasher = Sha256::new();
+        update(
+            &mut hasher,
+            self.section.canonical_string(manifest_dir)?.as_bytes(),
+        );
+        update(&mut hasher, self.adapter_digest()?.as_bytes());
+
+        let mut resolutions: Vec<_> = resolution.target_resolutions.values().collect();
+        resolutions.sort_by_key(|r| r.name().to_string());
+        update(&mut hasher, &(resolutions.len() as u64).to_le_bytes());
+        for resolution in resolutions {
+            update(&mut hasher, resolution.name().as_ref().as_bytes());
+            match resolution {
+                DependencyResolution::Registry(res) => {
+                    update(&mut hasher, res.package.as_ref().as_bytes());
+                    update(&mut hasher, res.version.to_string().as_bytes());
+                    update(&mut hasher, res.digest.to_string().as_bytes());
+                }
+                DependencyResolution::Local(res) => {
+                    update(&mut hasher, res.digest()?.to_string().as_bytes());
+                }
+            }
+        }
+
+        // The digests of registry dependencies are taken from the lock file
+        // rather than resolving the dependencies again
+        let mut dependencies: Vec<_> = self
+            .section
+            .dependencies
+            .iter()
+            .map(|(name, dependency)| (name, self.section.patch.get(name).unwrap_or(dependency)))
+            .collect();
+        dependencies.sort_by_key(|(name, _)| name.to_string());
+        update(&mut hasher, &(dependencies.len() as u64).to_le_bytes());
+        for (name, dependency) in dependencies {
+            update(&mut hasher, name.as_ref().as_bytes());
+            let path = match dependency {
+                Dependency::Package(package) => {
+                    let locked = lock_file
+                        .map(|lock_file| {
+                            lock_file.locked_versions(
+                                package.name.as_ref().unwrap_or(name),
+                                &package.version,
+                            )
+                        })
+                        .unwrap_or_default();
+                    if locked.is_empty() {
+                        bail!(
+                            "dependency `{name}` with requirement `{requirement}` is not in the lock file; run `cargo component update` to lock it",
+                            requirement = package.version
+                        );
+                    }
+
+                    update(&mut hasher, &(locked.len() as u64).to_le_bytes());
+                    for (registry, locked) in locked {
+                        update(&mut hasher, registry.as_bytes());
+                        update(&mut hasher, locked.version.to_string().as_bytes());
+                        update(&mut hasher, locked.digest.to_string().as_bytes());
+                    }
+
+                    continue;
+                }
+                Dependency::Local(path) => path.clone(),
+                Dependency::Archive { path, subdir } => extract_archive(path, subdir.as_deref())?,
+            };
+
+            let local = LocalResolution {
+                name: name.clone(),
+                path,
+            };
+            update(&mut hasher, local.digest()?.to_string().as_bytes());
+        }
+
+        let mut files = Vec::new();
+        match BindingsGenerator::target_resolve(&resolution)? {
+            Some((resolve, pkg, sources)) => {
+                let world = BindingsGenerator::select_target_world(&resolution, &resolve, pkg)?;
+                let package = &resolve.packages[pkg];
+                update(
+                    &mut hasher,
+                    package
+                        .name
+                        .interface_id(&resolve.worlds[world].name)
+                        .as_bytes(),
+                );
+
+                for source in sources {
+                    let contents = fs::read(&source).with_context(|| {
+                        format!("failed to read WIT file `{path}`", path = source.display())
+                    })?;
+
+                    let path = relative_path(manifest_dir, &source)
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                        .collect::<Vec<_>>()
+                        .join("/");
+
+                    files.push((path, Sha256::digest(contents)));
+                }
+            }
+            None => update(&mut hasher, &[]),
+        }
+
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files.dedup_by(|a, b| a.0 == b.0);
+        for (path, digest) in files {
+            update(&mut hasher, path.as_bytes());
+            update(&mut hasher, &digest);
+        }
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect())
+    }
+
+    /// Computes a hash of the inputs to generating the bindings of the given
+    /// world with the given settings.
+    ///
+    /// The hash is a hex-encoded SHA-256 digest of the settings, the WIT of
+    /// every package in the resolve, the selected world, and the WASI adapter
+    /// (as a changed adapter requires the component to be rebuilt); the
+    /// bindings only need to be generated again when the hash changes.
+    pub fn bindings_hash(
+        &self,
+        settings: &Bindings,
+        resolve: &Resolve,
+        world: WorldId,
+    ) -> Result<String> {
+        let mut hasher = Sha256::new();
+        update(&mut hasher, env!("CARGO_PKG_VERSION").as_bytes());
+        update(&mut hasher, settings.canonical_string()?.as_bytes());
+        update(&mut hasher, &[self.section.bindings_crate.is_some() as u8]);
+        update(&mut hasher, self.adapter_digest()?.as_bytes());
+
+        let mut packages: Vec<_> = resolve
+            .packages
+            .iter()
+            .map(|(id, package)| (package.name.to_string(), id))
+            .collect();
+        packages.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, id) in packages {
+            let wit = WitPrinter::default()
+                .print(resolve, id)
+                .with_context(|| format!("failed to print WIT of package `{name}`"))?;
+            update(&mut hasher, name.as_bytes());
+            update(&mut hasher, wit.as_bytes());
+        }
+
+        let world = &resolve.worlds[world];
+        let package = world
+            .package
+            .map(|id| resolve.packages[id].name.to_string());
+        update(&mut hasher, package.unwrap_or_default().as_bytes());
+        update(&mut hasher, world.name.as_bytes());
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect())
+    }
+
+    /// Computes a digest of the WASI adapter used to encode the component.
+    ///
+    /// For an `adapter` setting, this is a hex-encoded SHA-256 digest of the
+    /// adapter file contents; otherwise, it is a digest of the version and
+    /// kind of the built-in adapter.
+    ///
+    /// Returns an error if the adapter file cannot be read.
+    pub fn adapter_digest(&self) -> Result<String> {
+        let digest = match self.adapter_path() {
+            Some(path) => Sha256::digest(fs::read(&path).with_context(|| {
+                format!(
+                    "failed to read module adapter `{path}`",
+                    path = path.display()
+                )
+            })?),
+            None => Sha256::digest(format!(
+                "built-in:{version}:{wasi}:{kind}",
+                version = env!("WASI_ADAPTER_VERSION"),
+                wasi = self
+                    .section
+                    .wasi_version
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+                kind = match self.section.proxy {
+                    ProxyAdapter::Enabled => "proxy",
+                    ProxyAdapter::None => "none",
+                    ProxyAdapter::Disabled if self.section.auto_adapter => "auto",
+                    ProxyAdapter::Disabled => "default",
+                }
+            )),
+        };
+
+        Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+    }
+
+    async fn target_resolution(
+        &self,
+        config: &Config,
+        lock_file: Option<LockFileResolver<'_>>,
+        network_allowed: bool,
+    ) -> Result<PackageDependencyResolution<'_>> {
+        let target_resolutions = PackageDependencyResolution::resolve_target_deps(
+            config,
+            self,
+            lock_file,
+            FrozenMode::from_network_allowed(network_allowed),
+        )
+        .await
+        .with_context(|| match self.target_package() {
+            Some(name) if !network_allowed => format!(
+                "target package `{name}` has not been fetched and network access is disabled; \
+                 run the command without `--offline` to fetch it"
+            ),
+            _ => format!(
+                "failed to resolve the target dependencies of package `{name}`",
+                name = self.name
+            ),
+        })?;
+
+        Ok(PackageDependencyResolution {
+            metadata: self,
+            target_resolutions,
+            resolutions: Default::default(),
+        })
+    }
+
+    /// Gets the directory containing the manifest file.
+    ///
+    /// Returns an error if the manifest path has no parent directory.
+    pub fn manifest_dir(&self) -> Result<&Path> {
+        self.manifest_path.parent().with_context(|| {
+            format!(
+                "manifest path `{path}` has no parent directory",
+                path = self.manifest_path.display()
+            )
+        })
+    }
+
+    /// Gets the file name of the component produced by building the crate.
+    ///
+    /// Components are built from the crate's library, whose name is the
+    /// crate name with hyphens replaced by underscores unless overridden by
+    /// the `name` of the `[lib]` table in the manifest. A crate without a
+    /// library is built from its binary, which keeps the crate name as is.
+    pub fn output_filename(&self) -> Result<String> {
+        let manifest = match fs::read_to_string(&self.manifest_path) {
+            Ok(contents) => contents
+                .parse::<toml_edit::DocumentMut>()
+                .with_context(|| {
+                    format!(
+                        "failed to parse manifest `{path}`",
+                        path = self.manifest_path.display()
+                    )
+                })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "failed to read manifest `{path}`",
+                        path = self.manifest_path.display()
+                    )
+                })
+            }
+        };
+
+        let lib = manifest.get("lib");
+        if let Some(name) = lib.and_then(|lib| lib.get("name")).and_then(|n| n.as_str()) {
+            return Ok(format!("{name}.wasm"));
+        }
+
+        let name = if lib.is_some() || self.manifest_dir()?.join("src/lib.rs").is_file() {
+            self.name.replace('-', "_")
+        } else {
+            self.name.clone()
+        };
+
+        Ok(format!("{name}.wasm"))
+    }
+
+    /// Gets the path to a local target.
+    ///
+    /// The path may be a directory or a single WIT file; the packages in the
+    /// `deps` directory beside a WIT file are available to it.
+    ///
+    /// An archive target is extracted and the path to its extracted WIT
+    /// package is returned.
+    ///
+    /// Returns `Ok(None)` if the target is a registry package or
+    /// if a path is not specified and the default path does not exist.
+    pub fn target_path(&self) -> Result<Option<Cow<Path>>> {
+        match &self.section.target {
+            Target::Local {
+                path: Some(path), ..
+            } => Ok(Some(path.into())),
+            Target::Local { path: None, .. } => {
+                let path = self.manifest_dir()?.join(DEFAULT_WIT_DIR);
+
+                if path.exists() {
+                    Ok(Some(path.into()))
+                } else {
+                    Ok(None)
+                }
+            }
+            Target::Archive { path, subdir, .. } => {
+                Ok(Some(extract_archive(path, subdir.as_deref())?.into()))
+            }
+            Target::Package { .. } => Ok(None),
+        }
+    }
+
+    /// Gets the WIT files of a local target and its local dependencies.
+    ///
+    /// Directories are searched recursively, including any `deps` directory.
+    /// For an archive target, the archive itself is also included.
+    pub fn local_wit_files(&self) -> Result<LocalWitFiles> {
+        let mut paths = Vec::new();
+        let dependencies = match &self.section.target {
+            Target::Local { dependencies, .. } => dependencies,
+            Target::Archive {
+                path, dependencies, ..
+            } => {
+                paths.push(path.clone());
+                dependencies
+            }
+            Target::Package { .. } => return Ok(LocalWitFiles::default()),
+        };
+
+        if let Some(path) = self.target_path()? {
+            collect_wit_files(&path, &mut paths)?;
+
+            if path.is_file() {
+                if let Some(dir) = path.parent() {
+                    collect_wit_files(&dir.join("deps"), &mut paths)?;
+                }
+            }
+        }
+
+        let manifest_dir = self.manifest_dir()?;
+        for dependency in dependencies.values() {
+            match dependency {
+                Dependency::Local(path) => collect_wit_files(&manifest_dir.join(path), &mut paths)?,
+                Dependency::Archive { path, .. } => paths.push(manifest_dir.join(path)),
+                Dependency::Package(_) => {}
+            }
+        }
+
+        paths.sort();
+        paths.dedup();
+
+        Ok(LocalWitFiles { local: true, paths })
+    }
+
+    /// Gets the package name to publish the component as.
+    ///
+    /// This is the `publish_as` setting if present; otherwise, it is the
+    /// [package name](Self::package_name) of the component.
+    pub fn publish_name(&self) -> Result<Cow<PackageName>> {
+        match &self.section.publish_as {
+            Some(name) => Ok(Cow::Borrowed(name)),
+            None => self.package_name(),
+        }
+    }
+
+    /// Gets the package name of the component.
+    ///
+    /// This is the `package` setting if present; otherwise, it is derived
+    /// from the crate name, with underscores converted to hyphens, in the
+    /// namespace of the `namespace` setting.
+    pub fn package_name(&self) -> Result<Cow<PackageName>> {
+        if let Some(name) = &self.section.package {
+            return Ok(Cow::Borrowed(name));
+        }
+
+        let namespace = self.section.namespace.as_deref().with_context(|| {
+            format!(
+                "package `{name}` is missing a `package.metadata.component.package` or `package.metadata.component.namespace` setting in manifest `{path}`",
+                name = self.name,
+                path = self.manifest_path.display()
+            )
+        })?;
+
+        let name = format!("{namespace}:{name}", name = self.name.replace('_', "-"));
+        name.parse().map(Cow::Owned).with_context(|| {
+            format!(
+                "crate name `{crate_name}` does not form a valid package name `{name}`; \
+                 specify the `package.metadata.component.package` setting in manifest `{path}`",
+                crate_name = self.name,
+                path = self.manifest_path.display()
+            )
+        })
+    }
+
+    /// Gets the URL of the registry used by each registry package dependency.
+    ///
+    /// See [`ComponentSection::resolved_registries`].
+    pub fn resolved_registries(
+        &self,
+        config: &Config,
+    ) -> Result<HashMap<PackageName, RegistryRef>> {
+        self.section
+            .resolved_registries(config.warg().home_url.as_deref())
+    }
+
+    /// Gets the distinct URLs of the registries the component is resolved
+    /// from and published to.
+    ///
+    /// The registry published to is the given registry, or the default
+    /// registry if `None`. OCI registries have no URL and are not included.
+    pub fn registry_urls(
+        &self,
+        config: &Config,
+        publish_registry: Option<&str>,
+    ) -> Result<BTreeSet<Url>> {
+        let mut urls: BTreeSet<_> = self
+            .resolved_registries(config)?
+            .into_values()
+            .filter_map(|registry| match registry {
+                RegistryRef::Warg { url, .. } => Some(url),
+                RegistryRef::Oci(_) => None,
+            })
+            .collect();
+
+        let url = find_url(
+            publish_registry,
+            &self.section.registries,
+            config.warg().home_url.as_deref(),
+        )
+        .context("failed to find the registry to publish to")?;
+        let name = self.publish_name()?;
+        let url = expand_url(&url, &name, &self.version.to_string())?;
+        urls.insert(Url::parse(&url).with_context(|| format!("invalid registry URL `{url}`"))?);
+
+        Ok(urls)
+    }
+
+    /// Gets the target world.
+    ///
+    /// The `CARGO_COMPONENT_WORLD` environment variable, if set, takes
+    /// precedence over the world specified in the manifest.
+    ///
+    /// Returns `None` if there is no target world.
+    pub fn target_world(&self) -> Option<Cow<str>> {
+        self.target_world_with_env(std::env::var(WORLD_ENV_VAR).ok())
+    }
+
+    /// Gets the target world, given the value of the `CARGO_COMPONENT_WORLD`
+    /// environment variable.
+    fn target_world_with_env(&self, env_world: Option<String>) -> Option<Cow<str>> {
+        match env_world {
+            Some(world) if !world.is_empty() => Some(world.into()),
+            _ => self.section.target.world().map(Into::into),
+        }
+    }
+
+    /// Gets the path to the module adapter, if any.
+    ///
+    /// The `CARGO_COMPONENT_ADAPTER` environment variable, if set, takes
+    /// precedence over both the adapter specified in the manifest and the
+    /// built-in adapters.
+    ///
+    /// Returns `None` if a built-in adapter should be used.
+    pub fn adapter_path(&self) -> Option<Cow<Path>> {
+        self.adapter_path_with_env(std::env::var_os(ADAPTER_ENV_VAR))
+    }
+
+    /// Gets the path to the module adapter, given the value of the
+    /// `CARGO_COMPONENT_ADAPTER` environment variable.
+    fn adapter_path_with_env(&self, env_path: Option<OsString>) -> Option<Cow<Path>> {
+        match env_path {
+            Some(path) if !path.is_empty() => Some(PathBuf::from(path).into()),
+            _ => self.section.adapter.as_deref().map(Into::into),
+        }
+    }
+
+    /// Gets the local directory that overrides the registry package target of
+    /// the component with the `CARGO_COMPONENT_TARGET_PATH` environment
+    /// variable.
+    ///
+    /// Returns `None` if the target is not overridden.
+    pub fn target_path_override(&self) -> Result<Option<PathBuf>> {
+        self.target_path_override_with_env(std::env::var_os(TARGET_PATH_ENV_VAR))
+    }
+
+    /// Gets the local directory that overrides the registry package target,
+    /// given the value of the `CARGO_COMPONENT_TARGET_PATH` environment
+    /// variable.
+    fn target_path_override_with_env(&self, env_path: Option<OsString>) -> Result<Option<PathBuf>> {
+        Ok(target_path_override(env_path)?.filter(|dir| {
+            matches!(&self.section.target, Target::Local { path: Some(path), .. } if path == dir)
+        }))
+    }
+
+    /// Creates a copy of the metadata with the component section modified by
+    /// the given function.
+    ///
+    /// The copy is considered modified now, so any bindings generated from
+    /// the original metadata are out of date for the copy.
+    pub fn with_overridden_section(&self, f: impl FnOnce(&mut ComponentSection)) -> Self {
+        let mut metadata = self.clone();
+        f(&mut metadata.section);
+        metadata.modified_at = SystemTime::now();
+        metadata
+    }
+}
+
+/// Checks that the given encoded component exports exactly the exports of
+/// the given world.
+///
+/// The error names the missing and extra exports of the component.
+fn check_component_exports(resolve: &Resolve, world: WorldId, component: &[u8]) -> Result<()> {
+    let (actual, actual_world) =
+        match wit_component::decode(component).context("failed to decode the component")? {
+            DecodedWasm::Component(resolve, world) => (resolve, world),
+            DecodedWasm::WitPackage(..) => bail!("expected a component but found a WIT package"),
+        };
+
+    let exports = |resolve: &Resolve, world: WorldId| -> BTreeSet<String> {
+        resolve.worlds[world]
+            .exports
+            .iter()
+            .filter(|(_, item)| !matches!(item, WorldItem::Type(_)))
+            .map(|(key, _)| resolve.name_world_key(key))
+            .collect()
+    };
+
+    let expected = exports(resolve, world);
+    let actual = exports(&actual, actual_world);
+    let list = |names: Vec<&String>| {
+        names
+            .into_iter()
+            .map(|n| format!("`{n}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let missing = list(expected.difference(&actual).collect());
+    let extra = list(actual.difference(&expected).collect());
+    if missing.is_empty() && extra.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "the component does not match target world `{world}`",
+        world = resolve.worlds[world].name
+    );
+    if !missing.is_empty() {
+        message.push_str(&format!("\n  missing exports: {missing}"));
+    }
+
+    if !extra.is_empty() {
+        message.push_str(&format!("\n  extra exports: {extra}"));
+    }
+
+    bail!(message)
+}
+
+/// Gets the directory given by the value of the `CARGO_COMPONENT_TARGET_PATH`
+/// environment variable, relative to the current directory.
+///
+/// Returns `None` if the variable is not set or is empty.
+fn target_path_override(env_path: Option<OsString>) -> Result<Option<PathBuf>> {
+    match env_path {
+        Some(path) if !path.is_empty() => Ok(Some(
+            std::env::current_dir()
+                .context("failed to get the current directory")?
+                .join(path),
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Gets `path` relative to `base`, assuming both paths are absolute.
+fn relative_path(base: &Path, path: &Path) -> PathBuf {
+    let mut base = base.components().peekable();
+    let mut path = path.components().peekable();
+    while let (Some(a), Some(b)) = (base.peek(), path.peek()) {
+        if a != b {
+            break;
+        }
+
+        base.next();
+        path.next();
+    }
+
+    base.map(|_| Component::ParentDir).chain(path).collect()
+}
+
+#[cfg(test)]
+impl ComponentMetadata {
+    /// Creates component metadata for tests with the given section.
+    ///
+    /// Unlike with [`ComponentMetadataBuilder`], the section is used as is.
+    pub(crate) fn for_test(
+        name: &str,
+        manifest_path: impl Into<PathBuf>,
+        section: ComponentSection,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            version: Version::new(0, 1, 0),
+            manifest_path: manifest_path.into(),
+            modified_at: SystemTime::now(),
+            section,
+            section_present: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cargo_component_core::{
+        lock::{LockFile, LockedPackage, LockedPackageVersion},
+        terminal::{Color, Terminal, Verbosity},
+    };
+
+    #[test]
+    fn it_checks_insecure_registries() {
+        let section: ComponentSection = toml_edit::de::from_str(
+            r#"
+registries = { secure = "https://example.com", local = "http://127.0.0.1:8090", internal = "http://internal.example.com", other = "http://other.example.com" }
+insecure_registries = ["internal"]
+"#,
+        )
+        .unwrap();
+
+        for (name, registry) in &section.registries {
+            let res = section.check_registry_url(name, registry.warg_url().unwrap().as_str());
+            if name == "other" {
+                assert!(res
+                    .unwrap_err()
+                    .to_string()
+                    .contains("component registry `other` uses insecure URL"));
+            } else {
+                res.unwrap();
+            }
+        }
+
+        let section: ComponentSection = toml_edit::de::from_str(
+            r#"
+registries = { local = "http://localhost:8090" }
+allow_insecure_localhost = false
+"#,
+        )
+        .unwrap();
+
+        assert!(section
+            .check_registry_url("local", &section.registries["local"].to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn it_gets_publish_names() {
+        let mut metadata = ComponentMetadata::for_test(
+            "my_component",
+            "Cargo.toml",
+            ComponentSection {
+                package: Some("foo:explicit".parse().unwrap()),
+                namespace: Some("bar".to_string()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(metadata.publish_name().unwrap().to_string(), "foo:explicit");
+
+        // A fork may be published under another name
+        metadata.section.publish_as = Some("ours:fork".parse().unwrap());
+        assert_eq!(metadata.publish_name().unwrap().to_string(), "ours:fork");
+        assert_eq!(metadata.package_name().unwrap().to_string(), "foo:explicit");
+        metadata.section.publish_as = None;
+
+        assert!(
+            toml_edit::de::from_str::<ComponentSection>(r#"publish_as = "not a name""#).is_err()
+        );
+
+        metadata.section.package = None;
+        assert_eq!(
+            metadata.publish_name().unwrap().to_string(),
+            "bar:my-component"
+        );
+
+        metadata.name = "my__component".to_string();
+        assert!(metadata
+            .publish_name()
+            .unwrap_err()
+            .to_string()
+            .contains("does not form a valid package name `bar:my--component`"));
+
+        metadata.section.namespace = None;
+        assert!(metadata
+            .publish_name()
+            .unwrap_err()
+            .to_string()
+            .contains("`package.metadata.component.namespace`"));
+    }
+
+    #[test]
+    fn it_resolves_dependency_registries() {
+        let mut section: ComponentSection = toml_edit::de::from_str(
+            r#"
+target = { path = "wit", dependencies = { "foo:target" = "1.0.0", "foo:local" = { path = "local" } } }
+registries = { default = "https://default.example.com", other = "https://other.example.com" }
+
+[dependencies]
+"foo:named" = { version = "1.0.0", registry = "other" }
+"foo:unnamed" = "1.0.0"
+
+[patch]
+"foo:local" = { version = "1.0.0", registry = "other" }
+"#,
+        )
+        .unwrap();
+
+        let urls = section
+            .resolved_registries(Some("https://config.example.com"))
+            .unwrap();
+        let url = |name: &str| urls[&name.parse::<PackageName>().unwrap()].to_string();
+        assert_eq!(urls.len(), 4);
+        assert_eq!(url("foo:target"), "https://default.example.com/");
+        assert_eq!(url("foo:local"), "https://other.example.com/");
+        assert_eq!(url("foo:named"), "https://other.example.com/");
+        assert_eq!(url("foo:unnamed"), "https://default.example.com/");
+
+        // Without a default in the section, the configured default is used
+        section.registries.remove(DEFAULT_REGISTRY_NAME);
+        let urls = section
+            .resolved_registries(Some("https://config.example.com"))
+            .unwrap();
+        assert_eq!(
+            urls[&"foo:unnamed".parse::<PackageName>().unwrap()].to_string(),
+            "https://config.example.com/"
+        );
+
+        // The first unresolved dependency is reported
+        assert!(section
+            .resolved_registries(None)
+            .unwrap_err()
+            .to_string()
+            .contains("dependency `foo:target`"));
+    }
+
+    #[test]
+    fn it_checks_component_exports() {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(
+                wit_parser::UnresolvedPackage::parse(
+                    Path::new("test.wit"),
+                    r#"package foo:bar;
+
+interface handler {
+    handle: func();
+}
+
+world full {
+    export handler;
+    export run: func();
+}
+
+world partial {
+    export run: func();
+    export extra: func();
+}
+"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let encode = |world: &str| {
+            let world = resolve.select_world(pkg, Some(world)).unwrap();
+            let mut module = wit_component::dummy_module(&resolve, world);
+            wit_component::embed_component_metadata(
+                &mut module,
+                &resolve,
+                world,
+                wit_component::StringEncoding::UTF8,
+            )
+            .unwrap();
+            wit_component::ComponentEncoder::default()
+                .module(&module)
+                .unwrap()
+                .validate(true)
+                .encode()
+                .unwrap()
+        };
+
+        let full = resolve.select_world(pkg, Some("full")).unwrap();
+        check_component_exports(&resolve, full, &encode("full")).unwrap();
+
+        let err = check_component_exports(&resolve, full, &encode("partial"))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "the component does not match target world `full`\n  missing exports: `foo:bar/handler`\n  extra exports: `extra`"
+        );
+    }
+
+    #[test]
+    fn it_lists_distinct_registry_urls() {
+        let section: ComponentSection = toml_edit::de::from_str(
+            r#"
+package = "foo:component"
+target = "foo:target@1.0.0"
+registries = { default = "https://default.example.com", other = "https://other.example.com", publish = "https://publish.example.com", oci = { oci = "ghcr.io/example" } }
+
+[dependencies]
+"foo:named" = { version = "1.0.0", registry = "other" }
+"foo:fallback" = { version = "1.0.0", registries = ["other", "default"] }
+"foo:unnamed" = "1.0.0"
+"foo:oci" = { version = "1.0.0", registry = "oci" }
+"foo:local" = { path = "local" }
+"#,
+        )
+        .unwrap();
+        let metadata = ComponentMetadata::for_test("component", "Cargo.toml", section);
+
+        let mut config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        config.warg.home_url = Some("https://config.example.com".to_string());
+
+        let urls = |registry| {
+            metadata
+                .registry_urls(&config, registry)
+                .unwrap()
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            urls(None),
+            ["https://default.example.com/", "https://other.example.com/"]
+        );
+        assert_eq!(
+            urls(Some("publish")),
+            [
+                "https://default.example.com/",
+                "https://other.example.com/",
+                "https://publish.example.com/"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_deserializes_registry_client_configs() {
+        let section: ComponentSection = toml_edit::de::from_str(
+            r#"
+[registry_config.flaky]
+timeout_secs = 10
+max_retries = 5
+
+[registry_config.slow]
+timeout_secs = 120
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            section.registry_config["flaky"],
+            RegistryClientConfig {
+                timeout_secs: 10,
+                max_retries: 5,
+            }
+        );
+        assert_eq!(
+            section.registry_config["slow"],
+            RegistryClientConfig {
+                timeout_secs: 120,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn it_selects_bundled_adapters_by_wasi_version() {
+        let versions = available_bundled_wasi_versions();
+        assert!(!versions.is_empty());
+
+        let latest = versions.last().unwrap();
+        assert_eq!(
+            AdapterKind::Reactor.bundled(Some(latest)).unwrap(),
+            AdapterKind::Reactor.bundled(None).unwrap()
+        );
+
+        let section: ComponentSection =
+            toml_edit::de::from_str(r#"wasi_version = "0.1.0""#).unwrap();
+        let err = AdapterKind::Command
+            .bundled(section.wasi_version.as_ref())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("WASI version `0.1.0` is not supported"));
+        for version in &versions {
+            assert!(err.contains(&format!("`{version}`")));
+        }
+    }
+
+    #[test]
+    fn it_deserializes_proxy_adapters() {
+        let section: ComponentSection = toml_edit::de::from_str("proxy = true").unwrap();
+        assert_eq!(section.proxy, ProxyAdapter::Enabled);
+        assert_eq!(section.proxy.adapter(), Some(AdapterKind::Proxy));
+
+        // The proxy world is still targeted, but no adapter is applied
+        let section: ComponentSection = toml_edit::de::from_str(r#"proxy = "none""#).unwrap();
+        assert_eq!(section.proxy, ProxyAdapter::None);
+        assert!(section.proxy.is_enabled());
+        assert_eq!(section.proxy.adapter(), None);
+
+        let section: ComponentSection = toml_edit::de::from_str("proxy = false").unwrap();
+        assert!(!section.proxy.is_enabled());
+
+        assert!(toml_edit::de::from_str::<ComponentSection>(r#"proxy = "reactor""#).is_err());
+    }
+
+    #[test]
+    fn it_applies_comment_overrides() {
+        let mut component = Some(serde_json::json!({ "bindings": { "derives": ["Clone"] } }));
+
+        apply_comment_overrides(
+            &mut component,
+            r#"[package]
+name = "foo"
+# cargo-component: bindings.format = false
+
+[package.metadata.component.bindings]
+derives = ["Clone"]
+"#,
+        )
+        .unwrap();
+
+        let section: ComponentSection = from_value(component.unwrap()).unwrap();
+        assert!(!section.bindings.format.enabled());
+        assert_eq!(section.bindings.derives, ["Clone"]);
+
+        let err = apply_comment_overrides(&mut None, "# cargo-component: bindings.unknown = 1")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unknown override path `bindings.unknown`"));
+    }
+
+    #[test]
+    fn it_validates_export_macro_visibilities() {
+        for visibility in ["pub", "pub(crate)", "pub(super)", "pub(in crate::foo)"] {
+            let bindings = Bindings {
+                export_macro_visibility: Some(visibility.to_string()),
+                ..Default::default()
+            };
+            bindings.validate().unwrap();
+        }
+
+        for visibility in [
+            "",
+            "crate",
+            "pub(foo)",
+            "pub(in foo)",
+            "pub(crate",
+            "pub(in crate::1)",
+        ] {
+            let bindings = Bindings {
+                export_macro_visibility: Some(visibility.to_string()),
+                ..Default::default()
+            };
+            assert!(matches!(
+                bindings.validate().unwrap_err(),
+                MetadataError::InvalidValue {
+                    field: "bindings.export_macro_visibility",
+                    ..
+                }
+            ));
+        }
+    }
+
+    #[test]
+    fn it_validates_with_paths() {
+        for path in [
+            "crate::audio",
+            "my_crate::Thing::<()>",
+            "other::Wrapper<u32, Vec<u8>>",
+        ] {
+            let bindings = Bindings {
+                with: HashMap::from([(
+                    "foo:bar/audio".to_string(),
+                    WithMapping::Path(path.to_string()),
+                )]),
+                ..Default::default()
+            };
+            bindings.validate().unwrap();
+        }
+
+        for (name, path) in [
+            ("foo:bar/audio", "my_crate::Thing<"),
+            ("foo:bar/audio", "my_crate::Thing<>"),
+            ("foo:bar/audio", "my crate"),
+            ("*", "other::<u32>"),
+        ] {
+            let bindings = Bindings {
+                with: HashMap::from([(name.to_string(), WithMapping::Path(path.to_string()))]),
+                ..Default::default()
+            };
+            assert!(matches!(
+                bindings.validate().unwrap_err(),
+                MetadataError::InvalidValue {
+                    field: "bindings.with",
+                    reason,
+                } if reason.contains(&format!("invalid Rust path `{path}`"))
+            ));
+        }
+    }
+
+    #[test]
+    fn it_validates_export_prefixes() {
+        for prefix in ["foo", "foo-", "foo-bar-"] {
+            let bindings = Bindings {
+                export_prefix: Some(prefix.to_string()),
+                ..Default::default()
+            };
+            bindings.validate().unwrap();
+        }
+
+        for prefix in ["", "-", "Foo_", "foo--", "1foo"] {
+            let bindings = Bindings {
+                export_prefix: Some(prefix.to_string()),
+                ..Default::default()
+            };
+            assert!(matches!(
+                bindings.validate().unwrap_err(),
+                MetadataError::InvalidValue {
+                    field: "bindings.export_prefix",
+                    reason,
+                } if reason.contains(&format!("invalid bindings export prefix `{prefix}`"))
+            ));
+        }
+    }
+
+    #[test]
+    fn it_deserializes_format_settings() {
+        let bindings: Bindings = toml_edit::de::from_str("format = false").unwrap();
+        assert!(!bindings.format.enabled());
+        assert!(bindings.format.rustfmt_args().is_empty());
+
+        let bindings: Bindings = toml_edit::de::from_str("").unwrap();
+        assert!(bindings.format.enabled());
+        assert!(!bindings.format.explicit());
+        assert!(bindings.format.options().is_none());
+
+        let bindings: Bindings = toml_edit::de::from_str("format = true").unwrap();
+        assert!(bindings.format.enabled());
+        assert!(bindings.format.explicit());
+
+        let bindings: Bindings = toml_edit::de::from_str(
+            r#"format = { edition = "2018", config_path = "rustfmt.toml" }"#,
+        )
+        .unwrap();
+        assert!(bindings.format.enabled());
+        assert_eq!(
+            bindings.format.rustfmt_args(),
+            ["--edition", "2018", "--config-path", "rustfmt.toml"]
+        );
+
+        assert!(toml_edit::de::from_str::<Bindings>(r#"format = { unknown = "2018" }"#).is_err());
+    }
+
+    #[test]
+    fn it_requires_rustfmt_only_when_explicit() {
+        // Formatting by default falls back to unformatted bindings
+        let format = Format::default();
+        assert!(format.check_rustfmt(true).unwrap());
+        assert!(!format.check_rustfmt(false).unwrap());
+
+        // Explicitly requesting formatting requires `rustfmt`
+        let format = Format::Enabled(Some(true));
+        assert!(format.check_rustfmt(true).unwrap());
+        let err = format.check_rustfmt(false).unwrap_err().to_string();
+        assert!(err.contains("`rustfmt` is not available"));
+        assert!(err.contains("rustup component add rustfmt"));
+        assert!(Format::Options(Default::default())
+            .check_rustfmt(false)
+            .is_err());
+
+        assert!(!Format::Enabled(Some(false)).check_rustfmt(false).unwrap());
+    }
+
+    #[test]
+    fn it_enumerates_target_packages() {
+        let section: ComponentSection = toml_edit::de::from_str(
+            r#"
+[target]
+path = "wit"
+
+[target.dependencies]
+"wasi:io" = "0.2.0"
+"foo:local" = { path = "local" }
+
+[dependencies]
+"wasi:io" = "0.2.0"
+"wasi:http" = { version = "0.2.0", registry = "other" }
+"bar:local" = { path = "local" }
+"#,
+        )
+        .unwrap();
+
+        let metadata = ComponentMetadata::for_test("foo", "Cargo.toml", section);
+
+        let mut packages: Vec<_> = metadata
+            .target_packages()
+            .into_iter()
+            .map(|(name, package)| {
+                (
+                    name.to_string(),
+                    package.version.to_string(),
+                    package.registry,
+                )
+            })
+            .collect();
+        packages.sort();
+
+        assert_eq!(
+            packages,
+            [
+                (
+                    "wasi:http".to_string(),
+                    "^0.2.0".to_string(),
+                    Some("other".to_string())
+                ),
+                ("wasi:io".to_string(), "^0.2.0".to_string(), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_resolves_nested_wit_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let wit = dir.path().join("wit");
+        for (path, source) in [
+            (
+                "app.wit",
+                "package foo:app;\n\nworld app {\n  import foo:types/types;\n  import foo:util/util;\n}\n",
+            ),
+            ("types/types.wit", "package foo:types;\n\ninterface types {\n  type id = u32;\n}\n"),
+            (
+                "nested/util/util.wit",
+                "package foo:util;\n\ninterface util {\n  use foo:types/types.{id};\n}\n",
+            ),
+        ] {
+            let path = wit.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+
+        let mut metadata = ComponentMetadata::for_test(
+            "foo",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: Some(wit.clone()),
+                    world: None,
+                    dependencies: Default::default(),
+                },
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+
+        // The nested packages are not found unless searched for
+        let resolution = metadata
+            .target_resolution(&config, None, false)
+            .await
+            .unwrap();
+        assert!(BindingsGenerator::target_resolve(&resolution).is_err());
+
+        metadata.section.recursive_wit = true;
+        let resolution = metadata
+            .target_resolution(&config, None, false)
+            .await
+            .unwrap();
+        let (resolve, pkg, files) = BindingsGenerator::target_resolve(&resolution)
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolve.packages[pkg].name.to_string(), "foo:app");
+        for name in ["foo:types", "foo:util"] {
+            assert!(resolve.package_names.keys().any(|n| n.to_string() == name));
+        }
+        assert_eq!(files.len(), 3);
+
+        // Packages defined in more than one directory are reported
+        let other = wit.join("other");
+        fs::create_dir_all(&other).unwrap();
+        fs::write(other.join("types.wit"), "package foo:types;\n").unwrap();
+        let resolution = metadata
+            .target_resolution(&config, None, false)
+            .await
+            .unwrap();
+        let message = format!(
+            "{e:#}",
+            e = BindingsGenerator::target_resolve(&resolution).unwrap_err()
+        );
+        assert!(message.contains("duplicate definitions of package `foo:types`"));
+    }
+
+    #[test]
+    fn it_hashes_bindings_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("src/lib.rs");
+        fs::create_dir_all(lib.parent().unwrap()).unwrap();
+        fs::write(&lib, "mod bindings;\n").unwrap();
+
+        let metadata =
+            ComponentMetadata::for_test("foo", dir.path().join("Cargo.toml"), Default::default());
+
+        let world = |source: &str| {
+            let mut resolve = Resolve::default();
+            let pkg = resolve
+                .push(wit_parser::UnresolvedPackage::parse(Path::new("test.wit"), source).unwrap())
+                .unwrap();
+            let world = resolve.select_world(pkg, None).unwrap();
+            (resolve, world)
+        };
+
+        let (resolve, world_id) =
+            world("package foo:bar;\n\nworld example {\n  export run: func();\n}\n");
+        let settings = Bindings::default();
+        let hash = metadata
+            .bindings_hash(&settings, &resolve, world_id)
+            .unwrap();
+
+        // Editing Rust sources does not affect the bindings
+        fs::write(&lib, "mod bindings;\n\nfn unrelated() {}\n").unwrap();
+        assert_eq!(
+            metadata
+                .bindings_hash(&settings, &resolve, world_id)
+                .unwrap(),
+            hash
+        );
+
+        // Changing a setting does
+        let changed = Bindings {
+            derives: vec!["Hash".to_string()],
+            ..Default::default()
+        };
+        assert_ne!(
+            metadata
+                .bindings_hash(&changed, &resolve, world_id)
+                .unwrap(),
+            hash
+        );
+
+        // The order of the entries of a table does not matter
+        let rename = |entries: &[(&str, &str)]| Bindings {
+            rename: entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            metadata
+                .bindings_hash(
+                    &rename(&[("a:b/c", "c"), ("a:b/d", "d")]),
+                    &resolve,
+                    world_id
+                )
+                .unwrap(),
+            metadata
+                .bindings_hash(
+                    &rename(&[("a:b/d", "d"), ("a:b/c", "c")]),
+                    &resolve,
+                    world_id
+                )
+                .unwrap()
+        );
+
+        // Changing the world does
+        let (resolve, world_id) =
+            world("package foo:bar;\n\nworld example {\n  export run: func(arg: u32);\n}\n");
+        assert_ne!(
+            metadata
+                .bindings_hash(&settings, &resolve, world_id)
+                .unwrap(),
+            hash
+        );
+    }
+
+    #[test]
+    fn it_gets_the_output_filename() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        let metadata = |name: &str| {
+            ComponentMetadata::for_test(name, manifest_path.clone(), Default::default())
+        };
+
+        // Without a library, the binary keeps the crate name
+        fs::write(&manifest_path, "[package]\nname = \"my-app\"\n").unwrap();
+        assert_eq!(metadata("my-app").output_filename().unwrap(), "my-app.wasm");
+
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        for name in ["my_component", "my-component", "my_other-component"] {
+            assert_eq!(
+                metadata(name).output_filename().unwrap(),
+                format!("{name}.wasm", name = name.replace('-', "_"))
+            );
+        }
+
+        fs::write(
+            &manifest_path,
+            "[package]\nname = \"my_component\"\n\n[lib]\nname = \"custom\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            metadata("my_component").output_filename().unwrap(),
+            "custom.wasm"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_resolves_a_target_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let wit = dir.path().join("wit");
+        for (path, source) in [
+            (
+                "world.wit",
+                "package foo:app;\n\nworld app {\n  import foo:types/types;\n}\n",
+            ),
+            ("other.wit", "package foo:other;\n\nworld other {}\n"),
+            (
+                "deps/types/types.wit",
+                "package foo:types;\n\ninterface types {\n  type id = u32;\n}\n",
+            ),
+        ] {
+            let path = wit.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+
+        let metadata = ComponentMetadata::for_test(
+            "foo",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: Some(wit.join("world.wit")),
+                    world: None,
+                    dependencies: Default::default(),
+                },
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        let resolution = metadata
+            .target_resolution(&config, None, false)
+            .await
+            .unwrap();
+
+        // Only the target file and its dependencies are parsed
+        let (resolve, pkg, files) = BindingsGenerator::target_resolve(&resolution)
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolve.packages[pkg].name.to_string(), "foo:app");
+        assert!(resolve
+            .package_names
+            .keys()
+            .any(|n| n.to_string() == "foo:types"));
+        assert!(!resolve
+            .package_names
+            .keys()
+            .any(|n| n.to_string() == "foo:other"));
+        assert_eq!(files.len(), 2);
+
+        let files = metadata.local_wit_files().unwrap();
+        assert!(files.paths.contains(&wit.join("deps/types/types.wit")));
+        assert!(!files.paths.contains(&wit.join("other.wit")));
+    }
+
+    #[tokio::test]
+    async fn it_lists_available_worlds() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("worlds.wit"),
+            "package foo:bar@1.0.0;\n\nworld first {}\n\nworld second {}\n",
+        )
+        .unwrap();
+
+        let mut metadata = ComponentMetadata::for_test(
+            "foo",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: Some(dir.path().to_path_buf()),
+                    world: Some("second".to_string()),
+                    dependencies: Default::default(),
+                },
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        assert_eq!(
+            metadata
+                .available_worlds(&config, None, false)
+                .await
+                .unwrap(),
+            [
+                ("foo:bar/first@1.0.0".to_string(), false),
+                ("foo:bar/second@1.0.0".to_string(), true)
+            ]
+        );
+
+        // Without a specified world, the selection is ambiguous
+        if let Target::Local { world, .. } = &mut metadata.section.target {
+            *world = None;
+        }
+
+        assert_eq!(
+            metadata
+                .available_worlds(&config, None, false)
+                .await
+                .unwrap(),
+            [
+                ("foo:bar/first@1.0.0".to_string(), false),
+                ("foo:bar/second@1.0.0".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_overrides_the_section() {
+        let metadata = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path("/project/Cargo.toml")
+            .build()
+            .unwrap();
+        assert!(metadata.section.bindings.format.enabled());
+
+        let overridden = metadata.with_overridden_section(|section| {
+            section.bindings.format = Format::Enabled(Some(false));
+        });
+        assert!(!overridden.section.bindings.format.enabled());
+        assert!(overridden.modified_at >= metadata.modified_at);
+        assert_eq!(overridden.name, metadata.name);
+
+        // The original metadata is unchanged
+        assert!(metadata.section.bindings.format.enabled());
+    }
+
+    #[test]
+    fn it_checks_pinned_versions() {
+        let metadata = |version: &str| {
+            ComponentMetadataBuilder::new()
+                .with_name("foo")
+                .with_manifest_path("/project/Cargo.toml")
+                .with_target(Target::Package {
+                    name: "foo:bar".parse().unwrap(),
+                    package: version.parse().unwrap(),
+                    world: None,
+                })
+                .build()
+                .unwrap()
+        };
+
+        metadata("1.2.0").check_pinned_versions().unwrap();
+        metadata("^0.1").check_pinned_versions().unwrap();
+
+        let mut unpinned = metadata("*");
+        unpinned.section.dependencies.insert(
+            "foo:baz".parse().unwrap(),
+            Dependency::Package("*".parse().unwrap()),
+        );
+        let message = unpinned.check_pinned_versions().unwrap_err().to_string();
+        assert!(message.contains("`foo:bar`, `foo:baz`"));
+        assert!(message.contains("--require-pinned-targets"));
+    }
+
+    #[test]
+    fn it_overrides_the_adapter_from_the_environment() {
+        let mut metadata = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path("/project/Cargo.toml")
+            .build()
+            .unwrap();
+        assert!(metadata.adapter_path().is_none());
+
+        metadata.section.adapter = Some(PathBuf::from("/project/manifest.wasm"));
+
+        assert_eq!(
+            metadata
+                .adapter_path_with_env(Some("/debug/adapter.wasm".into()))
+                .as_deref(),
+            Some(Path::new("/debug/adapter.wasm"))
+        );
+        assert_eq!(
+            metadata
+                .adapter_path_with_env(Some(OsString::new()))
+                .as_deref(),
+            Some(Path::new("/project/manifest.wasm"))
+        );
+    }
+
+    #[test]
+    fn it_overrides_package_targets_with_a_local_path() {
+        let mut section: ComponentSection = toml_edit::de::from_str(
+            r#"target = { package = "foo:bar", version = "1.0.0", world = "baz" }"#,
+        )
+        .unwrap();
+        section
+            .prepare(Path::new("/project/Cargo.toml"), None)
+            .unwrap();
+
+        let dir = Path::new("/unpublished/wit");
+        assert!(section.override_target_path(dir));
+        assert!(matches!(
+            &section.target,
+            Target::Local { path: Some(path), world: Some(world), dependencies }
+                if path == dir && world == "baz" && dependencies.is_empty()
+        ));
+
+        let metadata = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path("/project/Cargo.toml")
+            .with_target(section.target.clone())
+            .build()
+            .unwrap();
+        assert_eq!(metadata.target_path().unwrap().as_deref(), Some(dir));
+        assert!(metadata.target_package().is_none());
+
+        assert_eq!(
+            metadata
+                .target_path_override_with_env(Some(dir.into()))
+                .unwrap()
+                .as_deref(),
+            Some(dir)
+        );
+        assert!(metadata
+            .target_path_override_with_env(Some("/other".into()))
+            .unwrap()
+            .is_none());
+        assert!(metadata
+            .target_path_override_with_env(None)
+            .unwrap()
+            .is_none());
+
+        // Local targets are not overridden
+        assert!(!section.override_target_path(Path::new("/other")));
+        assert!(matches!(&section.target, Target::Local { path: Some(path), .. } if path == dir));
+    }
+
+    #[test]
+    fn it_overrides_the_target_world_from_the_environment() {
+        let metadata = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path("/project/Cargo.toml")
+            .with_target(Target::Local {
+                path: None,
+                world: Some("manifest".to_string()),
+                dependencies: Default::default(),
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            metadata
+                .target_world_with_env(Some("override".to_string()))
+                .as_deref(),
+            Some("override")
+        );
+        assert_eq!(
+            metadata
+                .target_world_with_env(Some(String::new()))
+                .as_deref(),
+            Some("manifest")
+        );
+        assert_eq!(
+            metadata.target_world_with_env(None).as_deref(),
+            Some("manifest")
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fingerprints_bindings_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("wit")).unwrap();
+        fs::write(
+            dir.path().join("wit/world.wit"),
+            "package foo:bar@1.0.0;\n\nworld example {}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("README.md"), "foo").unwrap();
+
+        let metadata = |root: &Path| {
+            ComponentMetadata::for_test(
+                "foo",
+                root.join("Cargo.toml"),
+                ComponentSection {
+                    target: Target::Local {
+                        path: Some(root.join("wit")),
+                        world: None,
+                        dependencies: Default::default(),
+                    },
+                    ..Default::default()
+                },
+            )
+        };
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        let fingerprint = metadata(dir.path())
+            .fingerprint(&config, None, false)
+            .await
+            .unwrap();
+        assert_eq!(fingerprint.len(), 64);
+
+        // Touching an unrelated file doesn't change the fingerprint
+        fs::write(dir.path().join("README.md"), "bar").unwrap();
+        assert_eq!(
+            metadata(dir.path())
+                .fingerprint(&config, None, false)
+                .await
+                .unwrap(),
+            fingerprint
+        );
+
+        // The fingerprint is independent of the package location
+        let other = tempfile::tempdir().unwrap();
+        fs::create_dir(other.path().join("wit")).unwrap();
+        fs::copy(
+            dir.path().join("wit/world.wit"),
+            other.path().join("wit/world.wit"),
+        )
+        .unwrap();
+        assert_eq!(
+            metadata(other.path())
+                .fingerprint(&config, None, false)
+                .await
+                .unwrap(),
+            fingerprint
+        );
+
+        // Editing a WIT file changes the fingerprint
+        fs::write(
+            dir.path().join("wit/world.wit"),
+            "package foo:bar@1.0.0;\n\nworld example {\n  export run: func();\n}\n",
+        )
+        .unwrap();
+        let fingerprint = {
+            let edited = metadata(dir.path())
+                .fingerprint(&config, None, false)
+                .await
+                .unwrap();
+            assert_ne!(edited, fingerprint);
+            edited
+        };
+
+        // Overriding the section changes the fingerprint
+        let overridden = metadata(dir.path())
+            .with_overridden_section(|section| section.bindings.derives.push("Clone".into()));
+        assert_ne!(
+            overridden.fingerprint(&config, None, false).await.unwrap(),
+            fingerprint
+        );
+
+        // Editing a local dependency changes the fingerprint
+        fs::write(
+            dir.path().join("dep.wit"),
+            "package foo:dep;\n\ninterface a {}\n",
+        )
+        .unwrap();
+        let with_dependency = metadata(dir.path()).with_overridden_section(|section| {
+            section.dependencies.insert(
+                "foo:dep".parse().unwrap(),
+                Dependency::Local(dir.path().join("dep.wit")),
+            );
+        });
+        let fingerprint = with_dependency
+            .fingerprint(&config, None, false)
+            .await
+            .unwrap();
+        fs::write(
+            dir.path().join("dep.wit"),
+            "package foo:dep;\n\ninterface b {}\n",
+        )
+        .unwrap();
+        assert_ne!(
+            with_dependency
+                .fingerprint(&config, None, false)
+                .await
+                .unwrap(),
+            fingerprint
+        );
+
+        // Selecting WIT features of a dependency changes the fingerprint
+        let fingerprint = metadata(dir.path())
+            .fingerprint(&config, None, false)
+            .await
+            .unwrap();
+        let with_features = metadata(dir.path()).with_overridden_section(|section| {
+            section.dependency_features.insert(
+                "foo:dep".parse().unwrap(),
+                DependencyFeatures {
+                    features: vec!["fancy".into()],
+                    default_features: true,
+                },
+            );
+        });
+        assert_ne!(
+            with_features
+                .fingerprint(&config, None, false)
+                .await
+                .unwrap(),
+            fingerprint
+        );
+
+        // Registry dependencies are fingerprinted by their locked digests
+        let with_registry_dependency = metadata(dir.path()).with_overridden_section(|section| {
+            section.dependencies.insert(
+                "foo:reg".parse().unwrap(),
+                Dependency::Package("1.0.0".parse().unwrap()),
+            );
+        });
+        let err = with_registry_dependency
+            .fingerprint(&config, None, false)
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("dependency `foo:reg` with requirement `^1.0.0` is not in the lock file"));
+
+        let lock_file = |digest: &str| {
+            LockFile::new(vec![LockedPackage {
+                name: "foo:reg".parse().unwrap(),
+                registry: None,
+                versions: vec![LockedPackageVersion {
+                    requirement: "^1.0.0".into(),
+                    version: Version::new(1, 0, 0),
+                    digest: format!("sha256:{digest}").parse().unwrap(),
+                }],
+            }])
+        };
+        let first = lock_file(&"0".repeat(64));
+        let second = lock_file(&"1".repeat(64));
+        let fingerprint = with_registry_dependency
+            .fingerprint(&config, Some(LockFileResolver::new(&first)), false)
+            .await
+            .unwrap();
+        assert_ne!(
+            with_registry_dependency
+                .fingerprint(&config, Some(LockFileResolver::new(&second)), false)
+                .await
+                .unwrap(),
+            fingerprint
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fingerprints_the_adapter() {
+        let dir = tempfile::tempdir().unwrap();
+        let adapter = dir.path().join("adapter.wasm");
+        fs::write(&adapter, "first").unwrap();
+
+        let mut metadata = ComponentMetadata::for_test(
+            "foo",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                adapter: Some(adapter.clone()),
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        let digest = metadata.adapter_digest().unwrap();
+        let fingerprint = metadata.fingerprint(&config, None, false).await.unwrap();
+
+        fs::write(&adapter, "second").unwrap();
+        assert_ne!(metadata.adapter_digest().unwrap(), digest);
+        assert_ne!(
+            metadata.fingerprint(&config, None, false).await.unwrap(),
+            fingerprint
+        );
+
+        fs::remove_file(&adapter).unwrap();
+        assert!(metadata
+            .adapter_digest()
+            .unwrap_err()
+            .to_string()
+            .contains("failed to read module adapter"));
+
+        // The built-in adapters are distinguished by kind
+        metadata.section.adapter = None;
+        let builtin = metadata.adapter_digest().unwrap();
+        metadata.section.proxy = ProxyAdapter::Enabled;
+        let proxy = metadata.adapter_digest().unwrap();
+        assert_ne!(proxy, builtin);
+        metadata.section.proxy = ProxyAdapter::None;
+        assert_ne!(metadata.adapter_digest().unwrap(), proxy);
+    }
+
+    #[tokio::test]
+    async fn it_detects_the_proxy_world() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("http.wit"),
+            r#"package wasi:http@0.2.0;
+
+interface types {
+    resource incoming-request;
+}
+
+interface incoming-handler {
+    use types.{incoming-request};
+    handle: func(request: incoming-request);
+}
+
+interface outgoing-handler {
+    use types.{incoming-request};
+}
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("proxy.wit"),
+            r#"package foo:proxy;
+
+world example {
+    import wasi:http/outgoing-handler@0.2.0;
+    export wasi:http/incoming-handler@0.2.0;
+}
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("other.wit"),
+            r#"package foo:other;
+
+world example {
+    import wasi:http/outgoing-handler@0.2.0;
+    export wasi:http/incoming-handler@0.2.0;
+    export run: func();
+}
+"#,
+        )
+        .unwrap();
+
+        let metadata = |target: &str| {
+            ComponentMetadata::for_test(
+                "foo",
+                dir.path().join("Cargo.toml"),
+                ComponentSection {
+                    target: Target::Local {
+                        path: Some(dir.path().join(target)),
+                        world: None,
+                        dependencies: HashMap::from([(
+                            "wasi:http".parse().unwrap(),
+                            Dependency::Local(dir.path().join("http.wit")),
+                        )]),
+                    },
+                    ..Default::default()
+                },
+            )
+        };
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        assert!(metadata("proxy.wit")
+            .uses_proxy_world(&config, None, false)
+            .await
+            .unwrap());
+        assert!(!metadata("other.wit")
+            .uses_proxy_world(&config, None, false)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn it_lists_wasi_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("io.wit"),
+            "package wasi:io@0.2.0;\n\ninterface streams {\n  resource output-stream;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("uses.wit"),
+            "package foo:uses;\n\nworld example {\n  import wasi:io/streams@0.2.0;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("none.wit"),
+            "package foo:none;\n\nworld example {\n  export run: func();\n}\n",
+        )
+        .unwrap();
+
+        let metadata = |target: &str| {
+            ComponentMetadata::for_test(
+                "foo",
+                dir.path().join("Cargo.toml"),
+                ComponentSection {
+                    target: Target::Local {
+                        path: Some(dir.path().join(target)),
+                        world: None,
+                        dependencies: HashMap::from([(
+                            "wasi:io".parse().unwrap(),
+                            Dependency::Local(dir.path().join("io.wit")),
+                        )]),
+                    },
+                    ..Default::default()
+                },
+            )
+        };
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+
+        let uses = metadata("uses.wit");
+        assert_eq!(
+            uses.wasi_imports(&config, None, false)
+                .await
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["wasi:io@0.2.0"]
+        );
+        assert!(uses.uses_wasi(&config, None, false).await.unwrap());
+
+        let none = metadata("none.wit");
+        assert!(none
+            .wasi_imports(&config, None, false)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!none.uses_wasi(&config, None, false).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn it_computes_the_min_wasi_version() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("io.wit"),
+            "package wasi:io@0.2.0;\n\ninterface streams {\n  resource output-stream;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("clocks.wit"),
+            "package wasi:clocks@0.2.1;\n\ninterface wall-clock {\n  now: func() -> u64;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("uses.wit"),
+            "package foo:uses;\n\nworld example {\n  import wasi:io/streams@0.2.0;\n  import wasi:clocks/wall-clock@0.2.1;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("none.wit"),
+            "package foo:none;\n\nworld example {\n  export run: func();\n}\n",
+        )
+        .unwrap();
+
+        let metadata = |target: &str| {
+            ComponentMetadata::for_test(
+                "foo",
+                dir.path().join("Cargo.toml"),
+                ComponentSection {
+                    target: Target::Local {
+                        path: Some(dir.path().join(target)),
+                        world: None,
+                        dependencies: HashMap::from([
+                            (
+                                "wasi:io".parse().unwrap(),
+                                Dependency::Local(dir.path().join("io.wit")),
+                            ),
+                            (
+                                "wasi:clocks".parse().unwrap(),
+                                Dependency::Local(dir.path().join("clocks.wit")),
+                            ),
+                        ]),
+                    },
+                    ..Default::default()
+                },
+            )
+        };
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        assert_eq!(
+            metadata("uses.wit")
+                .min_wasi_version(&config, None, false)
+                .await
+                .unwrap(),
+            Some(Version::new(0, 2, 1))
+        );
+        assert_eq!(
+            metadata("none.wit")
+                .min_wasi_version(&config, None, false)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn it_lists_world_imports_and_exports() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("world.wit"),
+            r#"package foo:bar@1.0.0;
+
+interface logging {
+    log: func(message: string);
+}
+
+interface handler {
+    handle: func();
+}
+
+world example {
+    import logging;
+    import now: func() -> u64;
+    export handler;
+    export run: func();
+}
+"#,
+        )
+        .unwrap();
+
+        let metadata = ComponentMetadata::for_test(
+            "foo",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: Some(dir.path().join("world.wit")),
+                    world: None,
+                    dependencies: Default::default(),
+                },
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        assert_eq!(
+            metadata.imports(&config, None, false).await.unwrap(),
+            [
+                WorldItemInfo {
+                    name: "foo:bar/logging@1.0.0".to_string(),
+                    kind: WorldItemKind::Interface,
+                },
+                WorldItemInfo {
+                    name: "now".to_string(),
+                    kind: WorldItemKind::Function,
+                },
+            ]
+        );
+        assert_eq!(
+            metadata.exports(&config, None, false).await.unwrap(),
+            [
+                WorldItemInfo {
+                    name: "run".to_string(),
+                    kind: WorldItemKind::Function,
+                },
+                WorldItemInfo {
+                    name: "foo:bar/handler@1.0.0".to_string(),
+                    kind: WorldItemKind::Interface,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_lists_exported_resources() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("world.wit"),
+            r#"package foo:bar@1.0.0;
+
+interface store {
+    resource entry {
+        constructor(key: string);
+        get: func() -> string;
+        open: static func(key: string) -> entry;
+    }
+
+    clear: func();
+}
+
+interface logging {
+    resource logger;
+}
+
+world example {
+    import logging;
+    export store;
+}
+"#,
+        )
+        .unwrap();
+
+        let metadata = ComponentMetadata::for_test(
+            "foo",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: Some(dir.path().join("world.wit")),
+                    world: None,
+                    dependencies: Default::default(),
+                },
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        assert_eq!(
+            metadata
+                .exported_resources(&config, None, false)
+                .await
+                .unwrap(),
+            [ExportedResource {
+                interface: "foo:bar/store@1.0.0".to_string(),
+                name: "entry".to_string(),
+                constructor: true,
+                methods: vec!["get".to_string()],
+                static_functions: vec!["open".to_string()],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_prints_the_target_world() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("io.wit"),
+            "package wasi:io@0.2.0;\n\ninterface streams {\n  resource output-stream;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("world.wit"),
+            r#"package foo:bar;
+
+interface logging {
+    use wasi:io/streams@0.2.0.{output-stream};
+    log: func(output: borrow<output-stream>);
+}
+
+world first {
+    import logging;
+}
+
+world second {
+    export run: func();
+}
+"#,
+        )
+        .unwrap();
+
+        let metadata = ComponentMetadata::for_test(
+            "foo",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: Some(dir.path().join("world.wit")),
+                    world: Some("first".to_string()),
+                    dependencies: HashMap::from([(
+                        "wasi:io".parse().unwrap(),
+                        Dependency::Local(dir.path().join("io.wit")),
+                    )]),
+                },
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        let wit = metadata
+            .world_wit(&config, None, false)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Parse each printed package back, dependencies first
+        let mut resolve = wit_parser::Resolve::default();
+        let mut documents: Vec<String> = Vec::new();
+        for line in wit.lines() {
+            if line.starts_with("package ") {
+                documents.push(String::new());
+            }
+
+            let document = documents.last_mut().unwrap();
+            document.push_str(line);
+            document.push('\n');
+        }
+
+        assert_eq!(documents.len(), 2);
+        let mut pkg = None;
+        for (i, document) in documents.iter().enumerate() {
+            pkg = Some(
+                resolve
+                    .push(
+                        wit_parser::UnresolvedPackage::parse(
+                            Path::new(&format!("{i}.wit")),
+                            document,
+                        )
+                        .unwrap(),
+                    )
+                    .unwrap(),
+            );
+        }
+
+        let package = &resolve.packages[pkg.unwrap()];
+        assert_eq!(package.name.to_string(), "foo:bar");
+        assert_eq!(package.worlds.keys().collect::<Vec<_>>(), ["first"]);
+        assert!(package.interfaces.contains_key("logging"));
+    }
+
+    #[test]
+    fn it_lists_local_wit_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let wit = dir.path().join("wit");
+        fs::create_dir_all(wit.join("deps/foo/nested")).unwrap();
+        fs::create_dir_all(dir.path().join("other")).unwrap();
+        for path in [
+            "wit/world.wit",
+            "wit/types.wit",
+            "wit/README.md",
+            "wit/deps/foo/foo.wit",
+            "wit/deps/foo/nested/bar.wit",
+            "other/other.wit",
+        ] {
+            fs::write(dir.path().join(path), "").unwrap();
+        }
+
+        let mut metadata = ComponentMetadata::for_test(
+            "foo",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: None,
+                    world: None,
+                    dependencies: [(
+                        "foo:other".parse().unwrap(),
+                        Dependency::Local(dir.path().join("other")),
+                    )]
+                    .into(),
+                },
+                ..Default::default()
+            },
+        );
+
+        let files = metadata.local_wit_files().unwrap();
+        assert!(files.local);
+        assert_eq!(
+            files.paths,
+            [
+                "other/other.wit",
+                "wit/deps/foo/foo.wit",
+                "wit/deps/foo/nested/bar.wit",
+                "wit/types.wit",
+                "wit/world.wit",
+            ]
+            .map(|p| dir.path().join(p))
+        );
+
+        let time = SystemTime::now() + std::time::Duration::from_secs(60);
+        assert!(!files.modified_since(time).unwrap());
+
+        metadata.section.target = "wasi:http/proxy@0.2.0".parse().unwrap();
+        assert_eq!(
+            metadata.local_wit_files().unwrap(),
+            LocalWitFiles::default()
+        );
+    }
+
+    #[test]
+    fn it_finds_breaking_changes() {
+        fn world(source: &str) -> (Resolve, WorldId) {
+            let mut resolve = Resolve::default();
+            let pkg = resolve
+                .push(wit_parser::UnresolvedPackage::parse(Path::new("test.wit"), source).unwrap())
+                .unwrap();
+            let world = resolve.select_world(pkg, None).unwrap();
+            (resolve, world)
+        }
+
+        let (previous, previous_world) = world(
+            r#"package foo:bar@1.0.0;
+
+interface api {
+    get: func(key: string) -> option<string>;
+    remove: func(key: string);
+}
+
+world example {
+    export api;
+    export run: func();
+}
+"#,
+        );
+
+        // Adding exports and functions is compatible
+        let (resolve, current) = world(
+            r#"package foo:bar@1.1.0;
+
+interface api {
+    get: func(key: string) -> option<string>;
+    remove: func(key: string);
+    clear: func();
+}
+
+world example {
+    export api;
+    export run: func();
+    export stop: func();
+}
+"#,
+        );
+        assert!(breaking_changes((&resolve, current), (&previous, previous_world)).is_empty());
+
+        let (resolve, current) = world(
+            r#"package foo:bar@2.0.0;
+
+interface api {
+    get: func(key: string) -> result<string>;
+}
+
+world example {
+    export api;
+}
+"#,
+        );
+        assert_eq!(
+            breaking_changes((&resolve, current), (&previous, previous_world)),
+            [
+                BreakingChange::RemovedExport("run".to_string()),
+                BreakingChange::ChangedSignature {
+                    function: "foo:bar/api#get".to_string(),
+                    previous: "func(key: string) -> option<string>".to_string(),
+                    current: "func(key: string) -> result<string, _>".to_string(),
+                },
+                BreakingChange::RemovedFunction {
+                    interface: "foo:bar/api".to_string(),
+                    function: "remove".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_deserializes_ownership() {
+        let bindings: Bindings = toml_edit::de::from_str(r#"ownership = "borrowing""#).unwrap();
+        assert_eq!(bindings.ownership, Ownership::Borrowing);
+        assert_eq!(bindings.ownership.imports(), OwnershipModel::Borrowing);
+        assert_eq!(bindings.ownership.exports(), OwnershipModel::Borrowing);
+
+        let bindings: Bindings =
+            toml_edit::de::from_str(r#"ownership = { imports = "borrowing", exports = "owning" }"#)
+                .unwrap();
+        assert_eq!(
+            bindings.ownership,
+            Ownership::Split {
+                imports: OwnershipModel::Borrowing,
+                exports: OwnershipModel::Owning,
+            }
+        );
+
+        assert!(toml_edit::de::from_str::<Bindings>(r#"ownership = "unknown""#).is_err());
+        assert!(toml_edit::de::from_str::<Bindings>(
+            r#"ownership = { imports = "owning", other = "owning" }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_errors_for_root_manifest_paths() {
+        let metadata = ComponentMetadata::for_test("foo", "/", Default::default());
+
+        assert!(metadata
+            .manifest_dir()
+            .unwrap_err()
+            .to_string()
+            .contains("has no parent directory"));
+        assert!(metadata.target_path().is_err());
+    }
+
+    #[test]
+    fn it_selects_profile_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        fs::write(&manifest_path, "").unwrap();
+
+        let package: Package = from_value(serde_json::json!({
+            "name": "foo",
+            "version": "0.1.0",
+            "id": "foo 0.1.0 (path+file:///foo)",
+            "dependencies": [],
+            "targets": [],
+            "features": {},
+            "manifest_path": manifest_path,
+            "metadata": {
+                "component": {
+                    "target": { "path": "wit", "world": "full" },
+                    "profiles": {
+                        "release": { "path": "wit", "world": "minimal" }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let world = |profile| {
+            ComponentMetadata::from_package(&package, profile)
+                .unwrap()
+                .target_world()
+                .map(|world| world.to_string())
+        };
+
+        assert_eq!(world(None).as_deref(), Some("full"));
+        assert_eq!(world(Some("dev")).as_deref(), Some("full"));
+        assert_eq!(world(Some("release")).as_deref(), Some("minimal"));
+
+        let metadata = ComponentMetadata::from_package(&package, Some("release")).unwrap();
+        match &metadata.section.target {
+            Target::Local { path, .. } => {
+                assert_eq!(path.as_deref(), Some(&*dir.path().join("wit")))
+            }
+            _ => panic!("expected a local target"),
+        }
+    }
+
+    #[test]
+    fn it_reads_an_external_component_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        fs::write(&manifest_path, "").unwrap();
+
+        let package: Package = from_value(serde_json::json!({
+            "name": "foo",
+            "version": "0.1.0",
+            "id": "foo 0.1.0 (path+file:///foo)",
+            "dependencies": [],
+            "targets": [],
+            "features": {},
+            "manifest_path": manifest_path,
+            "metadata": {
+                "component": {
+                    "target": { "path": "manifest-wit", "world": "manifest" }
+                }
+            }
+        }))
+        .unwrap();
+
+        let component = serde_json::json!({
+            "target": { "path": "wit", "world": "external" },
+            "dependencies": { "foo:bar": { "path": "deps/bar.wit" } },
+            "fetch_concurrency": 2
+        });
+        let metadata =
+            ComponentMetadata::from_manifest_and_component(&package, component.clone()).unwrap();
+
+        assert!(metadata.section_present);
+        assert_eq!(metadata.section.fetch_concurrency, Some(2));
+        assert_eq!(metadata.target_world().as_deref(), Some("external"));
+        match &metadata.section.target {
+            Target::Local { path, .. } => {
+                assert_eq!(path.as_deref(), Some(&*dir.path().join("wit")))
+            }
+            _ => panic!("expected a local target"),
+        }
+        assert!(matches!(
+            &metadata.section.dependencies[&"foo:bar".parse::<PackageName>().unwrap()],
+            Dependency::Local(path) if *path == dir.path().join("deps/bar.wit")
+        ));
+    }
+
+    #[test]
+    fn it_inherits_workspace_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("member/Cargo.toml");
+        fs::create_dir(dir.path().join("member")).unwrap();
+        fs::write(&manifest_path, "").unwrap();
+
+        let package: Package = from_value(serde_json::json!({
+            "name": "foo",
+            "version": "0.1.0",
+            "id": "foo 0.1.0 (path+file:///foo)",
+            "dependencies": [],
+            "targets": [],
+            "features": {},
+            "manifest_path": manifest_path,
+            "metadata": {
+                "component": {
+                    "dependencies": {
+                        "wasi:http": { "workspace": true },
+                        "foo:local": { "workspace": true },
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let workspace = serde_json::json!({
+            "component": {
+                "dependencies": {
+                    "wasi:http": { "version": "0.2.0", "registry": "other" },
+                    "foo:local": { "path": "wit/local" },
+                }
+            }
+        });
+
+        let metadata =
+            ComponentMetadata::from_workspace_package(&package, &workspace, dir.path(), None)
+                .unwrap();
+
+        let http: PackageName = "wasi:http".parse().unwrap();
+        match &metadata.section.dependencies[&http] {
+            Dependency::Package(package) => {
+                assert_eq!(package.version.to_string(), "^0.2.0");
+                assert_eq!(package.registry.as_deref(), Some("other"));
+            }
+            _ => panic!("expected a registry dependency"),
+        }
+
+        let local: PackageName = "foo:local".parse().unwrap();
+        assert_eq!(
+            metadata.section.dependencies[&local],
+            Dependency::Local(dir.path().join("wit/local"))
+        );
+
+        // The workspace must define inherited dependencies
+        let err = ComponentMetadata::from_package(&package, None).unwrap_err();
+        assert!(matches!(&err, MetadataError::Manifest { path, .. } if *path == manifest_path));
+        assert!(format!("{:#}", anyhow::Error::from(err)).contains(
+            "is inherited from the workspace but `[workspace.metadata.component.dependencies]` does not define it"
+        ));
+    }
+
+    #[test]
+    fn it_parses_pre_release_targets() {
+        match "wasi:http/proxy@0.2.0-alpha.1".parse::<Target>().unwrap() {
+            Target::Package {
+                name,
+                package,
+                world,
+            } => {
+                assert_eq!(name.to_string(), "wasi:http");
+                assert_eq!(package.version.to_string(), "^0.2.0-alpha.1");
+                assert_eq!(world.as_deref(), Some("proxy"));
+            }
+            _ => panic!("expected a package target"),
+        }
+    }
+
+    #[test]
+    fn it_builds_component_metadata() {
+        let root = Path::new("/project");
+        let metadata = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path(root.join("Cargo.toml"))
+            .with_target(Target::Local {
+                path: Some("wit".into()),
+                world: Some("example".to_string()),
+                dependencies: Default::default(),
+            })
+            .with_dependency(
+                "foo:bar".parse().unwrap(),
+                Dependency::Local("deps/bar.wit".into()),
+            )
+            .with_bindings(Bindings {
+                derives: vec!["PartialEq".to_string()],
+                ..Default::default()
+            })
+            .with_registry("example", Url::parse("https://example.com").unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(metadata.name, "foo");
+        assert_eq!(metadata.version, Version::new(0, 1, 0));
+        assert!(metadata.section_present);
+        assert_eq!(metadata.manifest_dir().unwrap(), root);
+        assert_eq!(
+            metadata.target_path().unwrap().as_deref(),
+            Some(&*root.join("wit"))
+        );
+        assert_eq!(metadata.target_world().as_deref(), Some("example"));
+        let bar: PackageName = "foo:bar".parse().unwrap();
+        assert!(matches!(
+            &metadata.section.dependencies[&bar],
+            Dependency::Local(path) if path == &root.join("deps/bar.wit")
+        ));
+        assert_eq!(metadata.section.bindings.derives, ["PartialEq"]);
+        assert_eq!(
+            metadata.section.registries["example"].to_string(),
+            "https://example.com/"
+        );
+
+        // The same validation as `from_package` is performed
+        assert!(ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path(root.join("Cargo.toml"))
+            .with_bindings(Bindings {
+                export_prefix: Some("not valid".to_string()),
+                ..Default::default()
+            })
+            .build()
+            .unwrap_err()
+            .to_string()
+            .contains("invalid bindings configuration"));
+
+        assert!(matches!(
+            ComponentMetadataBuilder::new()
+                .with_manifest_path(root.join("Cargo.toml"))
+                .build()
+                .unwrap_err()
+                .downcast_ref::<MetadataError>(),
+            Some(MetadataError::MissingField("name"))
+        ));
+    }
+
+    #[test]
+    fn it_deserializes_with_mappings() {
+        let bindings: Bindings = toml_edit::de::from_str(
+            r#"with = { "*" = "generate", "foo:bar/audio" = "crate::audio" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            bindings.with,
+            HashMap::from([
+                ("*".to_string(), WithMapping::Generate),
+                (
+                    "foo:bar/audio".to_string(),
+                    WithMapping::Path("crate::audio".to_string())
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn it_matches_patterns() {
+        assert!(matches_pattern("foo", "foo"));
+        assert!(!matches_pattern("foo", "foobar"));
+        assert!(matches_pattern("foo*", "foobar"));
+        assert!(matches_pattern("*bar", "foobar"));
+        assert!(matches_pattern("wasi:*/types", "wasi:http/types"));
+        assert!(matches_pattern("*", "anything"));
+        assert!(!matches_pattern("wasi:*/types", "wasi:http/handler"));
+    }
+
+    #[test]
+    fn it_reports_metadata_errors() {
+        assert!(matches!(
+            "foo:bar@not-a-version".parse::<Target>().unwrap_err(),
+            MetadataError::InvalidVersion { version, .. } if version == "not-a-version"
+        ));
+        assert!(matches!(
+            "foo:bar".parse::<Target>().unwrap_err(),
+            MetadataError::InvalidValue {
+                field: "target",
+                ..
+            }
+        ));
+
+        let root = Path::new("/project");
+        let err = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path(root.join("Cargo.toml"))
+            .with_bindings(Bindings {
+                format: Format::Options(FormatOptions {
+                    config_path: Some("missing/rustfmt.toml".into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MetadataError>(),
+            Some(MetadataError::MissingPath(path)) if path == &root.join("missing/rustfmt.toml")
+        ));
+
+        let mut section = ComponentSection {
+            bindings_crate_path: Some("bindings".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            section
+                .prepare(&root.join("Cargo.toml"), None)
+                .unwrap_err()
+                .downcast_ref::<MetadataError>(),
+            Some(MetadataError::MissingField("bindings_crate"))
+        ));
+
+        // Loading a package returns the metadata error directly
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        fs::write(&manifest_path, "").unwrap();
+        let package: Package = from_value(serde_json::json!({
+            "name": "foo",
+            "version": "0.1.0",
+            "id": "foo 0.1.0 (path+file:///foo)",
+            "dependencies": [],
+            "targets": [],
+            "features": {},
+            "manifest_path": manifest_path,
+            "metadata": {
+                "component": { "bindings_crate_path": "bindings" }
+            }
+        }))
+        .unwrap();
+        assert!(matches!(
+            ComponentMetadata::from_package(&package, None).unwrap_err(),
+            MetadataError::MissingField("bindings_crate")
+        ));
+    }
+
+    #[test]
+    fn it_routes_namespaces_to_registries() {
+        let mut section: ComponentSection = toml_edit::de::from_str(
+            r#"
+registries = { myorg = "https://registry.myorg.example.com", other = "https://other.example.com" }
+namespace_registries = { wasi = "https://wasi.example.com", myorg = "myorg" }
+
+[dependencies]
+"wasi:http" = "0.2.0"
+"myorg:logging" = "1.0.0"
+"myorg:pinned" = { version = "1.0.0", registry = "other" }
+"example:other" = "1.0.0"
+"#,
+        )
+        .unwrap();
+
+        section
+            .prepare(Path::new("/project/Cargo.toml"), None)
+            .unwrap();
+
+        let registry =
+            |name: &str| match &section.dependencies[&name.parse::<PackageName>().unwrap()] {
+                Dependency::Package(package) => package.registry.clone(),
+                _ => panic!("expected a registry package"),
+            };
+        assert_eq!(
+            registry("wasi:http").as_deref(),
+            Some("https://wasi.example.com")
+        );
+        assert_eq!(registry("myorg:logging").as_deref(), Some("myorg"));
+        assert_eq!(registry("myorg:pinned").as_deref(), Some("other"));
+        assert_eq!(registry("example:other"), None);
+        assert_eq!(
+            section.registries["https://wasi.example.com"],
+            RegistryRef::from("https://wasi.example.com".parse::<Url>().unwrap())
+        );
+
+        let mut section: ComponentSection =
+            toml_edit::de::from_str(r#"namespace_registries = { wasi = "missing" }"#).unwrap();
+        assert!(matches!(
+            section
+                .prepare(Path::new("/project/Cargo.toml"), None)
+                .unwrap_err()
+                .downcast_ref::<MetadataError>(),
+            Some(MetadataError::InvalidValue {
+                field: "namespace_registries",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn it_validates_linked_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("lib.wasm"), wat::parse_str("(module)").unwrap()).unwrap();
+        fs::write(
+            root.join("component.wasm"),
+            wat::parse_str("(component)").unwrap(),
+        )
+        .unwrap();
+
+        let mut section: ComponentSection =
+            toml_edit::de::from_str(r#"link = ["lib.wasm"]"#).unwrap();
+        section.prepare(&root.join("Cargo.toml"), None).unwrap();
+        assert_eq!(section.link, [root.join("lib.wasm")]);
+
+        let mut section: ComponentSection =
+            toml_edit::de::from_str(r#"link = ["missing.wasm"]"#).unwrap();
+        assert!(matches!(
+            section
+                .prepare(&root.join("Cargo.toml"), None)
+                .unwrap_err()
+                .downcast_ref::<MetadataError>(),
+            Some(MetadataError::MissingPath(path)) if path == &root.join("missing.wasm")
+        ));
+
+        let mut section: ComponentSection =
+            toml_edit::de::from_str(r#"link = ["component.wasm"]"#).unwrap();
+        assert!(matches!(
+            section
+                .prepare(&root.join("Cargo.toml"), None)
+                .unwrap_err()
+                .downcast_ref::<MetadataError>(),
+            Some(MetadataError::InvalidValue { field: "link", .. })
+        ));
+    }
+
+    #[test]
+    fn it_reads_archive_targets() {
+        let mut section: ComponentSection = toml_edit::de::from_str(
+            r#"
+target = { archive = "wit.tar.gz", subdir = "wit", world = "foo" }
+dependencies = { "foo:bar" = { archive = "deps/bar.zip" } }
+"#,
+        )
+        .unwrap();
+
+        let root = Path::new("/project");
+        section.prepare(&root.join("Cargo.toml"), None).unwrap();
+        assert!(matches!(
+            &section.target,
+            Target::Archive {
+                path,
+                subdir: Some(subdir),
+                world: Some(world),
+                ..
+            } if path == &root.join("wit.tar.gz") && subdir == Path::new("wit") && world == "foo"
+        ));
+        assert_eq!(
+            section.dependencies[&"foo:bar".parse::<PackageName>().unwrap()],
+            Dependency::Archive {
+                path: root.join("deps/bar.zip"),
+                subdir: None,
+            }
+        );
+
+        assert!(toml_edit::de::from_str::<ComponentSection>(
+            r#"target = { archive = "wit.tar.gz", path = "wit" }"#
+        )
+        .unwrap_err()
+        .to_string()
+        .contains("cannot specify both `path` and `archive` fields"));
+        assert!(
+            toml_edit::de::from_str::<ComponentSection>(r#"target = { subdir = "wit" }"#)
+                .unwrap_err()
+                .to_string()
+                .contains("the `subdir` field requires the `archive` field")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("wit.rar"), "").unwrap();
+        let metadata = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path(dir.path().join("Cargo.toml"))
+            .with_target(Target::Archive {
+                path: dir.path().join("wit.rar"),
+                subdir: None,
+                world: None,
+                dependencies: Default::default(),
+            })
+            .build()
+            .unwrap();
+        assert!(metadata
+            .target_path()
+            .unwrap_err()
+            .to_string()
+            .contains("unsupported extension"));
+    }
+
+    #[test]
+    fn it_validates_derive_paths() {
+        let bindings: Bindings =
+            toml_edit::de::from_str(r#"derives = ["serde::Serialize", "::std::hash::Hash"]"#)
+                .unwrap();
+        bindings.validate().unwrap();
+        assert!(bindings.unknown_derives().is_empty());
+
+        let bindings: Bindings =
+            toml_edit::de::from_str(r#"derives = ["PartialEq", "Hahs"]"#).unwrap();
+        bindings.validate().unwrap();
+        assert_eq!(bindings.unknown_derives(), ["Hahs"]);
+
+        for derive in [
+            "serde::",
+            "Partial Eq",
+            "#[derive(Debug)]",
+            "serde:Serialize",
+        ] {
+            let bindings = Bindings {
+                derives: vec![derive.to_string()],
+                ..Default::default()
+            };
+            assert!(matches!(
+                bindings.validate().unwrap_err(),
+                MetadataError::InvalidValue {
+                    field: "bindings.derives",
+                    reason,
+                } if reason == format!("`{derive}` is not a valid derive path")
+            ));
+        }
+    }
+
+    #[test]
+    fn it_checks_rust_versions() {
+        let old = Version::parse("1.80.0").unwrap();
+        let new = Version::parse("1.86.0-nightly").unwrap();
+
+        let bindings: Bindings =
+            toml_edit::de::from_str(r#"format = { edition = "2024" }"#).unwrap();
+        bindings.check_rust_version(&new).unwrap();
+        let err = bindings.check_rust_version(&old).unwrap_err().to_string();
+        assert!(err.contains(
+            "bindings feature `format.edition = \"2024\"` requires Rust 1.85.0 but the active `rustc` is Rust 1.80.0"
+        ));
+
+        // An older `min_rust` is checked in place of the active version
+        let mut bindings: Bindings = toml_edit::de::from_str(
+            r#"
+format = { edition = "2021" }
+min_rust = "1.50.0"
+"#,
+        )
+        .unwrap();
+        let err = bindings.check_rust_version(&new).unwrap_err().to_string();
+        assert!(err
+            .contains("requires Rust 1.56.0 but the configured `min_rust` version is Rust 1.50.0"));
+
+        bindings.min_rust = Some(Version::new(1, 56, 0));
+        bindings.check_rust_version(&new).unwrap();
+        Bindings::default().check_rust_version(&old).unwrap();
+    }
+
+    #[test]
+    fn it_merges_conditional_bindings_settings() {
+        let bindings: Bindings = toml_edit::de::from_str(
+            r#"
+derives = ["PartialEq"]
+with = { "foo:bar/audio" = "crate::audio" }
+
+[cfg.'target_os = "wasi"']
+derives = ["Hash"]
+with = { "foo:bar/video" = "crate::video" }
+
+[cfg.'all(target_arch = "wasm32", not(target_os = "wasi"))']
+no_std = true
+"#,
+        )
+        .unwrap();
+        bindings.validate().unwrap();
+
+        let wasi = [
+            ("target_arch".to_string(), Some("wasm32".to_string())),
+            ("target_os".to_string(), Some("wasi".to_string())),
+        ];
+        let settings = bindings.for_target_cfg(&wasi).unwrap();
+        assert!(settings.cfg.is_empty());
+        assert!(!settings.no_std);
+        assert_eq!(settings.derives, ["PartialEq", "Hash"]);
+        assert_eq!(
+            settings.with.get("foo:bar/audio"),
+            Some(&WithMapping::Path("crate::audio".to_string()))
+        );
+        assert_eq!(
+            settings.with.get("foo:bar/video"),
+            Some(&WithMapping::Path("crate::video".to_string()))
+        );
+
+        let unknown = [
+            ("target_arch".to_string(), Some("wasm32".to_string())),
+            ("target_os".to_string(), Some("unknown".to_string())),
+        ];
+        let settings = bindings.for_target_cfg(&unknown).unwrap();
+        assert!(settings.no_std);
+        assert_eq!(settings.derives, ["PartialEq"]);
+        assert!(!settings.with.contains_key("foo:bar/video"));
+
+        let bindings: Bindings =
+            toml_edit::de::from_str(r#"cfg."any(target_os" = { no_std = true }"#).unwrap();
+        assert!(matches!(
+            bindings.validate().unwrap_err(),
+            MetadataError::InvalidValue {
+                field: "bindings.cfg",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn it_suggests_unknown_fields() {
+        let error = from_value::<ComponentSection>(serde_json::json!({
+            "bindigs": {}
+        }))
+        .unwrap_err();
+        assert!(suggest_unknown_field(error)
+            .to_string()
+            .ends_with("did you mean `bindings`?"));
+
+        let error = from_value::<ComponentSection>(serde_json::json!({
+            "regsitries": {}
+        }))
+        .unwrap_err();
+        assert!(suggest_unknown_field(error)
+            .to_string()
+            .ends_with("did you mean `registries`?"));
+
+        let error = from_value::<ComponentSection>(serde_json::json!({
+            "unrelated": true
+        }))
+        .unwrap_err();
+        assert!(!suggest_unknown_field(error)
+            .to_string()
+            .contains("did you mean"));
+    }
+
+    #[test]
+    fn it_rejects_contradictory_only_entries() {
+        let bindings = Bindings {
+            only: vec!["wasi:http/types".to_string()],
+            skip: vec!["wasi:http/*".to_string()],
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            bindings.validate().unwrap_err(),
+            MetadataError::InvalidValue {
+                field: "bindings.only",
+                reason,
+            } if reason == "entry `wasi:http/types` is excluded by `skip` entry `wasi:http/*`"
+        ));
+    }
+
+    #[test]
+    fn it_rejects_no_std_with_std_feature() {
+        let bindings: Bindings = toml_edit::de::from_str("no_std = true").unwrap();
+        assert!(bindings.no_std);
+        bindings.validate().unwrap();
+
+        let bindings: Bindings =
+            toml_edit::de::from_str("no_std = true\nstd_feature = true").unwrap();
+        assert!(matches!(
+            bindings.validate().unwrap_err(),
+            MetadataError::ConflictingFields {
+                first: "bindings.no_std",
+                second: "bindings.std_feature",
+            }
+        ));
+    }
+
+    #[test]
+    fn it_rejects_suffix_without_type_section() {
+        let bindings: Bindings = toml_edit::de::from_str("emit_type_section = false").unwrap();
+        assert!(!bindings.emit_type_section);
+        bindings.validate().unwrap();
+
+        let bindings: Bindings = toml_edit::de::from_str(
+            r#"
+emit_type_section = false
+type_section_suffix = "foo"
+"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            bindings.validate().unwrap_err(),
+            MetadataError::ConflictingFields {
+                first: "bindings.type_section_suffix",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn it_diffs_component_metadata() {
+        let old = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path("/project/Cargo.toml")
+            .with_target(Target::Local {
+                path: None,
+                world: Some("old".to_string()),
+                dependencies: Default::default(),
+            })
+            .with_dependency("foo:a".parse().unwrap(), "1.0.0".parse().unwrap())
+            .with_dependency("foo:b".parse().unwrap(), "1.0.0".parse().unwrap())
+            .build()
+            .unwrap();
+
+        let new = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path("/project/Cargo.toml")
+            .with_target(Target::Local {
+                path: None,
+                world: Some("new".to_string()),
+                dependencies: Default::default(),
+            })
+            .with_dependency("foo:b".parse().unwrap(), "2.0.0".parse().unwrap())
+            .with_dependency("foo:c".parse().unwrap(), "1.0.0".parse().unwrap())
+            .with_bindings(Bindings {
+                derives: vec!["Clone".to_string()],
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        assert!(old.diff(&old).is_empty());
+
+        let diff = old.diff(&new);
+        let names =
+            |names: &[PackageName]| names.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(names(&diff.added_dependencies), ["foo:c"]);
+        assert_eq!(names(&diff.removed_dependencies), ["foo:a"]);
+        assert_eq!(names(&diff.changed_dependencies), ["foo:b"]);
+        assert!(matches!(
+            diff.target,
+            Some((
+                Target::Local { world: Some(old), .. },
+                Target::Local { world: Some(new), .. },
+            )) if old == "old" && new == "new"
+        ));
+        assert_eq!(diff.bindings, ["derives"]);
+
+        // Any change to the target is reported, not just the world
+        let moved = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path("/project/Cargo.toml")
+            .with_target(Target::Local {
+                path: Some("/project/other-wit".into()),
+                world: Some("old".to_string()),
+                dependencies: Default::default(),
+            })
+            .with_dependency("foo:a".parse().unwrap(), "1.0.0".parse().unwrap())
+            .with_dependency("foo:b".parse().unwrap(), "1.0.0".parse().unwrap())
+            .build()
+            .unwrap();
+        assert!(old.diff(&moved).target.is_some());
+
+        let cfg = ComponentMetadataBuilder::new()
+            .with_name("foo")
+            .with_manifest_path("/project/Cargo.toml")
+            .with_bindings(Bindings {
+                cfg: HashMap::from([(
+                    "target_os = \"wasi\"".to_string(),
+                    Bindings {
+                        no_std: true,
+                        ..Default::default()
+                    },
+                )]),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        assert_eq!(
+            cfg.section.bindings.changed_settings(&Bindings::default()),
+            ["cfg"]
+        );
+    }
+
+    #[test]
+    fn it_selects_adapters_from_worlds() {
+        let mut resolve = Resolve::default();
+        let pkg = resolve
+            .push(
+                wit_parser::UnresolvedPackage::parse(
+                    Path::new("test.wit"),
+                    r#"package wasi:http@0.2.0;
+
+interface incoming-handler {
+    handle: func();
+}
+
+world proxy {
+    export incoming-handler;
+}
+
+world other {
+    import incoming-handler;
+}
+"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let proxy = resolve.select_world(pkg, Some("proxy")).unwrap();
+        assert_eq!(AdapterKind::from_world(&resolve, proxy), AdapterKind::Proxy);
+
+        let other = resolve.select_world(pkg, Some("other")).unwrap();
+        assert_eq!(
+            AdapterKind::from_world(&resolve, other),
+            AdapterKind::Reactor
+        );
     }
 }
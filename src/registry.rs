@@ -1,16 +1,49 @@
 //! Module for interacting with component registries.
 
-use crate::{config::Config, metadata::ComponentMetadata};
-use anyhow::Result;
+use crate::{
+    config::Config,
+    diagnostics::{Diagnostic, ManifestSpan},
+    metadata::{ComponentMetadata, Target},
+    wit_deps::{read_wit_deps, reconcile_wit_deps},
+};
+use anyhow::{bail, Context, Result};
 use cargo_component_core::{
-    lock::{LockFile, LockFileResolver, LockedPackage, LockedPackageVersion},
-    registry::{DependencyResolution, DependencyResolutionMap, DependencyResolver},
+    features,
+    lock::{LockFile, LockFileResolver, LockedLocalPackage, LockedPackage, LockedPackageVersion},
+    registry::{
+        DecodedDependency, Dependency, DependencyResolution, DependencyResolutionMap,
+        DependencyResolver, FrozenMode, SourceReplacement, DEFAULT_FETCH_CONCURRENCY,
+        DEFAULT_REGISTRY_NAME,
+    },
 };
 use cargo_metadata::PackageId;
 use semver::Version;
-use std::collections::HashMap;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
 use warg_crypto::hash::AnyHash;
 use warg_protocol::registry::PackageName;
+use wit_component::WitPrinter;
+use wit_parser::UnresolvedPackage;
+
+/// Options for vendoring resolved dependencies.
+#[derive(Debug, Default, Clone)]
+pub struct VendorOptions {
+    /// The dependencies to vendor; if empty, all dependencies are vendored.
+    pub include: Vec<PackageName>,
+    /// The dependencies to exclude from vendoring.
+    pub exclude: Vec<PackageName>,
+}
+
+impl VendorOptions {
+    fn includes(&self, name: &PackageName) -> bool {
+        (self.include.is_empty() || self.include.contains(name)) && !self.exclude.contains(name)
+    }
+}
 
 /// Represents a resolution of dependencies for a Cargo package.
 #[derive(Debug, Clone)]
@@ -31,19 +64,309 @@ impl<'a> PackageDependencyResolution<'a> {
         config: &Config,
         metadata: &'a ComponentMetadata,
         lock_file: Option<LockFileResolver<'_>>,
-        network_allowed: bool,
+        mode: FrozenMode,
     ) -> Result<PackageDependencyResolution<'a>> {
-        Ok(Self {
+        let mut resolution = Self {
             metadata,
-            target_resolutions: Self::resolve_target_deps(
-                config,
-                metadata,
-                lock_file,
-                network_allowed,
-            )
-            .await?,
-            resolutions: Self::resolve_deps(config, metadata, lock_file, network_allowed).await?,
-        })
+            target_resolutions: Self::resolve_target_deps(config, metadata, lock_file, mode)
+                .await?,
+            resolutions: Self::resolve_deps(config, metadata, lock_file, mode).await?,
+        };
+
+        resolution.check_local_cycles()?;
+
+        for name in resolution.unmatched_patches()? {
+            config.report(
+                Diagnostic::warning(
+                    "unmatched-patch",
+                    format!(
+                        "patch for package `{name}` in manifest `{path}` did not match any dependency",
+                        path = metadata.manifest_path.display()
+                    ),
+                )
+                .with_span(ManifestSpan::find(
+                    &metadata.manifest_path,
+                    &["package", "metadata", "component", "patch"],
+                )),
+            )?;
+        }
+
+        for name in resolution.exclude_dependencies()? {
+            config.report(
+                Diagnostic::warning(
+                    "unmatched-exclude",
+                    format!(
+                        "excluded dependency `{name}` in manifest `{path}` did not match any dependency",
+                        path = metadata.manifest_path.display()
+                    ),
+                )
+                .with_span(ManifestSpan::find(
+                    &metadata.manifest_path,
+                    &["package", "metadata", "component", "exclude_dependencies"],
+                )),
+            )?;
+        }
+
+        Ok(resolution)
+    }
+
+    /// Checks that the local target and the local dependencies do not
+    /// reference each other in a cycle.
+    ///
+    /// The error lists the paths of the packages forming the cycle.
+    fn check_local_cycles(&self) -> Result<()> {
+        let unversioned = |namespace: &str, name: &str| format!("{namespace}:{name}");
+        let mut graph: BTreeMap<String, (PathBuf, Vec<String>)> = BTreeMap::new();
+        let mut add = |path: PathBuf, package: UnresolvedPackage| {
+            let deps = package
+                .foreign_deps
+                .keys()
+                .map(|n| unversioned(&n.namespace, &n.name))
+                .collect();
+            graph.insert(
+                unversioned(&package.name.namespace, &package.name.name),
+                (path, deps),
+            );
+        };
+
+        if let Target::Local { .. } = &self.metadata.section.target {
+            if let Some(path) = self.metadata.target_path()? {
+                let features = self.metadata.section.bindings.feature_set();
+                let package = features::parse_path(&path, &features).with_context(|| {
+                    format!(
+                        "failed to parse local target `{path}`",
+                        path = path.display()
+                    )
+                })?;
+                add(path.into_owned(), package);
+            }
+        }
+
+        for (_, resolution) in self.all() {
+            let DependencyResolution::Local(local) = resolution else {
+                continue;
+            };
+
+            if let DecodedDependency::Wit { package, .. } = resolution.decode()? {
+                add(local.path.clone(), package);
+            }
+        }
+
+        let mut visited = HashSet::new();
+        for node in graph.keys() {
+            visit(node, &graph, &mut Vec::new(), &mut visited)?;
+        }
+
+        return Ok(());
+
+        fn visit<'b>(
+            node: &'b str,
+            graph: &'b BTreeMap<String, (PathBuf, Vec<String>)>,
+            stack: &mut Vec<&'b str>,
+            visited: &mut HashSet<&'b str>,
+        ) -> Result<()> {
+            if visited.contains(node) {
+                return Ok(());
+            }
+
+            if let Some(start) = stack.iter().position(|n| *n == node) {
+                let cycle = stack[start..]
+                    .iter()
+                    .chain(std::iter::once(&node))
+                    .map(|n| format!("`{path}`", path = graph[*n].0.display()))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                bail!("local WIT packages form a dependency cycle: {cycle}");
+            }
+
+            stack.push(node);
+            for dep in &graph[node].1 {
+                if let Some((dep, _)) = graph.get_key_value(dep) {
+                    visit(dep, graph, stack, visited)?;
+                }
+            }
+
+            stack.pop();
+            visited.insert(node);
+            Ok(())
+        }
+    }
+
+    /// Removes the dependencies named by the `exclude_dependencies` setting.
+    ///
+    /// It is an error to exclude a dependency that is used by the local
+    /// target or by another dependency.
+    ///
+    /// Returns the names of the excluded dependencies that did not match any
+    /// resolved dependency.
+    fn exclude_dependencies(&mut self) -> Result<Vec<&'a PackageName>> {
+        let section = &self.metadata.section;
+        if section.exclude_dependencies.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut unmatched = Vec::new();
+        for name in &section.exclude_dependencies {
+            let target = self.target_resolutions.remove(name);
+            let component = self.resolutions.remove(name);
+            if target.is_none() && component.is_none() {
+                unmatched.push(name);
+            } else {
+                log::debug!("excluding dependency `{name}` from the resolved dependencies");
+            }
+        }
+
+        // Packages are referenced without their versions
+        let unversioned = |namespace: &str, name: &str| format!("{namespace}:{name}");
+        let mut required = Vec::new();
+        if let Some(path) = self.metadata.target_path()? {
+            let features = self.metadata.section.bindings.feature_set();
+            let package = features::parse_path(&path, &features).with_context(|| {
+                format!(
+                    "failed to parse local target `{path}`",
+                    path = path.display()
+                )
+            })?;
+            required.push(("the local target".to_string(), package));
+        }
+
+        for (name, resolution) in self.all() {
+            if let DecodedDependency::Wit { package, .. } = resolution.decode()? {
+                required.push((format!("dependency `{name}`"), package));
+            }
+        }
+
+        for name in &section.exclude_dependencies {
+            let excluded = unversioned(name.namespace(), name.name());
+            for (user, package) in &required {
+                if package
+                    .foreign_deps
+                    .keys()
+                    .any(|n| unversioned(&n.namespace, &n.name) == excluded)
+                {
+                    bail!("cannot exclude dependency `{name}` because it is used by {user}");
+                }
+            }
+        }
+
+        Ok(unmatched)
+    }
+
+    /// Gets the names of the patches that do not match any dependency.
+    ///
+    /// A patch matches if it is a direct dependency or if it is required by
+    /// another resolved dependency.
+    pub fn unmatched_patches(&self) -> Result<Vec<&'a PackageName>> {
+        let section = &self.metadata.section;
+        if section.patch.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let direct = section.target.dependencies();
+        let mut required = Vec::new();
+        for (name, resolution) in self.all() {
+            let names: HashSet<String> = match resolution.decode()? {
+                DecodedDependency::Wit { package, .. } => package
+                    .foreign_deps
+                    .keys()
+                    .map(|n| format!("{ns}:{name}", ns = n.namespace, name = n.name))
+                    .collect(),
+                DecodedDependency::Wasm { decoded, .. } => decoded
+                    .resolve()
+                    .packages
+                    .iter()
+                    .map(|(_, p)| format!("{ns}:{name}", ns = p.name.namespace, name = p.name.name))
+                    .collect(),
+            };
+            required.push((name, names));
+        }
+
+        let mut unmatched: Vec<_> = section
+            .patch
+            .keys()
+            .filter(|patch| {
+                !direct.contains_key(*patch)
+                    && !section.dependencies.contains_key(*patch)
+                    && !required
+                        .iter()
+                        .any(|(name, names)| *name != *patch && names.contains(&patch.to_string()))
+            })
+            .collect();
+
+        unmatched.sort();
+        Ok(unmatched)
+    }
+
+    /// Vendors the WIT of all resolved dependencies into the given directory.
+    ///
+    /// Each package is written to `deps/<namespace>/<name>` relative to the
+    /// directory.
+    ///
+    /// Returns the paths of the written files.
+    pub fn vendor(&self, dir: &Path, options: &VendorOptions) -> Result<Vec<PathBuf>> {
+        let mut vendored: HashMap<PathBuf, &PackageName> = HashMap::new();
+        let mut written = Vec::new();
+
+        for (name, resolution) in self.all() {
+            if !options.includes(name) {
+                log::debug!("skipping vendoring of dependency `{name}`");
+                continue;
+            }
+
+            let decoded = resolution.decode()?;
+            let package = decoded.package_name().clone();
+            let package_dir = dir
+                .join("deps")
+                .join(&package.namespace)
+                .join(&package.name);
+
+            if let Some(other) = vendored.insert(package_dir.clone(), name) {
+                bail!(
+                    "dependencies `{other}` and `{name}` both vendor package `{package}` to `{path}`",
+                    path = package_dir.display()
+                );
+            }
+
+            fs::create_dir_all(&package_dir).with_context(|| {
+                format!(
+                    "failed to create directory `{path}`",
+                    path = package_dir.display()
+                )
+            })?;
+
+            match decoded {
+                DecodedDependency::Wit {
+                    package: unresolved,
+                    ..
+                } => {
+                    for source in unresolved.source_files() {
+                        let path = package_dir.join(source.file_name().with_context(|| {
+                            format!("invalid WIT source file `{path}`", path = source.display())
+                        })?);
+                        fs::copy(source, &path).with_context(|| {
+                            format!(
+                                "failed to copy WIT source file `{source}` to `{path}`",
+                                source = source.display(),
+                                path = path.display()
+                            )
+                        })?;
+                        written.push(path);
+                    }
+                }
+                DecodedDependency::Wasm { decoded, .. } => {
+                    let path = package_dir.join(format!("{name}.wit", name = package.name));
+                    let wit = WitPrinter::default()
+                        .print(decoded.resolve(), decoded.package())
+                        .with_context(|| format!("failed to print WIT of dependency `{name}`"))?;
+                    fs::write(&path, wit).with_context(|| {
+                        format!("failed to write `{path}`", path = path.display())
+                    })?;
+                    written.push(path);
+                }
+            }
+        }
+
+        Ok(written)
     }
 
     /// Iterates over all dependency resolutions of the package.
@@ -53,51 +376,276 @@ impl<'a> PackageDependencyResolution<'a> {
             .chain(self.resolutions.iter())
     }
 
-    async fn resolve_target_deps(
+    /// Determines if the resolved dependencies of the package changed since
+    /// they were locked in the given lock file.
+    ///
+    /// A registry dependency changed if the lock file has no version locked
+    /// from the same registry for its requirement, or if the locked version or
+    /// digest differs. A local dependency changed if its digest differs from
+    /// a digest locked for it; local dependencies are only considered for
+    /// packages that enable `lock_local_dependencies`.
+    ///
+    /// Returns whether anything changed and the names of the changed
+    /// dependencies.
+    pub fn changed_since(&self, lock_file: &LockFile) -> Result<(bool, Vec<PackageName>)> {
+        let resolver = LockFileResolver::new(lock_file);
+        let mut changed = Vec::new();
+        for (name, resolution) in self.all() {
+            let unchanged = match resolution {
+                DependencyResolution::Registry(pkg) => {
+                    let registry = pkg.registry.as_deref().unwrap_or(DEFAULT_REGISTRY_NAME);
+                    resolver
+                        .resolve(registry, &pkg.package, &pkg.requirement)?
+                        .is_some_and(|locked| {
+                            locked.version == pkg.version && locked.digest == pkg.digest
+                        })
+                }
+                DependencyResolution::Local(local) => {
+                    if !self.metadata.section.lock_local_dependencies {
+                        continue;
+                    }
+
+                    let digest = local.digest().with_context(|| {
+                        format!(
+                            "failed to compute the digest of local dependency `{name}`",
+                            name = local.name
+                        )
+                    })?;
+
+                    lock_file
+                        .local_packages
+                        .iter()
+                        .any(|locked| locked.name == local.name && locked.digest == digest)
+                }
+            };
+
+            if !unchanged {
+                changed.push(name.clone());
+            }
+        }
+
+        changed.sort_by_key(ToString::to_string);
+        changed.dedup();
+        Ok((!changed.is_empty(), changed))
+    }
+
+    /// Creates a CycloneDX software bill of materials listing the resolved
+    /// registry packages of the component.
+    ///
+    /// Each package is listed once with its version, registry, and content
+    /// digest. If given, the adapter used to encode the component is listed
+    /// by name with the digest of its bytes.
+    pub fn sbom(&self, adapter: Option<(&str, &[u8])>) -> Value {
+        let mut components: Vec<Value> = Vec::new();
+        let mut refs = HashSet::new();
+        for (_, dependency) in self.all() {
+            let DependencyResolution::Registry(resolution) = dependency else {
+                continue;
+            };
+
+            let bom_ref = format!(
+                "{name}@{version}",
+                name = resolution.package,
+                version = resolution.version
+            );
+            if !refs.insert(bom_ref.clone()) {
+                continue;
+            }
+
+            let digest = resolution.digest.to_string();
+            let (algorithm, content) = digest.split_once(':').unwrap_or(("sha256", &digest));
+            components.push(json!({
+                "type": "library",
+                "bom-ref": bom_ref,
+                "name": resolution.package.to_string(),
+                "version": resolution.version.to_string(),
+                "hashes": [{
+                    "alg": sbom_hash_algorithm(algorithm),
+                    "content": content,
+                }],
+                "properties": [{
+                    "name": "cargo-component:registry",
+                    "value": resolution.registry.as_deref().unwrap_or("default"),
+                }],
+            }));
+        }
+
+        components.sort_by(|a, b| a["bom-ref"].as_str().cmp(&b["bom-ref"].as_str()));
+
+        if let Some((name, bytes)) = adapter {
+            let content: String = Sha256::digest(bytes)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect();
+            components.push(json!({
+                "type": "file",
+                "bom-ref": format!("adapter:{name}"),
+                "name": name,
+                "hashes": [{
+                    "alg": "SHA-256",
+                    "content": content,
+                }],
+            }));
+        }
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "component": {
+                    "type": "application",
+                    "name": self.metadata.name,
+                    "version": self.metadata.version.to_string(),
+                },
+            },
+            "components": components,
+        })
+    }
+
+    /// Resolves only the dependencies of the package's target.
+    pub async fn resolve_target_deps(
         config: &Config,
         metadata: &ComponentMetadata,
         lock_file: Option<LockFileResolver<'_>>,
-        network_allowed: bool,
+        mode: FrozenMode,
     ) -> Result<DependencyResolutionMap> {
         let target_deps = metadata.section.target.dependencies();
-        if target_deps.is_empty() {
+        let wit_deps = Self::wit_deps(config, metadata, &target_deps)?;
+        if target_deps.is_empty() && wit_deps.is_empty() {
             return Ok(Default::default());
         }
 
+        let source_replacement = SourceReplacement::find(metadata.manifest_dir()?)?;
         let mut resolver = DependencyResolver::new(
             config.warg(),
             &metadata.section.registries,
             lock_file,
             config.terminal(),
-            network_allowed,
-        )?;
+            mode.network_allowed(),
+        )?
+        .with_client_configs(&metadata.section.registry_config)
+        .with_source_replacement(&source_replacement)
+        .with_frozen_mode(mode)
+        .with_prefer_cached(config.prefer_cached())
+        .with_concurrency(
+            metadata
+                .section
+                .fetch_concurrency
+                .unwrap_or(DEFAULT_FETCH_CONCURRENCY),
+        );
+
+        for (name, dependency) in &wit_deps {
+            resolver.add_dependency(name, dependency).await?;
+        }
 
+        let patch = &metadata.section.patch;
         for (name, dependency) in target_deps.iter() {
+            let dependency = patch.get(name).unwrap_or(dependency);
+            metadata
+                .section
+                .check_dependency_registry(dependency, config.warg().home_url.as_deref())?;
             resolver.add_dependency(name, dependency).await?;
         }
 
+        // Patched packages that are not direct dependencies may still be
+        // required by the dependencies of a local target
+        if let Target::Local { .. } | Target::Archive { .. } = &metadata.section.target {
+            for (name, dependency) in patch {
+                if target_deps.contains_key(name) {
+                    continue;
+                }
+
+                metadata
+                    .section
+                    .check_dependency_registry(dependency, config.warg().home_url.as_deref())?;
+                resolver.add_dependency(name, dependency).await?;
+            }
+        }
+
         resolver.resolve().await
     }
 
-    async fn resolve_deps(
+    /// Gets the dependencies of a local target's `wit-deps` manifest that
+    /// are not otherwise specified by the target.
+    ///
+    /// A warning is emitted for each target dependency that does not match
+    /// its `wit-deps` counterpart.
+    fn wit_deps(
+        config: &Config,
+        metadata: &ComponentMetadata,
+        target_deps: &HashMap<PackageName, Dependency>,
+    ) -> Result<Vec<(PackageName, Dependency)>> {
+        // The `deps` directory is already merged as nested packages of a
+        // target file or of a recursive target directory
+        let (wit_dir, nested) = match &metadata.section.target {
+            Target::Local { .. } => match metadata.target_path()? {
+                Some(path) => {
+                    let path = metadata.manifest_dir()?.join(path);
+                    if path.is_file() {
+                        match path.parent() {
+                            Some(parent) => (parent.to_path_buf(), true),
+                            None => return Ok(Vec::new()),
+                        }
+                    } else {
+                        (path, metadata.section.recursive_wit)
+                    }
+                }
+                None => return Ok(Vec::new()),
+            },
+            Target::Package { .. } | Target::Archive { .. } => return Ok(Vec::new()),
+        };
+
+        let entries = read_wit_deps(&wit_dir)?;
+        let (added, mismatches) =
+            reconcile_wit_deps(&entries, target_deps, metadata.manifest_dir()?);
+        for mismatch in mismatches {
+            config.report(Diagnostic::warning(
+                "wit-deps-mismatch",
+                format!(
+                    "{mismatch} (the dependency specified in `{path}` will be used)",
+                    path = metadata.manifest_path.display()
+                ),
+            ))?;
+        }
+
+        Ok(if nested { Vec::new() } else { added })
+    }
+
+    pub(crate) async fn resolve_deps(
         config: &Config,
         metadata: &ComponentMetadata,
         lock_file: Option<LockFileResolver<'_>>,
-        network_allowed: bool,
+        mode: FrozenMode,
     ) -> Result<DependencyResolutionMap> {
         if metadata.section.dependencies.is_empty() {
             return Ok(Default::default());
         }
 
+        let source_replacement = SourceReplacement::find(metadata.manifest_dir()?)?;
         let mut resolver = DependencyResolver::new(
             config.warg(),
             &metadata.section.registries,
             lock_file,
             config.terminal(),
-            network_allowed,
-        )?;
+            mode.network_allowed(),
+        )?
+        .with_client_configs(&metadata.section.registry_config)
+        .with_source_replacement(&source_replacement)
+        .with_frozen_mode(mode)
+        .with_prefer_cached(config.prefer_cached())
+        .with_concurrency(
+            metadata
+                .section
+                .fetch_concurrency
+                .unwrap_or(DEFAULT_FETCH_CONCURRENCY),
+        );
 
         for (name, dependency) in &metadata.section.dependencies {
+            let dependency = metadata.section.patch.get(name).unwrap_or(dependency);
+            metadata
+                .section
+                .check_dependency_registry(dependency, config.warg().home_url.as_deref())?;
             resolver.add_dependency(name, dependency).await?;
         }
 
@@ -105,6 +653,16 @@ impl<'a> PackageDependencyResolution<'a> {
     }
 }
 
+/// Gets the CycloneDX name of a digest algorithm.
+fn sbom_hash_algorithm(algorithm: &str) -> String {
+    match algorithm {
+        "sha256" => "SHA-256".to_string(),
+        "sha384" => "SHA-384".to_string(),
+        "sha512" => "SHA-512".to_string(),
+        other => other.to_uppercase(),
+    }
+}
+
 /// Represents a mapping between all component packages and their dependency resolutions.
 #[derive(Debug, Default, Clone)]
 pub struct PackageResolutionMap<'a>(HashMap<PackageId, PackageDependencyResolution<'a>>);
@@ -128,13 +686,31 @@ impl<'a> PackageResolutionMap<'a> {
     }
 
     /// Converts the resolution map into a lock file.
-    pub fn to_lock_file(&self) -> LockFile {
+    ///
+    /// Local dependencies are locked by digest only for packages that
+    /// enable `lock_local_dependencies`.
+    pub fn to_lock_file(&self) -> Result<LockFile> {
         type PackageKey = (PackageName, Option<String>);
         type VersionsMap = HashMap<String, (Version, AnyHash)>;
         let mut packages: HashMap<PackageKey, VersionsMap> = HashMap::new();
+        let mut local_packages = Vec::new();
 
         for resolution in self.0.values() {
             for (_, dep) in resolution.all() {
+                if let DependencyResolution::Local(local) = dep {
+                    if resolution.metadata.section.lock_local_dependencies {
+                        local_packages.push(LockedLocalPackage {
+                            name: local.name.clone(),
+                            digest: local.digest().with_context(|| {
+                                format!(
+                                    "failed to compute the digest of local dependency `{name}`",
+                                    name = local.name
+                                )
+                            })?,
+                        });
+                    }
+                }
+
                 match dep.key() {
                     Some((name, registry)) => {
                         let pkg = match dep {
@@ -184,6 +760,449 @@ impl<'a> PackageResolutionMap<'a> {
 
         packages.sort_by(|a, b| a.key().cmp(&b.key()));
 
-        LockFile::new(packages)
+        local_packages.sort_by(|a, b| a.key().cmp(&b.key()));
+        local_packages.dedup();
+
+        let mut lock_file = LockFile::new(packages);
+        lock_file.local_packages = local_packages;
+        Ok(lock_file)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{bindings::BindingsGenerator, metadata::ComponentSection};
+    use cargo_component_core::{
+        registry::{Dependency, LocalResolution, RegistryResolution},
+        terminal::{Color, Terminal, Verbosity},
+    };
+
+    #[test]
+    fn it_vendors_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut resolutions = DependencyResolutionMap::new();
+        for (name, wit) in [
+            ("foo:types", "package foo:types;\n\ninterface types {}\n"),
+            ("bar:types", "package bar:types;\n\ninterface types {}\n"),
+            ("baz:other", "package baz:other;\n\ninterface other {}\n"),
+        ] {
+            let path = dir.path().join(format!("{name}.wit").replace(':', "-"));
+            fs::write(&path, wit).unwrap();
+
+            let name: PackageName = name.parse().unwrap();
+            resolutions.insert(
+                name.clone(),
+                DependencyResolution::Local(LocalResolution { name, path }),
+            );
+        }
+
+        let metadata = ComponentMetadata::for_test(
+            "component",
+            dir.path().join("Cargo.toml"),
+            ComponentSection::default(),
+        );
+
+        let resolution = PackageDependencyResolution {
+            metadata: &metadata,
+            target_resolutions: resolutions,
+            resolutions: Default::default(),
+        };
+
+        let wit = dir.path().join("wit");
+        let mut written = resolution
+            .vendor(
+                &wit,
+                &VendorOptions {
+                    include: Vec::new(),
+                    exclude: vec!["baz:other".parse().unwrap()],
+                },
+            )
+            .unwrap();
+        written.sort();
+
+        // Packages with the same name in different namespaces do not collide
+        assert_eq!(
+            written,
+            [
+                wit.join("deps/bar/types/bar-types.wit"),
+                wit.join("deps/foo/types/foo-types.wit"),
+            ]
+        );
+        assert!(!wit.join("deps/baz").exists());
+    }
+
+    #[test]
+    fn it_detects_local_dependency_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut resolutions = DependencyResolutionMap::new();
+        for (name, wit) in [
+            (
+                "foo:a",
+                "package foo:a;\n\ninterface a {\n  use foo:b/b.{t};\n  type u = t;\n}\n",
+            ),
+            (
+                "foo:b",
+                "package foo:b;\n\ninterface b {\n  use foo:a/a.{u};\n  type t = u;\n}\n",
+            ),
+        ] {
+            let path = dir.path().join(name.replace(':', "-"));
+            fs::create_dir(&path).unwrap();
+            fs::write(path.join("package.wit"), wit).unwrap();
+
+            let name: PackageName = name.parse().unwrap();
+            resolutions.insert(
+                name.clone(),
+                DependencyResolution::Local(LocalResolution { name, path }),
+            );
+        }
+
+        let metadata = ComponentMetadata::for_test(
+            "component",
+            dir.path().join("Cargo.toml"),
+            ComponentSection::default(),
+        );
+
+        let resolution = PackageDependencyResolution {
+            metadata: &metadata,
+            target_resolutions: resolutions,
+            resolutions: Default::default(),
+        };
+
+        let e = resolution.check_local_cycles().unwrap_err().to_string();
+        let a = dir.path().join("foo-a");
+        let b = dir.path().join("foo-b");
+        assert_eq!(
+            e,
+            format!(
+                "local WIT packages form a dependency cycle: `{a}` -> `{b}` -> `{a}`",
+                a = a.display(),
+                b = b.display()
+            )
+        );
+    }
+
+    #[test]
+    fn it_creates_an_sbom() {
+        let digest = |byte: char| -> AnyHash {
+            format!("sha256:{digest}", digest = byte.to_string().repeat(64))
+                .parse()
+                .unwrap()
+        };
+
+        let mut resolutions = DependencyResolutionMap::new();
+        for (name, version, registry, hash) in [
+            ("foo:bar", "1.2.0", None, digest('a')),
+            ("baz:qux", "0.1.0", Some("mirror"), digest('b')),
+        ] {
+            let name: PackageName = name.parse().unwrap();
+            let version: Version = version.parse().unwrap();
+            resolutions.insert(
+                name.clone(),
+                DependencyResolution::Registry(RegistryResolution {
+                    name: name.clone(),
+                    package: name,
+                    registry: registry.map(str::to_string),
+                    requirement: format!("={version}").parse().unwrap(),
+                    version,
+                    digest: hash,
+                    path: PathBuf::new(),
+                }),
+            );
+        }
+
+        let local: PackageName = "local:types".parse().unwrap();
+        resolutions.insert(
+            local.clone(),
+            DependencyResolution::Local(LocalResolution {
+                name: local,
+                path: PathBuf::from("wit"),
+            }),
+        );
+
+        let metadata = ComponentMetadata::for_test(
+            "component",
+            "/project/Cargo.toml",
+            ComponentSection::default(),
+        );
+
+        let resolution = PackageDependencyResolution {
+            metadata: &metadata,
+            target_resolutions: resolutions.clone(),
+            resolutions,
+        };
+
+        let sbom = resolution.sbom(Some(("wasi_snapshot_preview1.reactor", b"adapter")));
+        assert_eq!(sbom["bomFormat"], "CycloneDX");
+        assert_eq!(sbom["metadata"]["component"]["name"], "component");
+
+        // Packages resolved for both the target and the component are listed once
+        let components = sbom["components"].as_array().unwrap();
+        assert_eq!(components.len(), 3);
+
+        assert_eq!(components[0]["name"], "baz:qux");
+        assert_eq!(components[0]["version"], "0.1.0");
+        assert_eq!(components[0]["hashes"][0]["alg"], "SHA-256");
+        assert_eq!(components[0]["hashes"][0]["content"], "b".repeat(64));
+        assert_eq!(components[0]["properties"][0]["value"], "mirror");
+
+        assert_eq!(components[1]["name"], "foo:bar");
+        assert_eq!(components[1]["version"], "1.2.0");
+        assert_eq!(components[1]["hashes"][0]["content"], "a".repeat(64));
+        assert_eq!(components[1]["properties"][0]["value"], "default");
+
+        assert_eq!(components[2]["name"], "wasi_snapshot_preview1.reactor");
+        assert_eq!(
+            components[2]["hashes"][0]["content"],
+            "ae1eae1d76e5b7c865c4122ce366a08025842566d2d96c75cc13e6353a73db0d"
+        );
+    }
+
+    #[test]
+    fn it_detects_changed_dependencies() {
+        let digest = |byte: char| -> AnyHash {
+            format!("sha256:{digest}", digest = byte.to_string().repeat(64))
+                .parse()
+                .unwrap()
+        };
+
+        let resolve = |name: &str, version: &str, hash: AnyHash| {
+            let name: PackageName = name.parse().unwrap();
+            DependencyResolution::Registry(RegistryResolution {
+                name: name.clone(),
+                package: name,
+                registry: None,
+                requirement: "^1.0.0".parse().unwrap(),
+                version: version.parse().unwrap(),
+                digest: hash,
+                path: PathBuf::new(),
+            })
+        };
+
+        let metadata = ComponentMetadata::for_test(
+            "component",
+            "/project/Cargo.toml",
+            ComponentSection::default(),
+        );
+
+        let mut resolutions = DependencyResolutionMap::new();
+        resolutions.insert(
+            "foo:bar".parse().unwrap(),
+            resolve("foo:bar", "1.0.0", digest('a')),
+        );
+        resolutions.insert(
+            "baz:qux".parse().unwrap(),
+            resolve("baz:qux", "1.1.0", digest('b')),
+        );
+
+        let resolution = PackageDependencyResolution {
+            metadata: &metadata,
+            target_resolutions: Default::default(),
+            resolutions,
+        };
+
+        let lock_file = |qux: &str| {
+            LockFile::new(vec![
+                LockedPackage {
+                    name: "baz:qux".parse().unwrap(),
+                    registry: None,
+                    versions: vec![LockedPackageVersion {
+                        requirement: "^1.0.0".to_string(),
+                        version: qux.parse().unwrap(),
+                        digest: digest('b'),
+                    }],
+                },
+                LockedPackage {
+                    name: "foo:bar".parse().unwrap(),
+                    registry: None,
+                    versions: vec![LockedPackageVersion {
+                        requirement: "^1.0.0".to_string(),
+                        version: "1.0.0".parse().unwrap(),
+                        digest: digest('a'),
+                    }],
+                },
+            ])
+        };
+
+        let (changed, packages) = resolution.changed_since(&lock_file("1.1.0")).unwrap();
+        assert!(!changed);
+        assert!(packages.is_empty());
+
+        let (changed, packages) = resolution.changed_since(&lock_file("1.0.0")).unwrap();
+        assert!(changed);
+        assert_eq!(packages, ["baz:qux".parse::<PackageName>().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn it_patches_transitive_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("app.wit"),
+            "package foo:app;\n\nworld app {\n  import foo:http/handler;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("http.wit"),
+            "package foo:http;\n\ninterface handler {\n  use wasi:io/streams.{output-stream};\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("io.wit"),
+            "package wasi:io;\n\ninterface streams {\n  resource output-stream;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("unused.wit"),
+            "package foo:unused;\n\ninterface unused {}\n",
+        )
+        .unwrap();
+
+        let metadata = ComponentMetadata::for_test(
+            "component",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: Some(dir.path().join("app.wit")),
+                    world: None,
+                    dependencies: HashMap::from([(
+                        "foo:http".parse().unwrap(),
+                        Dependency::Local(dir.path().join("http.wit")),
+                    )]),
+                },
+                patch: HashMap::from([
+                    (
+                        "wasi:io".parse().unwrap(),
+                        Dependency::Local(dir.path().join("io.wit")),
+                    ),
+                    (
+                        "foo:unused".parse().unwrap(),
+                        Dependency::Local(dir.path().join("unused.wit")),
+                    ),
+                ]),
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        let resolution =
+            PackageDependencyResolution::new(&config, &metadata, None, FrozenMode::Offline)
+                .await
+                .unwrap();
+
+        // The `wasi:io` package required by `foo:http` comes from the patch
+        let (resolve, _, _) = BindingsGenerator::target_resolve(&resolution)
+            .unwrap()
+            .unwrap();
+        assert!(resolve
+            .package_names
+            .keys()
+            .any(|name| name.to_string() == "wasi:io"));
+
+        assert_eq!(
+            resolution
+                .unmatched_patches()
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["foo:unused"]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_excludes_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("app.wit"),
+            "package foo:app;\n\nworld app {\n  import foo:http/handler;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("http.wit"),
+            "package foo:http;\n\ninterface handler {\n  use wasi:io/streams.{output-stream};\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("io.wit"),
+            "package wasi:io;\n\ninterface streams {\n  resource output-stream;\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("unused.wit"),
+            "package foo:unused;\n\ninterface unused {}\n",
+        )
+        .unwrap();
+
+        let mut metadata = ComponentMetadata::for_test(
+            "component",
+            dir.path().join("Cargo.toml"),
+            ComponentSection {
+                target: Target::Local {
+                    path: Some(dir.path().join("app.wit")),
+                    world: None,
+                    dependencies: HashMap::from([
+                        (
+                            "foo:http".parse().unwrap(),
+                            Dependency::Local(dir.path().join("http.wit")),
+                        ),
+                        (
+                            "wasi:io".parse().unwrap(),
+                            Dependency::Local(dir.path().join("io.wit")),
+                        ),
+                    ]),
+                },
+                // Patches supply the transitive dependencies of a local target
+                patch: HashMap::from([(
+                    "foo:unused".parse().unwrap(),
+                    Dependency::Local(dir.path().join("unused.wit")),
+                )]),
+                exclude_dependencies: vec![
+                    "foo:unused".parse().unwrap(),
+                    "foo:missing".parse().unwrap(),
+                ],
+                ..Default::default()
+            },
+        );
+
+        let config = Config::new(Terminal::new(Verbosity::Quiet, Color::Never)).unwrap();
+        let mut resolution = PackageDependencyResolution {
+            metadata: &metadata,
+            target_resolutions: PackageDependencyResolution::resolve_target_deps(
+                &config,
+                &metadata,
+                None,
+                FrozenMode::Offline,
+            )
+            .await
+            .unwrap(),
+            resolutions: Default::default(),
+        };
+
+        let unmatched = resolution.exclude_dependencies().unwrap();
+        assert_eq!(
+            unmatched
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["foo:missing"]
+        );
+
+        let mut names: Vec<_> = resolution
+            .target_resolutions
+            .keys()
+            .map(ToString::to_string)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["foo:http", "wasi:io"]);
+        BindingsGenerator::target_resolve(&resolution).unwrap();
+
+        // A dependency used by another dependency cannot be excluded
+        metadata.section.exclude_dependencies = vec!["wasi:io".parse().unwrap()];
+        let e = PackageDependencyResolution::new(&config, &metadata, None, FrozenMode::Offline)
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains(
+            "cannot exclude dependency `wasi:io` because it is used by dependency `foo:http`"
+        ));
     }
 }
@@ -1,8 +1,10 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use semver::Version;
 use std::{
     env,
     path::PathBuf,
     process::{Command, Stdio},
+    sync::OnceLock,
 };
 
 use crate::config::Config;
@@ -59,3 +61,173 @@ fn get_sysroot() -> Result<PathBuf> {
 
     Ok(sysroot)
 }
+
+/// Gets the version of the active `rustc`.
+///
+/// The version is only detected once per process.
+pub fn rustc_version() -> Result<&'static Version> {
+    static VERSION: OnceLock<Version> = OnceLock::new();
+
+    if let Some(version) = VERSION.get() {
+        return Ok(version);
+    }
+
+    let output = Command::new("rustc").arg("--version").output()?;
+
+    if !output.status.success() {
+        bail!(
+            "failed to execute `rustc --version`, \
+                 command exited with error: {output}",
+            output = String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    // The output is of the form `rustc 1.76.0 (07dca489a 2024-02-04)`
+    let output = String::from_utf8(output.stdout)?;
+    let version = output
+        .split_whitespace()
+        .nth(1)
+        .with_context(|| format!("unexpected output from `rustc --version`: {output}"))?;
+    let version = version
+        .parse()
+        .with_context(|| format!("invalid `rustc` version `{version}`"))?;
+
+    Ok(VERSION.get_or_init(|| version))
+}
+
+/// The `cfg` settings of a target, as printed by `rustc --print cfg`.
+///
+/// Each setting is a name with an optional value (e.g. `target_os = "wasi"`).
+pub type TargetCfg = Vec<(String, Option<String>)>;
+
+/// Gets the `cfg` settings of the given target triple.
+pub fn target_cfg(target: &str) -> Result<TargetCfg> {
+    let output = Command::new("rustc")
+        .arg("--print")
+        .arg("cfg")
+        .arg("--target")
+        .arg(target)
+        .output()?;
+
+    if !output.status.success() {
+        bail!(
+            "failed to execute `rustc --print cfg --target {target}`, \
+                 command exited with error: {output}",
+            output = String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((name, value)) => (
+                name.trim().to_string(),
+                Some(value.trim().trim_matches('"').to_string()),
+            ),
+            None => (line.trim().to_string(), None),
+        })
+        .collect())
+}
+
+/// Determines if the given `cfg` expression matches the given settings.
+///
+/// Expressions use the syntax of `#[cfg(...)]` attributes: names, `name =
+/// "value"` pairs, and the `all`, `any`, and `not` predicates.
+pub fn cfg_matches(expr: &str, cfg: &[(String, Option<String>)]) -> Result<bool> {
+    let mut parser = CfgParser { input: expr };
+    let matches = parser
+        .expr(cfg)
+        .with_context(|| format!("invalid cfg expression `{expr}`"))?;
+    parser.skip_whitespace();
+    if !parser.input.is_empty() {
+        bail!(
+            "invalid cfg expression `{expr}`: unexpected `{rest}`",
+            rest = parser.input
+        );
+    }
+
+    Ok(matches)
+}
+
+struct CfgParser<'a> {
+    input: &'a str,
+}
+
+impl<'a> CfgParser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.input = self.input.trim_start();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        match self.input.strip_prefix(c) {
+            Some(rest) => {
+                self.input = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str> {
+        self.skip_whitespace();
+        let len = self
+            .input
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.input.len());
+        if len == 0 {
+            bail!("expected an identifier");
+        }
+
+        let (ident, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(ident)
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        self.skip_whitespace();
+        let rest = self.input.strip_prefix('"').context("expected a string")?;
+        let end = rest.find('"').context("unterminated string")?;
+        self.input = &rest[end + 1..];
+        Ok(&rest[..end])
+    }
+
+    fn expr(&mut self, cfg: &[(String, Option<String>)]) -> Result<bool> {
+        let name = self.ident()?;
+        if self.eat('(') {
+            let mut results = Vec::new();
+            if !self.eat(')') {
+                loop {
+                    results.push(self.expr(cfg)?);
+                    if self.eat(')') {
+                        break;
+                    }
+                    if !self.eat(',') {
+                        bail!("expected `,` or `)`");
+                    }
+                    // Permit a trailing comma
+                    if self.eat(')') {
+                        break;
+                    }
+                }
+            }
+
+            return match name {
+                "all" => Ok(results.iter().all(|r| *r)),
+                "any" => Ok(results.iter().any(|r| *r)),
+                "not" if results.len() == 1 => Ok(!results[0]),
+                "not" => bail!("`not` takes exactly one predicate"),
+                _ => bail!("unknown predicate `{name}`"),
+            };
+        }
+
+        if self.eat('=') {
+            let value = self.string()?;
+            return Ok(cfg
+                .iter()
+                .any(|(n, v)| n == name && v.as_deref() == Some(value)));
+        }
+
+        Ok(cfg.iter().any(|(n, v)| n == name && v.is_none()))
+    }
+}
@@ -0,0 +1,212 @@
+//! Module for interoperating with the dependencies managed by `wit-deps`.
+
+use anyhow::{Context, Result};
+use cargo_component_core::registry::Dependency;
+use semver::Version;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::DocumentMut;
+use warg_protocol::registry::PackageName;
+use wit_parser::UnresolvedPackage;
+
+/// The name of the `wit-deps` manifest in a WIT directory.
+pub const WIT_DEPS_MANIFEST: &str = "deps.toml";
+
+/// The name of the `wit-deps` lock file in a WIT directory.
+pub const WIT_DEPS_LOCK: &str = "deps.lock";
+
+/// A dependency of a `wit-deps` manifest that has been fetched into the
+/// `deps` directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WitDepsEntry {
+    /// The name of the entry in the manifest.
+    pub name: String,
+    /// The name of the WIT package of the dependency.
+    pub package: PackageName,
+    /// The version of the WIT package of the dependency, if it has one.
+    pub version: Option<Version>,
+    /// The path to the directory of the dependency.
+    pub path: PathBuf,
+}
+
+/// Reads the dependencies of the `wit-deps` manifest and lock file in the
+/// given WIT directory.
+///
+/// Only the dependencies that have been fetched into the `deps` directory
+/// are returned; the entries are sorted by name.
+pub fn read_wit_deps(wit_dir: &Path) -> Result<Vec<WitDepsEntry>> {
+    let mut names = BTreeSet::new();
+    for file in [WIT_DEPS_MANIFEST, WIT_DEPS_LOCK] {
+        let path = wit_dir.join(file);
+        if !path.is_file() {
+            continue;
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read `{path}`", path = path.display()))?;
+        let document: DocumentMut = contents
+            .parse()
+            .with_context(|| format!("failed to parse `{path}`", path = path.display()))?;
+        names.extend(document.iter().map(|(name, _)| name.to_string()));
+    }
+
+    let mut entries = Vec::with_capacity(names.len());
+    for name in names {
+        let path = wit_dir.join("deps").join(&name);
+        if !path.is_dir() {
+            log::debug!(
+                "`wit-deps` dependency `{name}` has not been fetched to `{path}`",
+                path = path.display()
+            );
+            continue;
+        }
+
+        let package = UnresolvedPackage::parse_dir(&path).with_context(|| {
+            format!(
+                "failed to parse `wit-deps` dependency `{name}` from directory `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        entries.push(WitDepsEntry {
+            package: format!(
+                "{ns}:{pkg}",
+                ns = package.name.namespace,
+                pkg = package.name.name
+            )
+            .parse()
+            .with_context(|| {
+                format!(
+                    "`wit-deps` dependency `{name}` has an invalid package name `{package}`",
+                    package = package.name
+                )
+            })?,
+            version: package.name.version,
+            name,
+            path,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Reconciles the given `wit-deps` dependencies with the given dependencies
+/// of a component.
+///
+/// A dependency specified by the component takes precedence over a
+/// `wit-deps` dependency of the same package; the `wit-deps` dependencies of
+/// other packages are returned as local dependencies to add.
+///
+/// Returns the dependencies to add and a description of each mismatch
+/// between a dependency of the component and a `wit-deps` dependency.
+pub fn reconcile_wit_deps(
+    entries: &[WitDepsEntry],
+    dependencies: &HashMap<PackageName, Dependency>,
+    manifest_dir: &Path,
+) -> (Vec<(PackageName, Dependency)>, Vec<String>) {
+    let mut added = Vec::new();
+    let mut mismatches = Vec::new();
+    for entry in entries {
+        match dependencies.get(&entry.package) {
+            None => added.push((entry.package.clone(), Dependency::Local(entry.path.clone()))),
+            Some(Dependency::Package(package)) => {
+                if let Some(version) = &entry.version {
+                    if !package.version.matches(version) {
+                        mismatches.push(format!(
+                            "dependency `{name}` requires version `{req}` but `wit-deps` dependency `{entry}` is version `{version}`",
+                            name = entry.package,
+                            req = package.version,
+                            entry = entry.name,
+                        ));
+                    }
+                }
+            }
+            Some(Dependency::Local(path)) => {
+                let path = manifest_dir.join(path);
+                if fs::canonicalize(&path).ok() != fs::canonicalize(&entry.path).ok() {
+                    mismatches.push(format!(
+                        "dependency `{name}` is at `{path}` but `wit-deps` dependency `{entry}` is at `{entry_path}`",
+                        name = entry.package,
+                        path = path.display(),
+                        entry = entry.name,
+                        entry_path = entry.path.display(),
+                    ));
+                }
+            }
+            Some(Dependency::Archive { .. }) => {}
+        }
+    }
+
+    (added, mismatches)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_reads_wit_deps_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let wit = dir.path().join("wit");
+        fs::create_dir_all(wit.join("deps/io")).unwrap();
+        fs::write(
+            wit.join(WIT_DEPS_MANIFEST),
+            "io = \"https://example.com/wasi-io.tar.gz\"\n\n[unfetched]\nurl = \"https://example.com/unfetched.tar.gz\"\n",
+        )
+        .unwrap();
+        fs::write(
+            wit.join("deps/io/streams.wit"),
+            "package wasi:io@0.2.0;\n\ninterface streams {}\n",
+        )
+        .unwrap();
+
+        let entries = read_wit_deps(&wit).unwrap();
+        assert_eq!(
+            entries,
+            [WitDepsEntry {
+                name: "io".to_string(),
+                package: "wasi:io".parse().unwrap(),
+                version: Some(Version::new(0, 2, 0)),
+                path: wit.join("deps/io"),
+            }]
+        );
+
+        // A matching dependency of the component is used in place of the entry
+        let dependencies = HashMap::from([(
+            "wasi:io".parse().unwrap(),
+            Dependency::Package("0.2.0".parse().unwrap()),
+        )]);
+        let (added, mismatches) = reconcile_wit_deps(&entries, &dependencies, dir.path());
+        assert!(added.is_empty());
+        assert!(mismatches.is_empty());
+
+        let dependencies = HashMap::from([(
+            "wasi:io".parse().unwrap(),
+            Dependency::Package("0.3.0".parse().unwrap()),
+        )]);
+        let (_, mismatches) = reconcile_wit_deps(&entries, &dependencies, dir.path());
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].contains("`wit-deps` dependency `io` is version `0.2.0`"));
+
+        let dependencies = HashMap::from([(
+            "wasi:io".parse().unwrap(),
+            Dependency::Local("wit/deps/io".into()),
+        )]);
+        let (_, mismatches) = reconcile_wit_deps(&entries, &dependencies, dir.path());
+        assert!(mismatches.is_empty());
+
+        // Otherwise, the entry is added as a local dependency
+        let (added, mismatches) = reconcile_wit_deps(&entries, &HashMap::new(), dir.path());
+        assert_eq!(
+            added,
+            [(
+                "wasi:io".parse().unwrap(),
+                Dependency::Local(wit.join("deps/io"))
+            )]
+        );
+        assert!(mismatches.is_empty());
+    }
+}